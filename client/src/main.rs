@@ -1,16 +1,34 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use ethers::prelude::*;
 use mantle_config::MantleConfig;
-use merkle_tree::{hash_data, MerkleTree};
+use merkle_tree::render::{proof_to_ascii, proof_to_dot, tree_to_ascii, tree_to_dot};
+use merkle_tree::{
+    chunked_leaf, hash_data, hex_to_hash, parse_root_cid, Chunker, Hash, MerkleProof, MerkleTree,
+};
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// Tenant whose batches live directly under the storage root (matches server)
+const DEFAULT_TENANT: &str = "default";
 
 /// MerkSeal Client - Verifiable file storage with Mantle L2 anchoring
 #[derive(Parser)]
 #[command(name = "MerkSeal")]
 #[command(about = "MerkSeal client for verifiable file storage", long_about = None)]
 struct Cli {
+    /// Server storage root holding the batches [default: `storage_dir` from --config, else batches]
+    #[arg(long, global = true, env = "MERKSEAL_STORAGE_DIR")]
+    storage_dir: Option<PathBuf>,
+    
+    /// Server TOML config file; its [server] storage_dir is used without --storage-dir
+    #[arg(long, global = true, env = "MERKSEAL_CONFIG")]
+    config: Option<PathBuf>,
+    
+    /// Tenant whose batches to read (the server keeps other tenants under `tenants/{id}`)
+    #[arg(long, global = true, env = "MERKSEAL_TENANT")]
+    tenant: Option<String>,
+    
     #[command(subcommand)]
     command: Commands,
 }
@@ -28,20 +46,242 @@ enum Commands {
         mantle_batch_id: Option<u64>,
     },
     
+    /// Verify a single-file inclusion proof against Mantle L2
+    VerifyProof {
+        /// Proof JSON from `GET /batches/{id}/proof/{name}` (file path or URL)
+        #[arg(short, long)]
+        proof: String,
+        
+        /// Local copy of the document to check against the proven leaf
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        
+        /// Mantle batch ID (optional, will read from the proof if not provided)
+        #[arg(short, long)]
+        mantle_batch_id: Option<u64>,
+    },
+    
+    /// Check a server-signed upload receipt offline
+    VerifyReceipt {
+        /// Receipt JSON, or the upload response holding it (file path or URL)
+        #[arg(short, long)]
+        receipt: String,
+        
+        /// Server keys from `GET /.well-known/merkseal-keys` (file path or URL)
+        #[arg(short, long)]
+        keys: Option<String>,
+    },
+    
+    /// Render a batch tree, or one file's proof path, for audits
+    Show {
+        /// Local batch ID to render
+        #[arg(short, long)]
+        batch_id: u64,
+        
+        /// File name to show the proof path for (whole tree if omitted)
+        #[arg(short, long)]
+        file: Option<String>,
+        
+        /// Output format
+        #[arg(long, value_enum, default_value_t = RenderFormat::Ascii)]
+        format: RenderFormat,
+    },
+    
     /// Show configuration
     Config,
 }
 
+/// Output format for `show`
+#[derive(Clone, Copy, ValueEnum)]
+enum RenderFormat {
+    /// Terminal-friendly diagram
+    Ascii,
+    /// Graphviz DOT (pipe into `dot -Tsvg`)
+    Dot,
+}
+
+/// Where the server keeps a tenant's batches on this machine (matches server layout)
+struct LocalStorage {
+    root: PathBuf,
+    tenant: Option<String>,
+}
+
+impl LocalStorage {
+    /// Directory of the tenant's batches; the default tenant uses the root itself
+    fn tenant_dir(&self) -> PathBuf {
+        match self.tenant.as_deref() {
+            Some(tenant) if tenant != DEFAULT_TENANT => self.root.join("tenants").join(tenant),
+            _ => self.root.clone(),
+        }
+    }
+    
+    fn batch_dir(&self, local_batch_id: u64) -> PathBuf {
+        self.tenant_dir().join(local_batch_id.to_string())
+    }
+    
+    /// Content-defined chunks, shared by every tenant
+    fn chunk_dir(&self) -> PathBuf {
+        self.root.join("chunks")
+    }
+    
+    fn blob_dir(&self) -> PathBuf {
+        self.tenant_dir().join("blobs")
+    }
+}
+
+/// The `[server]` section of the server's config file, as far as the client needs it
+#[derive(Debug, Default, Deserialize)]
+struct ServerFileSection {
+    storage_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ServerConfigFile {
+    #[serde(default)]
+    server: ServerFileSection,
+}
+
+/// Storage root from the flag or environment, then the server config file, then the default
+fn storage_root(storage_dir: Option<PathBuf>, config: Option<&Path>) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if let Some(dir) = storage_dir {
+        return Ok(dir);
+    }
+    let from_file = match config {
+        Some(path) => {
+            let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            toml::from_str::<ServerConfigFile>(&text)
+                .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?
+                .server
+                .storage_dir
+        }
+        None => None,
+    };
+    Ok(from_file.unwrap_or_else(|| PathBuf::from("batches")))
+}
+
+/// Per-file manifest entry (matches server format)
+#[derive(Debug, Serialize, Deserialize)]
+struct FileEntry {
+    name: String,
+    size: u64,
+    leaf: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    chunks: Option<Vec<String>>,
+}
+
 /// Batch metadata (matches server format)
 #[derive(Debug, Serialize, Deserialize)]
 struct BatchMetadata {
     local_batch_id: u64,
     root: String,
+    #[serde(default)]
+    root_cid: String,
     file_count: usize,
     suggested_meta_uri: String,
     registry_address: String,
+    #[serde(default)]
+    hash_only: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     mantle_batch_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    aggregate: Option<AggregateProof>,
+    #[serde(default)]
+    files: Vec<FileEntry>,
+}
+
+/// Proof from a batch root to the aggregate root that was anchored in its place
+#[derive(Debug, Serialize, Deserialize)]
+struct AggregateProof {
+    aggregate_id: u64,
+    root: String,
+    #[serde(flatten)]
+    proof: MerkleProof,
+}
+
+impl AggregateProof {
+    /// Check that `batch_root` is the proven leaf and return the aggregate root
+    fn verify(&self, batch_root: &Hash) -> Result<Hash, Box<dyn std::error::Error>> {
+        let root = hex_to_hash(self.root.trim_start_matches("0x"))?;
+        if self.proof.leaf != *batch_root {
+            return Err(format!("Aggregate {} proof is for a different batch root", self.aggregate_id).into());
+        }
+        if !self.proof.verify(&root) {
+            return Err(format!(
+                "Aggregate {} proof computes root {} but claims {}",
+                self.aggregate_id,
+                hex::encode(self.proof.compute_root()),
+                self.root
+            )
+            .into());
+        }
+        Ok(root)
+    }
+}
+
+/// Inclusion proof document (matches server `GET /batches/{id}/proof/{name}`)
+#[derive(Debug, Deserialize)]
+struct ProofDocument {
+    local_batch_id: u64,
+    file: String,
+    #[serde(flatten)]
+    proof: MerkleProof,
+    root: String,
+    mantle_batch_id: Option<u64>,
+    tx_hash: Option<String>,
+    registry_address: String,
+    aggregate: Option<AggregateProof>,
+}
+
+/// Server-signed upload receipt (matches server format)
+#[derive(Debug, Deserialize)]
+struct Receipt {
+    version: u32,
+    key_id: String,
+    #[serde(default)]
+    tenant: String,
+    batch_id: u64,
+    root: Option<String>,
+    leaves: Vec<String>,
+    issued_at: u64,
+    signature: String,
+}
+
+impl Receipt {
+    /// Signed text; must match `Receipt::message` in the server byte for byte
+    fn message(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let mut leaves = Vec::with_capacity(self.leaves.len() * 32);
+        for leaf in &self.leaves {
+            leaves.extend_from_slice(&hex_to_hash(leaf)?);
+        }
+        // v1 receipts predate tenants and have no tenant line
+        let tenant = match self.version {
+            1 => String::new(),
+            _ => format!("tenant: {}\n", self.tenant),
+        };
+        Ok(format!(
+            "MerkSeal receipt v{}\nkey: {}\n{}batch: {}\nroot: {}\nleaves: {} sha256:{}\nissued_at: {}",
+            self.version,
+            self.key_id.to_ascii_lowercase(),
+            tenant,
+            self.batch_id,
+            self.root.as_deref().unwrap_or("pending"),
+            self.leaves.len(),
+            hex::encode(hash_data(&leaves)),
+            self.issued_at
+        ))
+    }
+}
+
+/// Receipt keys published by a server
+#[derive(Debug, Deserialize)]
+struct KeySet {
+    keys: Vec<PublishedKey>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PublishedKey {
+    key_id: String,
+    algorithm: String,
 }
 
 abigen!(
@@ -54,19 +294,21 @@ abigen!(
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
-    
-    // Load Mantle configuration
-    let config = match MantleConfig::from_env() {
-        Ok(config) => config,
+    let root = match storage_root(cli.storage_dir, cli.config.as_deref()) {
+        Ok(root) => root,
         Err(e) => {
-            eprintln!("❌ Failed to load config: {}", e);
-            eprintln!("   Make sure MERKLE_BATCH_REGISTRY_ADDRESS is set in .env");
+            eprintln!("❌ {}", e);
             std::process::exit(1);
         }
     };
+    let storage = LocalStorage {
+        root,
+        tenant: cli.tenant,
+    };
     
     match cli.command {
         Commands::Config => {
+            let config = load_config();
             println!("MerkSeal Client Configuration");
             println!("═══════════════════════════════════════════════════════════");
             println!("  RPC URL: {}", config.rpc_url);
@@ -76,8 +318,31 @@ async fn main() {
             println!("  Explorer: {}", config.contract_url());
         }
         
+        Commands::Show { batch_id, file, format } => {
+            if let Err(e) = show_batch(&storage, batch_id, file.as_deref(), format) {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+        }
+        
+        Commands::VerifyReceipt { receipt, keys } => {
+            if let Err(e) = verify_receipt(&receipt, keys.as_deref()).await {
+                eprintln!("\n❌ Receipt verification failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        
+        Commands::VerifyProof { proof, file, mantle_batch_id } => {
+            let config = load_config();
+            if let Err(e) = verify_proof(&config, &proof, file.as_deref(), mantle_batch_id).await {
+                eprintln!("\n❌ Proof verification failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        
         Commands::Verify { batch_id, mantle_batch_id } => {
-            if let Err(e) = verify_batch(&config, batch_id, mantle_batch_id).await {
+            let config = load_config();
+            if let Err(e) = verify_batch(&config, &storage, batch_id, mantle_batch_id).await {
                 eprintln!("\n❌ Verification failed: {}", e);
                 std::process::exit(1);
             }
@@ -85,8 +350,21 @@ async fn main() {
     }
 }
 
+/// Load the Mantle configuration, for the commands that talk to the chain
+fn load_config() -> MantleConfig {
+    match MantleConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load config: {}", e);
+            eprintln!("   Make sure MERKLE_BATCH_REGISTRY_ADDRESS is set in .env");
+            std::process::exit(1);
+        }
+    }
+}
+
 async fn verify_batch(
     config: &MantleConfig,
+    storage: &LocalStorage,
     local_batch_id: u64,
     mantle_batch_id_arg: Option<u64>,
 ) -> Result<(), Box<dyn std::error::Error>> {
@@ -95,7 +373,7 @@ async fn verify_batch(
     
     // 1. Load local batch metadata
     println!("📂 Loading local batch metadata...");
-    let batch_dir = PathBuf::from(format!("batches/{}", local_batch_id));
+    let batch_dir = storage.batch_dir(local_batch_id);
     let metadata_path = batch_dir.join("metadata.json");
     
     if !metadata_path.exists() {
@@ -107,6 +385,13 @@ async fn verify_batch(
     
     println!("   ✓ Local batch ID: {}", metadata.local_batch_id);
     println!("   ✓ Local root: {}", metadata.root);
+    if !metadata.root_cid.is_empty() {
+        let cid_root = parse_root_cid(&metadata.root_cid)?;
+        if hex::encode(cid_root) != metadata.root.trim_start_matches("0x").to_lowercase() {
+            return Err(format!("Root CID {} does not match root {}", metadata.root_cid, metadata.root).into());
+        }
+        println!("   ✓ Root CID: {}", metadata.root_cid);
+    }
     println!("   ✓ File count: {}", metadata.file_count);
     println!();
     
@@ -158,15 +443,29 @@ async fn verify_batch(
         format!("0x{}", metadata.root)
     };
     
-    let roots_match = local_root.to_lowercase() == onchain_root_hex.to_lowercase();
+    // Aggregated batches were anchored through a root-of-roots
+    let anchored_root = match &metadata.aggregate {
+        Some(aggregate) => {
+            let root = aggregate.verify(&hex_to_hash(local_root.trim_start_matches("0x"))?)?;
+            println!(
+                "   ✓ Batch root is leaf #{} of aggregate {} (root 0x{})",
+                aggregate.proof.leaf_index,
+                aggregate.aggregate_id,
+                hex::encode(root)
+            );
+            format!("0x{}", hex::encode(root))
+        }
+        None => local_root.clone(),
+    };
+    let roots_match = anchored_root.to_lowercase() == onchain_root_hex.to_lowercase();
     
     if roots_match {
         println!("   ✅ ROOT MATCH!");
-        println!("   Local root:    {}", local_root);
+        println!("   Local root:    {}", anchored_root);
         println!("   On-chain root: {}", onchain_root_hex);
     } else {
         println!("   ❌ ROOT MISMATCH!");
-        println!("   Local root:    {}", local_root);
+        println!("   Local root:    {}", anchored_root);
         println!("   On-chain root: {}", onchain_root_hex);
         println!();
         println!("⚠️  WARNING: Roots do not match!");
@@ -181,25 +480,19 @@ async fn verify_batch(
     // 5. Verify local files match local root
     println!("📁 Verifying local files...");
     
-    let mut file_hashes = Vec::new();
-    let mut files: Vec<_> = fs::read_dir(&batch_dir)?
-        .filter_map(|e| e.ok())
-        .filter(|e| {
-            let path = e.path();
-            path.is_file() && path.file_name() != Some(std::ffi::OsStr::new("metadata.json"))
-        })
-        .collect();
-    
-    files.sort_by_key(|e| e.path());
-    
-    for entry in &files {
-        let path = entry.path();
-        let filename = path.file_name().unwrap().to_string_lossy();
-        let data = fs::read(&path)?;
-        let hash = hash_data(&data);
-        file_hashes.push(hash);
-        println!("   ✓ {}: {} bytes", filename, data.len());
-    }
+    // Hash-only batches have no contents; only the listed leaves can be checked
+    let file_hashes = if metadata.hash_only {
+        println!("   ℹ️  Hash-only batch: checking the listed leaves (no file contents stored)");
+        metadata
+            .files
+            .iter()
+            .map(|f| hex_to_hash(&f.leaf))
+            .collect::<Result<Vec<_>, _>>()?
+    } else if metadata.files.is_empty() {
+        hash_batch_dir(&batch_dir)?
+    } else {
+        hash_manifest_files(storage, &batch_dir, &metadata.files)?
+    };
     
     if file_hashes.is_empty() {
         return Err("No files found in batch directory".into());
@@ -245,3 +538,340 @@ async fn verify_batch(
     
     Ok(())
 }
+
+/// Verify one file's inclusion proof without trusting the server
+///
+/// The proof is recomputed locally and its root compared with the root
+/// registered on-chain, so only the Mantle RPC needs to be reachable.
+async fn verify_proof(
+    config: &MantleConfig,
+    source: &str,
+    file: Option<&Path>,
+    mantle_batch_id_arg: Option<u64>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("🔍 MerkSeal Inclusion Proof Verification");
+    println!("═══════════════════════════════════════════════════════════\n");
+    
+    // 1. Load the proof
+    println!("📂 Loading proof from {}...", source);
+    let doc: ProofDocument = serde_json::from_str(&read_source(source).await?)?;
+    println!("   ✓ File: {} (leaf #{})", doc.file, doc.proof.leaf_index);
+    println!("   ✓ Local batch ID: {}", doc.local_batch_id);
+    println!("   ✓ Leaf: {}", hex::encode(doc.proof.leaf));
+    println!();
+    
+    // 2. Check the document itself, if we have it
+    if let Some(path) = file {
+        println!("📄 Hashing {}...", path.display());
+        let data = fs::read(path)?;
+        // Chunked uploads use the chunk-tree root as leaf; small files hash the same either way
+        let (chunked, _) = Chunker::default().file_leaf(&data);
+        if hash_data(&data) != doc.proof.leaf && chunked != doc.proof.leaf {
+            return Err(format!("{} does not match the proven leaf", path.display()).into());
+        }
+        println!("   ✅ Document matches the proven leaf");
+        println!();
+    }
+    
+    // 3. Recompute the root from the sibling path
+    println!("🔐 Recomputing Merkle root from {} siblings...", doc.proof.siblings.len());
+    let root = hex_to_hash(doc.root.trim_start_matches("0x"))?;
+    if !doc.proof.verify(&root) {
+        return Err(format!(
+            "Proof computes root {} but claims {}",
+            hex::encode(doc.proof.compute_root()),
+            doc.root
+        )
+        .into());
+    }
+    println!("   ✅ Proof is consistent with root 0x{}", hex::encode(root));
+    // Aggregated batches add a second level up to the anchored root
+    let anchored_root = match &doc.aggregate {
+        Some(aggregate) => {
+            let anchored = aggregate.verify(&root)?;
+            println!(
+                "   ✅ Batch root is leaf #{} of aggregate {} (root 0x{})",
+                aggregate.proof.leaf_index,
+                aggregate.aggregate_id,
+                hex::encode(anchored)
+            );
+            anchored
+        }
+        None => root,
+    };
+    println!();
+    
+    // 4. Compare with the root registered on Mantle
+    let mantle_batch_id = mantle_batch_id_arg
+        .or(doc.mantle_batch_id)
+        .ok_or("Batch is not anchored yet. Provide --mantle-batch-id once it is")?;
+    if !doc.registry_address.eq_ignore_ascii_case(&config.registry_address) {
+        println!("⚠️  Proof names registry {}, checking configured registry {}", doc.registry_address, config.registry_address);
+    }
+    
+    println!("🔗 Querying Mantle L2 (batch {})...", mantle_batch_id);
+    let provider = Provider::<Http>::try_from(&config.rpc_url)?;
+    let registry_address: Address = config.registry_address.parse()?;
+    let contract = MerkleBatchRegistry::new(registry_address, provider.into());
+    let (onchain_root, owner, _, timestamp) = contract
+        .get_batch(U256::from(mantle_batch_id))
+        .call()
+        .await?;
+    println!("   ✓ On-chain root: 0x{}", hex::encode(onchain_root));
+    println!("   ✓ Owner: {}", owner);
+    println!("   ✓ Timestamp: {}", timestamp);
+    println!();
+    
+    if onchain_root != anchored_root {
+        println!("   ❌ ROOT MISMATCH!");
+        return Err("Proven root does not match the on-chain root".into());
+    }
+    
+    println!("═══════════════════════════════════════════════════════════");
+    println!("✅ {} IS INCLUDED IN MANTLE BATCH {}", doc.file, mantle_batch_id);
+    println!("═══════════════════════════════════════════════════════════");
+    if let Some(tx_hash) = &doc.tx_hash {
+        println!();
+        println!("🔍 Anchoring transaction:");
+        println!("   {}", config.tx_url(tx_hash));
+    }
+    println!();
+    
+    Ok(())
+}
+
+/// Check a receipt's signature and root without contacting the server
+///
+/// With `--keys`, the signing key must also be one the server publishes;
+/// otherwise only the receipt's own `key_id` is checked.
+async fn verify_receipt(source: &str, keys: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    println!("🔍 MerkSeal Receipt Verification");
+    println!("═══════════════════════════════════════════════════════════\n");
+    
+    // 1. Load the receipt, either on its own or inside an upload response
+    println!("📂 Loading receipt from {}...", source);
+    let mut json: serde_json::Value = serde_json::from_str(&read_source(source).await?)?;
+    if let Some(receipt) = json.get_mut("receipt") {
+        json = receipt.take();
+    }
+    let receipt: Receipt = serde_json::from_value(json)?;
+    if !receipt.tenant.is_empty() {
+        println!("   ✓ Tenant: {}", receipt.tenant);
+    }
+    println!("   ✓ Batch ID: {}", receipt.batch_id);
+    println!("   ✓ Leaves: {}", receipt.leaves.len());
+    println!("   ✓ Issued at: {}", receipt.issued_at);
+    println!();
+    
+    // 2. Recover the signer from the signature
+    println!("🔏 Checking signature...");
+    let key_id: Address = receipt.key_id.parse()?;
+    let signature: Signature = receipt.signature.parse()?;
+    let signer = signature.recover(receipt.message()?)?;
+    if signer != key_id {
+        println!("   ❌ Signed by {:?}, receipt names {:?}", signer, key_id);
+        return Err("Signature does not match the receipt's key".into());
+    }
+    println!("   ✅ Signed by {:?}", signer);
+    
+    // 3. Make sure the key is one the server publishes
+    match keys {
+        Some(keys) => {
+            let key_set: KeySet = serde_json::from_str(&read_source(keys).await?)?;
+            let published = key_set.keys.iter().any(|key| {
+                key.algorithm == "secp256k1-eip191" && key.key_id.parse::<Address>().ok() == Some(key_id)
+            });
+            if !published {
+                return Err(format!("Key {:?} is not published in {}", key_id, keys).into());
+            }
+            println!("   ✅ Key is published in {}", keys);
+        }
+        None => println!("   ⚠️  Key not checked against the server's keys (pass --keys)"),
+    }
+    println!();
+    
+    // 4. Recompute the root from the receipted leaves
+    match &receipt.root {
+        Some(root) => {
+            println!("🔐 Recomputing Merkle root from {} leaves...", receipt.leaves.len());
+            let leaves = receipt
+                .leaves
+                .iter()
+                .map(|leaf| hex_to_hash(leaf))
+                .collect::<Result<Vec<_>, _>>()?;
+            let computed = MerkleTree::new(leaves).root();
+            if computed != hex_to_hash(root.trim_start_matches("0x"))? {
+                println!("   ❌ Computed 0x{}, receipt claims {}", hex::encode(computed), root);
+                return Err("Leaves do not produce the receipted root".into());
+            }
+            println!("   ✅ Leaves produce root 0x{}", hex::encode(computed));
+        }
+        None => println!("⏳ Batch was still open; the receipt covers the leaves but no root yet"),
+    }
+    println!();
+    
+    println!("═══════════════════════════════════════════════════════════");
+    println!("✅ RECEIPT FOR BATCH {} IS VALID", receipt.batch_id);
+    println!("═══════════════════════════════════════════════════════════");
+    println!();
+    
+    Ok(())
+}
+
+/// Read a JSON document from a local file or an http(s) URL
+async fn read_source(source: &str) -> Result<String, Box<dyn std::error::Error>> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        Ok(reqwest::get(source).await?.error_for_status()?.text().await?)
+    } else {
+        Ok(fs::read_to_string(source)?)
+    }
+}
+
+/// Hash every file in a batch directory in name order (legacy batches without a file list)
+fn hash_batch_dir(batch_dir: &Path) -> Result<Vec<Hash>, Box<dyn std::error::Error>> {
+    let mut file_hashes = Vec::new();
+    let mut files: Vec<_> = fs::read_dir(batch_dir)?
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            let path = e.path();
+            path.is_file() && path.file_name() != Some(std::ffi::OsStr::new("metadata.json"))
+        })
+        .collect();
+    
+    files.sort_by_key(|e| e.path());
+    
+    for entry in &files {
+        let path = entry.path();
+        let filename = path.file_name().unwrap().to_string_lossy();
+        let data = fs::read(&path)?;
+        let hash = hash_data(&data);
+        file_hashes.push(hash);
+        println!("   ✓ {}: {} bytes", filename, data.len());
+    }
+    
+    Ok(file_hashes)
+}
+
+/// Hash the files listed in the batch manifest, in leaf order
+///
+/// Chunked files are reassembled from the shared chunk store and every chunk
+/// is checked against its content address. Files missing from the batch
+/// directory are read from the shared blob store (`MERKSEAL_BLOBS`).
+fn hash_manifest_files(
+    storage: &LocalStorage,
+    batch_dir: &Path,
+    entries: &[FileEntry],
+) -> Result<Vec<Hash>, Box<dyn std::error::Error>> {
+    let chunk_dir = storage.chunk_dir();
+    let blob_dir = storage.blob_dir();
+    let mut file_hashes = Vec::new();
+    
+    for entry in entries {
+        let (hash, size) = match &entry.chunks {
+            Some(chunks) => {
+                let mut chunk_hashes = Vec::new();
+                let mut size = 0;
+                for chunk_hex in chunks {
+                    let expected = hex_to_hash(chunk_hex)?;
+                    let data = fs::read(chunk_dir.join(chunk_hex))?;
+                    if hash_data(&data) != expected {
+                        return Err(format!("Chunk {} of {} is corrupted", chunk_hex, entry.name).into());
+                    }
+                    size += data.len();
+                    chunk_hashes.push(expected);
+                }
+                if chunk_hashes.is_empty() {
+                    return Err(format!("File {} has an empty chunk list", entry.name).into());
+                }
+                (chunked_leaf(&chunk_hashes), size)
+            }
+            None => {
+                let path = batch_dir.join(&entry.name);
+                let path = if path.is_file() { path } else { blob_dir.join(entry.leaf.to_lowercase()) };
+                let data = fs::read(path)?;
+                (hash_data(&data), data.len())
+            }
+        };
+        
+        if hex::encode(hash) != entry.leaf.to_lowercase() {
+            println!("   ✗ {}: leaf mismatch", entry.name);
+        } else if entry.chunks.is_some() {
+            println!("   ✓ {}: {} bytes ({} chunks)", entry.name, size, entry.chunks.as_ref().map_or(0, |c| c.len()));
+        } else {
+            println!("   ✓ {}: {} bytes", entry.name, size);
+        }
+        file_hashes.push(hash);
+    }
+    
+    Ok(file_hashes)
+}
+
+/// Print a batch tree or a single file's proof path
+fn show_batch(
+    storage: &LocalStorage,
+    local_batch_id: u64,
+    file: Option<&str>,
+    format: RenderFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let batch_dir = storage.batch_dir(local_batch_id);
+    let metadata_path = batch_dir.join("metadata.json");
+    if !metadata_path.exists() {
+        return Err(format!("Batch {} not found at {}", local_batch_id, metadata_path.display()).into());
+    }
+    let metadata: BatchMetadata = serde_json::from_str(&fs::read_to_string(&metadata_path)?)?;
+    
+    // Leaves and names come from the manifest; legacy batches fall back to the directory
+    let (leaves, names): (Vec<Hash>, Vec<String>) = if metadata.files.is_empty() {
+        let mut names: Vec<String> = fs::read_dir(&batch_dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .filter(|name| name != "metadata.json")
+            .collect();
+        names.sort();
+        (hash_batch_dir(&batch_dir)?, names)
+    } else {
+        let leaves = metadata
+            .files
+            .iter()
+            .map(|f| hex_to_hash(&f.leaf))
+            .collect::<Result<_, _>>()?;
+        (leaves, metadata.files.iter().map(|f| f.name.clone()).collect())
+    };
+    
+    if leaves.is_empty() {
+        return Err("Batch has no files".into());
+    }
+    
+    let tree = MerkleTree::new(leaves);
+    let root = hex_to_hash(metadata.root.trim_start_matches("0x"))?;
+    if tree.root() != root {
+        return Err(format!(
+            "Batch root {} does not match the root rebuilt from its leaves ({})",
+            metadata.root,
+            tree.root_hex()
+        )
+        .into());
+    }
+    let labels: Vec<&str> = names.iter().map(String::as_str).collect();
+    
+    let rendered = match file {
+        Some(name) => {
+            let index = names
+                .iter()
+                .position(|n| n == name)
+                .ok_or_else(|| format!("File {} not found in batch {}", name, local_batch_id))?;
+            let proof = tree.proof(index).ok_or("Leaf index out of range")?;
+            match format {
+                RenderFormat::Ascii => proof_to_ascii(&proof, &root, name),
+                RenderFormat::Dot => proof_to_dot(&proof, &root, name),
+            }
+        }
+        None => match format {
+            RenderFormat::Ascii => tree_to_ascii(&tree, &labels),
+            RenderFormat::Dot => tree_to_dot(&tree, &labels),
+        },
+    };
+    
+    print!("{}", rendered);
+    Ok(())
+}
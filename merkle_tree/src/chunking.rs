@@ -0,0 +1,370 @@
+//! Content-defined chunking (FastCDC) for deduplicating large files
+//!
+//! Files are split at positions chosen by a rolling gear hash, so an edit in
+//! one place only changes the chunks around it. The file leaf is the Merkle
+//! root over the chunk hashes, which means a file that fits in a single chunk
+//! keeps the same leaf as `hash_data(file)`.
+
+use crate::{hash_data, Hash, MerkleTree};
+use serde::{Deserialize, Serialize};
+
+/// Default minimum chunk size (2 KiB)
+pub const DEFAULT_MIN_SIZE: usize = 2 * 1024;
+/// Default target average chunk size (8 KiB)
+pub const DEFAULT_AVG_SIZE: usize = 8 * 1024;
+/// Default maximum chunk size (64 KiB)
+pub const DEFAULT_MAX_SIZE: usize = 64 * 1024;
+
+/// Chunk size bounds for the chunker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkerConfig {
+    /// No cut point is considered before this many bytes
+    pub min_size: usize,
+    /// Target average chunk size (must be a power of two)
+    pub avg_size: usize,
+    /// Chunks are always cut at this size
+    pub max_size: usize,
+}
+
+impl Default for ChunkerConfig {
+    fn default() -> Self {
+        Self {
+            min_size: DEFAULT_MIN_SIZE,
+            avg_size: DEFAULT_AVG_SIZE,
+            max_size: DEFAULT_MAX_SIZE,
+        }
+    }
+}
+
+/// A single content-defined chunk of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    /// Byte offset of the chunk within the file
+    pub offset: usize,
+    /// Chunk length in bytes
+    pub length: usize,
+    /// SHA-256 of the chunk contents
+    pub hash: Hash,
+}
+
+/// FastCDC chunker with normalized chunking
+#[derive(Debug, Clone)]
+pub struct Chunker {
+    config: ChunkerConfig,
+    /// Stricter mask used before the average size is reached
+    mask_small: u64,
+    /// Looser mask used after the average size is reached
+    mask_large: u64,
+}
+
+impl Chunker {
+    /// Create a chunker with the given size bounds
+    pub fn new(config: ChunkerConfig) -> Self {
+        if config.min_size == 0
+            || config.min_size > config.avg_size
+            || config.avg_size > config.max_size
+            || !config.avg_size.is_power_of_two()
+        {
+            panic!("Invalid chunker config: {:?}", config);
+        }
+
+        let bits = config.avg_size.trailing_zeros();
+
+        Self {
+            config,
+            mask_small: high_bits_mask(bits + 1),
+            mask_large: high_bits_mask(bits.saturating_sub(1)),
+        }
+    }
+
+    /// Get the chunker configuration
+    pub fn config(&self) -> ChunkerConfig {
+        self.config
+    }
+
+    /// Split data into content-defined chunks
+    ///
+    /// Empty input yields a single empty chunk so every file has a leaf.
+    pub fn chunk(&self, data: &[u8]) -> Vec<Chunk> {
+        if data.is_empty() {
+            return vec![Chunk {
+                offset: 0,
+                length: 0,
+                hash: hash_data(data),
+            }];
+        }
+
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let length = self.next_cut(&data[offset..]);
+            chunks.push(Chunk {
+                offset,
+                length,
+                hash: hash_data(&data[offset..offset + length]),
+            });
+            offset += length;
+        }
+
+        chunks
+    }
+
+    /// Chunk data and compute its file leaf in one pass
+    pub fn file_leaf(&self, data: &[u8]) -> (Hash, Vec<Chunk>) {
+        let chunks = self.chunk(data);
+        let hashes: Vec<Hash> = chunks.iter().map(|c| c.hash).collect();
+        (chunked_leaf(&hashes), chunks)
+    }
+
+    /// Find the length of the next chunk at the start of data
+    fn next_cut(&self, data: &[u8]) -> usize {
+        let len = data.len();
+        if len <= self.config.min_size {
+            return len;
+        }
+
+        let max = len.min(self.config.max_size);
+        let normal = len.min(self.config.avg_size);
+        let mut fingerprint = 0u64;
+        let mut i = self.config.min_size;
+
+        while i < normal {
+            fingerprint = (fingerprint << 1).wrapping_add(GEAR[data[i] as usize]);
+            if fingerprint & self.mask_small == 0 {
+                return i + 1;
+            }
+            i += 1;
+        }
+
+        while i < max {
+            fingerprint = (fingerprint << 1).wrapping_add(GEAR[data[i] as usize]);
+            if fingerprint & self.mask_large == 0 {
+                return i + 1;
+            }
+            i += 1;
+        }
+
+        max
+    }
+}
+
+impl Default for Chunker {
+    fn default() -> Self {
+        Self::new(ChunkerConfig::default())
+    }
+}
+
+/// Chunker for data that arrives in pieces (e.g. an upload stream)
+///
+/// Produces exactly the same chunks as `Chunker::chunk` over the whole input
+/// while buffering at most `max_size` bytes plus the latest piece.
+#[derive(Debug, Clone)]
+pub struct StreamingChunker {
+    chunker: Chunker,
+    buffer: Vec<u8>,
+    offset: usize,
+    hashes: Vec<Hash>,
+}
+
+impl StreamingChunker {
+    pub fn new(chunker: Chunker) -> Self {
+        Self {
+            chunker,
+            buffer: Vec::new(),
+            offset: 0,
+            hashes: Vec::new(),
+        }
+    }
+
+    /// Feed the next piece of data, returning chunks that are now complete
+    pub fn update(&mut self, data: &[u8]) -> Vec<(Chunk, Vec<u8>)> {
+        self.buffer.extend_from_slice(data);
+        let mut done = Vec::new();
+
+        // A cut point is final once a full max-size window is buffered
+        while self.buffer.len() >= self.chunker.config.max_size {
+            done.push(self.take_chunk());
+        }
+
+        done
+    }
+
+    /// Flush the remaining data and return the final chunks and the file leaf
+    pub fn finish(mut self) -> (Vec<(Chunk, Vec<u8>)>, Hash) {
+        let mut done = Vec::new();
+
+        while !self.buffer.is_empty() {
+            done.push(self.take_chunk());
+        }
+        if self.hashes.is_empty() {
+            // Empty input still has a leaf, like `Chunker::chunk`
+            let chunk = Chunk {
+                offset: 0,
+                length: 0,
+                hash: hash_data(b""),
+            };
+            self.hashes.push(chunk.hash);
+            done.push((chunk, Vec::new()));
+        }
+
+        (done, chunked_leaf(&self.hashes))
+    }
+
+    fn take_chunk(&mut self) -> (Chunk, Vec<u8>) {
+        let length = self.chunker.next_cut(&self.buffer);
+        let data: Vec<u8> = self.buffer.drain(..length).collect();
+        let chunk = Chunk {
+            offset: self.offset,
+            length,
+            hash: hash_data(&data),
+        };
+        self.offset += length;
+        self.hashes.push(chunk.hash);
+        (chunk, data)
+    }
+}
+
+/// Compute a file leaf from its chunk hashes (Merkle root over the chunks)
+pub fn chunked_leaf(chunk_hashes: &[Hash]) -> Hash {
+    if chunk_hashes.is_empty() {
+        panic!("Cannot compute chunked leaf with zero chunks");
+    }
+    MerkleTree::new(chunk_hashes.to_vec()).root()
+}
+
+/// Mask with the given number of bits set, taken from the top of the word
+///
+/// The high bits of the gear fingerprint depend on the most input bytes.
+fn high_bits_mask(bits: u32) -> u64 {
+    match bits {
+        0 => 0,
+        b if b >= 64 => u64::MAX,
+        b => !(u64::MAX >> b),
+    }
+}
+
+/// Gear table, derived deterministically with splitmix64 so all
+/// implementations agree on cut points
+const GEAR: [u64; 256] = build_gear_table();
+
+const fn build_gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x4d65_726b_5365_616c; // "MerkSeal"
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random test data
+    fn sample_data(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 33) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunks_cover_input() {
+        let data = sample_data(500_000, 1);
+        let chunker = Chunker::default();
+        let chunks = chunker.chunk(&data);
+
+        let mut expected_offset = 0;
+        for chunk in &chunks {
+            assert_eq!(chunk.offset, expected_offset);
+            assert!(chunk.length <= DEFAULT_MAX_SIZE);
+            expected_offset += chunk.length;
+        }
+        assert_eq!(expected_offset, data.len());
+
+        // All but the last chunk respect the minimum size
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert!(chunk.length >= DEFAULT_MIN_SIZE);
+        }
+    }
+
+    #[test]
+    fn test_small_file_leaf_matches_plain_hash() {
+        let chunker = Chunker::default();
+        let data = b"small file";
+        let (leaf, chunks) = chunker.file_leaf(data);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(leaf, hash_data(data));
+
+        let (empty_leaf, _) = chunker.file_leaf(b"");
+        assert_eq!(empty_leaf, hash_data(b""));
+    }
+
+    #[test]
+    fn test_insertion_only_changes_nearby_chunks() {
+        let chunker = Chunker::default();
+        let original = sample_data(400_000, 7);
+
+        let mut edited = original.clone();
+        edited.splice(200_000..200_000, b"inserted bytes".iter().copied());
+
+        let before: Vec<Hash> = chunker.chunk(&original).iter().map(|c| c.hash).collect();
+        let after: Vec<Hash> = chunker.chunk(&edited).iter().map(|c| c.hash).collect();
+
+        let shared = after.iter().filter(|h| before.contains(h)).count();
+        assert!(shared + 3 >= before.len(), "shared {} of {}", shared, before.len());
+
+        let (leaf_before, _) = chunker.file_leaf(&original);
+        let (leaf_after, _) = chunker.file_leaf(&edited);
+        assert_ne!(leaf_before, leaf_after);
+    }
+
+    #[test]
+    fn test_streaming_matches_whole_input() {
+        let chunker = Chunker::default();
+        let data = sample_data(300_000, 11);
+        let (expected_leaf, expected) = chunker.file_leaf(&data);
+
+        for piece in [1, 1000, 4096, 70_000, 300_000] {
+            let mut streaming = StreamingChunker::new(chunker.clone());
+            let mut chunks = Vec::new();
+            for part in data.chunks(piece) {
+                chunks.extend(streaming.update(part));
+            }
+            let (rest, leaf) = streaming.finish();
+            chunks.extend(rest);
+
+            assert_eq!(leaf, expected_leaf);
+            assert_eq!(chunks.iter().map(|(c, _)| *c).collect::<Vec<_>>(), expected);
+            for (chunk, bytes) in &chunks {
+                assert_eq!(&data[chunk.offset..chunk.offset + chunk.length], &bytes[..]);
+            }
+        }
+
+        let (empty, leaf) = StreamingChunker::new(chunker).finish();
+        assert_eq!(empty.len(), 1);
+        assert_eq!(leaf, hash_data(b""));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_config() {
+        Chunker::new(ChunkerConfig {
+            min_size: 1024,
+            avg_size: 3000,
+            max_size: 8192,
+        });
+    }
+}
@@ -0,0 +1,255 @@
+//! Self-describing multihash and CIDv1 encoding of roots and leaves
+//!
+//! Leaves are encoded with the `raw` codec, so the leaf of a file that fits
+//! in one IPFS block (`--raw-leaves`, sha2-256) is the same CID IPFS assigns
+//! to it. Roots use a MerkSeal codec from the multicodec private-use range,
+//! which pins the tree scheme (padding, pair hashing) into the identifier.
+//! CIDs are rendered as base32 multibase strings (`b...`).
+
+use crate::Hash;
+use std::fmt;
+use std::str::FromStr;
+
+/// Multihash code for sha2-256
+pub const MULTIHASH_SHA2_256: u64 = 0x12;
+/// Multicodec for raw binary content (file leaves)
+pub const CODEC_RAW: u64 = 0x55;
+/// Multicodec for MerkSeal v1 tree roots (private-use range)
+pub const CODEC_MERKSEAL_ROOT_V1: u64 = 0x30_0001;
+
+/// CID version emitted by this crate
+const CID_VERSION: u64 = 1;
+/// Multibase prefix for lowercase base32 without padding
+const MULTIBASE_BASE32: char = 'b';
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// A CIDv1 over a sha2-256 digest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cid {
+    /// Content codec (`CODEC_RAW` or `CODEC_MERKSEAL_ROOT_V1`)
+    pub codec: u64,
+    /// sha2-256 digest
+    pub digest: Hash,
+}
+
+impl Cid {
+    /// CID for a file leaf hash
+    pub fn leaf(hash: Hash) -> Self {
+        Self {
+            codec: CODEC_RAW,
+            digest: hash,
+        }
+    }
+
+    /// CID for a MerkSeal tree root
+    pub fn root(hash: Hash) -> Self {
+        Self {
+            codec: CODEC_MERKSEAL_ROOT_V1,
+            digest: hash,
+        }
+    }
+
+    /// Check whether this CID identifies a MerkSeal tree root
+    pub fn is_root(&self) -> bool {
+        self.codec == CODEC_MERKSEAL_ROOT_V1
+    }
+
+    /// Binary CID: version, codec and multihash as varints
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(40);
+        write_varint(&mut bytes, CID_VERSION);
+        write_varint(&mut bytes, self.codec);
+        bytes.extend_from_slice(&encode_multihash(&self.digest));
+        bytes
+    }
+
+    /// Parse a binary CID
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut rest = bytes;
+        let version = read_varint(&mut rest)?;
+        if version != CID_VERSION {
+            return Err(format!("Unsupported CID version {}", version));
+        }
+        let codec = read_varint(&mut rest)?;
+        let digest = decode_multihash(rest)?;
+        Ok(Self { codec, digest })
+    }
+}
+
+impl fmt::Display for Cid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", MULTIBASE_BASE32, base32_encode(&self.to_bytes()))
+    }
+}
+
+impl FromStr for Cid {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let encoded = s
+            .strip_prefix(MULTIBASE_BASE32)
+            .ok_or_else(|| "Only base32 (b...) CIDs are supported".to_string())?;
+        Self::from_bytes(&base32_decode(encoded)?)
+    }
+}
+
+/// Encode a sha2-256 digest as a multihash (code, length, digest)
+pub fn encode_multihash(hash: &Hash) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(34);
+    write_varint(&mut bytes, MULTIHASH_SHA2_256);
+    write_varint(&mut bytes, hash.len() as u64);
+    bytes.extend_from_slice(hash);
+    bytes
+}
+
+/// Decode a sha2-256 multihash, rejecting other hash functions
+pub fn decode_multihash(bytes: &[u8]) -> Result<Hash, String> {
+    let mut rest = bytes;
+    let code = read_varint(&mut rest)?;
+    if code != MULTIHASH_SHA2_256 {
+        return Err(format!("Unsupported multihash code 0x{:x}", code));
+    }
+    let len = read_varint(&mut rest)? as usize;
+    if len != 32 || rest.len() != 32 {
+        return Err(format!("Expected 32-byte sha2-256 digest, got {}", rest.len()));
+    }
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(rest);
+    Ok(hash)
+}
+
+/// CID string for a file leaf hash
+pub fn leaf_cid(hash: &Hash) -> String {
+    Cid::leaf(*hash).to_string()
+}
+
+/// CID string for a tree root
+pub fn root_cid(hash: &Hash) -> String {
+    Cid::root(*hash).to_string()
+}
+
+/// Parse a root CID string back into the root hash
+pub fn parse_root_cid(s: &str) -> Result<Hash, String> {
+    let cid: Cid = s.parse()?;
+    if !cid.is_root() {
+        return Err(format!("CID codec 0x{:x} is not a MerkSeal root", cid.codec));
+    }
+    Ok(cid.digest)
+}
+
+/// Parse a leaf CID string back into the leaf hash
+pub fn parse_leaf_cid(s: &str) -> Result<Hash, String> {
+    let cid: Cid = s.parse()?;
+    if cid.codec != CODEC_RAW {
+        return Err(format!("CID codec 0x{:x} is not a raw leaf", cid.codec));
+    }
+    Ok(cid.digest)
+}
+
+/// Append an unsigned LEB128 varint
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Read an unsigned LEB128 varint, advancing the input
+fn read_varint(input: &mut &[u8]) -> Result<u64, String> {
+    let mut value = 0u64;
+    for (i, byte) in input.iter().enumerate().take(9) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            *input = &input[i + 1..];
+            return Ok(value);
+        }
+    }
+    Err("Truncated or oversized varint".to_string())
+}
+
+fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for &byte in data {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    out
+}
+
+fn base32_decode(s: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(s.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in s.chars() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a as char == c.to_ascii_lowercase())
+            .ok_or_else(|| format!("Invalid base32 character '{}'", c))?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_data;
+
+    #[test]
+    fn test_leaf_cid_matches_ipfs_raw_block() {
+        // `echo -n "hello world" | ipfs add --raw-leaves --cid-version 1 -n`
+        let cid = leaf_cid(&hash_data(b"hello world"));
+        assert_eq!(cid, "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e");
+        assert_eq!(parse_leaf_cid(&cid).unwrap(), hash_data(b"hello world"));
+    }
+
+    #[test]
+    fn test_root_cid_roundtrip() {
+        let root = hash_data(b"root");
+        let cid = root_cid(&root);
+        assert!(cid.starts_with('b'));
+        assert_eq!(parse_root_cid(&cid).unwrap(), root);
+
+        // A root CID is not accepted as a leaf and vice versa
+        assert!(parse_leaf_cid(&cid).is_err());
+        assert!(parse_root_cid(&leaf_cid(&root)).is_err());
+    }
+
+    #[test]
+    fn test_multihash_encoding() {
+        let hash = hash_data(b"data");
+        let mh = encode_multihash(&hash);
+        assert_eq!(&mh[..2], &[0x12, 0x20]);
+        assert_eq!(decode_multihash(&mh).unwrap(), hash);
+
+        let mut other = mh.clone();
+        other[0] = 0x13; // sha2-512
+        assert!(decode_multihash(&other).is_err());
+    }
+
+    #[test]
+    fn test_rejects_malformed() {
+        assert!("zabc".parse::<Cid>().is_err());
+        assert!("b!!!".parse::<Cid>().is_err());
+        assert!("bafkrei".parse::<Cid>().is_err());
+    }
+}
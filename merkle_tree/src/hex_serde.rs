@@ -0,0 +1,39 @@
+//! Serde helpers for encoding hashes as lowercase hex strings
+
+use crate::{hex_to_hash, Hash};
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// Serialize a hash as a hex string
+pub fn serialize<S: Serializer>(hash: &Hash, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode(hash))
+}
+
+/// Deserialize a hash from a hex string (with or without `0x`)
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Hash, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    hex_to_hash(s.trim_start_matches("0x")).map_err(serde::de::Error::custom)
+}
+
+/// Serde helpers for lists of hashes
+pub mod vec {
+    use super::*;
+    use serde::ser::SerializeSeq;
+
+    /// Serialize hashes as a list of hex strings
+    pub fn serialize<S: Serializer>(hashes: &[Hash], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(hashes.len()))?;
+        for hash in hashes {
+            seq.serialize_element(&hex::encode(hash))?;
+        }
+        seq.end()
+    }
+
+    /// Deserialize a list of hex strings into hashes
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Hash>, D::Error> {
+        let strings = Vec::<String>::deserialize(deserializer)?;
+        strings
+            .iter()
+            .map(|s| hex_to_hash(s.trim_start_matches("0x")).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
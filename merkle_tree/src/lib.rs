@@ -1,5 +1,15 @@
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+pub mod chunking;
+pub mod cid;
+pub mod hex_serde;
+pub mod render;
+pub mod vectors;
+
+pub use chunking::{chunked_leaf, Chunk, Chunker, ChunkerConfig, StreamingChunker};
+pub use cid::{leaf_cid, parse_leaf_cid, parse_root_cid, root_cid, Cid};
+
 /// 32-byte hash type (SHA-256 output)
 pub type Hash = [u8; 32];
 
@@ -7,9 +17,11 @@ pub type Hash = [u8; 32];
 #[derive(Debug, Clone)]
 pub struct MerkleTree {
     /// All nodes stored in breadth-first order (root at index 0)
+    ///
+    /// The children of node `i` are at `2i + 1` and `2i + 2`; the padded
+    /// leaves occupy the last `width` slots.
     nodes: Vec<Hash>,
     /// Number of leaf nodes
-    #[allow(dead_code)]
     leaf_count: usize,
 }
 
@@ -28,29 +40,15 @@ impl MerkleTree {
             leaf_hashes.push([0u8; 32]); // Pad with zero hashes
         }
         
-        let mut nodes = Vec::new();
-        let mut current_level = leaf_hashes;
+        // Leaves go in the last `width` slots, parents are filled bottom-up
+        let width = leaf_hashes.len();
+        let mut nodes = vec![[0u8; 32]; 2 * width - 1];
+        nodes[width - 1..].copy_from_slice(&leaf_hashes);
         
-        // Build tree bottom-up
-        while current_level.len() > 1 {
-            let mut next_level = Vec::new();
-            
-            for i in (0..current_level.len()).step_by(2) {
-                let left = &current_level[i];
-                let right = &current_level[i + 1];
-                let parent = hash_pair(left, right);
-                next_level.push(parent);
-            }
-            
-            current_level = next_level;
+        for i in (0..width - 1).rev() {
+            nodes[i] = hash_pair(&nodes[2 * i + 1], &nodes[2 * i + 2]);
         }
         
-        // Root is the last remaining node
-        let root = current_level[0];
-        
-        // For simplicity, just store root (can expand to full tree if needed)
-        nodes.push(root);
-        
         Self { nodes, leaf_count }
     }
     
@@ -63,10 +61,104 @@ impl MerkleTree {
     pub fn root_hex(&self) -> String {
         hex::encode(self.root())
     }
+    
+    /// Get the root as a self-describing CIDv1 string
+    pub fn root_cid(&self) -> String {
+        root_cid(&self.root())
+    }
+    
+    /// Number of leaves the tree was built from (excluding padding)
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+    
+    /// Leaf hashes the tree was built from (excluding padding)
+    pub fn leaves(&self) -> &[Hash] {
+        let start = self.width() - 1;
+        &self.nodes[start..start + self.leaf_count]
+    }
+    
+    /// Build an inclusion proof for the leaf at `index`
+    pub fn proof(&self, index: usize) -> Option<MerkleProof> {
+        if index >= self.leaf_count {
+            return None;
+        }
+        
+        let mut node = self.width() - 1 + index;
+        let leaf = self.nodes[node];
+        let mut siblings = Vec::new();
+        
+        while node > 0 {
+            let sibling = if node % 2 == 1 { node + 1 } else { node - 1 };
+            siblings.push(self.nodes[sibling]);
+            node = (node - 1) / 2;
+        }
+        
+        Some(MerkleProof {
+            leaf_index: index,
+            leaf,
+            siblings,
+        })
+    }
+    
+    /// Number of leaf slots after padding to a power of two
+    fn width(&self) -> usize {
+        self.nodes.len().div_ceil(2)
+    }
+    
+    /// Leaf slot (including padding) of a node index, if the node is a leaf
+    fn leaf_slot(&self, node: usize) -> Option<usize> {
+        node.checked_sub(self.width() - 1)
+    }
+}
+
+/// Inclusion proof for a single leaf
+///
+/// Siblings are ordered from the leaf level up to just below the root. The
+/// bits of `leaf_index` decide whether each sibling is hashed on the left
+/// (bit set) or on the right (bit clear).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    /// Position of the leaf in the batch
+    pub leaf_index: usize,
+    /// Leaf hash being proven
+    #[serde(with = "hex_serde")]
+    pub leaf: Hash,
+    /// Sibling hashes from the leaf level upwards
+    #[serde(with = "hex_serde::vec")]
+    pub siblings: Vec<Hash>,
+}
+
+impl MerkleProof {
+    /// Recompute the root implied by this proof
+    pub fn compute_root(&self) -> Hash {
+        let mut current = self.leaf;
+        let mut index = self.leaf_index;
+        
+        for sibling in &self.siblings {
+            current = if index % 2 == 1 {
+                hash_pair(sibling, &current)
+            } else {
+                hash_pair(&current, sibling)
+            };
+            index /= 2;
+        }
+        
+        current
+    }
+    
+    /// Check the proof against an expected root
+    pub fn verify(&self, root: &Hash) -> bool {
+        // Reject indices that do not fit in a tree of this depth
+        if self.siblings.len() < usize::BITS as usize && self.leaf_index >> self.siblings.len() != 0 {
+            return false;
+        }
+        self.compute_root() == *root
+    }
 }
 
 /// Hash a pair of nodes to create parent hash
-fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+pub(crate) fn hash_pair(left: &Hash, right: &Hash) -> Hash {
     let mut hasher = Sha256::new();
     hasher.update(left);
     hasher.update(right);
@@ -80,6 +172,28 @@ pub fn hash_data(data: &[u8]) -> Hash {
     hasher.finalize().into()
 }
 
+/// Incremental version of `hash_data` for data that arrives in pieces
+#[derive(Debug, Clone, Default)]
+pub struct DataHasher {
+    hasher: Sha256,
+}
+
+impl DataHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Feed the next piece of data
+    pub fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+    
+    /// Finish hashing; equal to `hash_data` over everything fed in
+    pub fn finalize(self) -> Hash {
+        self.hasher.finalize().into()
+    }
+}
+
 /// Convert hex string to Hash
 pub fn hex_to_hash(hex_str: &str) -> Result<Hash, String> {
     let bytes = hex::decode(hex_str).map_err(|e| e.to_string())?;
@@ -129,6 +243,51 @@ mod tests {
         assert_ne!(tree1.root(), tree2.root());
     }
 
+    #[test]
+    fn test_proofs_verify_for_every_leaf() {
+        for count in 1..=9 {
+            let leaves: Vec<Hash> = (0..count)
+                .map(|i| hash_data(format!("file{}", i).as_bytes()))
+                .collect();
+            let tree = MerkleTree::new(leaves.clone());
+            assert_eq!(tree.leaves(), &leaves[..]);
+            
+            for (i, leaf) in leaves.iter().enumerate() {
+                let proof = tree.proof(i).unwrap();
+                assert_eq!(proof.leaf, *leaf);
+                assert!(proof.verify(&tree.root()));
+            }
+            assert!(tree.proof(count).is_none());
+        }
+    }
+
+    #[test]
+    fn test_tampered_proof_fails() {
+        let leaves: Vec<Hash> = (0..5u8).map(|i| hash_data(&[i])).collect();
+        let tree = MerkleTree::new(leaves);
+        
+        let mut proof = tree.proof(2).unwrap();
+        proof.leaf = hash_data(b"other");
+        assert!(!proof.verify(&tree.root()));
+        
+        let mut proof = tree.proof(2).unwrap();
+        proof.leaf_index = 3;
+        assert!(!proof.verify(&tree.root()));
+        
+        let mut proof = tree.proof(2).unwrap();
+        proof.leaf_index += 8;
+        assert!(!proof.verify(&tree.root()));
+    }
+
+    #[test]
+    fn test_incremental_hash_matches_hash_data() {
+        let mut hasher = DataHasher::new();
+        hasher.update(b"hello ");
+        hasher.update(b"");
+        hasher.update(b"world");
+        assert_eq!(hasher.finalize(), hash_data(b"hello world"));
+    }
+
     #[test]
     fn test_hex_conversion() {
         let hash = hash_data(b"test");
@@ -0,0 +1,229 @@
+//! Graphviz DOT and ASCII rendering of trees and proof paths for audits
+//!
+//! Hashes are abbreviated to their first eight hex characters. Leaves can be
+//! labelled with file names; padding leaves are marked as such.
+
+use crate::{Hash, MerkleProof, MerkleTree};
+use std::fmt::Write;
+
+/// Number of hex characters shown for abbreviated hashes
+pub const ABBREV_LEN: usize = 8;
+
+/// Abbreviate a hash to its first few hex characters
+pub fn abbrev(hash: &Hash) -> String {
+    let mut s = hex::encode(hash);
+    s.truncate(ABBREV_LEN);
+    s
+}
+
+/// Render the whole tree as an indented ASCII diagram
+///
+/// `labels[i]` is shown next to leaf `i`; missing labels are left blank.
+pub fn tree_to_ascii(tree: &MerkleTree, labels: &[&str]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "root {}", abbrev(&tree.nodes[0]));
+    write_ascii_children(tree, 0, "", labels, &mut out);
+    out
+}
+
+fn write_ascii_children(tree: &MerkleTree, node: usize, prefix: &str, labels: &[&str], out: &mut String) {
+    let children = [2 * node + 1, 2 * node + 2];
+    for (i, &child) in children.iter().enumerate() {
+        if child >= tree.nodes.len() {
+            return;
+        }
+        let last = i == 1;
+        let branch = if last { "└── " } else { "├── " };
+        let _ = writeln!(out, "{}{}{}", prefix, branch, node_label(tree, child, labels));
+
+        let next_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+        write_ascii_children(tree, child, &next_prefix, labels, out);
+    }
+}
+
+/// Render the whole tree as a Graphviz DOT digraph
+pub fn tree_to_dot(tree: &MerkleTree, labels: &[&str]) -> String {
+    let mut out = String::from("digraph merkle {\n  node [shape=box, fontname=\"monospace\"];\n");
+
+    for i in 0..tree.nodes.len() {
+        let style = match tree.leaf_slot(i) {
+            Some(slot) if slot >= tree.leaf_count => ", style=dashed, color=gray",
+            Some(_) => ", style=filled, fillcolor=lightyellow",
+            None if i == 0 => ", style=bold",
+            None => "",
+        };
+        let _ = writeln!(
+            out,
+            "  n{} [label=\"{}\"{}];",
+            i,
+            escape_dot(&node_label(tree, i, labels)),
+            style
+        );
+    }
+    for i in 0..tree.nodes.len() {
+        for child in [2 * i + 1, 2 * i + 2] {
+            if child < tree.nodes.len() {
+                let _ = writeln!(out, "  n{} -> n{};", i, child);
+            }
+        }
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+/// Render a proof path from a leaf to the root as ASCII
+///
+/// Each step shows the sibling, which side it is hashed on, and the parent it
+/// produces. The final line reports whether the path ends at `root`.
+pub fn proof_to_ascii(proof: &MerkleProof, root: &Hash, label: &str) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "leaf #{} {}  {}", proof.leaf_index, abbrev(&proof.leaf), label);
+
+    for (current, sibling, sibling_left, parent) in proof_steps(proof) {
+        let (left, right) = if sibling_left {
+            (abbrev(&sibling), abbrev(&current))
+        } else {
+            (abbrev(&current), abbrev(&sibling))
+        };
+        let side = if sibling_left { "left" } else { "right" };
+        let _ = writeln!(
+            out,
+            "  │ + sibling {} ({})  H({} | {})",
+            abbrev(&sibling),
+            side,
+            left,
+            right
+        );
+        let _ = writeln!(out, "  ▼ {}", abbrev(&parent));
+    }
+
+    let computed = proof.compute_root();
+    let status = if proof.verify(root) { "✓ matches" } else { "✗ MISMATCH" };
+    let _ = writeln!(out, "root {}  {} {}", abbrev(&computed), status, abbrev(root));
+    out
+}
+
+/// Render a proof path from a leaf to the root as a Graphviz DOT digraph
+pub fn proof_to_dot(proof: &MerkleProof, root: &Hash, label: &str) -> String {
+    let mut out = String::from("digraph proof {\n  rankdir=BT;\n  node [shape=box, fontname=\"monospace\"];\n");
+    let leaf_label = if label.is_empty() {
+        abbrev(&proof.leaf)
+    } else {
+        format!("{}\\n{}", abbrev(&proof.leaf), escape_dot(label))
+    };
+    let _ = writeln!(out, "  p0 [label=\"{}\", style=filled, fillcolor=lightyellow];", leaf_label);
+
+    for (level, (_, sibling, sibling_left, parent)) in proof_steps(proof).into_iter().enumerate() {
+        let next = level + 1;
+        let parent_style = if next == proof.siblings.len() {
+            if proof.verify(root) {
+                ", style=\"filled,bold\", fillcolor=palegreen"
+            } else {
+                ", style=\"filled,bold\", fillcolor=salmon"
+            }
+        } else {
+            ""
+        };
+        let _ = writeln!(out, "  s{} [label=\"{}\", style=dashed];", level, abbrev(&sibling));
+        let _ = writeln!(out, "  p{} [label=\"{}\"{}];", next, abbrev(&parent), parent_style);
+        let _ = writeln!(out, "  p{} -> p{};", level, next);
+        let _ = writeln!(
+            out,
+            "  s{} -> p{} [label=\"{}\"];",
+            level,
+            next,
+            if sibling_left { "left" } else { "right" }
+        );
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+/// Walk a proof, yielding (current, sibling, sibling_is_left, parent) per level
+fn proof_steps(proof: &MerkleProof) -> Vec<(Hash, Hash, bool, Hash)> {
+    let mut steps = Vec::with_capacity(proof.siblings.len());
+    let mut current = proof.leaf;
+    let mut index = proof.leaf_index;
+
+    for sibling in &proof.siblings {
+        let sibling_left = index % 2 == 1;
+        let parent = if sibling_left {
+            crate::hash_pair(sibling, &current)
+        } else {
+            crate::hash_pair(&current, sibling)
+        };
+        steps.push((current, *sibling, sibling_left, parent));
+        current = parent;
+        index /= 2;
+    }
+
+    steps
+}
+
+fn node_label(tree: &MerkleTree, node: usize, labels: &[&str]) -> String {
+    match tree.leaf_slot(node) {
+        Some(slot) if slot >= tree.leaf_count => format!("{} (padding)", abbrev(&tree.nodes[node])),
+        Some(slot) => match labels.get(slot).filter(|l| !l.is_empty()) {
+            Some(label) => format!("#{} {}  {}", slot, abbrev(&tree.nodes[node]), label),
+            None => format!("#{} {}", slot, abbrev(&tree.nodes[node])),
+        },
+        None => abbrev(&tree.nodes[node]),
+    }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_data;
+
+    fn sample_tree() -> MerkleTree {
+        MerkleTree::new(vec![hash_data(b"a"), hash_data(b"b"), hash_data(b"c")])
+    }
+
+    #[test]
+    fn test_tree_ascii_lists_every_leaf() {
+        let tree = sample_tree();
+        let ascii = tree_to_ascii(&tree, &["a.txt", "b.txt", "c.txt"]);
+
+        assert!(ascii.starts_with(&format!("root {}", abbrev(&tree.root()))));
+        assert!(ascii.contains("a.txt"));
+        assert!(ascii.contains("c.txt"));
+        assert!(ascii.contains("(padding)"));
+        assert_eq!(ascii.lines().count(), 7);
+    }
+
+    #[test]
+    fn test_tree_dot_is_well_formed() {
+        let tree = sample_tree();
+        let dot = tree_to_dot(&tree, &["a \"quoted\".txt"]);
+
+        assert!(dot.starts_with("digraph merkle {"));
+        assert!(dot.trim_end().ends_with('}'));
+        assert_eq!(dot.matches(" -> ").count(), 6);
+        assert!(dot.contains("a \\\"quoted\\\".txt"));
+    }
+
+    #[test]
+    fn test_proof_rendering() {
+        let tree = sample_tree();
+        let proof = tree.proof(2).unwrap();
+
+        let ascii = proof_to_ascii(&proof, &tree.root(), "c.txt");
+        assert!(ascii.contains("leaf #2"));
+        assert!(ascii.contains("✓ matches"));
+        assert_eq!(ascii.matches("sibling").count(), 2);
+
+        let wrong = proof_to_ascii(&proof, &hash_data(b"other"), "c.txt");
+        assert!(wrong.contains("MISMATCH"));
+
+        let dot = proof_to_dot(&proof, &tree.root(), "c.txt");
+        assert!(dot.contains("palegreen"));
+        assert_eq!(dot.matches(" -> ").count(), 4);
+    }
+}
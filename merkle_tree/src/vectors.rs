@@ -0,0 +1,242 @@
+//! Canonical test vectors for cross-implementation conformance
+//!
+//! The Rust crate is the reference implementation. `generate()` builds the
+//! full vector suite and `test-vectors/merkseal-v1.json` is its committed
+//! JSON export; the TypeScript SDK and scripts should check their root and
+//! proof computation against that file.
+
+use crate::chunking::{Chunker, ChunkerConfig};
+use crate::{chunked_leaf, hash_data, hex_serde, Hash, MerkleProof, MerkleTree};
+use serde::{Deserialize, Serialize};
+
+/// Largest leaf count covered by the sequential cases
+pub const MAX_SEQUENTIAL_LEAVES: usize = 17;
+
+/// Path of the committed vector file, relative to the crate root
+pub const VECTORS_PATH: &str = "test-vectors/merkseal-v1.json";
+
+/// Complete vector suite
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVectors {
+    /// Vector format version
+    pub version: u32,
+    /// Human-readable description of the tree scheme
+    pub scheme: SchemeDescription,
+    /// Tree cases (leaf sets, roots and proofs)
+    pub trees: Vec<TreeCase>,
+    /// Content-defined chunking cases
+    pub chunking: Vec<ChunkingCase>,
+}
+
+/// Rules every implementation must follow to match these vectors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemeDescription {
+    pub leaf_hash: String,
+    pub node_hash: String,
+    pub padding: String,
+    pub root_encoding: String,
+    pub onchain_encoding: String,
+    pub proof_order: String,
+}
+
+/// A single tree: its leaves, root and a proof for every leaf
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeCase {
+    pub name: String,
+    pub description: String,
+    #[serde(with = "hex_serde::vec")]
+    pub leaves: Vec<Hash>,
+    #[serde(with = "hex_serde")]
+    pub root: Hash,
+    /// Root as a CIDv1 string (MerkSeal root codec)
+    pub root_cid: String,
+    pub proofs: Vec<MerkleProof>,
+}
+
+/// Chunk boundaries and file leaf for a deterministic input
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkingCase {
+    pub name: String,
+    /// Input bytes are `input_len` outputs of the documented LCG, seeded with `seed`
+    pub seed: u64,
+    pub input_len: usize,
+    pub config: ChunkerConfig,
+    pub chunk_lengths: Vec<usize>,
+    #[serde(with = "hex_serde::vec")]
+    pub chunk_hashes: Vec<Hash>,
+    #[serde(with = "hex_serde")]
+    pub file_leaf: Hash,
+}
+
+/// Generate the full canonical vector suite
+pub fn generate() -> TestVectors {
+    let mut trees = Vec::new();
+
+    for count in 1..=MAX_SEQUENTIAL_LEAVES {
+        let leaves = (0..count)
+            .map(|i| hash_data(format!("merkseal-leaf-{}", i).as_bytes()))
+            .collect();
+        trees.push(tree_case(
+            &format!("sequential-{}", count),
+            &format!("Leaf set of size {}, leaf i = sha256(\"merkseal-leaf-{{i}}\")", count),
+            leaves,
+        ));
+    }
+
+    let a = hash_data(b"a");
+    let b = hash_data(b"b");
+    let zero = [0u8; 32];
+
+    trees.push(tree_case(
+        "duplicate-leaves",
+        "The same leaf twice; duplicates are kept and hashed as separate leaves",
+        vec![a, a],
+    ));
+    trees.push(tree_case(
+        "duplicate-leaves-odd",
+        "Duplicate leaves in an odd-sized set",
+        vec![a, b, a],
+    ));
+    trees.push(tree_case(
+        "zero-leaf",
+        "A single all-zero leaf; a one-leaf tree's root is the leaf itself",
+        vec![zero],
+    ));
+    trees.push(tree_case(
+        "zero-leaf-padding-collision",
+        "An explicit zero leaf is indistinguishable from padding: this root equals the root of [a, b, a]",
+        vec![a, b, a, zero],
+    ));
+    trees.push(tree_case(
+        "empty-file-leaf",
+        "Leaf of an empty file, sha256(\"\")",
+        vec![hash_data(b"")],
+    ));
+
+    let chunking = vec![
+        chunking_case("small-input", 1, 1_000, ChunkerConfig::default()),
+        chunking_case("default-config", 2, 300_000, ChunkerConfig::default()),
+        chunking_case(
+            "small-config",
+            3,
+            50_000,
+            ChunkerConfig {
+                min_size: 256,
+                avg_size: 1024,
+                max_size: 4096,
+            },
+        ),
+    ];
+
+    TestVectors {
+        version: 1,
+        scheme: SchemeDescription {
+            leaf_hash: "sha256(file bytes); chunked files use the tree root over sha256(chunk) values".to_string(),
+            node_hash: "sha256(left || right), 32-byte raw inputs".to_string(),
+            padding: "leaves are padded with 32 zero bytes up to the next power of two".to_string(),
+            root_encoding: "lowercase hex, 64 chars, no 0x prefix; or CIDv1 base32 with codec 0x300001 and a sha2-256 multihash".to_string(),
+            onchain_encoding: "bytes32, hex with 0x prefix".to_string(),
+            proof_order: "siblings from leaf level upwards; bit k of leaf_index set means sibling k is on the left".to_string(),
+        },
+        trees,
+        chunking,
+    }
+}
+
+/// Deterministic input bytes for chunking cases (64-bit LCG, high byte of each state)
+pub fn lcg_bytes(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 56) as u8
+        })
+        .collect()
+}
+
+fn tree_case(name: &str, description: &str, leaves: Vec<Hash>) -> TreeCase {
+    let tree = MerkleTree::new(leaves.clone());
+    let proofs = (0..leaves.len())
+        .map(|i| tree.proof(i).expect("leaf index in range"))
+        .collect();
+
+    TreeCase {
+        name: name.to_string(),
+        description: description.to_string(),
+        leaves,
+        root: tree.root(),
+        root_cid: tree.root_cid(),
+        proofs,
+    }
+}
+
+fn chunking_case(name: &str, seed: u64, input_len: usize, config: ChunkerConfig) -> ChunkingCase {
+    let data = lcg_bytes(seed, input_len);
+    let chunks = Chunker::new(config).chunk(&data);
+    let chunk_hashes: Vec<Hash> = chunks.iter().map(|c| c.hash).collect();
+
+    ChunkingCase {
+        name: name.to_string(),
+        seed,
+        input_len,
+        config,
+        chunk_lengths: chunks.iter().map(|c| c.length).collect(),
+        file_leaf: chunked_leaf(&chunk_hashes),
+        chunk_hashes,
+    }
+}
+
+/// Serialize vectors as pretty-printed JSON with a trailing newline
+pub fn to_json(vectors: &TestVectors) -> String {
+    let mut json = serde_json::to_string_pretty(vectors).expect("vectors serialize");
+    json.push('\n');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Regenerate the vectors and diff them against the committed file.
+    ///
+    /// Run with `MERKSEAL_UPDATE_VECTORS=1` to rewrite the file after an
+    /// intentional scheme change.
+    #[test]
+    fn test_committed_vectors_match() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(VECTORS_PATH);
+        let generated = to_json(&generate());
+
+        if std::env::var("MERKSEAL_UPDATE_VECTORS").is_ok() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, &generated).unwrap();
+        }
+
+        let committed = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", path.display(), e));
+        let parsed: TestVectors = serde_json::from_str(&committed).unwrap();
+        assert_eq!(parsed, generate(), "vectors drifted; rerun with MERKSEAL_UPDATE_VECTORS=1");
+        assert_eq!(committed, generated);
+    }
+
+    #[test]
+    fn test_vectors_are_self_consistent() {
+        let vectors = generate();
+        for case in &vectors.trees {
+            for proof in &case.proofs {
+                assert!(proof.verify(&case.root), "{} leaf {}", case.name, proof.leaf_index);
+            }
+        }
+
+        let collision = vectors.trees.iter().find(|c| c.name == "zero-leaf-padding-collision").unwrap();
+        let odd = vectors.trees.iter().find(|c| c.name == "duplicate-leaves-odd").unwrap();
+        assert_eq!(collision.root, odd.root);
+
+        for case in &vectors.chunking {
+            let total: usize = case.chunk_lengths.iter().sum();
+            assert_eq!(total, case.input_len);
+        }
+    }
+}
@@ -15,6 +15,10 @@ export interface MerkSealConfig {
     registryAddress: string;
     /** Private key for signing transactions (optional, for anchoring) */
     privateKey?: string;
+    /** API key sent as `X-API-Key` (optional, for servers with auth enabled) */
+    apiKey?: string;
+    /** JWT or API key sent as `Authorization: Bearer` (optional, takes precedence over `apiKey`) */
+    token?: string;
 }
 
 /**
@@ -24,9 +28,97 @@ export interface BatchMetadata {
     local_batch_id: number;
     root: string;
     file_count: number;
+    /** URI of the published canonical manifest, anchored as metaURI */
     suggested_meta_uri: string;
+    /** CIDv1 of the canonical manifest */
+    manifest_cid?: string;
     registry_address: string;
+    /** Built from leaf hashes via submitLeaves(); no file contents are stored */
+    hash_only?: boolean;
+    created_at?: number;
     mantle_batch_id?: number;
+    /** Present when the batch was anchored as part of an aggregate root */
+    aggregate?: AggregateInclusion;
+}
+
+/**
+ * Proof from a batch root to the aggregate root registered on-chain
+ */
+export interface AggregateInclusion {
+    aggregate_id: number;
+    /** Aggregate root (hex, no 0x) */
+    root: string;
+    leaf_index: number;
+    /** Batch root (hex, no 0x) */
+    leaf: string;
+    siblings: string[];
+}
+
+/**
+ * Server-signed upload receipt (see `GET /.well-known/merkseal-keys`)
+ */
+export interface Receipt {
+    version: number;
+    /** Address of the signing key */
+    key_id: string;
+    /** Tenant whose batch this is (absent from v1 receipts) */
+    tenant?: string;
+    batch_id: number;
+    /** Merkle root, or null while a rolling batch is still open */
+    root: string | null;
+    /** Receipted leaf hashes (hex), in leaf order */
+    leaves: string[];
+    issued_at: number;
+    /** EIP-191 signature over the receipt message */
+    signature: string;
+}
+
+/**
+ * Receipt for files appended to a rolling batch that is not sealed yet
+ */
+export interface PendingReceipt {
+    batch_id: number;
+    /** Unix time by which the batch is sealed */
+    seals_by?: number;
+    /** Uploaded files under their names in the batch */
+    files: { name: string; original_name: string; size: number; leaf: string; cid: string }[];
+    /** Proof URL paths, one per file */
+    proofs: string[];
+    /** Signed receipt for the appended leaves (no root yet) */
+    receipt: Receipt;
+}
+
+/**
+ * Inclusion proof from `GET /batches/{id}/proof/{name}`
+ */
+export interface FileProof {
+    local_batch_id: number;
+    file: string;
+    leaf_index: number;
+    leaf: string;
+    siblings: string[];
+    root: string;
+    mantle_batch_id?: number;
+    tx_hash?: string;
+    registry_address: string;
+    aggregate?: AggregateInclusion;
+}
+
+/**
+ * A batch containing a looked-up hash (`GET /lookup/{hash}`)
+ */
+export interface LookupMatch {
+    batch_id: number;
+    /** Whether the hash is the batch root, a leaf, or the whole-file hash of a chunked file */
+    match: 'root' | 'leaf' | 'file';
+    root: string;
+    leaf_index?: number;
+    file?: string;
+    /** Proof URL path for leaf and file matches */
+    proof?: string;
+    anchored: boolean;
+    mantle_batch_id?: number;
+    tx_hash?: string;
 }
 
 /**
@@ -96,6 +188,82 @@ export class MerkSeal {
      * @returns Batch metadata including Merkle root
      */
     async upload(filePaths: string[]): Promise<BatchMetadata> {
+        const data = await this.postFiles(filePaths);
+        if (data.pending) {
+            throw new Error('Server collects uploads into rolling batches; use uploadPending()');
+        }
+        return data.batch;
+    }
+
+    /**
+     * Seal a hash-only batch from leaf hashes computed locally
+     *
+     * The documents never leave this machine; only their SHA-256 hashes are sent.
+     * @param leaves Leaf hashes (hex) in tree order, with optional opaque labels
+     * @returns Batch metadata including Merkle root
+     */
+    async submitLeaves(leaves: { leaf: string; label?: string }[]): Promise<BatchMetadata> {
+        const response = await axios.post(
+            `${this.config.serverUrl}/leaves`,
+            { leaves },
+            { headers: this.authHeaders() }
+        );
+
+        if (!response.data.success) {
+            throw new Error('Submitting leaves failed');
+        }
+
+        return response.data.batch;
+    }
+
+    /**
+     * Upload files to a server running rolling batches
+     * @param filePaths Array of file paths to upload
+     * @returns Pending receipt; pass its `proofs` to waitForProof()
+     */
+    async uploadPending(filePaths: string[]): Promise<PendingReceipt> {
+        const data = await this.postFiles(filePaths);
+        if (!data.pending) {
+            throw new Error('Server sealed the upload as its own batch; use upload()');
+        }
+        return data;
+    }
+
+    /**
+     * Wait until a pending file's batch is sealed and return its proof
+     * @param proofPath Proof URL path from a pending receipt
+     * @param timeoutSecs Give up after this many seconds
+     */
+    async waitForProof(proofPath: string, timeoutSecs: number = 300): Promise<FileProof> {
+        const deadline = Date.now() + timeoutSecs * 1000;
+        while (true) {
+            const remaining = Math.ceil((deadline - Date.now()) / 1000);
+            const wait = Math.max(0, Math.min(60, remaining));
+            const response = await axios.get(`${this.config.serverUrl}${proofPath}`, {
+                params: { wait },
+                headers: this.authHeaders(),
+            });
+            if (response.status === 200) {
+                return response.data;
+            }
+            if (remaining <= 0) {
+                throw new Error(`Batch for ${proofPath} was not sealed within ${timeoutSecs}s`);
+            }
+        }
+    }
+
+    /** Credentials for the MerkSeal server, if configured */
+    private authHeaders(): Record<string, string> {
+        if (this.config.token) {
+            return { Authorization: `Bearer ${this.config.token}` };
+        }
+        if (this.config.apiKey) {
+            return { 'X-API-Key': this.config.apiKey };
+        }
+        return {};
+    }
+
+    private async postFiles(filePaths: string[]): Promise<any> {
         const FormData = require('form-data');
         const formData = new FormData();
 
@@ -109,7 +277,7 @@ export class MerkSeal {
             `${this.config.serverUrl}/upload`,
             formData,
             {
-                headers: formData.getHeaders()
+                headers: { ...formData.getHeaders(), ...this.authHeaders() }
             }
         );
 
@@ -117,7 +285,7 @@ export class MerkSeal {
             throw new Error('Upload failed');
         }
 
-        return response.data.batch;
+        return response.data;
     }
 
     /**
@@ -192,14 +360,22 @@ export class MerkSeal {
         const [onchainRoot, owner, metaURI, timestamp] = await this.contract.getBatch(mantleBatchId);
         const onchainRootHex = `0x${onchainRoot.slice(2)}`;
 
-        // Get local batch metadata
-        const metadataPath = path.join(process.cwd(), 'batches', localBatchId.toString(), 'metadata.json');
-        const metadata = JSON.parse(fs.readFileSync(metadataPath, 'utf8'));
+        // Get local batch metadata from the server
+        const metadata = await this.getLocalBatch(localBatchId);
 
         const localRoot = metadata.root.startsWith('0x') ? metadata.root : `0x${metadata.root}`;
 
+        // Aggregated batches were anchored through a root-of-roots
+        let anchoredRoot = localRoot;
+        if (metadata.aggregate) {
+            const { root, leaf } = metadata.aggregate;
+            const proofValid = `0x${leaf}`.toLowerCase() === localRoot.toLowerCase()
+                && computeProofRoot(metadata.aggregate) === `0x${root}`.toLowerCase();
+            anchoredRoot = proofValid ? `0x${root}` : '';
+        }
+
         // Compare roots
-        const rootsMatch = localRoot.toLowerCase() === onchainRootHex.toLowerCase();
+        const rootsMatch = anchoredRoot.toLowerCase() === onchainRootHex.toLowerCase();
 
         return {
             valid: rootsMatch,
@@ -210,6 +386,31 @@ export class MerkSeal {
         };
     }
 
+    /**
+     * Get the manifest of a batch stored on the MerkSeal server
+     * @param localBatchId Local batch ID from server
+     * @returns Batch metadata
+     */
+    async getLocalBatch(localBatchId: number): Promise<BatchMetadata> {
+        const response = await axios.get(`${this.config.serverUrl}/batches/${localBatchId}`, {
+            headers: this.authHeaders(),
+        });
+        return response.data;
+    }
+
+    /**
+     * Find every sealed batch containing a document, leaf or root
+     * @param hash SHA-256 (hex, with or without 0x) or a leaf/root CID
+     * @returns Matching batches, empty if none
+     */
+    async lookup(hash: string): Promise<LookupMatch[]> {
+        const response = await axios.get(`${this.config.serverUrl}/lookup/${hash}`, {
+            headers: this.authHeaders(),
+            validateStatus: (status) => status === 200 || status === 404,
+        });
+        return response.status === 200 ? response.data.matches : [];
+    }
+
     /**
      * Get batch information from Mantle
      * @param mantleBatchId Mantle batch ID
@@ -256,6 +457,8 @@ export function createMerkSeal(overrides?: Partial<MerkSealConfig>): MerkSeal {
         mantleRpc: process.env.MANTLE_RPC_URL || 'https://rpc.testnet.mantle.xyz',
         registryAddress: process.env.MERKLE_BATCH_REGISTRY_ADDRESS || '',
         privateKey: process.env.PRIVATE_KEY,
+        apiKey: process.env.MERKSEAL_API_KEY,
+        token: process.env.MERKSEAL_TOKEN,
         ...overrides
     };
 
@@ -268,3 +471,58 @@ export function createMerkSeal(overrides?: Partial<MerkSealConfig>): MerkSeal {
 
 // Export types
 export default MerkSeal;
+
+/**
+ * Recompute the root implied by a Merkle proof (SHA-256, sibling on the left
+ * when the index bit is set)
+ */
+export function computeProofRoot(proof: { leaf_index: number; leaf: string; siblings: string[] }): string {
+    let current = `0x${proof.leaf}`;
+    let index = proof.leaf_index;
+    for (const sibling of proof.siblings) {
+        const pair = index % 2 === 1 ? [`0x${sibling}`, current] : [current, `0x${sibling}`];
+        current = ethers.sha256(ethers.concat(pair));
+        index = Math.floor(index / 2);
+    }
+    return current.toLowerCase();
+}
+
+/**
+ * Leaf hash of a document as the server computes it (SHA-256, hex without 0x),
+ * for use with submitLeaves()
+ */
+export function leafHash(data: Uint8Array): string {
+    return ethers.sha256(data).slice(2);
+}
+
+/**
+ * Text a receipt signature covers; matches the server's `Receipt::message`
+ */
+export function receiptMessage(receipt: Receipt): string {
+    const leaves = ethers.concat(receipt.leaves.map((leaf) => `0x${leaf}`));
+    return [
+        `MerkSeal receipt v${receipt.version}`,
+        `key: ${receipt.key_id.toLowerCase()}`,
+        // v1 receipts predate tenants and have no tenant line
+        ...(receipt.version === 1 ? [] : [`tenant: ${receipt.tenant ?? ''}`]),
+        `batch: ${receipt.batch_id}`,
+        `root: ${receipt.root ?? 'pending'}`,
+        `leaves: ${receipt.leaves.length} sha256:${ethers.sha256(leaves).slice(2)}`,
+        `issued_at: ${receipt.issued_at}`,
+    ].join('\n');
+}
+
+/**
+ * Check a receipt's signature offline
+ * @param receipt Receipt from an upload, leaves or seal response
+ * @param keyIds Published key IDs (`GET /.well-known/merkseal-keys`); when
+ *   given, the signer must be one of them
+ * @returns Whether the receipt was signed by its key
+ */
+export function verifyReceipt(receipt: Receipt, keyIds?: string[]): boolean {
+    const signer = ethers.verifyMessage(receiptMessage(receipt), receipt.signature);
+    if (signer.toLowerCase() !== receipt.key_id.toLowerCase()) {
+        return false;
+    }
+    return !keyIds || keyIds.some((id) => id.toLowerCase() === signer.toLowerCase());
+}
@@ -0,0 +1,270 @@
+use async_trait::async_trait;
+use ethers::prelude::*;
+use ethers::providers::RpcError;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::utils::keccak256;
+use mantle_config::MantleConfig;
+use merkle_tree::Hash;
+
+use super::AnchorError;
+
+abigen!(
+    MerkleBatchRegistry,
+    r#"[
+        function registerBatch(bytes32 root, string metaURI) external returns (uint256)
+        function getBatch(uint256 batchId) external view returns (bytes32 root, address owner, string memory metaURI, uint256 timestamp)
+        event BatchRegistered(uint256 indexed batchId, bytes32 indexed root, address indexed owner, string metaURI)
+    ]"#
+);
+
+/// A mined `registerBatch` transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinedTx {
+    pub block_number: u64,
+    pub block_hash: H256,
+    /// `false` if the transaction reverted
+    pub success: bool,
+    /// Batch ID from the `BatchRegistered` event, if the registry emitted one
+    pub mantle_batch_id: Option<u64>,
+}
+
+/// A signed `registerBatch` transaction, not yet broadcast
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTx {
+    pub tx_hash: H256,
+    pub raw: Bytes,
+}
+
+/// Why a transaction could not be signed or was not accepted by the node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubmitError {
+    /// The nonce has already been used by a mined transaction
+    NonceTooLow,
+    /// The node did not answer (timeout, dropped connection); the transaction
+    /// may still have reached it
+    Unanswered(String),
+    /// Any other RPC or signing failure
+    Other(String),
+}
+
+/// The chain operations the anchoring queue needs
+///
+/// Kept small so the queue's state machine can be tested without a node.
+#[async_trait]
+pub trait Chain: Send + Sync {
+    /// Next nonce of the signer, counting pending transactions
+    async fn pending_nonce(&self) -> Result<u64, AnchorError>;
+
+    /// Current network gas price
+    async fn gas_price(&self) -> Result<U256, AnchorError>;
+
+    /// Latest block number
+    async fn block_number(&self) -> Result<u64, AnchorError>;
+
+    /// Sign `registerBatch(root, meta_uri)` without sending it
+    ///
+    /// Split from [`Chain::broadcast`] so the queue can record the hash first.
+    async fn sign(&self, root: Hash, meta_uri: &str, nonce: u64, gas_price: U256) -> Result<SignedTx, SubmitError>;
+
+    /// Send a signed transaction to the node
+    async fn broadcast(&self, tx: &SignedTx) -> Result<(), SubmitError>;
+
+    /// Receipt of a transaction, if it is in the canonical chain
+    async fn receipt(&self, tx_hash: H256) -> Result<Option<MinedTx>, AnchorError>;
+}
+
+/// `Chain` backed by an RPC node and a local signing key
+pub struct EthersChain {
+    provider: Provider<Http>,
+    wallet: LocalWallet,
+    registry: MerkleBatchRegistry<Provider<Http>>,
+}
+
+impl EthersChain {
+    /// Connect to the configured RPC with the anchoring key
+    pub fn new(mantle: &MantleConfig, private_key: &str) -> Result<Self, AnchorError> {
+        let provider = Provider::<Http>::try_from(mantle.rpc_url.as_str())
+            .map_err(|e| AnchorError::Config(format!("Invalid RPC URL: {}", e)))?;
+        let wallet: LocalWallet = private_key
+            .trim()
+            .parse()
+            .map_err(|_| AnchorError::Config("Invalid anchoring private key".to_string()))?;
+        let registry: Address = mantle
+            .registry_address
+            .parse()
+            .map_err(|e| AnchorError::Config(format!("Invalid registry address: {}", e)))?;
+
+        Ok(Self {
+            registry: MerkleBatchRegistry::new(registry, provider.clone().into()),
+            wallet: wallet.with_chain_id(mantle.chain_id),
+            provider,
+        })
+    }
+
+    /// Address that signs anchoring transactions
+    pub fn signer(&self) -> Address {
+        self.wallet.address()
+    }
+
+    /// Registry contract (read-only)
+    #[allow(dead_code)]
+    pub fn registry(&self) -> &MerkleBatchRegistry<Provider<Http>> {
+        &self.registry
+    }
+}
+
+#[async_trait]
+impl Chain for EthersChain {
+    async fn pending_nonce(&self) -> Result<u64, AnchorError> {
+        let nonce = self
+            .provider
+            .get_transaction_count(self.signer(), Some(BlockNumber::Pending.into()))
+            .await
+            .map_err(rpc)?;
+        Ok(nonce.as_u64())
+    }
+
+    async fn gas_price(&self) -> Result<U256, AnchorError> {
+        self.provider.get_gas_price().await.map_err(rpc)
+    }
+
+    async fn block_number(&self) -> Result<u64, AnchorError> {
+        Ok(self.provider.get_block_number().await.map_err(rpc)?.as_u64())
+    }
+
+    async fn sign(&self, root: Hash, meta_uri: &str, nonce: u64, gas_price: U256) -> Result<SignedTx, SubmitError> {
+        let call = self
+            .registry
+            .register_batch(root, meta_uri.to_string())
+            .from(self.signer());
+        let gas = call
+            .estimate_gas()
+            .await
+            .map_err(|e| SubmitError::Other(e.to_string()))?;
+
+        // Legacy transactions keep fee bumping to a single number
+        let tx: TypedTransaction = TransactionRequest::new()
+            .from(self.signer())
+            .to(self.registry.address())
+            .data(call.calldata().unwrap_or_default())
+            .nonce(nonce)
+            .gas(gas * 6 / 5)
+            .gas_price(gas_price)
+            .chain_id(self.wallet.chain_id())
+            .into();
+        let signature = self
+            .wallet
+            .sign_transaction(&tx)
+            .await
+            .map_err(|e| SubmitError::Other(e.to_string()))?;
+        let raw = tx.rlp_signed(&signature);
+        Ok(SignedTx {
+            tx_hash: H256::from(keccak256(&raw)),
+            raw,
+        })
+    }
+
+    async fn broadcast(&self, tx: &SignedTx) -> Result<(), SubmitError> {
+        match self.provider.send_raw_transaction(tx.raw.clone()).await {
+            Ok(_) => Ok(()),
+            // Only a JSON-RPC error response proves the node turned it down
+            Err(e) if !RpcError::is_error_response(&e) => Err(SubmitError::Unanswered(e.to_string())),
+            Err(e) => match classify_send_error(&e.to_string()) {
+                // The node already has this exact transaction
+                None => Ok(()),
+                Some(err) => Err(err),
+            },
+        }
+    }
+
+    async fn receipt(&self, tx_hash: H256) -> Result<Option<MinedTx>, AnchorError> {
+        let Some(receipt) = self.provider.get_transaction_receipt(tx_hash).await.map_err(rpc)? else {
+            return Ok(None);
+        };
+        let (Some(block_number), Some(block_hash)) = (receipt.block_number, receipt.block_hash) else {
+            return Ok(None);
+        };
+        Ok(Some(MinedTx {
+            block_number: block_number.as_u64(),
+            block_hash,
+            success: receipt.status == Some(U64::one()),
+            mantle_batch_id: registered_batch_id(&receipt.logs, self.registry.address()),
+        }))
+    }
+}
+
+/// Map a node's rejection message to a `SubmitError` (`None` if already known)
+fn classify_send_error(message: &str) -> Option<SubmitError> {
+    let lower = message.to_lowercase();
+    if lower.contains("already known") || lower.contains("known transaction") {
+        None
+    } else if lower.contains("nonce too low") || lower.contains("nonce has already been used") {
+        Some(SubmitError::NonceTooLow)
+    } else {
+        Some(SubmitError::Other(message.to_string()))
+    }
+}
+
+/// Find the batch ID in the `BatchRegistered` event emitted by `registry`
+pub fn registered_batch_id(logs: &[Log], registry: Address) -> Option<u64> {
+    logs.iter()
+        .filter(|log| log.address == registry)
+        .find_map(|log| parse_log::<BatchRegisteredFilter>(log.clone()).ok())
+        .map(|event| event.batch_id.as_u64())
+}
+
+fn rpc(e: ProviderError) -> AnchorError {
+    AnchorError::Rpc(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::{encode, Token};
+    use merkle_tree::hash_data;
+
+    fn registered_log(registry: Address, batch_id: u64) -> Log {
+        Log {
+            address: registry,
+            topics: vec![
+                BatchRegisteredFilter::signature(),
+                H256::from_low_u64_be(batch_id),
+                H256::from(hash_data(b"root")),
+                H256::from(Address::repeat_byte(0x22)),
+            ],
+            data: encode(&[Token::String("ipfs://meta".into())]).into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_batch_id_from_event() {
+        let registry = Address::repeat_byte(0x11);
+        let other = Address::repeat_byte(0x33);
+        let logs = vec![
+            Log {
+                address: registry,
+                topics: vec![H256::repeat_byte(0x99)],
+                ..Default::default()
+            },
+            registered_log(other, 5),
+            registered_log(registry, 42),
+        ];
+
+        assert_eq!(registered_batch_id(&logs, registry), Some(42));
+        assert_eq!(registered_batch_id(&logs[..2], registry), None);
+    }
+
+    #[test]
+    fn test_classify_send_errors() {
+        assert_eq!(classify_send_error("(code: -32000, message: already known)"), None);
+        assert_eq!(
+            classify_send_error("nonce too low: next nonce 5, tx nonce 3"),
+            Some(SubmitError::NonceTooLow)
+        );
+        assert!(matches!(
+            classify_send_error("insufficient funds for gas * price + value"),
+            Some(SubmitError::Other(_))
+        ));
+    }
+}
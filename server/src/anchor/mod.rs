@@ -0,0 +1,347 @@
+//! Server-side anchoring of sealed batch roots to `MerkleBatchRegistry`
+//!
+//! Sealed batches are put on a durable queue. A background worker signs
+//! `registerBatch(root, metaURI)` with the server's key, manages nonces
+//! locally so several batches can be in flight, replaces stuck transactions
+//! with higher fees and follows each one until it is final. Only then is the
+//! on-chain batch ID written into the batch manifest.
+
+mod chain;
+mod queue;
+
+pub use chain::{BatchRegisteredFilter, Chain, EthersChain};
+pub use queue::{AnchorQueue, QueueSettings};
+
+use crate::quotas::Limit;
+use crate::store::{BatchStore, StoreError};
+use crate::tenants::Tenant;
+use actix_web::{get, post, web, HttpResponse};
+use async_trait::async_trait;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// When the server anchors batches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnchorMode {
+    /// Only on `POST /batches/{id}/anchor`
+    Manual,
+    /// Right after every upload is sealed (and on request)
+    Auto,
+}
+
+/// Anchoring settings
+#[derive(Clone)]
+pub struct AnchorConfig {
+    pub mode: AnchorMode,
+    /// Hex private key of the account that pays for `registerBatch`
+    pub private_key: String,
+    /// File holding the persistent job queue
+    pub queue_path: String,
+    /// How often the worker checks on jobs
+    pub poll_interval: Duration,
+    pub queue: QueueSettings,
+}
+
+impl fmt::Debug for AnchorConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnchorConfig")
+            .field("mode", &self.mode)
+            .field("private_key", &"<redacted>")
+            .field("queue_path", &self.queue_path)
+            .field("poll_interval", &self.poll_interval)
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
+/// Where the worker finds the batches of each tenant on the queue
+#[async_trait]
+pub trait TenantStores: Send + Sync {
+    async fn store(&self, tenant: &str) -> Result<Arc<dyn BatchStore>, StoreError>;
+}
+
+/// A lone store holds the default tenant's batches
+#[cfg(test)]
+#[async_trait]
+impl TenantStores for Arc<dyn BatchStore> {
+    async fn store(&self, tenant: &str) -> Result<Arc<dyn BatchStore>, StoreError> {
+        match tenant {
+            crate::tenants::DEFAULT_TENANT => Ok(self.clone()),
+            other => Err(StoreError::NotFound(format!("Tenant {}", other))),
+        }
+    }
+}
+
+/// Advance the queue forever, one tick per `interval`
+pub async fn run_worker(
+    queue: Arc<AnchorQueue>,
+    chain: Arc<dyn Chain>,
+    stores: Arc<dyn TenantStores>,
+    interval: Duration,
+) {
+    loop {
+        if let Err(e) = queue.tick(chain.as_ref(), stores.as_ref(), crate::unix_now()).await {
+            eprintln!("✗ Anchoring worker: {}", e);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// POST /batches/{id}/anchor - Queue a sealed batch for anchoring on Mantle
+#[post("/batches/{id}/anchor")]
+async fn anchor_batch(
+    path: web::Path<u64>,
+    tenant: Tenant,
+    queue: Option<web::Data<Arc<AnchorQueue>>>,
+) -> HttpResponse {
+    let Some(queue) = queue else {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "success": false,
+            "error": "Anchoring is not enabled on this server (set MERKSEAL_ANCHOR)"
+        }));
+    };
+
+    let batch_id = path.into_inner();
+    let quota = match tenant.reserve(Limit::AnchorsPerDay, 1) {
+        Ok(quota) => quota,
+        Err(e) => return e.response(),
+    };
+    match queue.enqueue(tenant.store.as_ref(), &tenant.tenant, batch_id, crate::unix_now()).await {
+        Ok(job) => {
+            quota.settle(crate::unix_now());
+            println!("⚓ Batch {} queued for anchoring", job.key());
+            HttpResponse::Accepted().json(serde_json::json!({
+                "success": true,
+                "anchor": job
+            }))
+        }
+        Err(e) => e.response(),
+    }
+}
+
+/// GET /aggregates/{id} - Member batches and anchoring state of an aggregate root
+///
+/// Only aggregates holding one of the caller's batches are shown. Members of
+/// other tenants are listed as `null`; their roots stay in `leaves` so the
+/// tree can still be rebuilt.
+#[get("/aggregates/{id}")]
+async fn get_aggregate(
+    path: web::Path<u64>,
+    tenant: Tenant,
+    queue: Option<web::Data<Arc<AnchorQueue>>>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let aggregate = queue
+        .and_then(|queue| queue.aggregate(id))
+        .filter(|aggregate| aggregate.members.iter().any(|m| m.tenant == tenant.tenant));
+    match aggregate {
+        Some(aggregate) => {
+            let members: Vec<Option<u64>> = aggregate
+                .members
+                .iter()
+                .map(|m| (m.tenant == tenant.tenant).then_some(m.batch_id))
+                .collect();
+            let mut aggregate = serde_json::json!(aggregate);
+            aggregate["members"] = serde_json::json!(members);
+            HttpResponse::Ok().json(serde_json::json!({
+                "success": true,
+                "aggregate": aggregate
+            }))
+        }
+        None => HttpResponse::NotFound().json(serde_json::json!({
+            "success": false,
+            "error": format!("Aggregate {} not found", id)
+        })),
+    }
+}
+
+/// Anchoring failures
+#[derive(Debug)]
+pub enum AnchorError {
+    /// Bad key, RPC URL or registry address
+    Config(String),
+    /// Reading or updating the batch failed
+    Store(StoreError),
+    /// The batch already has an on-chain ID
+    AlreadyAnchored(u64, u64),
+    /// The batch is already on the anchoring queue
+    Queued(u64),
+    /// RPC error
+    Rpc(String),
+    /// Persisting the queue failed
+    Io(io::Error),
+}
+
+impl AnchorError {
+    /// JSON error response with the matching status code
+    pub fn response(&self) -> HttpResponse {
+        let mut builder = match self {
+            AnchorError::Store(StoreError::NotFound(_)) => HttpResponse::NotFound(),
+            AnchorError::AlreadyAnchored(..) | AnchorError::Queued(_) => HttpResponse::Conflict(),
+            AnchorError::Rpc(_) => HttpResponse::BadGateway(),
+            AnchorError::Config(_) | AnchorError::Store(_) | AnchorError::Io(_) => {
+                HttpResponse::InternalServerError()
+            }
+        };
+        builder.json(serde_json::json!({
+            "success": false,
+            "error": self.to_string()
+        }))
+    }
+}
+
+impl fmt::Display for AnchorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnchorError::Config(msg) => write!(f, "{}", msg),
+            AnchorError::Store(e) => write!(f, "{}", e),
+            AnchorError::AlreadyAnchored(id, mantle_id) => {
+                write!(f, "Batch {} is already anchored as Mantle batch {}", id, mantle_id)
+            }
+            AnchorError::Queued(id) => write!(f, "Batch {} is already queued for anchoring", id),
+            AnchorError::Rpc(e) => write!(f, "Mantle RPC error: {}", e),
+            AnchorError::Io(e) => write!(f, "Anchoring queue error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AnchorError {}
+
+impl From<StoreError> for AnchorError {
+    fn from(e: StoreError) -> Self {
+        AnchorError::Store(e)
+    }
+}
+
+impl From<io::Error> for AnchorError {
+    fn from(e: io::Error) -> Self {
+        AnchorError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batches::load_metadata;
+    use crate::store::FsStore;
+    use crate::tenants::DEFAULT_TENANT;
+    use crate::{BatchMetadata, FileEntry};
+    use ethers::prelude::*;
+    use ethers::utils::Anvil;
+    use mantle_config::MantleConfig;
+    use merkle_tree::{hash_data, leaf_cid};
+
+    #[test]
+    fn test_errors_map_to_status_codes() {
+        assert_eq!(AnchorError::AlreadyAnchored(1, 2).response().status(), 409);
+        assert_eq!(AnchorError::Queued(1).response().status(), 409);
+        assert_eq!(AnchorError::Rpc("timeout".into()).response().status(), 502);
+        assert_eq!(
+            AnchorError::Store(StoreError::NotFound("Batch 9".into())).response().status(),
+            404
+        );
+    }
+
+    /// Runs against a local anvil node with the compiled registry:
+    ///
+    /// ```text
+    /// (cd contracts && forge build)
+    /// cargo test -p server -- --ignored test_anchor_on_anvil
+    /// ```
+    #[tokio::test]
+    #[ignore = "requires anvil on PATH and `forge build` artifacts in contracts/out"]
+    async fn test_anchor_on_anvil() {
+        let artifact: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../contracts/out/MerkleBatchRegistry.sol/MerkleBatchRegistry.json"
+            ))
+            .expect("run `forge build` in contracts/ first"),
+        )
+        .unwrap();
+        let abi: ethers::abi::Abi = serde_json::from_value(artifact["abi"].clone()).unwrap();
+        let bytecode: Bytes = artifact["bytecode"]["object"].as_str().unwrap().parse().unwrap();
+
+        let anvil = Anvil::new().spawn();
+        let key = hex::encode(anvil.keys()[0].to_bytes());
+        let provider = Provider::<Http>::try_from(anvil.endpoint()).unwrap();
+        let deployer = Arc::new(SignerMiddleware::new(
+            provider,
+            LocalWallet::from(anvil.keys()[0].clone()).with_chain_id(anvil.chain_id()),
+        ));
+        let registry = ContractFactory::new(abi, bytecode, deployer)
+            .deploy(())
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+
+        let mantle = MantleConfig::new(anvil.endpoint(), anvil.chain_id(), format!("{:?}", registry.address()));
+        let chain = EthersChain::new(&mantle, &key).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn BatchStore> = Arc::new(FsStore::open(dir.path().join("batches")).unwrap());
+        let queue = AnchorQueue::open(
+            dir.path().join("queue.json"),
+            QueueSettings {
+                finality: 1,
+                stuck_after: 60,
+                max_in_flight: 4,
+                max_attempts: 3,
+                aggregate_every: None,
+            },
+        )
+        .unwrap();
+
+        let id = store.create_batch().await.unwrap();
+        let leaf = hash_data(b"anchored");
+        let metadata = BatchMetadata {
+            local_batch_id: id,
+            root: hex::encode(leaf),
+            root_cid: String::new(),
+            file_count: 1,
+            suggested_meta_uri: "ipfs://test".into(),
+            registry_address: mantle.registry_address.clone(),
+            hash_only: false,
+            manifest_cid: None,
+            created_at: None,
+            mantle_batch_id: None,
+            tx_hash: None,
+            block_number: None,
+            aggregate: None,
+            files: vec![FileEntry {
+                name: "a.txt".into(),
+                original_name: "a.txt".into(),
+                size: 8,
+                leaf: hex::encode(leaf),
+                cid: leaf_cid(&leaf),
+                chunks: None,
+                sha256: None,
+            }],
+        };
+        store.put_file(id, "a.txt", b"anchored").await.unwrap();
+        store.seal(id, &serde_json::to_vec(&metadata).unwrap()).await.unwrap();
+
+        queue.enqueue(store.as_ref(), DEFAULT_TENANT, id, 0).await.unwrap();
+        // Anvil mines on submission: one tick to send, one to see the receipt
+        for now in 0..3 {
+            queue.tick(&chain, &store, now).await.unwrap();
+        }
+
+        let on_disk = load_metadata(store.as_ref(), id).await.unwrap();
+        assert_eq!(on_disk.mantle_batch_id, Some(1));
+        assert!(on_disk.tx_hash.is_some());
+
+        let (root, owner, meta_uri, _) = chain.registry().get_batch(U256::one()).call().await.unwrap();
+        assert_eq!(root, leaf);
+        assert_eq!(owner, chain.signer());
+        assert_eq!(meta_uri, "ipfs://test");
+
+        assert!(matches!(
+            queue.enqueue(store.as_ref(), DEFAULT_TENANT, id, 4).await,
+            Err(AnchorError::AlreadyAnchored(_, 1))
+        ));
+    }
+}
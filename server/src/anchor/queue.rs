@@ -0,0 +1,1408 @@
+use super::chain::{Chain, MinedTx, SignedTx, SubmitError};
+use super::{AnchorError, TenantStores};
+use crate::batches::load_metadata;
+use crate::publish::{self, AggregateManifest, PublishConfig};
+use crate::store::BatchStore;
+use crate::tenants::DEFAULT_TENANT;
+use crate::AggregateInclusion;
+use ethers::types::{H256, U256};
+use merkle_tree::{hex_to_hash, Hash, MerkleTree};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Tuning knobs for the anchoring queue
+#[derive(Debug, Clone, Copy)]
+pub struct QueueSettings {
+    /// Confirmations after which an anchor is final and written to the manifest
+    pub finality: u64,
+    /// Seconds without a receipt before a transaction is replaced with a higher fee
+    pub stuck_after: u64,
+    /// Transactions that may be pending at the same time
+    pub max_in_flight: usize,
+    /// Rejected submissions before a batch is marked failed
+    pub max_attempts: u32,
+    /// Roll queued batches into one aggregate root at most this many seconds
+    /// after the oldest was queued (`None` anchors every batch on its own)
+    pub aggregate_every: Option<u64>,
+}
+
+/// Where a batch is in the anchoring pipeline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum AnchorState {
+    /// Sealed and waiting for a transaction (again, after a reorg)
+    Pending,
+    /// Broadcast, waiting to be mined
+    Submitted { submitted_at: u64 },
+    /// Mined, waiting for enough confirmations
+    Confirmed {
+        tx_hash: H256,
+        block_number: u64,
+        block_hash: H256,
+        confirmations: u64,
+        mantle_batch_id: u64,
+    },
+    /// Deep enough to be treated as permanent
+    Final {
+        tx_hash: H256,
+        block_number: u64,
+        mantle_batch_id: u64,
+    },
+    /// Reverted or repeatedly rejected; needs a new anchor request
+    Failed,
+}
+
+/// Progress of registering one root on-chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Submission {
+    /// Root passed to `registerBatch`
+    #[serde(with = "merkle_tree::hex_serde")]
+    pub root: Hash,
+    pub meta_uri: String,
+    #[serde(flatten)]
+    pub state: AnchorState,
+    /// Nonce reserved for this root's transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<u64>,
+    /// Gas price of the latest transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_price: Option<U256>,
+    /// Every transaction sent for this root, latest last (replacements share a nonce)
+    #[serde(default)]
+    pub tx_hashes: Vec<H256>,
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub updated_at: u64,
+}
+
+impl Submission {
+    fn new(root: Hash, meta_uri: String, now: u64) -> Self {
+        Self {
+            root,
+            meta_uri,
+            state: AnchorState::Pending,
+            nonce: None,
+            gas_price: None,
+            tx_hashes: Vec::new(),
+            attempts: 0,
+            last_error: None,
+            updated_at: now,
+        }
+    }
+}
+
+/// A batch on the queue: its tenant and its ID within the tenant
+///
+/// Serialized as the bare batch ID for the default tenant, so queue files
+/// from before tenants existed still load, and as `"{tenant}/{id}"` otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BatchKey {
+    pub tenant: String,
+    pub batch_id: u64,
+}
+
+impl BatchKey {
+    pub fn new(tenant: &str, batch_id: u64) -> Self {
+        Self {
+            tenant: tenant.to_string(),
+            batch_id,
+        }
+    }
+}
+
+impl fmt::Display for BatchKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.tenant == DEFAULT_TENANT {
+            write!(f, "{}", self.batch_id)
+        } else {
+            write!(f, "{}/{}", self.tenant, self.batch_id)
+        }
+    }
+}
+
+impl Serialize for BatchKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.tenant == DEFAULT_TENANT {
+            serializer.serialize_u64(self.batch_id)
+        } else {
+            serializer.collect_str(self)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for BatchKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeyVisitor;
+
+        impl Visitor<'_> for KeyVisitor {
+            type Value = BatchKey;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a batch ID or \"{tenant}/{batch ID}\"")
+            }
+
+            fn visit_u64<E: de::Error>(self, batch_id: u64) -> Result<BatchKey, E> {
+                Ok(BatchKey::new(DEFAULT_TENANT, batch_id))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<BatchKey, E> {
+                let (tenant, id) = value.split_once('/').unwrap_or((DEFAULT_TENANT, value));
+                let batch_id = id.parse().map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))?;
+                Ok(BatchKey::new(tenant, batch_id))
+            }
+        }
+
+        deserializer.deserialize_any(KeyVisitor)
+    }
+}
+
+fn default_tenant() -> String {
+    DEFAULT_TENANT.to_string()
+}
+
+/// One batch's anchoring job
+///
+/// When the batch is anchored through an aggregate, the on-chain fields
+/// mirror the aggregate's while `root` and `meta_uri` stay the batch's own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnchorJob {
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+    pub batch_id: u64,
+    /// Aggregate this batch was rolled into
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aggregate: Option<u64>,
+    #[serde(flatten)]
+    pub tx: Submission,
+    /// The final anchor has been written to the batch manifest
+    #[serde(default)]
+    pub recorded: bool,
+}
+
+impl AnchorJob {
+    pub fn key(&self) -> BatchKey {
+        BatchKey::new(&self.tenant, self.batch_id)
+    }
+}
+
+/// A root-of-roots over several batch roots, anchored in one transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Aggregate {
+    pub id: u64,
+    /// Member batches, in leaf order
+    pub members: Vec<BatchKey>,
+    /// Member batch roots (the aggregate tree's leaves)
+    #[serde(with = "merkle_tree::hex_serde::vec")]
+    pub leaves: Vec<Hash>,
+    /// CID of the published aggregate manifest, anchored as its metaURI
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest_cid: Option<String>,
+    #[serde(flatten)]
+    pub tx: Submission,
+    /// Every member's manifest has the final anchor
+    #[serde(default)]
+    pub recorded: bool,
+}
+
+/// Whether the worker still has anything to do
+fn is_active(tx: &Submission, recorded: bool) -> bool {
+    match tx.state {
+        AnchorState::Failed => false,
+        AnchorState::Final { .. } => !recorded,
+        _ => true,
+    }
+}
+
+/// Persisted queue contents
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct QueueState {
+    /// Next fresh nonce (`None` until read from the chain)
+    next_nonce: Option<u64>,
+    /// Nonces given back by failed jobs, reused before fresh ones
+    #[serde(default)]
+    free_nonces: BTreeSet<u64>,
+    #[serde(default)]
+    jobs: BTreeMap<BatchKey, AnchorJob>,
+    #[serde(default)]
+    aggregates: BTreeMap<u64, Aggregate>,
+}
+
+/// Durable anchoring job queue
+///
+/// Jobs and the local nonce counter are kept in a JSON file that is rewritten
+/// atomically after every change, so a restart resumes in-flight transactions
+/// instead of sending new ones. Nonces are allocated locally, which lets
+/// several batches be in the mempool at once.
+pub struct AnchorQueue {
+    path: PathBuf,
+    settings: QueueSettings,
+    /// Where aggregate manifests are published
+    publish: Option<PublishConfig>,
+    state: Mutex<QueueState>,
+    /// Only one tick runs at a time; it owns nonce allocation
+    tick_lock: tokio::sync::Mutex<()>,
+}
+
+impl AnchorQueue {
+    /// Open the queue file, creating an empty queue if it does not exist
+    pub fn open(path: impl Into<PathBuf>, settings: QueueSettings) -> io::Result<Self> {
+        let path = path.into();
+        let state = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Corrupt anchoring queue {}: {}", path.display(), e),
+                )
+            })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => QueueState::default(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            path,
+            settings,
+            publish: None,
+            state: Mutex::new(state),
+            tick_lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// Publish aggregate manifests to `publish`; aggregates wait until one is set
+    pub fn with_publisher(mut self, publish: PublishConfig) -> Self {
+        self.publish = Some(publish);
+        self
+    }
+
+    /// Current job for a tenant's batch, if it was ever queued
+    pub fn status(&self, tenant: &str, batch_id: u64) -> Option<AnchorJob> {
+        self.state.lock().unwrap().jobs.get(&BatchKey::new(tenant, batch_id)).cloned()
+    }
+
+    /// An aggregate by ID
+    pub fn aggregate(&self, id: u64) -> Option<Aggregate> {
+        self.state.lock().unwrap().aggregates.get(&id).cloned()
+    }
+
+    /// Queue a tenant's sealed batch for anchoring
+    ///
+    /// Failed jobs can be queued again; anything else already queued is a conflict.
+    pub async fn enqueue(
+        &self,
+        store: &dyn BatchStore,
+        tenant: &str,
+        batch_id: u64,
+        now: u64,
+    ) -> Result<AnchorJob, AnchorError> {
+        let batch = load_metadata(store, batch_id).await?;
+        if let Some(id) = batch.mantle_batch_id {
+            return Err(AnchorError::AlreadyAnchored(batch_id, id));
+        }
+        let root = hex_to_hash(batch.root.trim_start_matches("0x"))
+            .map_err(|e| AnchorError::Config(format!("Invalid root in batch {}: {}", batch_id, e)))?;
+
+        let key = BatchKey::new(tenant, batch_id);
+        let mut state = self.state.lock().unwrap();
+        if let Some(job) = state.jobs.get(&key) {
+            if job.tx.state != AnchorState::Failed {
+                return Err(AnchorError::Queued(batch_id));
+            }
+        }
+
+        let job = AnchorJob {
+            tenant: key.tenant.clone(),
+            batch_id,
+            aggregate: None,
+            tx: Submission::new(root, batch.suggested_meta_uri, now),
+            recorded: false,
+        };
+        state.jobs.insert(key, job.clone());
+        self.save(&state)?;
+        Ok(job)
+    }
+
+    /// Advance every active job by one step
+    ///
+    /// Errors for one job are recorded on it and do not stop the others.
+    pub async fn tick(&self, chain: &dyn Chain, stores: &dyn TenantStores, now: u64) -> Result<(), AnchorError> {
+        let _tick = self.tick_lock.lock().await;
+        let (mut jobs, mut aggregates, mut nonces, next_aggregate) = {
+            let state = self.state.lock().unwrap();
+            let jobs: Vec<AnchorJob> = state
+                .jobs
+                .values()
+                .filter(|j| is_active(&j.tx, j.recorded))
+                .cloned()
+                .collect();
+            let aggregates: Vec<Aggregate> = state
+                .aggregates
+                .values()
+                .filter(|a| is_active(&a.tx, a.recorded))
+                .cloned()
+                .collect();
+            let nonces = Nonces {
+                next: state.next_nonce,
+                free: state.free_nonces.clone(),
+            };
+            let next_aggregate = state.aggregates.keys().next_back().map_or(1, |id| id + 1);
+            (jobs, aggregates, nonces, next_aggregate)
+        };
+        if jobs.is_empty() && aggregates.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(aggregate) = self.roll_up(&mut jobs, next_aggregate, now) {
+            aggregates.push(aggregate);
+        }
+        // An aggregate is only anchored once its manifest is published. Ones
+        // already sent keep their URI so replacements match the original.
+        for aggregate in aggregates
+            .iter_mut()
+            .filter(|a| a.manifest_cid.is_none() && a.tx.tx_hashes.is_empty())
+        {
+            if let Err(e) = self.publish_aggregate(aggregate).await {
+                aggregate.tx.last_error = Some(e.to_string());
+                aggregate.tx.updated_at = now;
+            }
+        }
+
+        let head = chain.block_number().await?;
+        let step = Step {
+            chain,
+            head,
+            now,
+            settings: &self.settings,
+        };
+        let mut in_flight = jobs
+            .iter()
+            .filter(|j| j.aggregate.is_none())
+            .map(|j| &j.tx)
+            .chain(aggregates.iter().map(|a| &a.tx))
+            .filter(|tx| matches!(tx.state, AnchorState::Submitted { .. }))
+            .count();
+        let mut outbox = Vec::new();
+
+        for (index, aggregate) in aggregates.iter_mut().enumerate().filter(|(_, a)| !a.tx.meta_uri.is_empty()) {
+            let label = format!("Aggregate {}", aggregate.id);
+            match step.advance(&label, &mut aggregate.tx, &mut nonces, &mut in_flight).await {
+                Ok(Some(outgoing)) => outbox.push((Target::Aggregate(index), label, outgoing)),
+                Ok(None) => {}
+                Err(e) => {
+                    aggregate.tx.last_error = Some(e.to_string());
+                    aggregate.tx.updated_at = now;
+                }
+            }
+            if let Err(e) = record_aggregate(stores, aggregate).await {
+                aggregate.tx.last_error = Some(e.to_string());
+            }
+        }
+
+        for (index, job) in jobs
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, j)| j.aggregate.is_none() && !self.waiting(j))
+        {
+            let label = format!("Batch {}", job.key());
+            match step.advance(&label, &mut job.tx, &mut nonces, &mut in_flight).await {
+                Ok(Some(outgoing)) => outbox.push((Target::Job(index), label, outgoing)),
+                Ok(None) => {}
+                Err(e) => {
+                    job.tx.last_error = Some(e.to_string());
+                    job.tx.updated_at = now;
+                }
+            }
+            if let Err(e) = record_batch(stores, job).await {
+                job.tx.last_error = Some(e.to_string());
+            }
+        }
+
+        // Nonces, fees and hashes are on disk before anything is broadcast, so
+        // a crash cannot lose track of a transaction that may be mined
+        self.write_back(&jobs, &aggregates, &nonces)?;
+        if outbox.is_empty() {
+            return Ok(());
+        }
+        for (target, label, outgoing) in outbox {
+            let tx = match target {
+                Target::Aggregate(index) => &mut aggregates[index].tx,
+                Target::Job(index) => &mut jobs[index].tx,
+            };
+            if let Err(e) = step.broadcast(&label, tx, outgoing, &mut nonces).await {
+                tx.last_error = Some(e.to_string());
+            }
+        }
+        self.write_back(&jobs, &aggregates, &nonces)?;
+        Ok(())
+    }
+
+    /// Merge a tick's jobs, aggregates and nonces into the queue and persist it
+    fn write_back(&self, jobs: &[AnchorJob], aggregates: &[Aggregate], nonces: &Nonces) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.next_nonce = nonces.next;
+        state.free_nonces = nonces.free.clone();
+        for aggregate in aggregates {
+            state.aggregates.insert(aggregate.id, aggregate.clone());
+        }
+        for job in jobs {
+            let mut job = job.clone();
+            if let Some(aggregate) = job.aggregate.and_then(|id| state.aggregates.get(&id)) {
+                job.tx = Submission {
+                    root: job.tx.root,
+                    meta_uri: job.tx.meta_uri,
+                    ..aggregate.tx.clone()
+                };
+                job.recorded = aggregate.recorded;
+            }
+            state.jobs.insert(job.key(), job);
+        }
+        self.save(&state)
+    }
+
+    /// Whether a job is waiting to be rolled into an aggregate
+    fn waiting(&self, job: &AnchorJob) -> bool {
+        self.settings.aggregate_every.is_some()
+            && job.aggregate.is_none()
+            && job.tx.state == AnchorState::Pending
+            && job.tx.tx_hashes.is_empty()
+    }
+
+    /// Build an aggregate over the waiting jobs once the oldest has waited long enough
+    fn roll_up(&self, jobs: &mut [AnchorJob], id: u64, now: u64) -> Option<Aggregate> {
+        let every = self.settings.aggregate_every?;
+        let oldest = jobs.iter().filter(|j| self.waiting(j)).map(|j| j.tx.updated_at).min()?;
+        if now < oldest.saturating_add(every) {
+            return None;
+        }
+
+        let mut members = Vec::new();
+        let mut leaves = Vec::new();
+        for job in jobs.iter_mut() {
+            if self.waiting(job) {
+                job.aggregate = Some(id);
+                members.push(job.key());
+                leaves.push(job.tx.root);
+            }
+        }
+        let root = MerkleTree::new(leaves.clone()).root();
+        let listed: Vec<String> = members.iter().map(BatchKey::to_string).collect();
+        println!("🌳 Aggregate {} covers {} batches: {}", id, members.len(), listed.join(", "));
+
+        Some(Aggregate {
+            id,
+            members,
+            leaves,
+            manifest_cid: None,
+            // Set once the manifest is published
+            tx: Submission::new(root, String::new(), now),
+            recorded: false,
+        })
+    }
+
+    /// Publish an aggregate's canonical manifest and anchor its URI
+    async fn publish_aggregate(&self, aggregate: &mut Aggregate) -> Result<(), AnchorError> {
+        let publish = self
+            .publish
+            .as_ref()
+            .ok_or_else(|| AnchorError::Config("No manifest publisher for aggregates".to_string()))?;
+        let bytes = AggregateManifest::new(aggregate.id, &aggregate.leaves).to_bytes();
+        let (cid, uri) = publish::publish(bytes, publish)
+            .await
+            .map_err(|e| AnchorError::Config(e.to_string()))?;
+        println!("  Aggregate {} manifest: {}", aggregate.id, uri);
+        aggregate.manifest_cid = Some(cid);
+        aggregate.tx.meta_uri = uri;
+        Ok(())
+    }
+
+    /// Persist the queue atomically (write temp file, fsync, rename)
+    fn save(&self, state: &QueueState) -> io::Result<()> {
+        let tmp = tmp_path(&self.path);
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec_pretty(state).map_err(io::Error::other)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Local nonce allocator state
+struct Nonces {
+    next: Option<u64>,
+    free: BTreeSet<u64>,
+}
+
+impl Nonces {
+    async fn take(&mut self, chain: &dyn Chain) -> Result<u64, AnchorError> {
+        if let Some(nonce) = self.free.pop_first() {
+            return Ok(nonce);
+        }
+        let nonce = match self.next {
+            Some(nonce) => nonce,
+            None => chain.pending_nonce().await?,
+        };
+        self.next = Some(nonce + 1);
+        Ok(nonce)
+    }
+
+    /// Skip past nonces the chain has already used
+    fn resync(&mut self, chain_nonce: u64) {
+        self.next = Some(self.next.unwrap_or(0).max(chain_nonce));
+        self.free.retain(|n| *n >= chain_nonce);
+    }
+}
+
+/// Minimum replacement fee: +12.5% (nodes require at least +10%)
+fn bump(gas_price: U256) -> U256 {
+    gas_price + gas_price / 8 + 1
+}
+
+/// Where a signed transaction's submission sits in a tick's snapshot
+enum Target {
+    Aggregate(usize),
+    Job(usize),
+}
+
+/// A transaction signed during a tick, broadcast once the queue is saved
+struct Outgoing {
+    signed: SignedTx,
+    /// State to return to if the node turns the transaction down
+    resume: AnchorState,
+}
+
+/// Context for advancing submissions during one tick
+struct Step<'a> {
+    chain: &'a dyn Chain,
+    head: u64,
+    now: u64,
+    settings: &'a QueueSettings,
+}
+
+impl Step<'_> {
+    /// Move a submission along, returning a newly signed transaction to broadcast
+    async fn advance(
+        &self,
+        label: &str,
+        tx: &mut Submission,
+        nonces: &mut Nonces,
+        in_flight: &mut usize,
+    ) -> Result<Option<Outgoing>, AnchorError> {
+        let mut outgoing = None;
+        match tx.state.clone() {
+            AnchorState::Pending => {
+                // A transaction from before a reorg or restart may still get mined
+                if let Some((tx_hash, mined)) = self.find_mined(&tx.tx_hashes).await? {
+                    self.on_mined(tx, tx_hash, mined);
+                } else if *in_flight < self.settings.max_in_flight {
+                    outgoing = self.submit(tx, nonces).await?;
+                    if matches!(tx.state, AnchorState::Submitted { .. }) {
+                        *in_flight += 1;
+                    }
+                }
+            }
+            AnchorState::Submitted { submitted_at } => {
+                if let Some((tx_hash, mined)) = self.find_mined(&tx.tx_hashes).await? {
+                    self.on_mined(tx, tx_hash, mined);
+                    *in_flight = in_flight.saturating_sub(1);
+                } else if self.now.saturating_sub(submitted_at) >= self.settings.stuck_after {
+                    outgoing = self.replace(label, tx).await?;
+                }
+            }
+            AnchorState::Confirmed { tx_hash, block_hash, block_number, .. } => {
+                match self.chain.receipt(tx_hash).await? {
+                    Some(mined) if mined.block_hash == block_hash => {
+                        let confirmations = self.confirmations(block_number);
+                        tx.state = self.confirmed_or_final(tx_hash, &mined, confirmations);
+                    }
+                    // Re-mined in a different block on the new branch
+                    Some(mined) => self.on_mined(tx, tx_hash, mined),
+                    None => {
+                        println!("⚠️  Anchor of {} reorged out of block {}", label.to_lowercase(), block_number);
+                        tx.state = AnchorState::Pending;
+                        tx.last_error = Some(format!(
+                            "Transaction {:?} was dropped from block {} by a reorg",
+                            tx_hash, block_number
+                        ));
+                    }
+                }
+                tx.updated_at = self.now;
+            }
+            AnchorState::Final { .. } | AnchorState::Failed => {}
+        }
+        Ok(outgoing)
+    }
+
+    /// Sign the first transaction for a root (or a resend after a reorg)
+    async fn submit(&self, tx: &mut Submission, nonces: &mut Nonces) -> Result<Option<Outgoing>, AnchorError> {
+        let nonce = match tx.nonce {
+            Some(nonce) => nonce,
+            None => {
+                let nonce = nonces.take(self.chain).await?;
+                tx.nonce = Some(nonce);
+                nonce
+            }
+        };
+        let network = self.chain.gas_price().await?;
+        let gas_price = match tx.gas_price {
+            Some(previous) if !tx.tx_hashes.is_empty() => bump(previous).max(network),
+            _ => network,
+        };
+        tx.updated_at = self.now;
+
+        match self.chain.sign(tx.root, &tx.meta_uri, nonce, gas_price).await {
+            Ok(signed) => {
+                tx.tx_hashes.push(signed.tx_hash);
+                tx.gas_price = Some(gas_price);
+                tx.state = AnchorState::Submitted { submitted_at: self.now };
+                Ok(Some(Outgoing {
+                    signed,
+                    resume: AnchorState::Pending,
+                }))
+            }
+            Err(e) => {
+                self.failed_attempt(tx, &e, nonces);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Sign a replacement for a stuck transaction with the same nonce and a higher fee
+    async fn replace(&self, label: &str, tx: &mut Submission) -> Result<Option<Outgoing>, AnchorError> {
+        let Some(nonce) = tx.nonce else {
+            tx.state = AnchorState::Pending;
+            return Ok(None);
+        };
+        let gas_price = bump(tx.gas_price.unwrap_or_default()).max(self.chain.gas_price().await?);
+        tx.attempts += 1;
+        tx.updated_at = self.now;
+        tx.state = AnchorState::Submitted { submitted_at: self.now };
+
+        match self.chain.sign(tx.root, &tx.meta_uri, nonce, gas_price).await {
+            Ok(signed) => {
+                println!(
+                    "⚓ {} replacing stuck transaction (nonce {}, gas price {})",
+                    label, nonce, gas_price
+                );
+                tx.tx_hashes.push(signed.tx_hash);
+                tx.gas_price = Some(gas_price);
+                Ok(Some(Outgoing {
+                    signed,
+                    resume: tx.state.clone(),
+                }))
+            }
+            Err(e) => {
+                tx.last_error = Some(submit_error(&e));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Broadcast a transaction signed and saved earlier in the tick
+    async fn broadcast(
+        &self,
+        label: &str,
+        tx: &mut Submission,
+        outgoing: Outgoing,
+        nonces: &mut Nonces,
+    ) -> Result<(), AnchorError> {
+        let Outgoing { signed, resume } = outgoing;
+        let nonce = tx.nonce.unwrap_or_default();
+        match self.chain.broadcast(&signed).await {
+            Ok(()) => {
+                println!("⚓ {} submitted (nonce {}, tx {:?})", label, nonce, signed.tx_hash);
+                tx.last_error = None;
+            }
+            // It may have reached the node: watch for its receipt, and replace it if none comes
+            Err(SubmitError::Unanswered(e)) => {
+                tx.last_error = Some(format!("No answer to broadcast of {:?}: {}", signed.tx_hash, e));
+            }
+            Err(e) => {
+                // Turned down by the node, so this transaction cannot be mined
+                tx.tx_hashes.retain(|tx_hash| *tx_hash != signed.tx_hash);
+                tx.state = resume;
+                if !tx.tx_hashes.is_empty() {
+                    // An earlier transaction for this nonce may still be (or have been) mined
+                    tx.last_error = Some(submit_error(&e));
+                } else if e == SubmitError::NonceTooLow {
+                    // The nonce was used outside this queue: resync and take a fresh one
+                    tx.nonce = None;
+                    nonces.resync(self.chain.pending_nonce().await?);
+                    tx.last_error = Some(format!("Nonce {} was already used", nonce));
+                } else {
+                    self.failed_attempt(tx, &e, nonces);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Count a rejected attempt, giving up only if nothing signed could still be mined
+    fn failed_attempt(&self, tx: &mut Submission, e: &SubmitError, nonces: &mut Nonces) {
+        tx.attempts += 1;
+        tx.last_error = Some(submit_error(e));
+        if tx.tx_hashes.is_empty() && tx.attempts >= self.settings.max_attempts {
+            if let Some(nonce) = tx.nonce.take() {
+                nonces.free.insert(nonce);
+            }
+            tx.state = AnchorState::Failed;
+        }
+    }
+
+    async fn find_mined(&self, tx_hashes: &[H256]) -> Result<Option<(H256, MinedTx)>, AnchorError> {
+        for tx_hash in tx_hashes.iter().rev() {
+            if let Some(mined) = self.chain.receipt(*tx_hash).await? {
+                return Ok(Some((*tx_hash, mined)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn on_mined(&self, tx: &mut Submission, tx_hash: H256, mined: MinedTx) {
+        tx.updated_at = self.now;
+        if !mined.success {
+            tx.state = AnchorState::Failed;
+            tx.last_error = Some(format!("Transaction {:?} reverted", tx_hash));
+            return;
+        }
+        if mined.mantle_batch_id.is_none() {
+            tx.state = AnchorState::Failed;
+            tx.last_error = Some(format!("Transaction {:?} has no BatchRegistered event", tx_hash));
+            return;
+        }
+        let confirmations = self.confirmations(mined.block_number);
+        tx.state = self.confirmed_or_final(tx_hash, &mined, confirmations);
+        tx.last_error = None;
+    }
+
+    fn confirmations(&self, block_number: u64) -> u64 {
+        // The node may report a receipt before its head moves past the block
+        self.head.max(block_number) - block_number + 1
+    }
+
+    fn confirmed_or_final(&self, tx_hash: H256, mined: &MinedTx, confirmations: u64) -> AnchorState {
+        let mantle_batch_id = mined.mantle_batch_id.unwrap_or_default();
+        if confirmations >= self.settings.finality {
+            AnchorState::Final {
+                tx_hash,
+                block_number: mined.block_number,
+                mantle_batch_id,
+            }
+        } else {
+            AnchorState::Confirmed {
+                tx_hash,
+                block_number: mined.block_number,
+                block_hash: mined.block_hash,
+                confirmations,
+                mantle_batch_id,
+            }
+        }
+    }
+}
+
+/// Write a final single-batch anchor into the batch manifest
+async fn record_batch(stores: &dyn TenantStores, job: &mut AnchorJob) -> Result<(), AnchorError> {
+    let AnchorState::Final { tx_hash, block_number, mantle_batch_id } = job.tx.state else {
+        return Ok(());
+    };
+    if job.recorded {
+        return Ok(());
+    }
+    let store = stores.store(&job.tenant).await?;
+    write_anchor(store.as_ref(), job.batch_id, tx_hash, block_number, mantle_batch_id, None).await?;
+    job.recorded = true;
+    println!("✓ Batch {} anchored as Mantle batch {} (final)", job.key(), mantle_batch_id);
+    Ok(())
+}
+
+/// Write a final aggregate anchor, with each member's proof, into the member manifests
+async fn record_aggregate(stores: &dyn TenantStores, aggregate: &mut Aggregate) -> Result<(), AnchorError> {
+    let AnchorState::Final { tx_hash, block_number, mantle_batch_id } = aggregate.tx.state else {
+        return Ok(());
+    };
+    if aggregate.recorded {
+        return Ok(());
+    }
+
+    let tree = MerkleTree::new(aggregate.leaves.clone());
+    for (index, member) in aggregate.members.iter().enumerate() {
+        let inclusion = AggregateInclusion {
+            aggregate_id: aggregate.id,
+            root: hex::encode(tree.root()),
+            proof: tree.proof(index).expect("member index is within the aggregate"),
+        };
+        let store = stores.store(&member.tenant).await?;
+        write_anchor(store.as_ref(), member.batch_id, tx_hash, block_number, mantle_batch_id, Some(inclusion)).await?;
+    }
+    aggregate.recorded = true;
+    println!(
+        "✓ Aggregate {} anchored as Mantle batch {} (final, {} batches)",
+        aggregate.id,
+        mantle_batch_id,
+        aggregate.members.len()
+    );
+    Ok(())
+}
+
+async fn write_anchor(
+    store: &dyn BatchStore,
+    batch_id: u64,
+    tx_hash: H256,
+    block_number: u64,
+    mantle_batch_id: u64,
+    aggregate: Option<AggregateInclusion>,
+) -> Result<(), AnchorError> {
+    let mut batch = load_metadata(store, batch_id).await?;
+    batch.mantle_batch_id = Some(mantle_batch_id);
+    batch.tx_hash = Some(format!("{:?}", tx_hash));
+    batch.block_number = Some(block_number);
+    batch.aggregate = aggregate;
+    let manifest = serde_json::to_vec_pretty(&batch).unwrap();
+    store.write_manifest(batch_id, &manifest).await?;
+    Ok(())
+}
+
+fn submit_error(e: &SubmitError) -> String {
+    match e {
+        SubmitError::NonceTooLow => "Nonce too low".to_string(),
+        SubmitError::Unanswered(msg) | SubmitError::Other(msg) => msg.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{FsStore, StoreError};
+    use crate::{BatchMetadata, FileEntry};
+    use async_trait::async_trait;
+    use merkle_tree::{hash_data, leaf_cid};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    const SETTINGS: QueueSettings = QueueSettings {
+        finality: 3,
+        stuck_after: 60,
+        max_in_flight: 2,
+        max_attempts: 2,
+        aggregate_every: None,
+    };
+
+    /// In-memory chain: transactions are mined only when the test says so
+    #[derive(Default)]
+    struct FakeChain {
+        inner: Mutex<FakeState>,
+    }
+
+    #[derive(Default)]
+    struct FakeState {
+        head: u64,
+        chain_nonce: u64,
+        gas_price: u64,
+        /// Signed transactions: tx hash to (nonce, gas price)
+        signed: HashMap<H256, (u64, U256)>,
+        /// (tx hash, nonce, gas price) of every transaction that reached the node
+        sent: Vec<(H256, u64, U256)>,
+        mined: HashMap<H256, MinedTx>,
+        next_batch_id: u64,
+        /// Answer to the next broadcast (`Unanswered` still delivers it)
+        reject: Option<SubmitError>,
+        /// Queue file that must hold a transaction's hash before it is broadcast
+        queue_file: Option<PathBuf>,
+    }
+
+    impl FakeChain {
+        fn new() -> Self {
+            let chain = Self::default();
+            chain.inner.lock().unwrap().gas_price = 100;
+            chain
+        }
+
+        fn sent(&self) -> Vec<(H256, u64, U256)> {
+            self.inner.lock().unwrap().sent.clone()
+        }
+
+        /// Mine a transaction in a new block, optionally reverting
+        fn mine(&self, tx_hash: H256, success: bool) {
+            let mut state = self.inner.lock().unwrap();
+            state.head += 1;
+            state.next_batch_id += 1;
+            let mined = MinedTx {
+                block_number: state.head,
+                block_hash: H256::random(),
+                success,
+                mantle_batch_id: success.then_some(state.next_batch_id),
+            };
+            state.mined.insert(tx_hash, mined);
+        }
+
+        fn advance(&self, blocks: u64) {
+            self.inner.lock().unwrap().head += blocks;
+        }
+
+        fn reorg_out(&self, tx_hash: H256) {
+            self.inner.lock().unwrap().mined.remove(&tx_hash);
+        }
+    }
+
+    #[async_trait]
+    impl Chain for FakeChain {
+        async fn pending_nonce(&self) -> Result<u64, AnchorError> {
+            Ok(self.inner.lock().unwrap().chain_nonce)
+        }
+
+        async fn gas_price(&self) -> Result<U256, AnchorError> {
+            Ok(self.inner.lock().unwrap().gas_price.into())
+        }
+
+        async fn block_number(&self) -> Result<u64, AnchorError> {
+            Ok(self.inner.lock().unwrap().head)
+        }
+
+        async fn sign(&self, _root: Hash, _uri: &str, nonce: u64, gas_price: U256) -> Result<SignedTx, SubmitError> {
+            let tx_hash = H256::random();
+            self.inner.lock().unwrap().signed.insert(tx_hash, (nonce, gas_price));
+            Ok(SignedTx {
+                tx_hash,
+                raw: Default::default(),
+            })
+        }
+
+        async fn broadcast(&self, tx: &SignedTx) -> Result<(), SubmitError> {
+            let mut state = self.inner.lock().unwrap();
+            if let Some(path) = &state.queue_file {
+                let saved = fs::read_to_string(path).unwrap_or_default();
+                assert!(saved.contains(&format!("{:?}", tx.tx_hash)), "broadcast before the queue was saved");
+            }
+            let (nonce, gas_price) = state.signed[&tx.tx_hash];
+            match state.reject.take() {
+                Some(SubmitError::Unanswered(e)) => {
+                    state.sent.push((tx.tx_hash, nonce, gas_price));
+                    Err(SubmitError::Unanswered(e))
+                }
+                Some(e) => Err(e),
+                None => {
+                    state.sent.push((tx.tx_hash, nonce, gas_price));
+                    Ok(())
+                }
+            }
+        }
+
+        async fn receipt(&self, tx_hash: H256) -> Result<Option<MinedTx>, AnchorError> {
+            Ok(self.inner.lock().unwrap().mined.get(&tx_hash).cloned())
+        }
+    }
+
+    async fn sealed_batches(store: &dyn BatchStore, count: usize) -> Vec<u64> {
+        let mut ids = Vec::new();
+        for i in 0..count {
+            let id = store.create_batch().await.unwrap();
+            let leaf = hash_data(format!("file {}", i).as_bytes());
+            let metadata = BatchMetadata {
+                local_batch_id: id,
+                root: hex::encode(leaf),
+                root_cid: String::new(),
+                file_count: 1,
+                suggested_meta_uri: format!("ipfs://batch-{}", id),
+                registry_address: String::new(),
+                hash_only: false,
+                manifest_cid: None,
+                created_at: None,
+                mantle_batch_id: None,
+                tx_hash: None,
+                block_number: None,
+                aggregate: None,
+                files: vec![FileEntry {
+                    name: "a.txt".into(),
+                    original_name: "a.txt".into(),
+                    size: 6,
+                    leaf: hex::encode(leaf),
+                    cid: leaf_cid(&leaf),
+                    chunks: None,
+                    sha256: None,
+                }],
+            };
+            store.seal(id, &serde_json::to_vec(&metadata).unwrap()).await.unwrap();
+            ids.push(id);
+        }
+        ids
+    }
+
+    struct Fixture {
+        _dir: tempfile::TempDir,
+        store: Arc<dyn BatchStore>,
+        queue: AnchorQueue,
+        chain: FakeChain,
+        queue_path: PathBuf,
+    }
+
+    fn fixture() -> Fixture {
+        let dir = tempfile::tempdir().unwrap();
+        let queue_path = dir.path().join(".anchor_queue.json");
+        let chain = FakeChain::new();
+        chain.inner.lock().unwrap().queue_file = Some(queue_path.clone());
+        Fixture {
+            store: Arc::new(FsStore::open(dir.path().join("batches")).unwrap()),
+            queue: AnchorQueue::open(&queue_path, SETTINGS).unwrap(),
+            chain,
+            queue_path,
+            _dir: dir,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipelines_nonces_and_finalizes() {
+        let f = fixture();
+        let ids = sealed_batches(f.store.as_ref(), 3).await;
+        f.chain.inner.lock().unwrap().chain_nonce = 7;
+        for id in &ids {
+            f.queue.enqueue(f.store.as_ref(), DEFAULT_TENANT, *id, 0).await.unwrap();
+        }
+        assert!(matches!(f.queue.enqueue(f.store.as_ref(), DEFAULT_TENANT, ids[0], 0).await, Err(AnchorError::Queued(_))));
+
+        // Two in flight at once, with consecutive nonces
+        f.queue.tick(&f.chain, &f.store, 1).await.unwrap();
+        let sent = f.chain.sent();
+        assert_eq!(sent.iter().map(|s| s.1).collect::<Vec<_>>(), vec![7, 8]);
+        assert_eq!(f.queue.status(DEFAULT_TENANT, ids[2]).unwrap().tx.state, AnchorState::Pending);
+
+        f.chain.mine(sent[0].0, true);
+        f.queue.tick(&f.chain, &f.store, 2).await.unwrap();
+        assert!(matches!(
+            f.queue.status(DEFAULT_TENANT, ids[0]).unwrap().tx.state,
+            AnchorState::Confirmed { confirmations: 1, mantle_batch_id: 1, .. }
+        ));
+        assert_eq!(f.chain.sent()[2].1, 9);
+
+        f.chain.advance(2);
+        f.queue.tick(&f.chain, &f.store, 3).await.unwrap();
+        let job = f.queue.status(DEFAULT_TENANT, ids[0]).unwrap();
+        assert!(matches!(job.tx.state, AnchorState::Final { mantle_batch_id: 1, .. }));
+        assert!(job.recorded);
+
+        let metadata = load_metadata(f.store.as_ref(), ids[0]).await.unwrap();
+        assert_eq!(metadata.mantle_batch_id, Some(1));
+        assert_eq!(metadata.tx_hash, Some(format!("{:?}", sent[0].0)));
+        assert!(matches!(
+            f.queue.enqueue(f.store.as_ref(), DEFAULT_TENANT, ids[0], 4).await,
+            Err(AnchorError::AlreadyAnchored(_, 1))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stuck_transaction_is_replaced_with_higher_fee() {
+        let f = fixture();
+        let ids = sealed_batches(f.store.as_ref(), 1).await;
+        f.queue.enqueue(f.store.as_ref(), DEFAULT_TENANT, ids[0], 0).await.unwrap();
+
+        f.queue.tick(&f.chain, &f.store, 0).await.unwrap();
+        f.queue.tick(&f.chain, &f.store, 30).await.unwrap();
+        assert_eq!(f.chain.sent().len(), 1);
+
+        f.queue.tick(&f.chain, &f.store, 61).await.unwrap();
+        let sent = f.chain.sent();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].1, sent[1].1);
+        assert!(sent[1].2 >= sent[0].2 * 9 / 8);
+
+        // The original transaction wins the race: still recognized
+        f.chain.mine(sent[0].0, true);
+        f.chain.advance(2);
+        f.queue.tick(&f.chain, &f.store, 62).await.unwrap();
+        assert!(matches!(
+            f.queue.status(DEFAULT_TENANT, ids[0]).unwrap().tx.state,
+            AnchorState::Final { tx_hash, .. } if tx_hash == sent[0].0
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reorg_moves_batch_back_to_pending() {
+        let f = fixture();
+        let ids = sealed_batches(f.store.as_ref(), 1).await;
+        f.queue.enqueue(f.store.as_ref(), DEFAULT_TENANT, ids[0], 0).await.unwrap();
+        f.queue.tick(&f.chain, &f.store, 0).await.unwrap();
+        let first = f.chain.sent()[0];
+
+        f.chain.mine(first.0, true);
+        f.queue.tick(&f.chain, &f.store, 1).await.unwrap();
+        assert!(matches!(f.queue.status(DEFAULT_TENANT, ids[0]).unwrap().tx.state, AnchorState::Confirmed { .. }));
+
+        f.chain.reorg_out(first.0);
+        f.queue.tick(&f.chain, &f.store, 2).await.unwrap();
+        let job = f.queue.status(DEFAULT_TENANT, ids[0]).unwrap();
+        assert_eq!(job.tx.state, AnchorState::Pending);
+        assert!(job.tx.last_error.unwrap().contains("reorg"));
+
+        // Resubmitted with the same nonce and a higher fee
+        f.queue.tick(&f.chain, &f.store, 3).await.unwrap();
+        let resent = f.chain.sent()[1];
+        assert_eq!(resent.1, first.1);
+        assert!(resent.2 > first.2);
+        assert!(load_metadata(f.store.as_ref(), ids[0]).await.unwrap().mantle_batch_id.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reverted_and_rejected_jobs_fail() {
+        let f = fixture();
+        let ids = sealed_batches(f.store.as_ref(), 2).await;
+        f.queue.enqueue(f.store.as_ref(), DEFAULT_TENANT, ids[0], 0).await.unwrap();
+        f.queue.tick(&f.chain, &f.store, 0).await.unwrap();
+        f.chain.mine(f.chain.sent()[0].0, false);
+        f.queue.tick(&f.chain, &f.store, 1).await.unwrap();
+        assert_eq!(f.queue.status(DEFAULT_TENANT, ids[0]).unwrap().tx.state, AnchorState::Failed);
+
+        // A failed job can be queued again
+        assert_eq!(
+            f.queue.enqueue(f.store.as_ref(), DEFAULT_TENANT, ids[0], 2).await.unwrap().tx.state,
+            AnchorState::Pending
+        );
+
+        f.queue.enqueue(f.store.as_ref(), DEFAULT_TENANT, ids[1], 2).await.unwrap();
+        for now in 3..5 {
+            f.chain.inner.lock().unwrap().reject = Some(SubmitError::Other("insufficient funds".into()));
+            f.queue.tick(&f.chain, &f.store, now).await.unwrap();
+        }
+        let rejected: Vec<AnchorJob> = ids.iter().map(|id| f.queue.status(DEFAULT_TENANT, *id).unwrap()).collect();
+        assert!(rejected.iter().any(|j| j.tx.state == AnchorState::Failed));
+    }
+
+    #[tokio::test]
+    async fn test_nonce_too_low_resyncs() {
+        let f = fixture();
+        let ids = sealed_batches(f.store.as_ref(), 2).await;
+        f.queue.enqueue(f.store.as_ref(), DEFAULT_TENANT, ids[0], 0).await.unwrap();
+        f.queue.tick(&f.chain, &f.store, 0).await.unwrap();
+        assert_eq!(f.chain.sent()[0].1, 0);
+
+        // The key was used elsewhere: the locally allocated nonce 1 is stale
+        f.chain.inner.lock().unwrap().chain_nonce = 5;
+        f.chain.inner.lock().unwrap().reject = Some(SubmitError::NonceTooLow);
+        f.queue.enqueue(f.store.as_ref(), DEFAULT_TENANT, ids[1], 1).await.unwrap();
+        f.queue.tick(&f.chain, &f.store, 1).await.unwrap();
+        assert_eq!(f.queue.status(DEFAULT_TENANT, ids[1]).unwrap().tx.nonce, None);
+
+        f.queue.tick(&f.chain, &f.store, 2).await.unwrap();
+        assert_eq!(f.chain.sent()[1].1, 5);
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_broadcast_waits_for_receipt() {
+        let f = fixture();
+        let ids = sealed_batches(f.store.as_ref(), 1).await;
+        f.queue.enqueue(f.store.as_ref(), DEFAULT_TENANT, ids[0], 0).await.unwrap();
+
+        // The node timed out, but the transaction got through
+        for now in 0..SETTINGS.max_attempts as u64 + 1 {
+            f.chain.inner.lock().unwrap().reject = Some(SubmitError::Unanswered("timed out".into()));
+            f.queue.tick(&f.chain, &f.store, now).await.unwrap();
+        }
+        let job = f.queue.status(DEFAULT_TENANT, ids[0]).unwrap();
+        assert!(matches!(job.tx.state, AnchorState::Submitted { .. }));
+        assert_eq!((job.tx.nonce, job.tx.attempts), (Some(0), 0));
+        assert_eq!(job.tx.tx_hashes, vec![f.chain.sent()[0].0]);
+        assert!(job.tx.last_error.unwrap().contains("timed out"));
+
+        f.chain.mine(f.chain.sent()[0].0, true);
+        f.queue.tick(&f.chain, &f.store, 10).await.unwrap();
+        assert!(matches!(
+            f.queue.status(DEFAULT_TENANT, ids[0]).unwrap().tx.state,
+            AnchorState::Confirmed { .. }
+        ));
+
+        // A replacement that goes unanswered keeps every hash and the nonce
+        let ids = sealed_batches(f.store.as_ref(), 1).await;
+        f.queue.enqueue(f.store.as_ref(), DEFAULT_TENANT, ids[0], 20).await.unwrap();
+        f.queue.tick(&f.chain, &f.store, 20).await.unwrap();
+        for now in [81, 142] {
+            f.chain.inner.lock().unwrap().reject = Some(SubmitError::Unanswered("timed out".into()));
+            f.queue.tick(&f.chain, &f.store, now).await.unwrap();
+        }
+        let job = f.queue.status(DEFAULT_TENANT, ids[0]).unwrap();
+        assert!(matches!(job.tx.state, AnchorState::Submitted { .. }));
+        assert_eq!((job.tx.nonce, job.tx.tx_hashes.len()), (Some(1), 3));
+        assert!(f.queue.state.lock().unwrap().free_nonces.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_queue_survives_restart() {
+        let f = fixture();
+        let ids = sealed_batches(f.store.as_ref(), 1).await;
+        f.queue.enqueue(f.store.as_ref(), DEFAULT_TENANT, ids[0], 0).await.unwrap();
+        f.queue.tick(&f.chain, &f.store, 0).await.unwrap();
+        let before = f.queue.status(DEFAULT_TENANT, ids[0]).unwrap();
+
+        let reopened = AnchorQueue::open(&f.queue_path, SETTINGS).unwrap();
+        assert_eq!(reopened.status(DEFAULT_TENANT, ids[0]).unwrap(), before);
+
+        // Resumes watching the same transaction instead of sending a new one
+        f.chain.mine(f.chain.sent()[0].0, true);
+        reopened.tick(&f.chain, &f.store, 1).await.unwrap();
+        assert_eq!(f.chain.sent().len(), 1);
+        assert!(matches!(reopened.status(DEFAULT_TENANT, ids[0]).unwrap().tx.state, AnchorState::Confirmed { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_aggregates_batches_into_one_transaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn BatchStore> = Arc::new(FsStore::open(dir.path().join("batches")).unwrap());
+        let queue = AnchorQueue::open(
+            dir.path().join("queue.json"),
+            QueueSettings {
+                aggregate_every: Some(10),
+                ..SETTINGS
+            },
+        )
+        .unwrap()
+        .with_publisher(publisher(dir.path()));
+        let chain = FakeChain::new();
+
+        let ids = sealed_batches(store.as_ref(), 3).await;
+        for (i, id) in ids.iter().enumerate() {
+            queue.enqueue(store.as_ref(), DEFAULT_TENANT, *id, i as u64).await.unwrap();
+        }
+
+        // Nothing is sent until the oldest batch has waited long enough
+        queue.tick(&chain, &store, 9).await.unwrap();
+        assert!(chain.sent().is_empty());
+        queue.tick(&chain, &store, 10).await.unwrap();
+        let sent = chain.sent();
+        assert_eq!(sent.len(), 1);
+
+        // The aggregate's metaURI is its published manifest
+        let aggregate = queue.aggregate(1).unwrap();
+        let cid = aggregate.manifest_cid.clone().unwrap();
+        assert_eq!(aggregate.tx.meta_uri, format!("https://seal.example/manifests/{}", cid));
+        let bytes = fs::read(dir.path().join("manifests").join(format!("{}.json", cid))).unwrap();
+        let manifest: AggregateManifest = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(manifest.root, hex::encode(aggregate.tx.root));
+        assert_eq!(manifest.leaves.len(), 3);
+        assert_eq!(
+            aggregate.members,
+            ids.iter().map(|id| BatchKey::new(DEFAULT_TENANT, *id)).collect::<Vec<_>>()
+        );
+        let job = queue.status(DEFAULT_TENANT, ids[1]).unwrap();
+        assert_eq!(job.aggregate, Some(1));
+        assert!(matches!(job.tx.state, AnchorState::Submitted { .. }));
+        assert_ne!(job.tx.root, aggregate.tx.root);
+
+        chain.mine(sent[0].0, true);
+        chain.advance(2);
+        queue.tick(&chain, &store, 11).await.unwrap();
+        assert!(queue.aggregate(1).unwrap().recorded);
+
+        for id in &ids {
+            let metadata = load_metadata(store.as_ref(), *id).await.unwrap();
+            assert_eq!(metadata.mantle_batch_id, Some(1));
+            let inclusion = metadata.aggregate.unwrap();
+            assert_eq!(inclusion.root, hex::encode(aggregate.tx.root));
+            assert_eq!(hex::encode(inclusion.proof.leaf), metadata.root);
+            assert!(inclusion.proof.verify(&aggregate.tx.root));
+            assert!(queue.status(DEFAULT_TENANT, *id).unwrap().recorded);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_waits_for_its_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn BatchStore> = Arc::new(FsStore::open(dir.path().join("batches")).unwrap());
+        let settings = QueueSettings {
+            aggregate_every: Some(0),
+            ..SETTINGS
+        };
+        let queue = AnchorQueue::open(dir.path().join("queue.json"), settings).unwrap();
+        let chain = FakeChain::new();
+
+        let ids = sealed_batches(store.as_ref(), 2).await;
+        for id in &ids {
+            queue.enqueue(store.as_ref(), DEFAULT_TENANT, *id, 0).await.unwrap();
+        }
+        queue.tick(&chain, &store, 0).await.unwrap();
+        assert!(chain.sent().is_empty());
+        let aggregate = queue.aggregate(1).unwrap();
+        assert!(aggregate.manifest_cid.is_none() && aggregate.tx.meta_uri.is_empty());
+        assert!(aggregate.tx.last_error.is_some());
+
+        let queue = AnchorQueue::open(dir.path().join("queue.json"), settings)
+            .unwrap()
+            .with_publisher(publisher(dir.path()));
+        queue.tick(&chain, &store, 1).await.unwrap();
+        assert_eq!(chain.sent().len(), 1);
+        assert!(queue.aggregate(1).unwrap().tx.meta_uri.starts_with("https://seal.example/manifests/"));
+    }
+
+    /// Local manifest publishing under `dir`
+    fn publisher(dir: &Path) -> PublishConfig {
+        PublishConfig::Local {
+            dir: dir.join("manifests").display().to_string(),
+            base_url: "https://seal.example".to_string(),
+        }
+    }
+
+    /// Stores by tenant
+    struct Stores(HashMap<String, Arc<dyn BatchStore>>);
+
+    #[async_trait]
+    impl TenantStores for Stores {
+        async fn store(&self, tenant: &str) -> Result<Arc<dyn BatchStore>, StoreError> {
+            self.0
+                .get(tenant)
+                .cloned()
+                .ok_or_else(|| StoreError::NotFound(format!("Tenant {}", tenant)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tenants_with_the_same_batch_id_share_the_queue() {
+        let dir = tempfile::tempdir().unwrap();
+        let stores = Stores(HashMap::from(["default", "acme"].map(|tenant| {
+            let store: Arc<dyn BatchStore> = Arc::new(FsStore::open(dir.path().join(tenant)).unwrap());
+            (tenant.to_string(), store)
+        })));
+        let queue_path = dir.path().join("queue.json");
+        let settings = QueueSettings {
+            aggregate_every: Some(0),
+            ..SETTINGS
+        };
+        let queue = AnchorQueue::open(&queue_path, settings)
+            .unwrap()
+            .with_publisher(publisher(dir.path()));
+        let chain = FakeChain::new();
+
+        for tenant in ["default", "acme"] {
+            let ids = sealed_batches(stores.0[tenant].as_ref(), 1).await;
+            assert_eq!(ids, vec![1]);
+            queue.enqueue(stores.0[tenant].as_ref(), tenant, 1, 0).await.unwrap();
+        }
+        queue.tick(&chain, &stores, 0).await.unwrap();
+        let members = vec![BatchKey::new("acme", 1), BatchKey::new(DEFAULT_TENANT, 1)];
+        assert_eq!(queue.aggregate(1).unwrap().members, members);
+
+        // Default-tenant keys keep the pre-tenant format on disk
+        let saved: serde_json::Value = serde_json::from_slice(&fs::read(&queue_path).unwrap()).unwrap();
+        assert_eq!(saved["aggregates"]["1"]["members"], serde_json::json!(["acme/1", 1]));
+        assert_eq!(saved["jobs"]["acme/1"]["tenant"], "acme");
+        assert_eq!(saved["jobs"]["1"]["batch_id"], 1);
+        let reopened = AnchorQueue::open(&queue_path, settings)
+            .unwrap()
+            .with_publisher(publisher(dir.path()));
+        assert_eq!(reopened.aggregate(1).unwrap().members, members);
+
+        chain.mine(chain.sent()[0].0, true);
+        chain.advance(2);
+        reopened.tick(&chain, &stores, 1).await.unwrap();
+        for tenant in ["default", "acme"] {
+            let metadata = load_metadata(stores.0[tenant].as_ref(), 1).await.unwrap();
+            assert_eq!(metadata.mantle_batch_id, Some(1));
+            assert!(reopened.status(tenant, 1).unwrap().recorded);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use super::{AuthError, JwtConfig, Principal, Scope};
+use crate::tenants::is_valid_tenant;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde_json::{Map, Value};
+
+/// Verifies HS256 tokens against a shared secret and RS256 tokens against a
+/// local JWKS file
+pub struct JwtVerifier {
+    hs256: Option<DecodingKey>,
+    jwks: Option<JwkSet>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    tenant_claim: String,
+}
+
+impl JwtVerifier {
+    pub fn new(config: &JwtConfig) -> Result<Self, AuthError> {
+        let jwks = match &config.jwks_file {
+            Some(path) => {
+                let data = std::fs::read(path)
+                    .map_err(|e| AuthError::Config(format!("Failed to read JWKS file {}: {}", path, e)))?;
+                let jwks: JwkSet = serde_json::from_slice(&data)
+                    .map_err(|e| AuthError::Config(format!("Invalid JWKS file {}: {}", path, e)))?;
+                Some(jwks)
+            }
+            None => None,
+        };
+        Ok(Self {
+            hs256: config.secret.as_deref().map(|secret| DecodingKey::from_secret(secret.as_bytes())),
+            jwks,
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+            tenant_claim: config.tenant_claim.clone(),
+        })
+    }
+
+    /// Check a token's signature and claims and map it to a principal
+    ///
+    /// The tenant comes from the configured claim, the scopes from the
+    /// space-separated `scope` claim (read and write when absent).
+    pub fn verify(&self, token: &str) -> Result<Principal, AuthError> {
+        let header = decode_header(token).map_err(invalid)?;
+        let key = match header.alg {
+            Algorithm::HS256 => self
+                .hs256
+                .clone()
+                .ok_or_else(|| AuthError::Unauthorized("HS256 tokens are not accepted".to_string()))?,
+            Algorithm::RS256 => {
+                let jwks = self
+                    .jwks
+                    .as_ref()
+                    .ok_or_else(|| AuthError::Unauthorized("RS256 tokens are not accepted".to_string()))?;
+                let jwk = match &header.kid {
+                    Some(kid) => jwks.find(kid),
+                    None if jwks.keys.len() == 1 => jwks.keys.first(),
+                    None => None,
+                }
+                .ok_or_else(|| AuthError::Unauthorized("Token signed by an unknown key".to_string()))?;
+                DecodingKey::from_jwk(jwk).map_err(invalid)?
+            }
+            other => return Err(AuthError::Unauthorized(format!("Unsupported token algorithm {:?}", other))),
+        };
+
+        let mut validation = Validation::new(header.alg);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        let claims = decode::<Map<String, Value>>(token, &key, &validation).map_err(invalid)?.claims;
+
+        let tenant = claims
+            .get(&self.tenant_claim)
+            .and_then(Value::as_str)
+            .filter(|tenant| is_valid_tenant(tenant))
+            .ok_or_else(|| AuthError::Unauthorized(format!("Token has no valid {:?} claim", self.tenant_claim)))?;
+        let scopes = match claims.get("scope").and_then(Value::as_str) {
+            Some(scope) => scope.split_whitespace().filter_map(Scope::parse).collect(),
+            None => Scope::user(),
+        };
+        let subject = claims.get("sub").and_then(Value::as_str).unwrap_or("anonymous");
+        Ok(Principal {
+            tenant: tenant.to_string(),
+            scopes,
+            subject: format!("jwt:{}", subject),
+        })
+    }
+}
+
+fn invalid(e: jsonwebtoken::errors::Error) -> AuthError {
+    AuthError::Unauthorized(format!("Invalid token: {}", e))
+}
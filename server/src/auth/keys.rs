@@ -0,0 +1,81 @@
+use super::{AuthError, Principal, Scope};
+use crate::tenants::is_valid_tenant;
+use merkle_tree::{hash_data, hex_to_hash, Hash};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// One entry of the API key file
+///
+/// Keys are given either in the clear (`key`) or as the hex SHA-256 of the
+/// key (`sha256`), so the file need not hold usable secrets.
+#[derive(Debug, Deserialize)]
+struct KeyEntry {
+    #[serde(default)]
+    key: Option<String>,
+    #[serde(default)]
+    sha256: Option<String>,
+    tenant: String,
+    /// Defaults to read and write
+    #[serde(default = "Scope::user")]
+    scopes: Vec<Scope>,
+    /// Label for logs (defaults to the key's hash prefix)
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// Static API keys, indexed by their SHA-256
+pub struct ApiKeys {
+    keys: HashMap<Hash, Principal>,
+}
+
+impl ApiKeys {
+    /// Load the JSON key file (an array of key entries)
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AuthError> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .map_err(|e| AuthError::Config(format!("Failed to read API key file {}: {}", path.display(), e)))?;
+        let entries: Vec<KeyEntry> = serde_json::from_slice(&data)
+            .map_err(|e| AuthError::Config(format!("Invalid API key file {}: {}", path.display(), e)))?;
+
+        let mut keys = HashMap::new();
+        for (index, entry) in entries.into_iter().enumerate() {
+            let hash = match (&entry.key, &entry.sha256) {
+                (Some(key), None) if !key.is_empty() => hash_data(key.as_bytes()),
+                (None, Some(sha256)) => hex_to_hash(sha256)
+                    .map_err(|e| AuthError::Config(format!("API key {}: invalid sha256: {}", index, e)))?,
+                _ => {
+                    return Err(AuthError::Config(format!(
+                        "API key {}: set exactly one of \"key\" or \"sha256\"",
+                        index
+                    )))
+                }
+            };
+            if !is_valid_tenant(&entry.tenant) {
+                return Err(AuthError::Config(format!("API key {}: invalid tenant {:?}", index, entry.tenant)));
+            }
+            let principal = Principal {
+                subject: format!("key:{}", entry.name.unwrap_or_else(|| hex::encode(&hash[..4]))),
+                tenant: entry.tenant,
+                scopes: entry.scopes,
+            };
+            if keys.insert(hash, principal).is_some() {
+                return Err(AuthError::Config(format!("API key {} is listed twice", index)));
+            }
+        }
+        Ok(Self { keys })
+    }
+
+    /// The principal a presented key belongs to
+    pub fn verify(&self, key: &str) -> Result<Principal, AuthError> {
+        self.keys
+            .get(&hash_data(key.as_bytes()))
+            .cloned()
+            .ok_or_else(|| AuthError::Unauthorized("Invalid API key".to_string()))
+    }
+
+    /// Tenants that have keys, to open at startup
+    pub fn tenants(&self) -> impl Iterator<Item = &str> {
+        self.keys.values().map(|principal| principal.tenant.as_str())
+    }
+}
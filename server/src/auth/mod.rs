@@ -0,0 +1,476 @@
+//! Authentication and tenant assignment
+//!
+//! With authentication enabled, every request except the public ones (health,
+//! receipt keys, published manifests, the GraphiQL page) must carry a static
+//! API key or a JWT. Either one names the tenant whose batches the request
+//! works on and the scopes it holds: `read` for GET requests and GraphQL
+//! queries, `admin` for `/admin/*`, `write` for everything else. Missing or
+//! invalid credentials get `401`, valid ones without the needed scope get `403`.
+
+mod jwt;
+mod keys;
+
+pub use jwt::JwtVerifier;
+pub use keys::ApiKeys;
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, HttpMessage, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+/// Authentication settings
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    /// JSON file of static API keys
+    pub api_keys_file: Option<String>,
+    /// JWT verification (`None` accepts API keys only)
+    pub jwt: Option<JwtConfig>,
+}
+
+/// How JWTs are verified
+#[derive(Clone)]
+pub struct JwtConfig {
+    /// Shared secret for HS256 tokens
+    pub secret: Option<String>,
+    /// Local JWKS file with the public keys for RS256 tokens
+    pub jwks_file: Option<String>,
+    /// Required `iss` claim
+    pub issuer: Option<String>,
+    /// Required `aud` claim
+    pub audience: Option<String>,
+    /// Claim holding the tenant ID
+    pub tenant_claim: String,
+}
+
+impl fmt::Debug for JwtConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtConfig")
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .field("jwks_file", &self.jwks_file)
+            .field("issuer", &self.issuer)
+            .field("audience", &self.audience)
+            .field("tenant_claim", &self.tenant_claim)
+            .finish()
+    }
+}
+
+/// What a credential allows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// List, download and look up batches; run GraphQL queries
+    Read,
+    /// Upload, seal and anchor batches
+    Write,
+    /// Read every tenant's usage (`/admin/*`); never granted by default
+    Admin,
+}
+
+impl Scope {
+    /// Scopes of a credential that does not list any
+    pub fn user() -> Vec<Scope> {
+        vec![Scope::Read, Scope::Write]
+    }
+
+    pub fn parse(scope: &str) -> Option<Scope> {
+        match scope {
+            "read" => Some(Scope::Read),
+            "write" => Some(Scope::Write),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+/// An authenticated caller, stored in the request extensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub tenant: String,
+    pub scopes: Vec<Scope>,
+    /// Who the credential identifies, for logs (`key:{name}` or `jwt:{sub}`)
+    pub subject: String,
+}
+
+/// Verifies request credentials
+pub struct Authenticator {
+    keys: Option<ApiKeys>,
+    jwt: Option<JwtVerifier>,
+}
+
+impl Authenticator {
+    /// Load the key file and JWT keys named in the config
+    pub fn load(config: &AuthConfig) -> Result<Self, AuthError> {
+        let keys = config.api_keys_file.as_ref().map(ApiKeys::load).transpose()?;
+        let jwt = config.jwt.as_ref().map(JwtVerifier::new).transpose()?;
+        if keys.is_none() && jwt.is_none() {
+            return Err(AuthError::Config(
+                "Authentication needs an API key file, a JWT secret or a JWKS file".to_string(),
+            ));
+        }
+        Ok(Self { keys, jwt })
+    }
+
+    /// Tenants that have API keys
+    pub fn tenants(&self) -> Vec<String> {
+        let mut tenants: Vec<String> = self.keys.iter().flat_map(ApiKeys::tenants).map(str::to_string).collect();
+        tenants.sort();
+        tenants.dedup();
+        tenants
+    }
+
+    /// The caller identified by `Authorization: Bearer` (a JWT or an API key)
+    /// or `X-API-Key`
+    pub fn authenticate(&self, headers: &HeaderMap) -> Result<Principal, AuthError> {
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(' '))
+            .filter(|(kind, _)| kind.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim());
+        let api_key = headers.get("x-api-key").and_then(|value| value.to_str().ok());
+
+        match (bearer, api_key) {
+            (Some(token), _) if token.split('.').count() == 3 => match &self.jwt {
+                Some(jwt) => jwt.verify(token),
+                None => Err(AuthError::Unauthorized("JWT authentication is not enabled".to_string())),
+            },
+            (Some(key), _) | (None, Some(key)) => match &self.keys {
+                Some(keys) => keys.verify(key),
+                None => Err(AuthError::Unauthorized("API key authentication is not enabled".to_string())),
+            },
+            (None, None) => Err(AuthError::Unauthorized(
+                "Missing credentials (send Authorization: Bearer <token> or X-API-Key)".to_string(),
+            )),
+        }
+    }
+}
+
+/// Scope a request needs, `None` for public endpoints
+pub(crate) fn required_scope(method: &Method, path: &str) -> Option<Scope> {
+    let read = matches!(*method, Method::GET | Method::HEAD);
+    let public = path == "/health"
+        || path == "/.well-known/merkseal-keys"
+        || path.starts_with("/manifests/")
+        || (path == "/graphql" && read);
+    if public {
+        return None;
+    }
+    if path.starts_with("/admin/") {
+        return Some(Scope::Admin);
+    }
+    // GraphQL queries are reads even though they are POSTed
+    Some(if read || path == "/graphql" { Scope::Read } else { Scope::Write })
+}
+
+/// Middleware that authenticates requests and records the caller's `Principal`
+///
+/// Does nothing when no `Authenticator` is registered.
+pub async fn authenticate<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    let authenticator = req.app_data::<web::Data<Arc<Authenticator>>>().cloned();
+    let (Some(authenticator), Some(scope)) = (authenticator, required_scope(req.method(), req.path())) else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+
+    let checked = authenticator.authenticate(req.headers()).and_then(|principal| {
+        if principal.scopes.contains(&scope) {
+            Ok(principal)
+        } else {
+            Err(AuthError::Forbidden(format!(
+                "Credentials for tenant {} lack the {} scope",
+                principal.tenant,
+                scope.as_str()
+            )))
+        }
+    });
+    match checked {
+        Ok(principal) => {
+            req.extensions_mut().insert(principal);
+            next.call(req).await.map(ServiceResponse::map_into_left_body)
+        }
+        Err(e) => Ok(req.into_response(e.response()).map_into_right_body()),
+    }
+}
+
+/// Authentication failures
+#[derive(Debug)]
+pub enum AuthError {
+    /// Unreadable key file or JWKS
+    Config(String),
+    /// Missing, unknown, expired or badly signed credentials
+    Unauthorized(String),
+    /// Valid credentials without the scope the request needs
+    Forbidden(String),
+}
+
+impl AuthError {
+    /// JSON error response with the matching status code
+    pub fn response(&self) -> HttpResponse {
+        let mut builder = match self {
+            AuthError::Unauthorized(_) => {
+                let mut builder = HttpResponse::Unauthorized();
+                builder.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+                builder
+            }
+            AuthError::Forbidden(_) => HttpResponse::Forbidden(),
+            AuthError::Config(_) => HttpResponse::InternalServerError(),
+        };
+        builder.json(serde_json::json!({
+            "success": false,
+            "error": self.to_string()
+        }))
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::Config(msg) | AuthError::Unauthorized(msg) | AuthError::Forbidden(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::batches;
+    use crate::chunk_store::ChunkStore;
+    use crate::config::ServerConfig;
+    use crate::leaves;
+    use crate::publish::PublishConfig;
+    use crate::quotas::{QuotaConfig, Quotas};
+    use crate::receipts::ReceiptConfig;
+    use crate::store::StorageConfig;
+    use crate::tenants::Tenants;
+    use crate::upload::UploadLimits;
+    use actix_web::{middleware::from_fn, test as actix_test, App};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use mantle_config::MantleConfig;
+    use merkle_tree::hash_data;
+    use std::path::Path;
+
+    /// Modulus (base64url) of `testdata/rs256_test_key.pem`
+    const RS256_TEST_MODULUS: &str = "nr7z7nyJAcfKt0z-3jyj-0XT3zdE-R54JNZAl7Hi6bU0xUhbEIKVMj4Y5Pb6jXg1-AwEHsazau3Qtf2qDpAZPrZ-JBk4vKspnST8oiHSi3nl2AZM-2TqJdWvcI7NjScHWN43iedQGxUiAqP5BDx7NNKPn6BeZd7as95wxCKKPmpm9j5x1ddATHDTc2SeVrCXnVvyS0U-B0g48o-YCJ6qzrz4BILsgJujOhOZXle6LjoQdgjNsEN5ewznNbpvwN-8_Ii-uVjVhL8tDdGI2yU4kFhWu1Y_X8xDQ68vSQ17RrXDf8x1cqnNrOHHziRfazFwvFjtaHD8I1vIVjDigdpQgw";
+
+    /// Server config with every path under `dir`
+    pub(crate) fn server_config(dir: &Path, storage: StorageConfig) -> ServerConfig {
+        ServerConfig {
+            chunking: false,
+            storage,
+            chunk_dir: dir.join("chunks").display().to_string(),
+            blobs: None,
+            lookup_db: dir.join(".lookup.db").display().to_string(),
+            staging_dir: dir.join(".staging").display().to_string(),
+            limits: UploadLimits {
+                max_file_bytes: 1024,
+                max_batch_bytes: 1024,
+            },
+            anchor: None,
+            indexer: None,
+            publish: PublishConfig::Local {
+                dir: dir.join("manifests").display().to_string(),
+                base_url: "http://127.0.0.1:8080".to_string(),
+            },
+            session_file: dir.join(".sessions.json").display().to_string(),
+            session_ttl: 3600,
+            rolling: None,
+            receipts: ReceiptConfig {
+                key: None,
+                key_file: String::new(),
+            },
+            auth: None,
+            quotas: QuotaConfig::default(),
+        }
+    }
+
+    fn token(claims: serde_json::Value, header: Header, key: &EncodingKey) -> String {
+        encode(&header, &claims, key).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_credentials_scopes_and_tenant_isolation() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("batches");
+        let keys_file = dir.path().join("keys.json");
+        std::fs::write(
+            &keys_file,
+            serde_json::json!([
+                { "key": "acme-writer", "tenant": "acme" },
+                { "key": "acme-reader", "tenant": "acme", "scopes": ["read"] },
+                { "sha256": hex::encode(hash_data(b"globex-key")), "tenant": "globex", "name": "globex-ci" }
+            ])
+            .to_string(),
+        )
+        .unwrap();
+        let jwks_file = dir.path().join("jwks.json");
+        std::fs::write(
+            &jwks_file,
+            serde_json::json!({ "keys": [{
+                "kty": "RSA", "kid": "test-key", "alg": "RS256", "use": "sig",
+                "n": RS256_TEST_MODULUS, "e": "AQAB"
+            }] })
+            .to_string(),
+        )
+        .unwrap();
+        let auth = AuthConfig {
+            api_keys_file: Some(keys_file.display().to_string()),
+            jwt: Some(JwtConfig {
+                secret: Some("test-secret".into()),
+                jwks_file: Some(jwks_file.display().to_string()),
+                issuer: Some("https://issuer.example".into()),
+                audience: None,
+                tenant_claim: "tenant".into(),
+            }),
+        };
+        let authenticator = Authenticator::load(&auth).unwrap();
+        assert_eq!(authenticator.tenants(), vec!["acme", "globex"]);
+
+        let server_config = Arc::new(server_config(
+            dir.path(),
+            StorageConfig::Filesystem {
+                root: root.display().to_string(),
+            },
+        ));
+        let mantle = Arc::new(MantleConfig::new("http://localhost:8545".into(), 5003, "0x0".into()));
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(from_fn(authenticate))
+                .app_data(leaves::json_config(crate::listen::DEFAULT_MAX_PAYLOAD_BYTES))
+                .app_data(web::Data::new(Arc::new(authenticator)))
+                .app_data(web::Data::new(Arc::new(Tenants::new(
+                    mantle.clone(),
+                    server_config.clone(),
+                    None,
+                    Arc::new(Quotas::open(&server_config.quotas).unwrap()),
+                ))))
+                .app_data(web::Data::new(server_config))
+                .app_data(web::Data::new(mantle))
+                .app_data(web::Data::new(Arc::new(crate::receipts::ReceiptSigner::random())))
+                .app_data(web::Data::new(None::<ChunkStore>))
+                .service(leaves::submit_leaves)
+                .service(batches::list_batches)
+                .service(batches::get_batch)
+                .route("/health", web::get().to(crate::health)),
+        )
+        .await;
+
+        let call = |method: Method, uri: &str, credential: Option<(&str, String)>| {
+            let mut request = actix_test::TestRequest::default().method(method).uri(uri);
+            if let Some(credential) = credential {
+                request = request.insert_header(credential);
+            }
+            request
+        };
+        let bearer = |token: &str| Some(("Authorization", format!("Bearer {}", token)));
+        let api_key = |key: &str| Some(("X-API-Key", key.to_string()));
+        let seal = |credential: Option<(&'static str, String)>, leaf: &[u8]| {
+            call(Method::POST, "/leaves", credential)
+                .set_json(serde_json::json!({ "leaves": [{ "leaf": hex::encode(hash_data(leaf)) }] }))
+                .to_request()
+        };
+
+        // Public endpoints need nothing; everything else needs credentials
+        let resp = actix_test::call_service(&app, call(Method::GET, "/health", None).to_request()).await;
+        assert_eq!(resp.status(), 200);
+        let resp = actix_test::call_service(&app, call(Method::GET, "/batches", None).to_request()).await;
+        assert_eq!(resp.status(), 401);
+        assert_eq!(resp.headers().get(header::WWW_AUTHENTICATE).unwrap(), "Bearer");
+        let error: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(error["success"], false);
+        for credential in [api_key("wrong"), bearer("wrong"), bearer("a.b.c")] {
+            let resp = actix_test::call_service(&app, call(Method::GET, "/batches", credential).to_request()).await;
+            assert_eq!(resp.status(), 401);
+        }
+
+        // Read-only credentials cannot upload, and user credentials are not admins
+        let resp = actix_test::call_service(&app, seal(api_key("acme-reader"), b"nope")).await;
+        assert_eq!(resp.status(), 403);
+        let resp =
+            actix_test::call_service(&app, call(Method::GET, "/admin/usage", api_key("acme-writer")).to_request()).await;
+        assert_eq!(resp.status(), 403);
+
+        // Each tenant numbers its batches from 1 and sees only its own
+        let acme: serde_json::Value = actix_test::call_and_read_body_json(&app, seal(api_key("acme-writer"), b"acme")).await;
+        let globex: serde_json::Value = actix_test::call_and_read_body_json(&app, seal(bearer("globex-key"), b"globex")).await;
+        assert_eq!(acme["batch"]["local_batch_id"], 1);
+        assert_eq!(globex["batch"]["local_batch_id"], 1);
+        assert_ne!(acme["batch"]["root"], globex["batch"]["root"]);
+        for (credential, expected) in [(api_key("acme-reader"), &acme), (bearer("globex-key"), &globex)] {
+            let list: serde_json::Value =
+                actix_test::call_and_read_body_json(&app, call(Method::GET, "/batches", credential.clone()).to_request())
+                    .await;
+            assert_eq!(list["batches"].as_array().unwrap().len(), 1);
+            assert_eq!(list["batches"][0]["root"], expected["batch"]["root"]);
+            let manifest: serde_json::Value =
+                actix_test::call_and_read_body_json(&app, call(Method::GET, "/batches/1", credential).to_request())
+                    .await;
+            assert_eq!(manifest["root"], expected["batch"]["root"]);
+        }
+        assert!(root.join("tenants/acme/1/metadata.json").exists());
+        assert!(root.join("tenants/globex/1/metadata.json").exists());
+        assert!(!root.join("1").exists());
+
+        // HS256 tokens pick the tenant from the claim and honour `scope` and `exp`
+        let now = crate::unix_now();
+        let secret = EncodingKey::from_secret(b"test-secret");
+        let claims = |tenant: &str, exp: u64, scope: Option<&str>| {
+            let mut claims = serde_json::json!({
+                "sub": "alice", "tenant": tenant, "iss": "https://issuer.example", "exp": exp
+            });
+            if let Some(scope) = scope {
+                claims["scope"] = scope.into();
+            }
+            claims
+        };
+        let valid = token(claims("acme", now + 600, None), Header::default(), &secret);
+        let list: serde_json::Value =
+            actix_test::call_and_read_body_json(&app, call(Method::GET, "/batches", bearer(&valid)).to_request()).await;
+        assert_eq!(list["batches"][0]["root"], acme["batch"]["root"]);
+        for rejected in [
+            token(claims("acme", now - 600, None), Header::default(), &secret),
+            token(claims("acme", now + 600, None), Header::default(), &EncodingKey::from_secret(b"other")),
+            token(claims("Not A Tenant", now + 600, None), Header::default(), &secret),
+            token(
+                serde_json::json!({ "tenant": "acme", "iss": "https://evil.example", "exp": now + 600 }),
+                Header::default(),
+                &secret,
+            ),
+        ] {
+            let resp = actix_test::call_service(&app, call(Method::GET, "/batches", bearer(&rejected)).to_request()).await;
+            assert_eq!(resp.status(), 401);
+        }
+        let read_only = token(claims("acme", now + 600, Some("read")), Header::default(), &secret);
+        let resp = actix_test::call_service(&app, seal(bearer(&read_only), b"nope")).await;
+        assert_eq!(resp.status(), 403);
+
+        // RS256 tokens are checked against the JWKS; a new tenant is opened on first use
+        let rsa = EncodingKey::from_rsa_pem(include_bytes!("testdata/rs256_test_key.pem")).unwrap();
+        let mut header = Header::new(jsonwebtoken::Algorithm::RS256);
+        header.kid = Some("test-key".into());
+        let rs256 = token(claims("initech", now + 600, Some("read write")), header, &rsa);
+        let sealed: serde_json::Value = actix_test::call_and_read_body_json(&app, seal(bearer(&rs256), b"initech")).await;
+        assert_eq!(sealed["batch"]["local_batch_id"], 1);
+        let mut header = Header::new(jsonwebtoken::Algorithm::RS256);
+        header.kid = Some("unknown".into());
+        let unknown_key = token(claims("initech", now + 600, None), header, &rsa);
+        let resp = actix_test::call_service(&app, call(Method::GET, "/batches", bearer(&unknown_key)).to_request()).await;
+        assert_eq!(resp.status(), 401);
+    }
+}
@@ -0,0 +1,143 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// File (inside the storage root) holding the next batch ID to hand out
+const STATE_FILE: &str = ".next_batch_id";
+
+/// Crash-safe batch ID allocator backed by the storage directory
+///
+/// The next ID is persisted before a batch directory is created, and each
+/// directory is created with `create_dir` (which fails if it exists), so a
+/// restart or a second server process never reuses an existing batch.
+#[derive(Debug)]
+pub struct BatchIdAllocator {
+    root: PathBuf,
+    next: Mutex<u64>,
+}
+
+impl BatchIdAllocator {
+    /// Open the allocator, resuming after the highest existing batch
+    pub fn open(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+
+        let persisted = read_state(&root.join(STATE_FILE))?;
+        let highest = highest_existing_batch(&root)?;
+        let next = persisted.max(highest + 1).max(1);
+
+        Ok(Self {
+            root,
+            next: Mutex::new(next),
+        })
+    }
+
+    /// Reserve a fresh batch ID and create its (empty) directory
+    pub fn reserve(&self) -> io::Result<(u64, PathBuf)> {
+        let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+
+        loop {
+            let id = *next;
+            *next += 1;
+            write_state(&self.root, *next)?;
+
+            let dir = self.root.join(id.to_string());
+            match fs::create_dir(&dir) {
+                Ok(()) => return Ok((id, dir)),
+                // Taken by another process or left over from before a crash
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Find the highest numeric batch directory under the root (0 if none)
+fn highest_existing_batch(root: &Path) -> io::Result<u64> {
+    let mut highest = 0;
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        if let Some(id) = entry.file_name().to_str().and_then(|s| s.parse::<u64>().ok()) {
+            highest = highest.max(id);
+        }
+    }
+    Ok(highest)
+}
+
+fn read_state(path: &Path) -> io::Result<u64> {
+    match fs::read_to_string(path) {
+        Ok(s) => s.trim().parse().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Corrupt batch ID state in {}", path.display()),
+            )
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+/// Persist the next ID atomically (write temp file, fsync, rename)
+fn write_state(root: &Path, next: u64) -> io::Result<()> {
+    let tmp = root.join(format!("{}.tmp", STATE_FILE));
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(next.to_string().as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp, root.join(STATE_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let allocator = BatchIdAllocator::open(dir.path()).unwrap();
+        assert_eq!(allocator.reserve().unwrap().0, 1);
+        assert_eq!(allocator.reserve().unwrap().0, 2);
+        drop(allocator);
+
+        let allocator = BatchIdAllocator::open(dir.path()).unwrap();
+        let (id, path) = allocator.reserve().unwrap();
+        assert_eq!(id, 3);
+        assert!(path.is_dir());
+    }
+
+    #[test]
+    fn test_resumes_after_existing_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("7")).unwrap();
+        fs::create_dir(dir.path().join("chunks")).unwrap();
+
+        let allocator = BatchIdAllocator::open(dir.path()).unwrap();
+        assert_eq!(allocator.reserve().unwrap().0, 8);
+    }
+
+    #[test]
+    fn test_skips_directories_created_after_startup() {
+        let dir = tempfile::tempdir().unwrap();
+        let allocator = BatchIdAllocator::open(dir.path()).unwrap();
+
+        // Another process grabs the next ID behind our back
+        fs::create_dir(dir.path().join("1")).unwrap();
+        assert_eq!(allocator.reserve().unwrap().0, 2);
+    }
+
+    #[test]
+    fn test_lost_state_file_does_not_reuse_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let allocator = BatchIdAllocator::open(dir.path()).unwrap();
+        allocator.reserve().unwrap();
+        allocator.reserve().unwrap();
+        fs::remove_file(dir.path().join(STATE_FILE)).unwrap();
+
+        let allocator = BatchIdAllocator::open(dir.path()).unwrap();
+        assert_eq!(allocator.reserve().unwrap().0, 3);
+    }
+}
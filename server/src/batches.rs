@@ -0,0 +1,635 @@
+//! Read-only endpoints for sealed batches: listing, manifests and file downloads
+
+use crate::anchor::AnchorQueue;
+use crate::chunk_store::ChunkStore;
+use crate::store::{BatchStore, StoreError};
+use crate::tenants::Tenant;
+use crate::{AggregateInclusion, BatchMetadata};
+use actix_web::http::header::{self, ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use merkle_tree::{hex_to_hash, leaf_cid, root_cid, MerkleProof, MerkleTree};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Default page size for `GET /batches`
+const DEFAULT_PAGE_SIZE: usize = 50;
+/// Largest accepted page size for `GET /batches`
+const MAX_PAGE_SIZE: usize = 500;
+/// Longest accepted `?wait=` for a pending proof
+const MAX_PROOF_WAIT_SECS: u64 = 60;
+
+/// Query parameters for `GET /batches`
+#[derive(Debug, Default, Deserialize)]
+pub struct ListQuery {
+    /// Only anchored (`true`) or unanchored (`false`) batches
+    pub anchored: Option<bool>,
+    /// Only batches created at or after this Unix timestamp
+    pub since: Option<u64>,
+    /// Only batches created before this Unix timestamp
+    pub until: Option<u64>,
+    /// Resume after this batch ID (from `next_after`)
+    pub after: Option<u64>,
+    /// Page size (default 50, at most 500)
+    pub limit: Option<usize>,
+}
+
+impl ListQuery {
+    fn matches(&self, batch: &BatchMetadata) -> bool {
+        if let Some(anchored) = self.anchored {
+            if batch.mantle_batch_id.is_some() != anchored {
+                return false;
+            }
+        }
+        // Batches from before `created_at` was recorded never match a date filter
+        match (batch.created_at, self.since, self.until) {
+            (_, None, None) => true,
+            (None, _, _) => false,
+            (Some(created), since, until) => {
+                since.is_none_or(|s| created >= s) && until.is_none_or(|u| created < u)
+            }
+        }
+    }
+}
+
+/// One batch in a listing
+#[derive(Debug, Serialize)]
+pub struct BatchSummary {
+    pub local_batch_id: u64,
+    pub root: String,
+    pub root_cid: String,
+    pub file_count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mantle_batch_id: Option<u64>,
+}
+
+/// Response of `GET /batches`
+#[derive(Debug, Serialize)]
+pub struct BatchListResponse {
+    pub success: bool,
+    pub batches: Vec<BatchSummary>,
+    /// Pass as `after` to fetch the next page; absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_after: Option<u64>,
+}
+
+/// Response of `GET /batches/{id}/proof/{name}`
+///
+/// Self-contained: the proof can be checked offline and its root compared
+/// with `MerkleBatchRegistry.getBatch(mantle_batch_id)`. Batches anchored as
+/// part of an aggregate add a second proof from `root` to the aggregate root,
+/// which is the root registered on-chain.
+#[derive(Debug, Serialize)]
+pub struct ProofResponse {
+    pub success: bool,
+    pub local_batch_id: u64,
+    /// Stored file name
+    pub file: String,
+    #[serde(flatten)]
+    pub proof: MerkleProof,
+    /// Batch Merkle root (hex string)
+    pub root: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mantle_batch_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<String>,
+    pub registry_address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aggregate: Option<AggregateInclusion>,
+}
+
+/// GET /batches - List sealed batches in ascending ID order
+#[get("/batches")]
+async fn list_batches(
+    query: web::Query<ListQuery>,
+    tenant: Tenant,
+) -> HttpResponse {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let ids = match tenant.store.list_batches().await {
+        Ok(ids) => ids,
+        Err(e) => return store_error(e),
+    };
+
+    let mut batches = Vec::new();
+    let mut next_after = None;
+    for id in ids.into_iter().filter(|id| query.after.is_none_or(|after| *id > after)) {
+        let batch = match load_metadata(tenant.store.as_ref(), id).await {
+            Ok(batch) => batch,
+            Err(e) => {
+                eprintln!("✗ Skipping batch {} in listing: {}", id, e);
+                continue;
+            }
+        };
+        if !query.matches(&batch) {
+            continue;
+        }
+        if batches.len() == limit {
+            next_after = batches.last().map(|b: &BatchSummary| b.local_batch_id);
+            break;
+        }
+        batches.push(BatchSummary {
+            local_batch_id: batch.local_batch_id,
+            root: batch.root,
+            root_cid: batch.root_cid,
+            file_count: batch.file_count,
+            created_at: batch.created_at,
+            mantle_batch_id: batch.mantle_batch_id,
+        });
+    }
+
+    HttpResponse::Ok().json(BatchListResponse {
+        success: true,
+        batches,
+        next_after,
+    })
+}
+
+/// GET /batches/{id} - Return the batch manifest
+///
+/// When anchoring is enabled and the batch has been queued, its job state is
+/// included under `anchor`.
+#[get("/batches/{id}")]
+async fn get_batch(
+    path: web::Path<u64>,
+    tenant: Tenant,
+    anchor_queue: Option<web::Data<Arc<AnchorQueue>>>,
+) -> HttpResponse {
+    let batch_id = path.into_inner();
+    let manifest = match tenant.store.read_manifest(batch_id).await {
+        Ok(manifest) => manifest,
+        Err(e) => return store_error(e),
+    };
+    let Some(job) = anchor_queue.and_then(|queue| queue.status(&tenant.tenant, batch_id)) else {
+        return HttpResponse::Ok().content_type("application/json").body(manifest);
+    };
+
+    match serde_json::from_slice::<serde_json::Value>(&manifest) {
+        Ok(serde_json::Value::Object(mut fields)) => {
+            fields.insert("anchor".to_string(), serde_json::json!(job));
+            HttpResponse::Ok().json(fields)
+        }
+        _ => store_error(StoreError::Backend(format!("Corrupt manifest for batch {}", batch_id))),
+    }
+}
+
+/// GET /batches/{id}/files/{name} - Stream a stored file
+///
+/// The `ETag` is the file's leaf hash, so a client can check a download
+/// against the manifest without hashing it first. Single byte ranges are
+/// supported; multi-range requests get the whole file.
+#[get("/batches/{id}/files/{name}")]
+async fn download_file(
+    req: HttpRequest,
+    path: web::Path<(u64, String)>,
+    tenant: Tenant,
+    chunk_store: web::Data<Option<ChunkStore>>,
+) -> HttpResponse {
+    let (batch_id, name) = path.into_inner();
+    let batch = match load_metadata(tenant.store.as_ref(), batch_id).await {
+        Ok(batch) => batch,
+        Err(e) => return store_error(e),
+    };
+    let Some(file) = batch.files.into_iter().find(|f| f.name == name) else {
+        return store_error(StoreError::NotFound(format!("File {} in batch {}", name, batch_id)));
+    };
+    if batch.hash_only {
+        return store_error(StoreError::NotFound(format!(
+            "Contents of {} in hash-only batch {}",
+            name, batch_id
+        )));
+    }
+
+    let etag = format!("\"{}\"", file.leaf);
+    if header_value(&req, header::IF_NONE_MATCH).is_some_and(|v| etag_matches(v, &etag)) {
+        return HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .finish();
+    }
+
+    // A stale If-Range means the client's partial copy is outdated: send everything
+    let range_header = header_value(&req, header::RANGE)
+        .filter(|_| header_value(&req, header::IF_RANGE).is_none_or(|v| v == etag));
+    let range = match range_header.map(|v| parse_range(v, file.size)) {
+        None | Some(ByteRange::Ignored) => 0..file.size,
+        Some(ByteRange::Satisfiable(range)) => range,
+        Some(ByteRange::Unsatisfiable) => {
+            return HttpResponse::RangeNotSatisfiable()
+                .insert_header((header::CONTENT_RANGE, format!("bytes */{}", file.size)))
+                .json(serde_json::json!({
+                    "success": false,
+                    "error": format!("Range not satisfiable for {} byte file", file.size)
+                }));
+        }
+    };
+
+    let body = match &file.chunks {
+        Some(chunks) => {
+            let hashes = match chunks.iter().map(|c| hex_to_hash(c)).collect::<Result<Vec<_>, _>>() {
+                Ok(hashes) => hashes,
+                Err(e) => return store_error(StoreError::Backend(format!("Invalid chunk hash: {}", e))),
+            };
+            let Some(chunk_store) = chunk_store.get_ref() else {
+                return store_error(StoreError::Backend("Chunk store is not configured".to_string()));
+            };
+            chunk_store.read_range(hashes, range.clone())
+        }
+        None => match tenant.store.read_file_range(batch_id, &name, range.clone()).await {
+            Ok(stream) => stream,
+            Err(e) => return store_error(e),
+        },
+    };
+
+    let partial = range.end - range.start != file.size;
+    let mut response = if partial {
+        HttpResponse::PartialContent()
+    } else {
+        HttpResponse::Ok()
+    };
+    if partial {
+        response.insert_header((
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", range.start, range.end - 1, file.size),
+        ));
+    }
+    response
+        .insert_header((header::ETAG, etag))
+        .insert_header((header::ACCEPT_RANGES, "bytes"))
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file.name)],
+        })
+        .content_type("application/octet-stream")
+        .no_chunking(range.end - range.start)
+        .streaming(body)
+}
+
+/// Query parameters for `GET /batches/{id}/proof/{name}`
+#[derive(Debug, Default, Deserialize)]
+pub struct ProofQuery {
+    /// Seconds to wait for a pending batch to be sealed (at most 60)
+    pub wait: Option<u64>,
+}
+
+/// GET /batches/{id}/proof/{name} - Prove that a file is part of a batch
+///
+/// Files of a batch that is still open get `202 Accepted` with a pending
+/// receipt instead, after waiting up to `?wait=` seconds for the seal.
+#[get("/batches/{id}/proof/{name}")]
+async fn get_proof(
+    path: web::Path<(u64, String)>,
+    query: web::Query<ProofQuery>,
+    tenant: Tenant,
+) -> HttpResponse {
+    let (batch_id, name) = path.into_inner();
+    let deadline = Instant::now() + Duration::from_secs(query.wait.unwrap_or(0).min(MAX_PROOF_WAIT_SECS));
+    let batch = loop {
+        let sealed = tenant.sessions.sealed();
+        tokio::pin!(sealed);
+        sealed.as_mut().enable();
+
+        match load_metadata(tenant.store.as_ref(), batch_id).await {
+            Ok(batch) => break batch,
+            Err(StoreError::NotFound(e)) => match tenant.sessions.pending_file(batch_id, &name) {
+                Some(_) if Instant::now() < deadline => {
+                    tokio::time::timeout_at(deadline, sealed).await.ok();
+                }
+                Some((file, seals_by)) => {
+                    return HttpResponse::Accepted().json(serde_json::json!({
+                        "success": true,
+                        "pending": true,
+                        "local_batch_id": batch_id,
+                        "file": file,
+                        "seals_by": seals_by
+                    }));
+                }
+                None => return store_error(StoreError::NotFound(e)),
+            },
+            Err(e) => return store_error(e),
+        }
+    };
+    let Some(index) = batch.files.iter().position(|f| f.name == name) else {
+        return store_error(StoreError::NotFound(format!("File {} in batch {}", name, batch_id)));
+    };
+
+    let leaves = match batch.files.iter().map(|f| hex_to_hash(&f.leaf)).collect::<Result<Vec<_>, _>>() {
+        Ok(leaves) => leaves,
+        Err(e) => return store_error(StoreError::Backend(format!("Invalid leaf hash: {}", e))),
+    };
+    let tree = MerkleTree::new(leaves);
+    if tree.root_hex() != batch.root.trim_start_matches("0x").to_lowercase() {
+        return store_error(StoreError::Backend(format!(
+            "Manifest of batch {} does not match its root",
+            batch_id
+        )));
+    }
+    if let Some(aggregate) = &batch.aggregate {
+        if aggregate.proof.leaf != tree.root() || hex::encode(aggregate.proof.compute_root()) != aggregate.root {
+            return store_error(StoreError::Backend(format!(
+                "Aggregate proof of batch {} does not match its root",
+                batch_id
+            )));
+        }
+    }
+
+    HttpResponse::Ok().json(ProofResponse {
+        success: true,
+        local_batch_id: batch_id,
+        file: name,
+        proof: tree.proof(index).expect("index is within the manifest"),
+        root: tree.root_hex(),
+        mantle_batch_id: batch.mantle_batch_id,
+        tx_hash: batch.tx_hash,
+        registry_address: batch.registry_address,
+        aggregate: batch.aggregate,
+    })
+}
+
+/// Read and parse the manifest of a sealed batch
+pub async fn load_metadata(store: &dyn BatchStore, batch_id: u64) -> Result<BatchMetadata, StoreError> {
+    let manifest = store.read_manifest(batch_id).await?;
+    let mut batch: BatchMetadata = serde_json::from_slice(&manifest)
+        .map_err(|e| StoreError::Backend(format!("Invalid manifest for batch {}: {}", batch_id, e)))?;
+    backfill_cids(&mut batch);
+    Ok(batch)
+}
+
+/// Fill in the CIDs missing from manifests sealed before they were recorded
+fn backfill_cids(batch: &mut BatchMetadata) {
+    if batch.root_cid.is_empty() {
+        if let Ok(root) = hex_to_hash(&batch.root) {
+            batch.root_cid = root_cid(&root);
+        }
+    }
+    for file in batch.files.iter_mut().filter(|file| file.cid.is_empty()) {
+        let Ok(leaf) = hex_to_hash(&file.leaf) else { continue };
+        // Multi-chunk leaves are tree roots, not raw blocks
+        file.cid = match &file.chunks {
+            Some(chunks) if chunks.len() > 1 => root_cid(&leaf),
+            _ => leaf_cid(&leaf),
+        };
+    }
+}
+
+/// JSON error response for a storage error (404 for missing batches and files)
+pub fn store_error(e: StoreError) -> HttpResponse {
+    let mut builder = match e {
+        StoreError::NotFound(_) => HttpResponse::NotFound(),
+        _ => HttpResponse::InternalServerError(),
+    };
+    builder.json(serde_json::json!({
+        "success": false,
+        "error": e.to_string()
+    }))
+}
+
+fn header_value(req: &HttpRequest, name: header::HeaderName) -> Option<&str> {
+    req.headers().get(name).and_then(|v| v.to_str().ok())
+}
+
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|t| t.trim().trim_start_matches("W/"))
+        .any(|t| t == "*" || t == etag)
+}
+
+/// Outcome of parsing a `Range` header against a file size
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    /// Serve these bytes with 206
+    Satisfiable(Range<u64>),
+    /// Valid syntax but no overlap with the file (416)
+    Unsatisfiable,
+    /// Unsupported or malformed header: serve the whole file
+    Ignored,
+}
+
+/// Parse a single `bytes=` range (`a-b`, `a-` or `-n`)
+fn parse_range(value: &str, size: u64) -> ByteRange {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return ByteRange::Ignored;
+    };
+    if spec.contains(',') {
+        return ByteRange::Ignored;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Ignored;
+    };
+
+    let parse = |s: &str| s.trim().parse::<u64>().ok();
+    let range = match (start.trim().is_empty(), end.trim().is_empty()) {
+        // Suffix range: the last n bytes
+        (true, false) => match parse(end) {
+            Some(0) => return ByteRange::Unsatisfiable,
+            Some(n) => size.saturating_sub(n)..size,
+            None => return ByteRange::Ignored,
+        },
+        (false, true) => match parse(start) {
+            Some(start) => start..size,
+            None => return ByteRange::Ignored,
+        },
+        (false, false) => match (parse(start), parse(end)) {
+            (Some(start), Some(end)) if start <= end => start..size.min(end + 1),
+            _ => return ByteRange::Ignored,
+        },
+        (true, true) => return ByteRange::Ignored,
+    };
+
+    if range.start >= size {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Satisfiable(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::tests::server_config;
+    use crate::lookup::LookupIndex;
+    use crate::sessions::SessionRegistry;
+    use crate::store::{FsStore, StorageConfig};
+    use crate::tenants::Tenants;
+    use crate::FileEntry;
+    use actix_web::{test as actix_test, App};
+    use merkle_tree::hash_data;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-4", 10), ByteRange::Satisfiable(0..5));
+        assert_eq!(parse_range("bytes=5-", 10), ByteRange::Satisfiable(5..10));
+        assert_eq!(parse_range("bytes=-3", 10), ByteRange::Satisfiable(7..10));
+        assert_eq!(parse_range("bytes=-30", 10), ByteRange::Satisfiable(0..10));
+        assert_eq!(parse_range("bytes=8-100", 10), ByteRange::Satisfiable(8..10));
+        assert_eq!(parse_range("bytes=10-", 10), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 10), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-0", 0), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=5-2", 10), ByteRange::Ignored);
+        assert_eq!(parse_range("bytes=0-1,4-5", 10), ByteRange::Ignored);
+        assert_eq!(parse_range("items=0-1", 10), ByteRange::Ignored);
+    }
+
+    async fn sealed_batch(store: &FsStore, data: &[u8], created_at: u64, anchored: bool) -> u64 {
+        let id = store.create_batch().await.unwrap();
+        store.put_file(id, "doc.txt", data).await.unwrap();
+        let leaf = hash_data(data);
+        let metadata = BatchMetadata {
+            local_batch_id: id,
+            root: hex::encode(leaf),
+            root_cid: String::new(),
+            file_count: 1,
+            suggested_meta_uri: String::new(),
+            registry_address: String::new(),
+            hash_only: false,
+            manifest_cid: None,
+            created_at: Some(created_at),
+            mantle_batch_id: anchored.then_some(7),
+            tx_hash: None,
+            block_number: None,
+            aggregate: None,
+            files: vec![FileEntry {
+                name: "doc.txt".into(),
+                original_name: "doc.txt".into(),
+                size: data.len() as u64,
+                leaf: hex::encode(leaf),
+                cid: leaf_cid(&leaf),
+                chunks: None,
+                sha256: None,
+            }],
+        };
+        store.seal(id, &serde_json::to_vec(&metadata).unwrap()).await.unwrap();
+        id
+    }
+
+    #[tokio::test]
+    async fn test_load_manifest_without_cids() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FsStore::open(dir.path()).unwrap();
+        let id = store.create_batch().await.unwrap();
+        let leaf = hash_data(b"legacy");
+        // As sealed before CIDs were recorded
+        let manifest = serde_json::json!({
+            "local_batch_id": id,
+            "root": hex::encode(leaf),
+            "file_count": 1,
+            "suggested_meta_uri": "ipfs://placeholder-1",
+            "registry_address": "0x0",
+            "files": [{ "name": "doc.txt", "size": 6, "leaf": hex::encode(leaf) }]
+        });
+        store.seal(id, &serde_json::to_vec(&manifest).unwrap()).await.unwrap();
+
+        let batch = load_metadata(&store, id).await.unwrap();
+        assert_eq!(batch.root_cid, root_cid(&leaf));
+        assert_eq!(batch.files[0].cid, leaf_cid(&leaf));
+    }
+
+    #[actix_web::test]
+    async fn test_list_get_and_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let fs_store = FsStore::open(dir.path()).unwrap();
+        let a = sealed_batch(&fs_store, b"hello world", 100, false).await;
+        let b = sealed_batch(&fs_store, b"second", 200, true).await;
+        let c = sealed_batch(&fs_store, b"third", 300, true).await;
+
+        let store: Arc<dyn BatchStore> = Arc::new(fs_store);
+        let config = server_config(
+            dir.path(),
+            StorageConfig::Filesystem {
+                root: dir.path().display().to_string(),
+            },
+        );
+        let lookup = Arc::new(LookupIndex::open(&config.lookup_db).unwrap());
+        let sessions = Arc::new(SessionRegistry::open(&config.session_file, 3600).unwrap());
+        let tenants = Tenants::single(Arc::new(config), store, lookup, sessions);
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(Arc::new(tenants)))
+                .app_data(web::Data::new(None::<ChunkStore>))
+                .service(list_batches)
+                .service(get_batch)
+                .service(download_file)
+                .service(get_proof),
+        )
+        .await;
+
+        let list: serde_json::Value =
+            actix_test::call_and_read_body_json(&app, actix_test::TestRequest::get().uri("/batches?limit=2").to_request()).await;
+        assert_eq!(list["batches"].as_array().unwrap().len(), 2);
+        assert_eq!(list["next_after"], b);
+
+        let list: serde_json::Value = actix_test::call_and_read_body_json(
+            &app,
+            actix_test::TestRequest::get().uri("/batches?anchored=true&since=250").to_request(),
+        )
+        .await;
+        assert_eq!(list["batches"][0]["local_batch_id"], c);
+        assert_eq!(list["batches"].as_array().unwrap().len(), 1);
+        assert!(list.get("next_after").is_none());
+
+        let manifest: serde_json::Value =
+            actix_test::call_and_read_body_json(&app, actix_test::TestRequest::get().uri(&format!("/batches/{}", a)).to_request())
+                .await;
+        assert_eq!(manifest["files"][0]["name"], "doc.txt");
+
+        let missing = actix_test::call_service(&app, actix_test::TestRequest::get().uri("/batches/999").to_request()).await;
+        assert_eq!(missing.status(), 404);
+
+        let uri = format!("/batches/{}/files/doc.txt", a);
+        let etag = format!("\"{}\"", hex::encode(hash_data(b"hello world")));
+        let full = actix_test::call_service(&app, actix_test::TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(full.status(), 200);
+        assert_eq!(full.headers().get(header::ETAG).unwrap().to_str().unwrap(), etag);
+        assert_eq!(actix_test::read_body(full).await, &b"hello world"[..]);
+
+        let partial = actix_test::call_service(
+            &app,
+            actix_test::TestRequest::get().uri(&uri).insert_header((header::RANGE, "bytes=6-")).to_request(),
+        )
+        .await;
+        assert_eq!(partial.status(), 206);
+        assert_eq!(partial.headers().get(header::CONTENT_RANGE).unwrap(), "bytes 6-10/11");
+        assert_eq!(actix_test::read_body(partial).await, &b"world"[..]);
+
+        let cached = actix_test::call_service(
+            &app,
+            actix_test::TestRequest::get().uri(&uri).insert_header((header::IF_NONE_MATCH, etag)).to_request(),
+        )
+        .await;
+        assert_eq!(cached.status(), 304);
+
+        let unsatisfiable = actix_test::call_service(
+            &app,
+            actix_test::TestRequest::get().uri(&uri).insert_header((header::RANGE, "bytes=50-")).to_request(),
+        )
+        .await;
+        assert_eq!(unsatisfiable.status(), 416);
+
+        let no_file = actix_test::call_service(
+            &app,
+            actix_test::TestRequest::get().uri(&format!("/batches/{}/files/nope.txt", a)).to_request(),
+        )
+        .await;
+        assert_eq!(no_file.status(), 404);
+
+        let proof: serde_json::Value = actix_test::call_and_read_body_json(
+            &app,
+            actix_test::TestRequest::get().uri(&format!("/batches/{}/proof/doc.txt", b)).to_request(),
+        )
+        .await;
+        let parsed: MerkleProof = serde_json::from_value(proof.clone()).unwrap();
+        assert_eq!(parsed.leaf, hash_data(b"second"));
+        assert!(parsed.verify(&hex_to_hash(proof["root"].as_str().unwrap()).unwrap()));
+        assert_eq!(proof["mantle_batch_id"], 7);
+
+        let no_proof = actix_test::call_service(
+            &app,
+            actix_test::TestRequest::get().uri(&format!("/batches/{}/proof/nope.txt", b)).to_request(),
+        )
+        .await;
+        assert_eq!(no_proof.status(), 404);
+    }
+}
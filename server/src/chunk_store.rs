@@ -0,0 +1,235 @@
+use crate::store::FileStream;
+use actix_web::web::Bytes;
+use futures_util::{stream, StreamExt};
+use merkle_tree::Hash;
+use std::fs;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Makes temp file names unique within the process
+static NEXT_TMP: AtomicU64 = AtomicU64::new(0);
+
+/// Content-addressed store for file chunks shared by all batches
+///
+/// Chunks are stored as `<dir>/<hex hash>`, so identical data uploaded in
+/// different files or batches is only written once. A chunk created by an
+/// upload stays claimed until that upload finishes; if no upload using it is
+/// committed, it is deleted again.
+#[derive(Debug, Clone)]
+pub struct ChunkStore {
+    dir: PathBuf,
+    /// Chunks created by uploads that have not finished yet
+    pending: Arc<Mutex<HashMap<Hash, Pending>>>,
+}
+
+/// A chunk created by an unfinished upload
+#[derive(Debug)]
+struct Pending {
+    /// Claims held by unfinished uploads
+    claims: usize,
+    /// An upload using the chunk was committed, so it is never deleted
+    kept: bool,
+}
+
+/// Newly created chunks one upload has written or reused, released when it finishes
+#[derive(Debug, Default)]
+pub struct ChunkClaims(Vec<Hash>);
+
+impl ChunkStore {
+    /// Open (and create if needed) a chunk store rooted at `dir`
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            pending: Arc::default(),
+        })
+    }
+
+    /// Path where a chunk with the given hash is stored
+    pub fn path(&self, hash: &Hash) -> PathBuf {
+        self.dir.join(hex::encode(hash))
+    }
+
+    /// Store a chunk, returning `true` if it was not already present
+    ///
+    /// Chunks that are not yet kept by a committed upload are added to `claims`.
+    pub fn put(&self, hash: &Hash, data: &[u8], claims: &mut ChunkClaims) -> io::Result<bool> {
+        let path = self.path(hash);
+        // Chunks only appear and disappear under the lock
+        {
+            let mut pending = self.pending();
+            if path.exists() {
+                claim_existing(&mut pending, hash, claims);
+                return Ok(false);
+            }
+        }
+
+        // Write to a temp file of our own first, so neither a crash nor a concurrent
+        // writer of the same chunk ever leaves a partial chunk at `path`
+        let tmp = self.dir.join(format!(
+            ".{}.{}.{}.tmp",
+            hex::encode(hash),
+            std::process::id(),
+            NEXT_TMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        if let Err(e) = fs::File::create(&tmp).and_then(|mut file| file.write_all(data)) {
+            fs::remove_file(&tmp).ok();
+            return Err(e);
+        }
+
+        let mut pending = self.pending();
+        if path.exists() {
+            fs::remove_file(&tmp).ok();
+            claim_existing(&mut pending, hash, claims);
+            return Ok(false);
+        }
+        if let Err(e) = fs::rename(&tmp, &path) {
+            fs::remove_file(&tmp).ok();
+            return Err(e);
+        }
+        pending.insert(*hash, Pending { claims: 1, kept: false });
+        claims.0.push(*hash);
+        Ok(true)
+    }
+
+    /// Release an upload's claims, deleting the chunks no committed upload uses
+    pub fn release(&self, claims: ChunkClaims, committed: bool) {
+        let mut pending = self.pending();
+        for hash in claims.0 {
+            let Some(chunk) = pending.get_mut(&hash) else { continue };
+            chunk.claims -= 1;
+            chunk.kept |= committed;
+            if chunk.claims == 0 {
+                if !chunk.kept {
+                    fs::remove_file(self.path(&hash)).ok();
+                }
+                pending.remove(&hash);
+            }
+        }
+    }
+
+    fn pending(&self) -> MutexGuard<'_, HashMap<Hash, Pending>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Stream the bytes `range` of a file reassembled from its chunks
+    ///
+    /// Chunks wholly before the range are skipped without being read.
+    pub fn read_range(&self, chunks: Vec<Hash>, range: Range<u64>) -> FileStream {
+        let store = self.clone();
+        stream::unfold((chunks.into_iter(), 0u64), move |(mut chunks, mut pos)| {
+            let store = store.clone();
+            let range = range.clone();
+            async move {
+                while pos < range.end {
+                    let path = store.path(&chunks.next()?);
+                    let len = match tokio::fs::metadata(&path).await {
+                        Ok(meta) => meta.len(),
+                        Err(e) => return Some((Err(e), (chunks, range.end))),
+                    };
+                    let chunk_start = pos;
+                    pos += len;
+                    if pos <= range.start {
+                        continue;
+                    }
+
+                    let data = match tokio::fs::read(&path).await {
+                        Ok(data) => Bytes::from(data),
+                        Err(e) => return Some((Err(e), (chunks, range.end))),
+                    };
+                    let from = range.start.saturating_sub(chunk_start) as usize;
+                    let to = (range.end.min(pos) - chunk_start) as usize;
+                    return Some((Ok(data.slice(from..to)), (chunks, pos)));
+                }
+                None
+            }
+        })
+        .boxed()
+    }
+}
+
+/// Claim a stored chunk if an unfinished upload created it
+fn claim_existing(pending: &mut HashMap<Hash, Pending>, hash: &Hash, claims: &mut ChunkClaims) {
+    if let Some(chunk) = pending.get_mut(hash) {
+        chunk.claims += 1;
+        claims.0.push(*hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::TryStreamExt;
+    use merkle_tree::hash_data;
+
+    #[tokio::test]
+    async fn test_read_range_across_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ChunkStore::open(dir.path()).unwrap();
+        let parts: [&[u8]; 3] = [b"hello ", b"chunked ", b"world"];
+        let hashes: Vec<Hash> = parts.iter().map(|p| hash_data(p)).collect();
+        for (hash, part) in hashes.iter().zip(parts) {
+            store.put(hash, part, &mut ChunkClaims::default()).unwrap();
+        }
+
+        for (range, expected) in [
+            (0..19, &b"hello chunked world"[..]),
+            (3..10, b"lo chun"),
+            (6..14, b"chunked "),
+            (15..19, b"orld"),
+            (19..19, b""),
+        ] {
+            let read: Vec<Bytes> = store
+                .read_range(hashes.clone(), range)
+                .try_collect()
+                .await
+                .unwrap();
+            assert_eq!(read.concat(), expected);
+        }
+    }
+
+    #[test]
+    fn test_concurrent_puts_of_one_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ChunkStore::open(dir.path()).unwrap();
+        let data = vec![7u8; 1 << 20];
+        let hash = hash_data(&data);
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| store.put(&hash, &data, &mut ChunkClaims::default()).unwrap());
+            }
+        });
+        assert_eq!(fs::read(store.path(&hash)).unwrap(), data);
+        // Only the chunk itself is left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_released_chunks_kept_only_when_committed() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ChunkStore::open(dir.path()).unwrap();
+        let (shared, own) = (hash_data(b"shared"), hash_data(b"own"));
+
+        // `aborted` creates both chunks; `committed` reuses one before `aborted` gives up
+        let (mut aborted, mut committed) = (ChunkClaims::default(), ChunkClaims::default());
+        assert!(store.put(&shared, b"shared", &mut aborted).unwrap());
+        assert!(store.put(&own, b"own", &mut aborted).unwrap());
+        assert!(!store.put(&shared, b"shared", &mut committed).unwrap());
+        store.release(committed, true);
+        store.release(aborted, false);
+        assert!(store.path(&shared).is_file());
+        assert!(!store.path(&own).exists());
+
+        // Kept chunks are permanent and no longer claimed
+        let mut claims = ChunkClaims::default();
+        assert!(!store.put(&shared, b"shared", &mut claims).unwrap());
+        store.release(claims, false);
+        assert!(store.path(&shared).is_file());
+    }
+}
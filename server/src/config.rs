@@ -0,0 +1,315 @@
+use crate::anchor::{AnchorConfig, AnchorMode, QueueSettings};
+use crate::auth::{AuthConfig, JwtConfig};
+use crate::indexer::{IndexerConfig, IndexerSettings};
+use crate::listen::ListenConfig;
+use crate::publish::PublishConfig;
+use crate::quotas::{Limit, Limits, QuotaConfig};
+use crate::receipts::ReceiptConfig;
+use crate::rolling::RollingConfig;
+use crate::store::{BlobConfig, S3Config, StorageConfig};
+use crate::upload::UploadLimits;
+use std::env;
+use std::fmt;
+use std::time::Duration;
+
+/// Server-side configuration
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Split uploads into content-defined chunks and store them deduplicated
+    pub chunking: bool,
+    /// Where batch files and manifests are stored
+    pub storage: StorageConfig,
+    /// Local directory for the shared chunk store
+    pub chunk_dir: String,
+    /// Deduplicated content-addressed file storage (`None` stores files per batch)
+    pub blobs: Option<BlobConfig>,
+    /// SQLite index from root, leaf and file hashes to batches
+    pub lookup_db: String,
+    /// Local directory where uploads are received before being sealed
+    pub staging_dir: String,
+    /// Per-file and per-batch upload size limits
+    pub limits: UploadLimits,
+    /// Server-side anchoring (`None` when disabled)
+    pub anchor: Option<AnchorConfig>,
+    /// Mirror of on-chain `BatchRegistered` events (`None` when disabled)
+    pub indexer: Option<IndexerConfig>,
+    /// Where canonical batch manifests are published
+    pub publish: PublishConfig,
+    /// File holding open batch sessions
+    pub session_file: String,
+    /// Idle seconds before an open batch session is discarded
+    pub session_ttl: u64,
+    /// Rolling batches for `POST /upload` (`None` seals every upload on its own)
+    pub rolling: Option<RollingConfig>,
+    /// Key that signs upload receipts
+    pub receipts: ReceiptConfig,
+    /// API key and JWT authentication (`None` serves everything as the default tenant)
+    pub auth: Option<AuthConfig>,
+    /// Per-tenant rate limits and daily quotas
+    pub quotas: QuotaConfig,
+}
+
+/// Default per-file upload limit (1 GiB)
+const DEFAULT_MAX_FILE_BYTES: u64 = 1 << 30;
+/// Default per-batch upload limit (4 GiB)
+const DEFAULT_MAX_BATCH_BYTES: u64 = 4 << 30;
+/// Default idle time before an open batch session is discarded (24 hours)
+const DEFAULT_SESSION_TTL_SECS: u64 = 24 * 60 * 60;
+/// Default number of files that seals a rolling batch early
+const DEFAULT_ROLLING_MAX_FILES: u64 = 1000;
+
+impl ServerConfig {
+    /// Load configuration from environment variables with sensible defaults,
+    /// keeping state files under the listener's storage root
+    pub fn from_env(listen: &ListenConfig) -> Result<Self, ServerConfigError> {
+        let root = listen.storage_dir.as_str();
+        let chunking = env::var("MERKSEAL_CHUNKING")
+            .map(|v| parse_bool(&v))
+            .unwrap_or(false);
+        
+        let storage = match env::var("MERKSEAL_STORAGE")
+            .unwrap_or_else(|_| "fs".to_string())
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "fs" | "filesystem" => StorageConfig::Filesystem { root: root.to_string() },
+            "sqlite" => StorageConfig::Sqlite {
+                database: env::var("MERKSEAL_SQLITE_PATH")
+                    .unwrap_or_else(|_| format!("{}/index.db", root)),
+                root: root.to_string(),
+            },
+            "s3" => StorageConfig::S3(S3Config {
+                endpoint: require("MERKSEAL_S3_ENDPOINT")?,
+                bucket: require("MERKSEAL_S3_BUCKET")?,
+                region: env::var("MERKSEAL_S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+                access_key: require("MERKSEAL_S3_ACCESS_KEY")?,
+                secret_key: require("MERKSEAL_S3_SECRET_KEY")?,
+                prefix: env::var("MERKSEAL_S3_PREFIX").unwrap_or_default(),
+                path_style: env::var("MERKSEAL_S3_PATH_STYLE")
+                    .map(|v| parse_bool(&v))
+                    .unwrap_or(true),
+            }),
+            other => return Err(ServerConfigError::UnknownStorage(other.to_string())),
+        };
+        
+        let chunk_dir = env::var("MERKSEAL_CHUNK_DIR").unwrap_or_else(|_| format!("{}/chunks", root));
+        let blobs = if env::var("MERKSEAL_BLOBS").map(|v| parse_bool(&v)).unwrap_or(false) {
+            Some(BlobConfig {
+                dir: env::var("MERKSEAL_BLOB_DIR").unwrap_or_else(|_| format!("{}/blobs", root)),
+                gc_interval: Duration::from_secs(parse_u64("MERKSEAL_BLOB_GC_SECS", 3600)?.max(1)),
+            })
+        } else {
+            None
+        };
+        let lookup_db = env::var("MERKSEAL_LOOKUP_DB").unwrap_or_else(|_| format!("{}/.lookup.db", root));
+        let staging_dir = env::var("MERKSEAL_STAGING_DIR").unwrap_or_else(|_| format!("{}/.staging", root));
+        
+        let limits = UploadLimits {
+            max_file_bytes: parse_u64("MERKSEAL_MAX_FILE_BYTES", DEFAULT_MAX_FILE_BYTES)?,
+            max_batch_bytes: parse_u64("MERKSEAL_MAX_BATCH_BYTES", DEFAULT_MAX_BATCH_BYTES)?,
+        };
+        
+        let anchor_mode = match env::var("MERKSEAL_ANCHOR")
+            .unwrap_or_else(|_| "off".to_string())
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "off" | "false" | "0" => None,
+            "manual" => Some(AnchorMode::Manual),
+            "auto" | "true" | "1" => Some(AnchorMode::Auto),
+            other => return Err(ServerConfigError::Invalid("MERKSEAL_ANCHOR", other.to_string())),
+        };
+        let anchor = match anchor_mode {
+            Some(mode) => Some(AnchorConfig {
+                mode,
+                // Same key as scripts/anchor.js unless a dedicated one is set
+                private_key: require("MERKSEAL_ANCHOR_PRIVATE_KEY")
+                    .or_else(|_| require("PRIVATE_KEY"))
+                    .map_err(|_| ServerConfigError::Missing("MERKSEAL_ANCHOR_PRIVATE_KEY"))?,
+                queue_path: env::var("MERKSEAL_ANCHOR_QUEUE")
+                    .unwrap_or_else(|_| format!("{}/.anchor_queue.json", root)),
+                poll_interval: Duration::from_secs(parse_u64("MERKSEAL_ANCHOR_POLL_SECS", 5)?.max(1)),
+                queue: QueueSettings {
+                    finality: parse_u64("MERKSEAL_ANCHOR_CONFIRMATIONS", 3)?.max(1),
+                    stuck_after: parse_u64("MERKSEAL_ANCHOR_STUCK_SECS", 120)?,
+                    max_in_flight: parse_u64("MERKSEAL_ANCHOR_MAX_IN_FLIGHT", 16)?.max(1) as usize,
+                    max_attempts: parse_u64("MERKSEAL_ANCHOR_MAX_ATTEMPTS", 5)?.max(1) as u32,
+                    // 0 (the default) anchors every batch on its own
+                    aggregate_every: Some(parse_u64("MERKSEAL_ANCHOR_AGGREGATE_SECS", 0)?).filter(|s| *s > 0),
+                },
+            }),
+            None => None,
+        };
+        
+        let indexer = if env::var("MERKSEAL_INDEXER").map(|v| parse_bool(&v)).unwrap_or(false) {
+            Some(IndexerConfig {
+                db_path: env::var("MERKSEAL_INDEXER_DB").unwrap_or_else(|_| format!("{}/.chain_index.db", root)),
+                poll_interval: Duration::from_secs(parse_u64("MERKSEAL_INDEXER_POLL_SECS", 5)?.max(1)),
+                settings: IndexerSettings {
+                    start_block: parse_u64("MERKSEAL_INDEXER_START_BLOCK", 0)?,
+                    block_range: parse_u64("MERKSEAL_INDEXER_BLOCK_RANGE", 2000)?.max(1),
+                    reorg_depth: parse_u64("MERKSEAL_INDEXER_REORG_DEPTH", 64)?.max(1),
+                },
+            })
+        } else {
+            None
+        };
+        
+        let session_file = env::var("MERKSEAL_SESSION_FILE").unwrap_or_else(|_| format!("{}/.sessions.json", root));
+        let session_ttl = parse_u64("MERKSEAL_SESSION_TTL_SECS", DEFAULT_SESSION_TTL_SECS)?.max(1);
+        
+        // 0 (the default) seals every upload as its own batch
+        let rolling = match parse_u64("MERKSEAL_ROLLING_SECS", 0)? {
+            0 => None,
+            secs => Some(RollingConfig {
+                interval: Duration::from_secs(secs),
+                max_files: parse_u64("MERKSEAL_ROLLING_MAX_FILES", DEFAULT_ROLLING_MAX_FILES)?.max(1) as usize,
+            }),
+        };
+        
+        let publish = match env::var("MERKSEAL_PUBLISH")
+            .unwrap_or_else(|_| "local".to_string())
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "local" => PublishConfig::Local {
+                dir: env::var("MERKSEAL_MANIFEST_DIR").unwrap_or_else(|_| format!("{}/manifests", root)),
+                // Behind a proxy the anchored metaURI needs the public address instead
+                base_url: optional("MERKSEAL_PUBLIC_URL")
+                    .map(|url| url.trim_end_matches('/').to_string())
+                    .unwrap_or_else(|| listen.local_url()),
+            },
+            "ipfs" => PublishConfig::Ipfs {
+                api_url: env::var("MERKSEAL_IPFS_API")
+                    .unwrap_or_else(|_| "http://127.0.0.1:5001".to_string())
+                    .trim_end_matches('/')
+                    .to_string(),
+            },
+            "http" => PublishConfig::Http {
+                base_url: require("MERKSEAL_PUBLISH_URL")?.trim_end_matches('/').to_string(),
+                token: env::var("MERKSEAL_PUBLISH_TOKEN").ok().filter(|t| !t.is_empty()),
+            },
+            other => return Err(ServerConfigError::Invalid("MERKSEAL_PUBLISH", other.to_string())),
+        };
+        
+        let receipts = ReceiptConfig {
+            key: env::var("MERKSEAL_RECEIPT_KEY").ok().filter(|key| !key.trim().is_empty()),
+            key_file: env::var("MERKSEAL_RECEIPT_KEY_FILE").unwrap_or_else(|_| format!("{}/.receipt_key", root)),
+        };
+        
+        let auth = if env::var("MERKSEAL_AUTH").map(|v| parse_bool(&v)).unwrap_or(false) {
+            let secret = optional("MERKSEAL_JWT_SECRET");
+            let jwks_file = optional("MERKSEAL_JWKS_FILE");
+            let jwt = (secret.is_some() || jwks_file.is_some()).then(|| JwtConfig {
+                secret,
+                jwks_file,
+                issuer: optional("MERKSEAL_JWT_ISSUER"),
+                audience: optional("MERKSEAL_JWT_AUDIENCE"),
+                tenant_claim: optional("MERKSEAL_JWT_TENANT_CLAIM").unwrap_or_else(|| "tenant".to_string()),
+            });
+            let api_keys_file = optional("MERKSEAL_API_KEYS_FILE");
+            if api_keys_file.is_none() && jwt.is_none() {
+                // One of the key file or a JWT secret/JWKS is needed to admit anyone
+                return Err(ServerConfigError::Missing("MERKSEAL_API_KEYS_FILE"));
+            }
+            Some(AuthConfig { api_keys_file, jwt })
+        } else {
+            None
+        };
+        
+        // 0 (the default) leaves a limit off
+        let mut defaults = Limits::new();
+        for (name, limit) in [
+            ("MERKSEAL_QUOTA_REQUESTS_PER_MINUTE", Limit::RequestsPerMinute),
+            ("MERKSEAL_QUOTA_BYTES_PER_DAY", Limit::BytesPerDay),
+            ("MERKSEAL_QUOTA_BATCHES_PER_DAY", Limit::BatchesPerDay),
+            ("MERKSEAL_QUOTA_ANCHORS_PER_DAY", Limit::AnchorsPerDay),
+        ] {
+            match parse_u64(name, 0)? {
+                0 => {}
+                max => {
+                    defaults.insert(limit, max);
+                }
+            }
+        }
+        let quotas = QuotaConfig {
+            defaults,
+            overrides_file: optional("MERKSEAL_QUOTAS_FILE"),
+            usage_file: Some(env::var("MERKSEAL_USAGE_FILE").unwrap_or_else(|_| format!("{}/.usage.json", root))),
+        };
+        
+        Ok(Self {
+            chunking,
+            storage,
+            chunk_dir,
+            blobs,
+            lookup_db,
+            staging_dir,
+            limits,
+            anchor,
+            indexer,
+            publish,
+            session_file,
+            session_ttl,
+            rolling,
+            receipts,
+            auth,
+            quotas,
+        })
+    }
+}
+
+/// Parse a boolean flag from an environment variable value
+fn parse_bool(value: &str) -> bool {
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes" | "on"
+    )
+}
+
+/// Parse an optional numeric environment variable
+fn parse_u64(name: &'static str, default: u64) -> Result<u64, ServerConfigError> {
+    match env::var(name) {
+        Ok(value) => value.trim().parse().map_err(|_| ServerConfigError::Invalid(name, value)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// A non-empty environment variable
+fn optional(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+fn require(name: &'static str) -> Result<String, ServerConfigError> {
+    env::var(name).map_err(|_| ServerConfigError::Missing(name))
+}
+
+/// Server configuration errors
+#[derive(Debug)]
+pub enum ServerConfigError {
+    /// A required environment variable is not set
+    Missing(&'static str),
+    /// `MERKSEAL_STORAGE` names an unknown backend
+    UnknownStorage(String),
+    /// An environment variable has an unparseable value
+    Invalid(&'static str, String),
+    /// The config file could not be read or parsed
+    File(String, String),
+}
+
+impl fmt::Display for ServerConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerConfigError::Missing(name) => write!(f, "{} environment variable not set", name),
+            ServerConfigError::UnknownStorage(kind) => {
+                write!(f, "Unknown storage backend '{}' (expected fs, sqlite or s3)", kind)
+            }
+            ServerConfigError::Invalid(name, value) => {
+                write!(f, "Invalid value '{}' for {}", value, name)
+            }
+            ServerConfigError::File(path, e) => write!(f, "Invalid config file {}: {}", path, e),
+        }
+    }
+}
+
+impl std::error::Error for ServerConfigError {}
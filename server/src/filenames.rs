@@ -0,0 +1,246 @@
+use std::collections::HashSet;
+use std::fmt;
+
+/// Longest on-disk file name in bytes (common filesystem limit)
+const MAX_NAME_BYTES: usize = 255;
+
+/// Names the server uses for its own files inside a batch or storage root
+const RESERVED_NAMES: &[&str] = &["metadata.json", "chunks", "index.db"];
+
+/// Device names that cannot be used as file names on Windows
+const WINDOWS_DEVICE_NAMES: &[&str] = &[
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// Assigns safe, unique on-disk names to the files of one batch
+///
+/// Client-supplied names are reduced to a single path component with a
+/// conservative character set. Reserved names and hidden files get a `_`
+/// prefix, and duplicates (compared case-insensitively) get a `-N` suffix
+/// before the extension. Path traversal attempts are rejected outright.
+#[derive(Debug, Default)]
+pub struct BatchFileNames {
+    used: HashSet<String>,
+}
+
+impl BatchFileNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Continue naming a batch that already holds files with these names
+    pub fn with_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            used: names.into_iter().map(str::to_lowercase).collect(),
+        }
+    }
+
+    /// Pick the on-disk name for the `index`-th file of the batch
+    pub fn assign(&mut self, original: Option<&str>, index: usize) -> Result<String, FilenameError> {
+        let base = match original {
+            Some(name) => sanitize(name)?,
+            None => String::new(),
+        };
+        let base = if base.is_empty() {
+            format!("file_{}", index)
+        } else {
+            base
+        };
+
+        let (stem, ext) = split_extension(&base);
+        let mut candidate = base.clone();
+        let mut n = 1;
+        while self.used.contains(&candidate.to_lowercase()) {
+            candidate = truncate_name(&format!("{}-{}", stem, n), ext);
+            n += 1;
+        }
+
+        self.used.insert(candidate.to_lowercase());
+        Ok(candidate)
+    }
+}
+
+/// Normalize a client-supplied file name to a safe single path component
+///
+/// Returns an empty string if nothing usable is left, so the caller can fall
+/// back to a generated name.
+pub fn sanitize(original: &str) -> Result<String, FilenameError> {
+    if original.contains('\0') {
+        return Err(FilenameError::InvalidCharacter(original.to_string()));
+    }
+
+    let components: Vec<&str> = original.split(['/', '\\']).collect();
+    if components.iter().any(|c| c.trim() == "..") {
+        return Err(FilenameError::PathTraversal(original.to_string()));
+    }
+
+    // Keep only the last component ("C:\fakepath\a.txt" -> "a.txt")
+    let last = components.last().copied().unwrap_or_default();
+
+    let mut name: String = last
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '.' | '-' | '_' | ' ' | '(' | ')' | '+' | ',') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    // Trailing dots and spaces are silently dropped by some filesystems
+    name = name.trim().trim_end_matches(['.', ' ']).to_string();
+
+    if name.is_empty() || name == "." {
+        return Ok(String::new());
+    }
+
+    // Hidden files collide with the server's own temp and marker files
+    if name.starts_with('.') || is_reserved(&name) {
+        name = format!("_{}", name);
+    }
+
+    let (stem, ext) = split_extension(&name);
+    Ok(truncate_name(stem, ext))
+}
+
+fn is_reserved(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    let stem = lower.split('.').next().unwrap_or_default();
+    RESERVED_NAMES.contains(&lower.as_str())
+        || lower.parse::<u64>().is_ok()
+        || WINDOWS_DEVICE_NAMES.contains(&stem)
+}
+
+/// Split "name.ext" into ("name", ".ext"); names without a stem have no extension
+fn split_extension(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(i) if i > 0 => name.split_at(i),
+        _ => (name, ""),
+    }
+}
+
+/// Shorten the stem so `stem + ext` fits in `MAX_NAME_BYTES`
+fn truncate_name(stem: &str, ext: &str) -> String {
+    let ext = if ext.len() > MAX_NAME_BYTES / 2 { "" } else { ext };
+    let budget = MAX_NAME_BYTES - ext.len();
+    let mut end = stem.len().min(budget);
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &stem[..end], ext)
+}
+
+/// Reasons a client-supplied file name is rejected
+#[derive(Debug, PartialEq, Eq)]
+pub enum FilenameError {
+    /// Name contains `..` path components
+    PathTraversal(String),
+    /// Name contains a NUL byte
+    InvalidCharacter(String),
+}
+
+impl fmt::Display for FilenameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilenameError::PathTraversal(name) => {
+                write!(f, "File name {:?} contains a path traversal", name)
+            }
+            FilenameError::InvalidCharacter(name) => {
+                write!(f, "File name {:?} contains invalid characters", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FilenameError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assign_all(names: &[&str]) -> Vec<String> {
+        let mut assigned = BatchFileNames::new();
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, n)| assigned.assign(Some(n), i).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_plain_names_unchanged() {
+        assert_eq!(sanitize("report.pdf").unwrap(), "report.pdf");
+        assert_eq!(sanitize("Q3 results (final).xlsx").unwrap(), "Q3 results (final).xlsx");
+        assert_eq!(sanitize("données.csv").unwrap(), "données.csv");
+    }
+
+    #[test]
+    fn test_path_traversal_rejected() {
+        for name in ["../../etc/cron.d/x", "..", "a/../b", "..\\..\\windows\\win.ini", "ok/ .. /x"] {
+            assert!(
+                matches!(sanitize(name), Err(FilenameError::PathTraversal(_))),
+                "{} should be rejected",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_directories_are_stripped() {
+        assert_eq!(sanitize("/etc/passwd").unwrap(), "passwd");
+        assert_eq!(sanitize("C:\\fakepath\\invoice.pdf").unwrap(), "invoice.pdf");
+        assert_eq!(sanitize("nested/dir/file.txt").unwrap(), "file.txt");
+        assert_eq!(sanitize("trailing/").unwrap(), "");
+    }
+
+    #[test]
+    fn test_reserved_names_renamed() {
+        assert_eq!(sanitize("metadata.json").unwrap(), "_metadata.json");
+        assert_eq!(sanitize("METADATA.JSON").unwrap(), "_METADATA.JSON");
+        assert_eq!(sanitize(".next_batch_id").unwrap(), "_.next_batch_id");
+        assert_eq!(sanitize(".batch").unwrap(), "_.batch");
+        assert_eq!(sanitize("chunks").unwrap(), "_chunks");
+        assert_eq!(sanitize("42").unwrap(), "_42");
+        assert_eq!(sanitize("con.txt").unwrap(), "_con.txt");
+    }
+
+    #[test]
+    fn test_special_characters_replaced() {
+        assert_eq!(sanitize("a\u{7}b\r\n.txt").unwrap(), "ab.txt");
+        assert_eq!(sanitize("what?*<>|:\".txt").unwrap(), "what_______.txt");
+        assert_eq!(sanitize("name. . .").unwrap(), "name");
+        assert!(matches!(sanitize("a\0b"), Err(FilenameError::InvalidCharacter(_))));
+    }
+
+    #[test]
+    fn test_duplicates_renamed() {
+        assert_eq!(
+            assign_all(&["a.txt", "a.txt", "A.TXT", "dir/a.txt", "a-1.txt"]),
+            vec!["a.txt", "a-1.txt", "A-2.TXT", "a-3.txt", "a-1-1.txt"]
+        );
+    }
+
+    #[test]
+    fn test_missing_or_empty_names_get_generated_names() {
+        let mut assigned = BatchFileNames::new();
+        assert_eq!(assigned.assign(None, 0).unwrap(), "file_0");
+        assert_eq!(assigned.assign(Some(""), 1).unwrap(), "file_1");
+        assert_eq!(assigned.assign(Some("///"), 2).unwrap(), "file_2");
+        assert_eq!(assigned.assign(Some("file_0"), 3).unwrap(), "file_0-1");
+    }
+
+    #[test]
+    fn test_long_names_truncated_keeping_extension() {
+        let long = format!("{}.pdf", "é".repeat(300));
+        let name = sanitize(&long).unwrap();
+        assert!(name.len() <= MAX_NAME_BYTES);
+        assert!(name.ends_with(".pdf"));
+
+        let names = assign_all(&[&long, &long]);
+        assert_ne!(names[0], names[1]);
+        assert!(names[1].len() <= MAX_NAME_BYTES);
+    }
+}
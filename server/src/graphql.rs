@@ -0,0 +1,966 @@
+//! GraphQL API over on-chain registrations and local batches
+//!
+//! `Batch` and `BatchRegisteredEvent` follow the subgraph schema
+//! (`subgraph/schema.graphql`) and its query conventions (`first`, `skip`,
+//! `orderBy`, `orderDirection`, `where`), so clients written against the
+//! hosted subgraph can point at `POST /graphql` instead. They are served from
+//! the chain indexer and extended with the local batches sharing their root.
+//! `LocalBatch` exposes what only this server knows: files, leaf hashes,
+//! inclusion proofs and anchoring state.
+
+use crate::anchor::AnchorQueue;
+use crate::batches::load_metadata;
+use crate::indexer::{ChainBatch, ChainIndex, EventField, EventFilter, EventQuery};
+use crate::lookup::MatchKind;
+use crate::store::StoreError;
+use crate::tenants::{Tenant, Workspace};
+use crate::{AggregateInclusion, BatchMetadata};
+use actix_web::{get, post, web, HttpResponse};
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Enum, Error, InputObject, InputValueError, InputValueResult, Json,
+    Object, Result, Scalar, ScalarType, Schema, Value, ID,
+};
+use merkle_tree::{hex_to_hash, MerkleProof, MerkleTree};
+use rusqlite::types::Value as SqlValue;
+use std::sync::{Arc, OnceLock};
+
+/// Largest `first` accepted, as on the hosted subgraph
+const MAX_FIRST: i32 = 1000;
+/// Deepest query accepted
+const MAX_DEPTH: usize = 12;
+
+pub type MerkSealSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// What resolvers read from, attached to each request for the calling tenant
+struct Sources {
+    workspace: Arc<Workspace>,
+    chain: Option<Arc<ChainIndex>>,
+    queue: Option<Arc<AnchorQueue>>,
+}
+
+impl Sources {
+    fn chain(&self) -> Result<&ChainIndex> {
+        self.chain
+            .as_deref()
+            .ok_or_else(|| Error::new("Chain indexing is not enabled on this server (set MERKSEAL_INDEXER)"))
+    }
+
+    async fn local_batch(&self, batch_id: u64) -> Result<Option<LocalBatch>> {
+        match load_metadata(self.workspace.store.as_ref(), batch_id).await {
+            Ok(batch) => Ok(Some(LocalBatch::new(batch))),
+            Err(StoreError::NotFound(_)) => Ok(None),
+            Err(e) => Err(Error::new(e.to_string())),
+        }
+    }
+}
+
+/// Build the schema; stores and indexes are attached per request
+pub fn schema() -> MerkSealSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .finish()
+}
+
+/// Hex bytes with a `0x` prefix, as in the subgraph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bytes(String);
+
+impl Bytes {
+    fn from_hex(hex: &str) -> Self {
+        Bytes(format!("0x{}", hex.trim_start_matches("0x").to_ascii_lowercase()))
+    }
+
+    fn to_vec(&self) -> Vec<u8> {
+        // Validated when parsed
+        hex::decode(&self.0[2..]).unwrap_or_default()
+    }
+}
+
+#[Scalar]
+impl ScalarType for Bytes {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        let Value::String(s) = &value else {
+            return Err(InputValueError::expected_type(value));
+        };
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(InputValueError::custom("Expected hex bytes"));
+        }
+        Ok(Bytes::from_hex(hex))
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.0.clone())
+    }
+}
+
+/// Integer serialized as a decimal string, as in the subgraph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BigInt(u64);
+
+#[Scalar]
+impl ScalarType for BigInt {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        match &value {
+            Value::String(s) => s
+                .parse()
+                .map(BigInt)
+                .map_err(|_| InputValueError::custom("Expected an unsigned integer")),
+            Value::Number(n) => n
+                .as_u64()
+                .map(BigInt)
+                .ok_or_else(|| InputValueError::custom("Expected an unsigned integer")),
+            _ => Err(InputValueError::expected_type(value)),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.0.to_string())
+    }
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum OrderDirection {
+    #[graphql(name = "asc")]
+    Asc,
+    #[graphql(name = "desc")]
+    Desc,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(name = "Batch_orderBy")]
+pub enum BatchOrderBy {
+    #[graphql(name = "id")]
+    Id,
+    #[graphql(name = "root")]
+    Root,
+    #[graphql(name = "owner")]
+    Owner,
+    #[graphql(name = "metaURI")]
+    MetaUri,
+    #[graphql(name = "timestamp")]
+    Timestamp,
+    #[graphql(name = "blockNumber")]
+    BlockNumber,
+    #[graphql(name = "transactionHash")]
+    TransactionHash,
+}
+
+impl BatchOrderBy {
+    fn field(self) -> EventField {
+        match self {
+            BatchOrderBy::Id => EventField::MantleBatchId,
+            BatchOrderBy::Root => EventField::Root,
+            BatchOrderBy::Owner => EventField::Owner,
+            BatchOrderBy::MetaUri => EventField::MetaUri,
+            BatchOrderBy::Timestamp => EventField::Timestamp,
+            BatchOrderBy::BlockNumber => EventField::BlockNumber,
+            BatchOrderBy::TransactionHash => EventField::TxHash,
+        }
+    }
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(name = "BatchRegisteredEvent_orderBy")]
+pub enum EventOrderBy {
+    #[graphql(name = "id")]
+    Id,
+    #[graphql(name = "batchId")]
+    BatchId,
+    #[graphql(name = "root")]
+    Root,
+    #[graphql(name = "owner")]
+    Owner,
+    #[graphql(name = "metaURI")]
+    MetaUri,
+    #[graphql(name = "timestamp")]
+    Timestamp,
+    #[graphql(name = "blockNumber")]
+    BlockNumber,
+    #[graphql(name = "transactionHash")]
+    TransactionHash,
+}
+
+impl EventOrderBy {
+    fn field(self) -> EventField {
+        match self {
+            // Event IDs start with the transaction hash
+            EventOrderBy::Id | EventOrderBy::TransactionHash => EventField::TxHash,
+            EventOrderBy::BatchId => EventField::MantleBatchId,
+            EventOrderBy::Root => EventField::Root,
+            EventOrderBy::Owner => EventField::Owner,
+            EventOrderBy::MetaUri => EventField::MetaUri,
+            EventOrderBy::Timestamp => EventField::Timestamp,
+            EventOrderBy::BlockNumber => EventField::BlockNumber,
+        }
+    }
+}
+
+/// Conditions shared by both entity filters, flattened into each
+#[derive(InputObject, Default)]
+pub struct RegistrationFilter {
+    root: Option<Bytes>,
+    owner: Option<Bytes>,
+    #[graphql(name = "metaURI")]
+    meta_uri: Option<String>,
+    transaction_hash: Option<Bytes>,
+    #[graphql(name = "timestamp_gt")]
+    timestamp_gt: Option<BigInt>,
+    #[graphql(name = "timestamp_gte")]
+    timestamp_gte: Option<BigInt>,
+    #[graphql(name = "timestamp_lt")]
+    timestamp_lt: Option<BigInt>,
+    #[graphql(name = "timestamp_lte")]
+    timestamp_lte: Option<BigInt>,
+    #[graphql(name = "blockNumber_gt")]
+    block_number_gt: Option<BigInt>,
+    #[graphql(name = "blockNumber_gte")]
+    block_number_gte: Option<BigInt>,
+    #[graphql(name = "blockNumber_lt")]
+    block_number_lt: Option<BigInt>,
+    #[graphql(name = "blockNumber_lte")]
+    block_number_lte: Option<BigInt>,
+}
+
+impl RegistrationFilter {
+    fn push(&self, filters: &mut Vec<EventFilter>) {
+        if let Some(root) = &self.root {
+            filters.push(EventFilter::Eq(EventField::Root, SqlValue::Blob(root.to_vec())));
+        }
+        for (field, bytes) in [(EventField::Owner, &self.owner), (EventField::TxHash, &self.transaction_hash)] {
+            if let Some(bytes) = bytes {
+                filters.push(EventFilter::Eq(field, SqlValue::Text(bytes.0.clone())));
+            }
+        }
+        if let Some(uri) = &self.meta_uri {
+            filters.push(EventFilter::Eq(EventField::MetaUri, SqlValue::Text(uri.clone())));
+        }
+        let ranges = [
+            (EventField::Timestamp, [self.timestamp_gt, self.timestamp_gte, self.timestamp_lt, self.timestamp_lte]),
+            (
+                EventField::BlockNumber,
+                [self.block_number_gt, self.block_number_gte, self.block_number_lt, self.block_number_lte],
+            ),
+        ];
+        for (field, [gt, gte, lt, lte]) in ranges {
+            filters.extend(gt.map(|n| EventFilter::Gt(field, n.0)));
+            filters.extend(gte.map(|n| EventFilter::Gte(field, n.0)));
+            filters.extend(lt.map(|n| EventFilter::Lt(field, n.0)));
+            filters.extend(lte.map(|n| EventFilter::Lte(field, n.0)));
+        }
+    }
+}
+
+#[derive(InputObject, Default)]
+#[graphql(name = "Batch_filter")]
+pub struct BatchFilter {
+    id: Option<ID>,
+    #[graphql(name = "id_in")]
+    id_in: Option<Vec<ID>>,
+    #[graphql(flatten)]
+    registration: RegistrationFilter,
+}
+
+#[derive(InputObject, Default)]
+#[graphql(name = "BatchRegisteredEvent_filter")]
+pub struct EventFilterInput {
+    batch_id: Option<BigInt>,
+    #[graphql(name = "batchId_in")]
+    batch_id_in: Option<Vec<BigInt>>,
+    #[graphql(flatten)]
+    registration: RegistrationFilter,
+}
+
+fn parse_id(id: &ID) -> Result<u64> {
+    id.parse().map_err(|_| Error::new(format!("Invalid batch ID {:?}", id.as_str())))
+}
+
+fn integer(n: u64) -> SqlValue {
+    SqlValue::Integer(n as i64)
+}
+
+/// Check subgraph-style paging arguments
+fn page(first: i32, skip: i32) -> Result<(usize, usize)> {
+    if !(0..=MAX_FIRST).contains(&first) {
+        return Err(Error::new(format!("first must be between 0 and {}", MAX_FIRST)));
+    }
+    if skip < 0 {
+        return Err(Error::new("skip must not be negative"));
+    }
+    Ok((first as usize, skip as usize))
+}
+
+/// A batch registered on-chain (subgraph `Batch`)
+pub struct Batch(ChainBatch);
+
+#[Object]
+impl Batch {
+    /// Mantle batch ID
+    async fn id(&self) -> ID {
+        ID(self.0.mantle_batch_id.to_string())
+    }
+
+    /// Merkle root (32 bytes)
+    async fn root(&self) -> Bytes {
+        Bytes::from_hex(&self.0.root)
+    }
+
+    /// Address that registered the batch
+    async fn owner(&self) -> Bytes {
+        Bytes::from_hex(&self.0.owner)
+    }
+
+    #[graphql(name = "metaURI")]
+    async fn meta_uri(&self) -> &str {
+        &self.0.meta_uri
+    }
+
+    /// Block timestamp
+    async fn timestamp(&self) -> BigInt {
+        BigInt(self.0.timestamp)
+    }
+
+    async fn block_number(&self) -> BigInt {
+        BigInt(self.0.block_number)
+    }
+
+    async fn transaction_hash(&self) -> Bytes {
+        Bytes::from_hex(&self.0.tx_hash)
+    }
+
+    /// Sealed batches on this server with this root
+    async fn local_batches(&self, ctx: &Context<'_>) -> Result<Vec<LocalBatch>> {
+        let sources = ctx.data_unchecked::<Sources>();
+        let root = hex_to_hash(&self.0.root).map_err(|e| Error::new(format!("Invalid indexed root: {}", e)))?;
+        let hits = sources.workspace.lookup.lookup(&root).map_err(|e| Error::new(e.to_string()))?;
+        let mut batches = Vec::new();
+        for hit in hits.into_iter().filter(|hit| hit.kind == MatchKind::Root) {
+            batches.extend(sources.local_batch(hit.batch_id).await?);
+        }
+        Ok(batches)
+    }
+}
+
+/// A `BatchRegistered` log (subgraph `BatchRegisteredEvent`)
+pub struct BatchRegisteredEvent(ChainBatch);
+
+#[Object]
+impl BatchRegisteredEvent {
+    /// Transaction hash + log index
+    async fn id(&self) -> ID {
+        ID(format!("{}-{}", self.0.tx_hash, self.0.log_index))
+    }
+
+    async fn batch_id(&self) -> BigInt {
+        BigInt(self.0.mantle_batch_id)
+    }
+
+    async fn root(&self) -> Bytes {
+        Bytes::from_hex(&self.0.root)
+    }
+
+    async fn owner(&self) -> Bytes {
+        Bytes::from_hex(&self.0.owner)
+    }
+
+    #[graphql(name = "metaURI")]
+    async fn meta_uri(&self) -> &str {
+        &self.0.meta_uri
+    }
+
+    async fn timestamp(&self) -> BigInt {
+        BigInt(self.0.timestamp)
+    }
+
+    async fn block_number(&self) -> BigInt {
+        BigInt(self.0.block_number)
+    }
+
+    async fn transaction_hash(&self) -> Bytes {
+        Bytes::from_hex(&self.0.tx_hash)
+    }
+
+    /// The registered batch
+    async fn batch(&self) -> Batch {
+        Batch(self.0.clone())
+    }
+}
+
+/// A sealed batch on this server and its Merkle tree, built on first use
+struct Sealed {
+    metadata: BatchMetadata,
+    tree: OnceLock<Result<MerkleTree, String>>,
+}
+
+impl Sealed {
+    fn tree(&self) -> Result<&MerkleTree> {
+        let tree = self.tree.get_or_init(|| {
+            let leaves = self
+                .metadata
+                .files
+                .iter()
+                .map(|f| hex_to_hash(&f.leaf))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Invalid leaf hash: {}", e))?;
+            let tree = MerkleTree::new(leaves);
+            if tree.root_hex() != self.metadata.root.trim_start_matches("0x").to_lowercase() {
+                return Err(format!(
+                    "Manifest of batch {} does not match its root",
+                    self.metadata.local_batch_id
+                ));
+            }
+            Ok(tree)
+        });
+        tree.as_ref().map_err(|e| Error::new(e.clone()))
+    }
+}
+
+/// A sealed batch on this server
+pub struct LocalBatch(Arc<Sealed>);
+
+impl LocalBatch {
+    fn new(metadata: BatchMetadata) -> Self {
+        LocalBatch(Arc::new(Sealed {
+            metadata,
+            tree: OnceLock::new(),
+        }))
+    }
+
+    fn metadata(&self) -> &BatchMetadata {
+        &self.0.metadata
+    }
+}
+
+#[Object]
+impl LocalBatch {
+    /// Local batch ID
+    async fn id(&self) -> ID {
+        ID(self.metadata().local_batch_id.to_string())
+    }
+
+    async fn root(&self) -> Bytes {
+        Bytes::from_hex(&self.metadata().root)
+    }
+
+    async fn root_cid(&self) -> &str {
+        &self.metadata().root_cid
+    }
+
+    async fn file_count(&self) -> usize {
+        self.metadata().file_count
+    }
+
+    /// URI of the published canonical manifest
+    #[graphql(name = "metaURI")]
+    async fn meta_uri(&self) -> &str {
+        &self.metadata().suggested_meta_uri
+    }
+
+    async fn manifest_cid(&self) -> Option<&str> {
+        self.metadata().manifest_cid.as_deref()
+    }
+
+    /// Built from client-supplied leaf hashes, without file contents
+    async fn hash_only(&self) -> bool {
+        self.metadata().hash_only
+    }
+
+    async fn created_at(&self) -> Option<BigInt> {
+        self.metadata().created_at.map(BigInt)
+    }
+
+    /// The root (or an aggregate containing it) is recorded as anchored
+    async fn anchored(&self) -> bool {
+        self.metadata().mantle_batch_id.is_some()
+    }
+
+    async fn mantle_batch_id(&self) -> Option<BigInt> {
+        self.metadata().mantle_batch_id.map(BigInt)
+    }
+
+    async fn transaction_hash(&self) -> Option<Bytes> {
+        self.metadata().tx_hash.as_deref().map(Bytes::from_hex)
+    }
+
+    async fn block_number(&self) -> Option<BigInt> {
+        self.metadata().block_number.map(BigInt)
+    }
+
+    /// Anchoring job on the server's queue (as in `GET /batches/{id}`)
+    async fn anchor(&self, ctx: &Context<'_>) -> Option<Json<serde_json::Value>> {
+        let sources = ctx.data_unchecked::<Sources>();
+        let queue = sources.queue.as_ref()?;
+        let job = queue.status(&sources.workspace.tenant, self.metadata().local_batch_id)?;
+        Some(Json(serde_json::json!(job)))
+    }
+
+    /// Proof from this batch's root to the aggregate root that was anchored
+    async fn aggregate(&self) -> Option<Json<AggregateInclusion>> {
+        self.metadata().aggregate.clone().map(Json)
+    }
+
+    /// The on-chain registration of this batch (or of its aggregate), if indexed
+    async fn on_chain(&self, ctx: &Context<'_>) -> Result<Option<Batch>> {
+        let Some(chain) = ctx.data_unchecked::<Sources>().chain.as_deref() else {
+            return Ok(None);
+        };
+        let registration = match self.metadata().mantle_batch_id {
+            Some(id) => chain.get(id),
+            None => {
+                let root = hex_to_hash(&self.metadata().root).map_err(|e| Error::new(format!("Invalid root: {}", e)))?;
+                chain.by_root(&root).map(|batches| batches.into_iter().next())
+            }
+        };
+        Ok(registration.map_err(|e| Error::new(e.to_string()))?.map(Batch))
+    }
+
+    /// Files in leaf order
+    async fn files(
+        &self,
+        #[graphql(default = 100)] first: i32,
+        #[graphql(default = 0)] skip: i32,
+        name: Option<String>,
+    ) -> Result<Vec<File>> {
+        let (first, skip) = page(first, skip)?;
+        Ok((0..self.metadata().files.len())
+            .filter(|&index| name.as_ref().is_none_or(|name| self.metadata().files[index].name == *name))
+            .skip(skip)
+            .take(first)
+            .map(|index| File {
+                batch: self.0.clone(),
+                index,
+            })
+            .collect())
+    }
+}
+
+/// A file of a local batch
+pub struct File {
+    batch: Arc<Sealed>,
+    index: usize,
+}
+
+#[Object]
+impl File {
+    async fn leaf_index(&self) -> usize {
+        self.index
+    }
+
+    async fn name(&self) -> &str {
+        &self.batch.metadata.files[self.index].name
+    }
+
+    async fn original_name(&self) -> &str {
+        &self.batch.metadata.files[self.index].original_name
+    }
+
+    async fn size(&self) -> BigInt {
+        BigInt(self.batch.metadata.files[self.index].size)
+    }
+
+    /// Leaf hash
+    async fn leaf(&self) -> Bytes {
+        Bytes::from_hex(&self.batch.metadata.files[self.index].leaf)
+    }
+
+    async fn cid(&self) -> &str {
+        &self.batch.metadata.files[self.index].cid
+    }
+
+    /// Whole-file SHA-256 when it differs from the leaf (multi-chunk files)
+    async fn sha256(&self) -> Option<Bytes> {
+        self.batch.metadata.files[self.index].sha256.as_deref().map(Bytes::from_hex)
+    }
+
+    /// Inclusion proof of the leaf in the batch root
+    async fn proof(&self) -> Result<Proof> {
+        let tree = self.batch.tree()?;
+        Ok(Proof {
+            proof: tree.proof(self.index).expect("index is within the manifest"),
+            root: tree.root_hex(),
+        })
+    }
+}
+
+/// Inclusion proof of one leaf
+pub struct Proof {
+    proof: MerkleProof,
+    root: String,
+}
+
+#[Object]
+impl Proof {
+    async fn leaf_index(&self) -> usize {
+        self.proof.leaf_index
+    }
+
+    async fn leaf(&self) -> Bytes {
+        Bytes::from_hex(&hex::encode(self.proof.leaf))
+    }
+
+    /// Sibling hashes from the leaf level upwards
+    async fn siblings(&self) -> Vec<Bytes> {
+        self.proof.siblings.iter().map(|s| Bytes::from_hex(&hex::encode(s))).collect()
+    }
+
+    async fn root(&self) -> Bytes {
+        Bytes::from_hex(&self.root)
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object(name = "Query")]
+impl QueryRoot {
+    /// An on-chain batch by Mantle batch ID
+    async fn batch(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Batch>> {
+        let chain = ctx.data_unchecked::<Sources>().chain()?;
+        let batch = chain.get(parse_id(&id)?).map_err(|e| Error::new(e.to_string()))?;
+        Ok(batch.map(Batch))
+    }
+
+    /// On-chain batches
+    async fn batches(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 100)] first: i32,
+        #[graphql(default = 0)] skip: i32,
+        order_by: Option<BatchOrderBy>,
+        order_direction: Option<OrderDirection>,
+        #[graphql(name = "where")] filter: Option<BatchFilter>,
+    ) -> Result<Vec<Batch>> {
+        let chain = ctx.data_unchecked::<Sources>().chain()?;
+        let (first, skip) = page(first, skip)?;
+        let filter = filter.unwrap_or_default();
+        let mut filters = Vec::new();
+        if let Some(id) = &filter.id {
+            filters.push(EventFilter::Eq(EventField::MantleBatchId, integer(parse_id(id)?)));
+        }
+        if let Some(ids) = &filter.id_in {
+            let ids = ids.iter().map(|id| parse_id(id).map(integer)).collect::<Result<_>>()?;
+            filters.push(EventFilter::In(EventField::MantleBatchId, ids));
+        }
+        filter.registration.push(&mut filters);
+
+        let batches = chain
+            .query(&EventQuery {
+                filters,
+                order_by: order_by.unwrap_or(BatchOrderBy::Id).field(),
+                descending: order_direction == Some(OrderDirection::Desc),
+                first,
+                skip,
+            })
+            .map_err(|e| Error::new(e.to_string()))?;
+        Ok(batches.into_iter().map(Batch).collect())
+    }
+
+    /// A `BatchRegistered` event by `{transaction hash}-{log index}`
+    async fn batch_registered_event(&self, ctx: &Context<'_>, id: ID) -> Result<Option<BatchRegisteredEvent>> {
+        let chain = ctx.data_unchecked::<Sources>().chain()?;
+        let Some((tx_hash, log_index)) = id.rsplit_once('-') else {
+            return Ok(None);
+        };
+        let Ok(log_index) = log_index.parse() else {
+            return Ok(None);
+        };
+        let events = chain
+            .query(&EventQuery {
+                filters: vec![
+                    EventFilter::Eq(EventField::TxHash, SqlValue::Text(tx_hash.to_ascii_lowercase())),
+                    EventFilter::Eq(EventField::LogIndex, integer(log_index)),
+                ],
+                order_by: EventField::MantleBatchId,
+                descending: false,
+                first: 1,
+                skip: 0,
+            })
+            .map_err(|e| Error::new(e.to_string()))?;
+        Ok(events.into_iter().next().map(BatchRegisteredEvent))
+    }
+
+    /// `BatchRegistered` events
+    async fn batch_registered_events(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 100)] first: i32,
+        #[graphql(default = 0)] skip: i32,
+        order_by: Option<EventOrderBy>,
+        order_direction: Option<OrderDirection>,
+        #[graphql(name = "where")] filter: Option<EventFilterInput>,
+    ) -> Result<Vec<BatchRegisteredEvent>> {
+        let chain = ctx.data_unchecked::<Sources>().chain()?;
+        let (first, skip) = page(first, skip)?;
+        let filter = filter.unwrap_or_default();
+        let mut filters = Vec::new();
+        if let Some(id) = filter.batch_id {
+            filters.push(EventFilter::Eq(EventField::MantleBatchId, integer(id.0)));
+        }
+        if let Some(ids) = &filter.batch_id_in {
+            filters.push(EventFilter::In(
+                EventField::MantleBatchId,
+                ids.iter().map(|id| integer(id.0)).collect(),
+            ));
+        }
+        filter.registration.push(&mut filters);
+
+        let events = chain
+            .query(&EventQuery {
+                filters,
+                order_by: order_by.unwrap_or(EventOrderBy::Id).field(),
+                descending: order_direction == Some(OrderDirection::Desc),
+                first,
+                skip,
+            })
+            .map_err(|e| Error::new(e.to_string()))?;
+        Ok(events.into_iter().map(BatchRegisteredEvent).collect())
+    }
+
+    /// A sealed batch on this server by local batch ID
+    async fn local_batch(&self, ctx: &Context<'_>, id: ID) -> Result<Option<LocalBatch>> {
+        ctx.data_unchecked::<Sources>().local_batch(parse_id(&id)?).await
+    }
+
+    /// Sealed batches on this server, by local batch ID
+    async fn local_batches(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 100)] first: i32,
+        #[graphql(default = 0)] skip: i32,
+        order_direction: Option<OrderDirection>,
+        anchored: Option<bool>,
+    ) -> Result<Vec<LocalBatch>> {
+        let sources = ctx.data_unchecked::<Sources>();
+        let (first, skip) = page(first, skip)?;
+        let mut ids = sources.workspace.store.list_batches().await.map_err(|e| Error::new(e.to_string()))?;
+        ids.sort_unstable();
+        if order_direction == Some(OrderDirection::Desc) {
+            ids.reverse();
+        }
+
+        let mut batches = Vec::new();
+        let mut skipped = 0;
+        for id in ids {
+            if batches.len() == first {
+                break;
+            }
+            let Some(batch) = sources.local_batch(id).await? else {
+                continue;
+            };
+            if anchored.is_some_and(|anchored| anchored != batch.metadata().mantle_batch_id.is_some()) {
+                continue;
+            }
+            if skipped < skip {
+                skipped += 1;
+                continue;
+            }
+            batches.push(batch);
+        }
+        Ok(batches)
+    }
+}
+
+/// POST /graphql - Run a GraphQL query
+#[post("/graphql")]
+async fn graphql(
+    schema: web::Data<MerkSealSchema>,
+    request: web::Json<async_graphql::Request>,
+    tenant: Tenant,
+    chain_index: Option<web::Data<Arc<ChainIndex>>>,
+    anchor_queue: Option<web::Data<Arc<AnchorQueue>>>,
+) -> HttpResponse {
+    let sources = Sources {
+        workspace: tenant.0,
+        chain: chain_index.map(|index| index.get_ref().clone()),
+        queue: anchor_queue.map(|queue| queue.get_ref().clone()),
+    };
+    HttpResponse::Ok().json(schema.execute(request.into_inner().data(sources)).await)
+}
+
+/// GET /graphql - GraphiQL explorer for the schema
+#[get("/graphql")]
+async fn graphiql() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::{FakeSource, IndexerSettings};
+    use crate::auth::tests::server_config;
+    use crate::lookup::LookupIndex;
+    use crate::sessions::SessionRegistry;
+    use crate::store::{BatchStore, FsStore, StorageConfig};
+    use crate::tenants::{Tenants, DEFAULT_TENANT};
+    use crate::FileEntry;
+    use actix_web::{test as actix_test, App};
+    use async_graphql::Request;
+    use ethers::types::Address;
+    use merkle_tree::{hash_data, leaf_cid, Hash};
+
+    async fn seal(store: &FsStore, lookup: &LookupIndex, leaves: &[Hash]) -> u64 {
+        let id = store.create_batch().await.unwrap();
+        let tree = MerkleTree::new(leaves.to_vec());
+        let batch = BatchMetadata {
+            local_batch_id: id,
+            root: tree.root_hex(),
+            root_cid: tree.root_cid(),
+            file_count: leaves.len(),
+            suggested_meta_uri: format!("ipfs://local-{}", id),
+            registry_address: "0x0".into(),
+            hash_only: false,
+            manifest_cid: None,
+            created_at: Some(1_700_000_000),
+            mantle_batch_id: None,
+            tx_hash: None,
+            block_number: None,
+            aggregate: None,
+            files: leaves
+                .iter()
+                .enumerate()
+                .map(|(index, leaf)| FileEntry {
+                    name: format!("{}.txt", index),
+                    original_name: format!("{}.txt", index),
+                    size: 1,
+                    leaf: hex::encode(leaf),
+                    cid: leaf_cid(leaf),
+                    chunks: None,
+                    sha256: None,
+                })
+                .collect(),
+        };
+        store.seal(id, &serde_json::to_vec(&batch).unwrap()).await.unwrap();
+        lookup.index_batch(&batch).unwrap();
+        id
+    }
+
+    #[actix_web::test]
+    async fn test_subgraph_queries_with_local_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FsStore::open(dir.path().join("batches")).unwrap();
+        let lookup = Arc::new(LookupIndex::open(dir.path().join("lookup.db")).unwrap());
+        let settings = IndexerSettings {
+            start_block: 0,
+            block_range: 100,
+            reorg_depth: 8,
+        };
+        let chain = Arc::new(ChainIndex::open(dir.path().join("chain.db"), Address::zero(), settings).unwrap());
+
+        // A one-file batch, registered on-chain by someone else, and an unanchored pair
+        let single = seal(&store, &lookup, &[hash_data(b"local")]).await;
+        let pair = seal(&store, &lookup, &[hash_data(b"x"), hash_data(b"y")]).await;
+        let source = FakeSource::default();
+        source.mine(&[]);
+        source.mine(&[(1, b"local")]);
+        source.mine(&[(2, b"remote")]);
+        chain.tick(&source).await.unwrap();
+
+        let store: Arc<dyn BatchStore> = Arc::new(store);
+        let config = server_config(
+            dir.path(),
+            StorageConfig::Filesystem {
+                root: dir.path().join("batches").display().to_string(),
+            },
+        );
+        let sessions = Arc::new(SessionRegistry::open(&config.session_file, 3600).unwrap());
+        let tenants = Arc::new(Tenants::single(Arc::new(config), store, lookup, sessions));
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(schema()))
+                .app_data(web::Data::new(tenants.clone()))
+                .app_data(web::Data::new(chain))
+                .service(graphql)
+                .service(graphiql),
+        )
+        .await;
+        let query = |query: &str| {
+            actix_test::TestRequest::post()
+                .uri("/graphql")
+                .set_json(serde_json::json!({ "query": query }))
+                .to_request()
+        };
+
+        let body: serde_json::Value = actix_test::call_and_read_body_json(
+            &app,
+            query(
+                r#"{
+                    batches(first: 10, orderBy: timestamp, orderDirection: desc, where: { owner: "0x0101010101010101010101010101010101010101" }) {
+                        id root metaURI timestamp blockNumber transactionHash
+                        localBatches { id files { name leaf } }
+                    }
+                    batchRegisteredEvents(where: { batchId_in: ["2"], blockNumber_gte: 2 }) { id batchId batch { id } }
+                }"#,
+            ),
+        )
+        .await;
+        assert!(body.get("errors").is_none(), "{}", body);
+        let batches = body["data"]["batches"].as_array().unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0]["id"], "2");
+        assert_eq!(batches[0]["localBatches"], serde_json::json!([]));
+        assert_eq!(batches[1]["root"], format!("0x{}", hex::encode(hash_data(b"local"))));
+        assert_eq!(batches[1]["timestamp"], "1700000001");
+        assert_eq!(batches[1]["localBatches"][0]["id"], single.to_string());
+        assert_eq!(batches[1]["localBatches"][0]["files"][0]["name"], "0.txt");
+        let event = &body["data"]["batchRegisteredEvents"][0];
+        assert_eq!(event["batchId"], "2");
+        assert!(event["id"].as_str().unwrap().ends_with("-0"));
+        assert_eq!(event["batch"]["id"], "2");
+
+        let body: serde_json::Value = actix_test::call_and_read_body_json(
+            &app,
+            query(&format!(
+                r#"{{
+                    pair: localBatch(id: "{}") {{ anchored onChain {{ id }} files(skip: 1) {{ leafIndex proof {{ leaf siblings root }} }} }}
+                    single: localBatch(id: "{}") {{ onChain {{ id owner }} }}
+                    unanchored: localBatches(anchored: false, orderDirection: desc) {{ id }}
+                }}"#,
+                pair, single
+            )),
+        )
+        .await;
+        assert!(body.get("errors").is_none(), "{}", body);
+        let pair_batch = &body["data"]["pair"];
+        assert_eq!(pair_batch["anchored"], false);
+        assert!(pair_batch["onChain"].is_null());
+        let proof = &pair_batch["files"][0]["proof"];
+        assert_eq!(pair_batch["files"][0]["leafIndex"], 1);
+        assert_eq!(proof["leaf"], format!("0x{}", hex::encode(hash_data(b"y"))));
+        assert_eq!(proof["siblings"], serde_json::json!([format!("0x{}", hex::encode(hash_data(b"x")))]));
+        assert_eq!(
+            proof["root"],
+            format!("0x{}", MerkleTree::new(vec![hash_data(b"x"), hash_data(b"y")]).root_hex())
+        );
+        assert_eq!(body["data"]["single"]["onChain"]["id"], "1");
+        assert_eq!(
+            body["data"]["unanchored"],
+            serde_json::json!([{ "id": pair.to_string() }, { "id": single.to_string() }])
+        );
+
+        let body: serde_json::Value =
+            actix_test::call_and_read_body_json(&app, query("{ batches(first: 5000) { id } }")).await;
+        assert!(body["errors"][0]["message"].as_str().unwrap().contains("first"));
+
+        let page = actix_test::call_service(&app, actix_test::TestRequest::get().uri("/graphql").to_request()).await;
+        assert_eq!(page.status(), 200);
+
+        // Without the indexer, local data still resolves and chain queries say why they fail
+        let workspace = tenants.get(DEFAULT_TENANT).await.unwrap();
+        let local_only = || Sources {
+            workspace: workspace.clone(),
+            chain: None,
+            queue: None,
+        };
+        let response = schema()
+            .execute(
+                Request::new(format!(r#"{{ localBatch(id: "{}") {{ fileCount onChain {{ id }} }} }}"#, pair))
+                    .data(local_only()),
+            )
+            .await;
+        assert!(response.errors.is_empty());
+        let response = schema().execute(Request::new("{ batches { id } }").data(local_only())).await;
+        assert!(response.errors[0].message.contains("MERKSEAL_INDEXER"));
+    }
+}
@@ -0,0 +1,596 @@
+use super::source::{BlockRef, EventSource, RegisteredLog};
+use super::IndexerError;
+use ethers::types::{Address, H256};
+use merkle_tree::Hash;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS events (
+        mantle_batch_id INTEGER PRIMARY KEY,
+        root BLOB NOT NULL,
+        owner TEXT NOT NULL,
+        meta_uri TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        block_number INTEGER NOT NULL,
+        block_hash TEXT NOT NULL,
+        tx_hash TEXT NOT NULL,
+        log_index INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS events_by_root ON events (root);
+    CREATE INDEX IF NOT EXISTS events_by_block ON events (block_number);
+    CREATE TABLE IF NOT EXISTS checkpoints (
+        number INTEGER PRIMARY KEY,
+        hash TEXT NOT NULL
+    );
+";
+
+const EVENT_COLUMNS: &str =
+    "mantle_batch_id, root, owner, meta_uri, timestamp, block_number, block_hash, tx_hash, log_index";
+
+/// Tuning knobs for the chain indexer
+#[derive(Debug, Clone, Copy)]
+pub struct IndexerSettings {
+    /// First block to scan (the registry's deployment block)
+    pub start_block: u64,
+    /// Blocks per `eth_getLogs` request
+    pub block_range: u64,
+    /// Checkpoints kept below the indexed head; deeper reorgs reindex from `start_block`
+    pub reorg_depth: u64,
+}
+
+/// A batch registered on-chain, as mirrored from its `BatchRegistered` event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChainBatch {
+    pub mantle_batch_id: u64,
+    /// Registered Merkle root (hex)
+    pub root: String,
+    pub owner: String,
+    pub meta_uri: String,
+    /// Timestamp of the block that included the registration
+    pub timestamp: u64,
+    pub block_number: u64,
+    pub block_hash: String,
+    pub tx_hash: String,
+    pub log_index: u64,
+}
+
+/// A column of indexed events, for filtering and ordering
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventField {
+    MantleBatchId,
+    Root,
+    Owner,
+    MetaUri,
+    Timestamp,
+    BlockNumber,
+    TxHash,
+    LogIndex,
+}
+
+impl EventField {
+    fn column(self) -> &'static str {
+        match self {
+            EventField::MantleBatchId => "mantle_batch_id",
+            EventField::Root => "root",
+            EventField::Owner => "owner",
+            EventField::MetaUri => "meta_uri",
+            EventField::Timestamp => "timestamp",
+            EventField::BlockNumber => "block_number",
+            EventField::TxHash => "tx_hash",
+            EventField::LogIndex => "log_index",
+        }
+    }
+}
+
+/// One condition on indexed events
+///
+/// Values are stored as written: roots as raw bytes, addresses and hashes
+/// as lowercase `0x` hex, numbers as integers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventFilter {
+    Eq(EventField, Value),
+    In(EventField, Vec<Value>),
+    Gt(EventField, u64),
+    Gte(EventField, u64),
+    Lt(EventField, u64),
+    Lte(EventField, u64),
+}
+
+/// A filtered, ordered page of indexed events
+#[derive(Debug, Clone)]
+pub struct EventQuery {
+    pub filters: Vec<EventFilter>,
+    pub order_by: EventField,
+    pub descending: bool,
+    pub first: usize,
+    pub skip: usize,
+}
+
+/// What one `tick` did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Events dropped because their blocks left the canonical chain
+    pub rolled_back: usize,
+    /// Events added
+    pub indexed: usize,
+    /// Last block now covered by the index
+    pub indexed_through: Option<u64>,
+    /// `false` while there are more blocks to scan up to the head
+    pub caught_up: bool,
+}
+
+/// SQLite mirror of the registry's `BatchRegistered` events
+///
+/// Every scanned range ends in a checkpoint holding the hash of its last
+/// block (and of every block with an event). Before scanning further, the
+/// newest checkpoint is compared with the node; if it was reorged away, the
+/// index rolls back to the newest checkpoint still canonical and rescans.
+pub struct ChainIndex {
+    conn: Mutex<Connection>,
+    settings: IndexerSettings,
+    tick_lock: tokio::sync::Mutex<()>,
+}
+
+impl ChainIndex {
+    /// Open (and create if needed) the index for `registry`
+    ///
+    /// An index built for a different registry address is cleared.
+    pub fn open(path: impl AsRef<Path>, registry: Address, settings: IndexerSettings) -> Result<Self, IndexerError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+
+        let registry = format!("{:?}", registry);
+        let indexed: Option<String> = conn
+            .query_row("SELECT value FROM meta WHERE key = 'registry'", [], |row| row.get(0))
+            .optional()?;
+        if indexed.as_deref() != Some(registry.as_str()) {
+            conn.execute_batch("DELETE FROM events; DELETE FROM checkpoints;")?;
+            conn.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('registry', ?1)",
+                params![registry],
+            )?;
+        }
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            settings,
+            tick_lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Last block covered by the index
+    pub fn indexed_through(&self) -> Result<Option<u64>, IndexerError> {
+        let number: Option<i64> = self
+            .conn()
+            .query_row("SELECT MAX(number) FROM checkpoints", [], |row| row.get(0))?;
+        Ok(number.map(|n| n as u64))
+    }
+
+    /// One on-chain batch by its registry ID
+    pub fn get(&self, mantle_batch_id: u64) -> Result<Option<ChainBatch>, IndexerError> {
+        let conn = self.conn();
+        let batch = conn
+            .query_row(
+                &format!("SELECT {} FROM events WHERE mantle_batch_id = ?1", EVENT_COLUMNS),
+                params![mantle_batch_id as i64],
+                chain_batch,
+            )
+            .optional()?;
+        Ok(batch)
+    }
+
+    /// On-chain batches with IDs above `after`, in ID order
+    pub fn list(&self, after: u64, limit: usize) -> Result<Vec<ChainBatch>, IndexerError> {
+        let conn = self.conn();
+        let mut query = conn.prepare(&format!(
+            "SELECT {} FROM events WHERE mantle_batch_id > ?1 ORDER BY mantle_batch_id LIMIT ?2",
+            EVENT_COLUMNS
+        ))?;
+        let batches = query
+            .query_map(params![after as i64, limit as i64], chain_batch)?
+            .collect::<Result<_, _>>()?;
+        Ok(batches)
+    }
+
+    /// Every on-chain registration of `root`, in ID order
+    pub fn by_root(&self, root: &Hash) -> Result<Vec<ChainBatch>, IndexerError> {
+        let conn = self.conn();
+        let mut query = conn.prepare(&format!(
+            "SELECT {} FROM events WHERE root = ?1 ORDER BY mantle_batch_id",
+            EVENT_COLUMNS
+        ))?;
+        let batches = query
+            .query_map(params![&root[..]], chain_batch)?
+            .collect::<Result<_, _>>()?;
+        Ok(batches)
+    }
+
+    /// Events matching every filter, ordered by one column (then by ID)
+    pub fn query(&self, query: &EventQuery) -> Result<Vec<ChainBatch>, IndexerError> {
+        let mut sql = format!("SELECT {} FROM events WHERE 1 = 1", EVENT_COLUMNS);
+        let mut values = Vec::new();
+        for filter in &query.filters {
+            let (field, op, value) = match filter {
+                EventFilter::In(field, options) => {
+                    let placeholders = vec!["?"; options.len()].join(", ");
+                    sql.push_str(&format!(" AND {} IN ({})", field.column(), placeholders));
+                    values.extend(options.iter().cloned());
+                    continue;
+                }
+                EventFilter::Eq(field, value) => (field, "=", value.clone()),
+                EventFilter::Gt(field, n) => (field, ">", Value::Integer(*n as i64)),
+                EventFilter::Gte(field, n) => (field, ">=", Value::Integer(*n as i64)),
+                EventFilter::Lt(field, n) => (field, "<", Value::Integer(*n as i64)),
+                EventFilter::Lte(field, n) => (field, "<=", Value::Integer(*n as i64)),
+            };
+            sql.push_str(&format!(" AND {} {} ?", field.column(), op));
+            values.push(value);
+        }
+        let direction = if query.descending { "DESC" } else { "ASC" };
+        sql.push_str(&format!(
+            " ORDER BY {} {}, mantle_batch_id {} LIMIT ? OFFSET ?",
+            query.order_by.column(),
+            direction,
+            direction
+        ));
+        values.push(Value::Integer(query.first as i64));
+        values.push(Value::Integer(query.skip as i64));
+
+        let conn = self.conn();
+        let mut statement = conn.prepare(&sql)?;
+        let batches = statement
+            .query_map(params_from_iter(values), chain_batch)?
+            .collect::<Result<_, _>>()?;
+        Ok(batches)
+    }
+
+    /// Checkpoints, newest first
+    fn checkpoints(&self) -> Result<Vec<(u64, H256)>, IndexerError> {
+        let conn = self.conn();
+        let mut query = conn.prepare("SELECT number, hash FROM checkpoints ORDER BY number DESC")?;
+        let rows = query
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter()
+            .map(|(number, hash)| {
+                let hash = hash
+                    .parse()
+                    .map_err(|_| IndexerError::Corrupt(format!("Invalid block hash {:?} in checkpoint", hash)))?;
+                Ok((number as u64, hash))
+            })
+            .collect()
+    }
+
+    /// Drop everything above block `keep` (`None` drops everything)
+    fn rollback(&self, keep: Option<u64>) -> Result<usize, IndexerError> {
+        // Block numbers fit in i64; -1 is below every block
+        let keep = keep.map_or(-1, |n| n as i64);
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let removed = tx.execute("DELETE FROM events WHERE block_number > ?1", params![keep])?;
+        tx.execute("DELETE FROM checkpoints WHERE number > ?1", params![keep])?;
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    /// Store the events and checkpoints of one scanned range
+    fn record(&self, events: &[(RegisteredLog, u64)], checkpoints: &[BlockRef]) -> Result<(), IndexerError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare(&format!(
+                "INSERT OR REPLACE INTO events ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                EVENT_COLUMNS
+            ))?;
+            for (log, timestamp) in events {
+                insert.execute(params![
+                    log.mantle_batch_id as i64,
+                    &log.root[..],
+                    format!("{:?}", log.owner),
+                    log.meta_uri,
+                    *timestamp as i64,
+                    log.block_number as i64,
+                    format!("{:?}", log.block_hash),
+                    format!("{:?}", log.tx_hash),
+                    log.log_index as i64,
+                ])?;
+            }
+            let mut checkpoint = tx.prepare("INSERT OR REPLACE INTO checkpoints (number, hash) VALUES (?1, ?2)")?;
+            for block in checkpoints {
+                checkpoint.execute(params![block.number as i64, format!("{:?}", block.hash)])?;
+            }
+        }
+        if let Some(newest) = checkpoints.iter().map(|b| b.number).max() {
+            let oldest = newest.saturating_sub(self.settings.reorg_depth);
+            tx.execute("DELETE FROM checkpoints WHERE number < ?1", params![oldest as i64])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Undo indexed blocks that are no longer canonical, returning dropped events
+    async fn unwind(&self, source: &dyn EventSource, head: u64) -> Result<usize, IndexerError> {
+        let checkpoints = self.checkpoints()?;
+        let Some(&(newest, _)) = checkpoints.first() else {
+            return Ok(0);
+        };
+        for (number, hash) in checkpoints {
+            if number > head {
+                continue;
+            }
+            if source.block(number).await?.is_some_and(|block| block.hash == hash) {
+                return if number == newest { Ok(0) } else { self.rollback(Some(number)) };
+            }
+        }
+        // Deeper than any checkpoint: start over
+        self.rollback(None)
+    }
+
+    /// Follow the chain by one step: undo reorged blocks, then scan the next range
+    pub async fn tick(&self, source: &dyn EventSource) -> Result<Progress, IndexerError> {
+        let _tick = self.tick_lock.lock().await;
+        let head = source.head().await?;
+        let rolled_back = self.unwind(source, head).await?;
+        if rolled_back > 0 {
+            println!("↩ Chain reorg: dropped {} indexed batch registration(s)", rolled_back);
+        }
+
+        let from = match self.indexed_through()? {
+            Some(number) => number + 1,
+            None => self.settings.start_block,
+        };
+        if from > head {
+            return Ok(Progress {
+                rolled_back,
+                indexed_through: from.checked_sub(1),
+                caught_up: true,
+                ..Progress::default()
+            });
+        }
+        let to = head.min(from + self.settings.block_range.max(1) - 1);
+
+        let Some(end) = source.block(to).await? else {
+            // The node has not caught up with its own head yet
+            return Ok(Progress {
+                rolled_back,
+                indexed_through: from.checked_sub(1),
+                caught_up: true,
+                ..Progress::default()
+            });
+        };
+        let logs = source.logs(from, to).await?;
+
+        let mut blocks = BTreeMap::new();
+        for log in &logs {
+            if blocks.contains_key(&log.block_number) {
+                continue;
+            }
+            match source.block(log.block_number).await? {
+                Some(block) if block.hash == log.block_hash => {
+                    blocks.insert(block.number, block);
+                }
+                _ => return Err(IndexerError::Reorged(log.block_number)),
+            }
+        }
+        // Logs and headers must all come from the chain that ends in `end`
+        if source.block(to).await?.map(|block| block.hash) != Some(end.hash) {
+            return Err(IndexerError::Reorged(to));
+        }
+
+        let events: Vec<(RegisteredLog, u64)> = logs
+            .into_iter()
+            .map(|log| {
+                let timestamp = blocks[&log.block_number].timestamp;
+                (log, timestamp)
+            })
+            .collect();
+        blocks.insert(end.number, end);
+        let checkpoints: Vec<BlockRef> = blocks.into_values().collect();
+        self.record(&events, &checkpoints)?;
+
+        if !events.is_empty() {
+            println!(
+                "⛓ Indexed {} batch registration(s) up to block {}",
+                events.len(),
+                to
+            );
+        }
+        Ok(Progress {
+            rolled_back,
+            indexed: events.len(),
+            indexed_through: Some(to),
+            caught_up: to == head,
+        })
+    }
+}
+
+fn chain_batch(row: &Row<'_>) -> rusqlite::Result<ChainBatch> {
+    Ok(ChainBatch {
+        mantle_batch_id: row.get::<_, i64>(0)? as u64,
+        root: hex::encode(row.get::<_, Vec<u8>>(1)?),
+        owner: row.get(2)?,
+        meta_uri: row.get(3)?,
+        timestamp: row.get::<_, i64>(4)? as u64,
+        block_number: row.get::<_, i64>(5)? as u64,
+        block_hash: row.get(6)?,
+        tx_hash: row.get(7)?,
+        log_index: row.get::<_, i64>(8)? as u64,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use merkle_tree::hash_data;
+
+    /// In-memory chain whose blocks can be replaced to simulate reorgs
+    #[derive(Default)]
+    pub(crate) struct FakeSource {
+        /// Block hash and logs per block number, index = number
+        blocks: Mutex<Vec<(H256, Vec<RegisteredLog>)>>,
+    }
+
+    impl FakeSource {
+        /// Mine a block with one registration per root
+        pub(crate) fn mine(&self, registrations: &[(u64, &[u8])]) {
+            let mut blocks = self.blocks.lock().unwrap();
+            let number = blocks.len() as u64;
+            let hash = H256::random();
+            let logs = registrations
+                .iter()
+                .enumerate()
+                .map(|(index, (id, root))| RegisteredLog {
+                    mantle_batch_id: *id,
+                    root: hash_data(root),
+                    owner: Address::repeat_byte(1),
+                    meta_uri: format!("ipfs://{}", id),
+                    block_number: number,
+                    block_hash: hash,
+                    tx_hash: H256::random(),
+                    log_index: index as u64,
+                })
+                .collect();
+            blocks.push((hash, logs));
+        }
+
+        /// Drop every block from `number` on
+        fn reorg(&self, number: u64) {
+            self.blocks.lock().unwrap().truncate(number as usize);
+        }
+    }
+
+    #[async_trait]
+    impl EventSource for FakeSource {
+        async fn head(&self) -> Result<u64, IndexerError> {
+            Ok(self.blocks.lock().unwrap().len() as u64 - 1)
+        }
+
+        async fn block(&self, number: u64) -> Result<Option<BlockRef>, IndexerError> {
+            Ok(self.blocks.lock().unwrap().get(number as usize).map(|(hash, _)| BlockRef {
+                number,
+                hash: *hash,
+                timestamp: 1_700_000_000 + number,
+            }))
+        }
+
+        async fn logs(&self, from: u64, to: u64) -> Result<Vec<RegisteredLog>, IndexerError> {
+            let blocks = self.blocks.lock().unwrap();
+            Ok(blocks[from as usize..=to as usize]
+                .iter()
+                .flat_map(|(_, logs)| logs.clone())
+                .collect())
+        }
+    }
+
+    fn roots(index: &ChainIndex) -> Vec<(u64, String)> {
+        index
+            .list(0, 100)
+            .unwrap()
+            .into_iter()
+            .map(|b| (b.mantle_batch_id, b.root))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_follows_registrations_across_reorgs() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = IndexerSettings {
+            start_block: 1,
+            block_range: 2,
+            reorg_depth: 10,
+        };
+        let registry = Address::repeat_byte(0xaa);
+        let index = ChainIndex::open(dir.path().join("chain.db"), registry, settings).unwrap();
+
+        let chain = FakeSource::default();
+        chain.mine(&[]); // genesis, before the registry existed
+        chain.mine(&[(1, b"a")]);
+        chain.mine(&[]);
+        chain.mine(&[(2, b"b"), (3, b"c")]);
+
+        // Two blocks per request: catching up takes two ticks
+        let first = index.tick(&chain).await.unwrap();
+        assert_eq!((first.indexed, first.indexed_through, first.caught_up), (1, Some(2), false));
+        let second = index.tick(&chain).await.unwrap();
+        assert_eq!((second.indexed, second.indexed_through, second.caught_up), (2, Some(3), true));
+        assert_eq!(roots(&index).len(), 3);
+
+        let c = index.get(3).unwrap().unwrap();
+        assert_eq!(c.root, hex::encode(hash_data(b"c")));
+        assert_eq!((c.block_number, c.log_index, c.timestamp), (3, 1, 1_700_000_003));
+        assert_eq!(index.by_root(&hash_data(b"a")).unwrap()[0].mantle_batch_id, 1);
+
+        let newest_in_block_3 = index
+            .query(&EventQuery {
+                filters: vec![
+                    EventFilter::Gte(EventField::BlockNumber, 3),
+                    EventFilter::In(EventField::MantleBatchId, vec![Value::Integer(1), Value::Integer(3)]),
+                ],
+                order_by: EventField::Timestamp,
+                descending: true,
+                first: 10,
+                skip: 0,
+            })
+            .unwrap();
+        assert_eq!(newest_in_block_3, vec![c]);
+        let page = index
+            .query(&EventQuery {
+                filters: vec![EventFilter::Eq(EventField::Owner, Value::Text(format!("{:?}", Address::repeat_byte(1))))],
+                order_by: EventField::MantleBatchId,
+                descending: true,
+                first: 1,
+                skip: 1,
+            })
+            .unwrap();
+        assert_eq!(page.iter().map(|b| b.mantle_batch_id).collect::<Vec<_>>(), vec![2]);
+
+        // Block 3 is replaced: batch 3 now registers a different root
+        chain.reorg(3);
+        chain.mine(&[(2, b"b"), (3, b"d")]);
+        chain.mine(&[]);
+        let progress = index.tick(&chain).await.unwrap();
+        assert_eq!(progress.rolled_back, 2);
+        assert_eq!(progress.indexed, 2);
+        assert_eq!(index.get(3).unwrap().unwrap().root, hex::encode(hash_data(b"d")));
+        assert!(index.by_root(&hash_data(b"c")).unwrap().is_empty());
+
+        // Nothing new: nothing changes
+        let idle = index.tick(&chain).await.unwrap();
+        assert_eq!(idle, Progress { indexed_through: Some(4), caught_up: true, ..Progress::default() });
+
+        // The chain shrinks below everything indexed after block 1
+        chain.reorg(2);
+        chain.mine(&[]);
+        let progress = index.tick(&chain).await.unwrap();
+        assert_eq!(progress.rolled_back, 2);
+        assert_eq!(roots(&index), vec![(1, hex::encode(hash_data(b"a")))]);
+
+        // Reopening keeps the index; another registry starts from scratch
+        drop(index);
+        let reopened = ChainIndex::open(dir.path().join("chain.db"), registry, settings).unwrap();
+        assert_eq!(reopened.indexed_through().unwrap(), Some(2));
+        drop(reopened);
+        let other = ChainIndex::open(dir.path().join("chain.db"), Address::repeat_byte(0xbb), settings).unwrap();
+        assert_eq!(other.indexed_through().unwrap(), None);
+        assert!(roots(&other).is_empty());
+    }
+}
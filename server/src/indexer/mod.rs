@@ -0,0 +1,422 @@
+//! Mirror of the registry's `BatchRegistered` events
+//!
+//! A background task follows the configured `MerkleBatchRegistry` with
+//! `eth_getLogs`, stores every registration with its block and transaction in
+//! SQLite, and rolls back whatever a reorg removed. On-chain batches are linked
+//! back to local batches by root through the lookup index, so registrations
+//! made outside this server (e.g. by `scripts/anchor.js`) are found too.
+
+mod index;
+mod source;
+
+pub use index::{ChainBatch, ChainIndex, EventField, EventFilter, EventQuery, IndexerSettings};
+pub use source::{parse_registry, EthersSource, EventSource};
+#[cfg(test)]
+pub(crate) use index::tests::FakeSource;
+
+use crate::batches::store_error;
+use crate::lookup::{LookupIndex, MatchKind};
+use crate::store::StoreError;
+use crate::tenants::Tenant;
+use actix_web::{get, web, HttpResponse};
+use merkle_tree::hex_to_hash;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default page size for `GET /chain/batches`
+const DEFAULT_PAGE_SIZE: usize = 50;
+/// Largest page `GET /chain/batches` returns
+const MAX_PAGE_SIZE: usize = 500;
+
+/// Chain indexer settings
+#[derive(Debug, Clone)]
+pub struct IndexerConfig {
+    /// SQLite database holding indexed events and block checkpoints
+    pub db_path: String,
+    /// How often to look for new blocks once caught up
+    pub poll_interval: Duration,
+    pub settings: IndexerSettings,
+}
+
+/// Follow the chain forever, pausing `interval` whenever caught up
+pub async fn run_indexer(index: Arc<ChainIndex>, source: Arc<dyn EventSource>, interval: Duration) {
+    loop {
+        match index.tick(source.as_ref()).await {
+            Ok(progress) if !progress.caught_up => continue,
+            Ok(_) => {}
+            Err(e) => eprintln!("✗ Chain indexer: {}", e),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// An on-chain batch and the local batches with the same root
+#[derive(Debug, Serialize)]
+pub struct LinkedBatch {
+    #[serde(flatten)]
+    pub batch: ChainBatch,
+    /// Sealed batches on this server whose root was registered
+    pub local_batch_ids: Vec<u64>,
+}
+
+/// Attach the local batches whose root `batch` registered
+pub fn link(batch: ChainBatch, lookup: &LookupIndex) -> Result<LinkedBatch, StoreError> {
+    let root = hex_to_hash(&batch.root).map_err(|e| StoreError::Backend(format!("Invalid indexed root: {}", e)))?;
+    let mut local_batch_ids: Vec<u64> = lookup
+        .lookup(&root)?
+        .into_iter()
+        .filter(|hit| hit.kind == MatchKind::Root)
+        .map(|hit| hit.batch_id)
+        .collect();
+    local_batch_ids.dedup();
+    Ok(LinkedBatch { batch, local_batch_ids })
+}
+
+/// Query parameters for `GET /chain/batches`
+#[derive(Debug, Default, Deserialize)]
+pub struct ChainListQuery {
+    /// Resume after this on-chain batch ID (from `next_after`)
+    pub after: Option<u64>,
+    /// Page size (default 50, at most 500)
+    pub limit: Option<usize>,
+}
+
+fn not_enabled() -> HttpResponse {
+    HttpResponse::ServiceUnavailable().json(serde_json::json!({
+        "success": false,
+        "error": "Chain indexing is not enabled on this server (set MERKSEAL_INDEXER)"
+    }))
+}
+
+/// GET /chain/batches - Batches registered on-chain, in registry ID order
+#[get("/chain/batches")]
+async fn list_chain_batches(
+    query: web::Query<ChainListQuery>,
+    index: Option<web::Data<Arc<ChainIndex>>>,
+    tenant: Tenant,
+) -> HttpResponse {
+    let Some(index) = index else {
+        return not_enabled();
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let (batches, indexed_through) = match index
+        .list(query.after.unwrap_or(0), limit)
+        .and_then(|batches| Ok((batches, index.indexed_through()?)))
+    {
+        Ok(found) => found,
+        Err(e) => return e.response(),
+    };
+    let batches = match batches
+        .into_iter()
+        .map(|batch| link(batch, &tenant.lookup))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(batches) => batches,
+        Err(e) => return store_error(e),
+    };
+    let next_after = (batches.len() == limit)
+        .then(|| batches.last().map(|b| b.batch.mantle_batch_id))
+        .flatten();
+
+    let mut body = serde_json::json!({
+        "success": true,
+        "indexed_through": indexed_through,
+        "batches": batches
+    });
+    if let Some(next_after) = next_after {
+        body["next_after"] = next_after.into();
+    }
+    HttpResponse::Ok().json(body)
+}
+
+/// GET /chain/batches/{id} - One on-chain batch and the local batches it anchors
+#[get("/chain/batches/{id}")]
+async fn get_chain_batch(
+    path: web::Path<u64>,
+    index: Option<web::Data<Arc<ChainIndex>>>,
+    tenant: Tenant,
+) -> HttpResponse {
+    let Some(index) = index else {
+        return not_enabled();
+    };
+    let id = path.into_inner();
+    let batch = match index.get(id) {
+        Ok(Some(batch)) => batch,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "success": false,
+                "error": format!("Mantle batch {} has not been indexed", id)
+            }));
+        }
+        Err(e) => return e.response(),
+    };
+    match link(batch, &tenant.lookup) {
+        Ok(batch) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "batch": batch
+        })),
+        Err(e) => store_error(e),
+    }
+}
+
+/// Chain indexer failures
+#[derive(Debug)]
+pub enum IndexerError {
+    /// Bad RPC URL or registry address
+    Config(String),
+    /// RPC error
+    Rpc(String),
+    /// A block changed while its range was being indexed; retried next tick
+    Reorged(u64),
+    /// The index database holds something it could not have written
+    Corrupt(String),
+    /// SQLite error
+    Db(rusqlite::Error),
+    /// Creating the database directory failed
+    Io(io::Error),
+}
+
+impl IndexerError {
+    /// JSON error response with the matching status code
+    pub fn response(&self) -> HttpResponse {
+        HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "error": self.to_string()
+        }))
+    }
+}
+
+impl fmt::Display for IndexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexerError::Config(msg) => write!(f, "{}", msg),
+            IndexerError::Rpc(e) => write!(f, "Mantle RPC error: {}", e),
+            IndexerError::Reorged(block) => write!(f, "Block {} changed while it was being indexed", block),
+            IndexerError::Corrupt(msg) => write!(f, "Chain index is corrupt: {}", msg),
+            IndexerError::Db(e) => write!(f, "Chain index error: {}", e),
+            IndexerError::Io(e) => write!(f, "Chain index error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for IndexerError {}
+
+impl From<rusqlite::Error> for IndexerError {
+    fn from(e: rusqlite::Error) -> Self {
+        IndexerError::Db(e)
+    }
+}
+
+impl From<io::Error> for IndexerError {
+    fn from(e: io::Error) -> Self {
+        IndexerError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::tests::server_config;
+    use crate::sessions::SessionRegistry;
+    use crate::store::{BatchStore, FsStore, StorageConfig};
+    use crate::tenants::Tenants;
+    use crate::{BatchMetadata, FileEntry};
+    use actix_web::{test as actix_test, App};
+    use ethers::types::Address;
+    use merkle_tree::{hash_data, leaf_cid};
+
+    fn settings() -> IndexerSettings {
+        IndexerSettings {
+            start_block: 0,
+            block_range: 1000,
+            reorg_depth: 16,
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_chain_batches_link_to_local_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = Arc::new(ChainIndex::open(dir.path().join("chain.db"), Address::zero(), settings()).unwrap());
+        let config = server_config(
+            dir.path(),
+            StorageConfig::Filesystem {
+                root: dir.path().join("batches").display().to_string(),
+            },
+        );
+        let lookup = Arc::new(LookupIndex::open(&config.lookup_db).unwrap());
+        let sessions = Arc::new(SessionRegistry::open(&config.session_file, 3600).unwrap());
+
+        // A local one-file batch: its root is the file's leaf
+        let store: Arc<dyn BatchStore> = Arc::new(FsStore::open(dir.path().join("batches")).unwrap());
+        let id = store.create_batch().await.unwrap();
+        let leaf = hash_data(b"local");
+        lookup
+            .index_batch(&BatchMetadata {
+                local_batch_id: id,
+                root: hex::encode(leaf),
+                root_cid: String::new(),
+                file_count: 1,
+                suggested_meta_uri: String::new(),
+                registry_address: "0x0".into(),
+                hash_only: false,
+                manifest_cid: None,
+                created_at: None,
+                mantle_batch_id: None,
+                tx_hash: None,
+                block_number: None,
+                aggregate: None,
+                files: vec![FileEntry {
+                    name: "a.txt".into(),
+                    original_name: "a.txt".into(),
+                    size: 5,
+                    leaf: hex::encode(leaf),
+                    cid: leaf_cid(&leaf),
+                    chunks: None,
+                    sha256: None,
+                }],
+            })
+            .unwrap();
+
+        let chain = FakeSource::default();
+        chain.mine(&[]);
+        chain.mine(&[(1, b"local"), (2, b"elsewhere")]);
+        index.tick(&chain).await.unwrap();
+
+        let tenants = web::Data::new(Arc::new(Tenants::single(Arc::new(config), store, lookup, sessions)));
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(index.clone()))
+                .app_data(tenants.clone())
+                .service(list_chain_batches)
+                .service(get_chain_batch),
+        )
+        .await;
+
+        let list: serde_json::Value = actix_test::call_and_read_body_json(
+            &app,
+            actix_test::TestRequest::get().uri("/chain/batches?limit=1").to_request(),
+        )
+        .await;
+        assert_eq!(list["indexed_through"], 1);
+        assert_eq!(list["batches"][0]["mantle_batch_id"], 1);
+        assert_eq!(list["batches"][0]["local_batch_ids"], serde_json::json!([id]));
+        assert_eq!(list["next_after"], 1);
+
+        let other: serde_json::Value = actix_test::call_and_read_body_json(
+            &app,
+            actix_test::TestRequest::get().uri("/chain/batches/2").to_request(),
+        )
+        .await;
+        assert_eq!(other["batch"]["root"], hex::encode(hash_data(b"elsewhere")));
+        assert_eq!(other["batch"]["local_batch_ids"], serde_json::json!([]));
+
+        let missing = actix_test::call_service(
+            &app,
+            actix_test::TestRequest::get().uri("/chain/batches/3").to_request(),
+        )
+        .await;
+        assert_eq!(missing.status(), 404);
+
+        // Without an indexer the endpoints say so
+        let disabled = actix_test::init_service(
+            App::new()
+                .app_data(tenants)
+                .service(get_chain_batch),
+        )
+        .await;
+        let resp = actix_test::call_service(
+            &disabled,
+            actix_test::TestRequest::get().uri("/chain/batches/1").to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), 503);
+    }
+
+    /// Runs against a local anvil node with the compiled registry:
+    ///
+    /// ```text
+    /// (cd contracts && forge build)
+    /// cargo test -p server -- --ignored test_index_on_anvil
+    /// ```
+    #[tokio::test]
+    #[ignore = "requires anvil on PATH and `forge build` artifacts in contracts/out"]
+    async fn test_index_on_anvil() {
+        use ethers::prelude::*;
+        use ethers::utils::Anvil;
+        use mantle_config::MantleConfig;
+
+        let artifact: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../contracts/out/MerkleBatchRegistry.sol/MerkleBatchRegistry.json"
+            ))
+            .expect("run `forge build` in contracts/ first"),
+        )
+        .unwrap();
+        let abi: ethers::abi::Abi = serde_json::from_value(artifact["abi"].clone()).unwrap();
+        let bytecode: Bytes = artifact["bytecode"]["object"].as_str().unwrap().parse().unwrap();
+
+        let anvil = Anvil::new().spawn();
+        let provider = Provider::<Http>::try_from(anvil.endpoint()).unwrap();
+        let client = Arc::new(SignerMiddleware::new(
+            provider.clone(),
+            LocalWallet::from(anvil.keys()[0].clone()).with_chain_id(anvil.chain_id()),
+        ));
+        let registry = ContractFactory::new(abi, bytecode, client)
+            .deploy(())
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        for (root, uri) in [(hash_data(b"one"), "ipfs://one"), (hash_data(b"two"), "ipfs://two")] {
+            registry
+                .method::<_, U256>("registerBatch", (root, uri.to_string()))
+                .unwrap()
+                .send()
+                .await
+                .unwrap()
+                .await
+                .unwrap();
+        }
+
+        let mantle = MantleConfig::new(anvil.endpoint(), anvil.chain_id(), format!("{:?}", registry.address()));
+        let source = EthersSource::new(&mantle).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let index = ChainIndex::open(dir.path().join("chain.db"), registry.address(), settings()).unwrap();
+
+        let progress = index.tick(&source).await.unwrap();
+        assert_eq!(progress.indexed, 2);
+        assert!(progress.caught_up);
+
+        let two = index.get(2).unwrap().unwrap();
+        assert_eq!(two.root, hex::encode(hash_data(b"two")));
+        assert_eq!(two.meta_uri, "ipfs://two");
+        assert_eq!(two.owner, format!("{:?}", anvil.addresses()[0]));
+        let block = provider.get_block(two.block_number).await.unwrap().unwrap();
+        assert_eq!(two.block_hash, format!("{:?}", block.hash.unwrap()));
+        assert_eq!(two.timestamp, block.timestamp.as_u64());
+
+        // Revert the second registration, as a reorg would
+        let snapshot: U256 = provider.request("evm_snapshot", ()).await.unwrap();
+        registry
+            .method::<_, U256>("registerBatch", (hash_data(b"three"), "ipfs://three".to_string()))
+            .unwrap()
+            .send()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        assert_eq!(index.tick(&source).await.unwrap().indexed, 1);
+        let _: bool = provider.request("evm_revert", [snapshot]).await.unwrap();
+        provider.request::<_, serde_json::Value>("evm_mine", ()).await.unwrap();
+        provider.request::<_, serde_json::Value>("evm_mine", ()).await.unwrap();
+
+        let progress = index.tick(&source).await.unwrap();
+        assert_eq!(progress.rolled_back, 1);
+        assert!(index.get(3).unwrap().is_none());
+        assert_eq!(index.by_root(&hash_data(b"one")).unwrap()[0].mantle_batch_id, 1);
+    }
+}
@@ -0,0 +1,123 @@
+use async_trait::async_trait;
+use ethers::prelude::*;
+use mantle_config::MantleConfig;
+
+use super::IndexerError;
+use crate::anchor::BatchRegisteredFilter;
+
+/// The parts of a block header the indexer checkpoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockRef {
+    pub number: u64,
+    pub hash: H256,
+    /// Unix timestamp (seconds)
+    pub timestamp: u64,
+}
+
+/// A `BatchRegistered` event and where it sits in the chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisteredLog {
+    pub mantle_batch_id: u64,
+    pub root: [u8; 32],
+    pub owner: Address,
+    pub meta_uri: String,
+    pub block_number: u64,
+    pub block_hash: H256,
+    pub tx_hash: H256,
+    pub log_index: u64,
+}
+
+/// The chain reads the indexer needs
+///
+/// Kept small so reorg handling can be tested without a node.
+#[async_trait]
+pub trait EventSource: Send + Sync {
+    /// Latest block number
+    async fn head(&self) -> Result<u64, IndexerError>;
+
+    /// Header of a canonical block, `None` if the node does not have it (yet)
+    async fn block(&self, number: u64) -> Result<Option<BlockRef>, IndexerError>;
+
+    /// `BatchRegistered` events from the registry in blocks `from..=to`
+    async fn logs(&self, from: u64, to: u64) -> Result<Vec<RegisteredLog>, IndexerError>;
+}
+
+/// `EventSource` backed by an RPC node, using `eth_getLogs`
+pub struct EthersSource {
+    provider: Provider<Http>,
+    registry: Address,
+}
+
+impl EthersSource {
+    /// Follow the configured registry over the configured RPC
+    pub fn new(mantle: &MantleConfig) -> Result<Self, IndexerError> {
+        let provider = Provider::<Http>::try_from(mantle.rpc_url.as_str())
+            .map_err(|e| IndexerError::Config(format!("Invalid RPC URL: {}", e)))?;
+        Ok(Self {
+            provider,
+            registry: parse_registry(&mantle.registry_address)?,
+        })
+    }
+}
+
+/// Parse the registry address the index belongs to
+pub fn parse_registry(address: &str) -> Result<Address, IndexerError> {
+    address
+        .parse()
+        .map_err(|e| IndexerError::Config(format!("Invalid registry address: {}", e)))
+}
+
+#[async_trait]
+impl EventSource for EthersSource {
+    async fn head(&self) -> Result<u64, IndexerError> {
+        Ok(self.provider.get_block_number().await.map_err(rpc)?.as_u64())
+    }
+
+    async fn block(&self, number: u64) -> Result<Option<BlockRef>, IndexerError> {
+        let Some(block) = self.provider.get_block(number).await.map_err(rpc)? else {
+            return Ok(None);
+        };
+        // Pending blocks have no hash yet
+        Ok(block.hash.map(|hash| BlockRef {
+            number,
+            hash,
+            timestamp: block.timestamp.low_u64(),
+        }))
+    }
+
+    async fn logs(&self, from: u64, to: u64) -> Result<Vec<RegisteredLog>, IndexerError> {
+        let filter = Filter::new()
+            .address(self.registry)
+            .topic0(BatchRegisteredFilter::signature())
+            .from_block(from)
+            .to_block(to);
+        let mut logs = Vec::new();
+        for log in self.provider.get_logs(&filter).await.map_err(rpc)? {
+            if log.removed == Some(true) {
+                continue;
+            }
+            let (Some(block_number), Some(block_hash), Some(tx_hash), Some(log_index)) =
+                (log.block_number, log.block_hash, log.transaction_hash, log.log_index)
+            else {
+                return Err(IndexerError::Rpc("Node returned a log without its block position".to_string()));
+            };
+            let event = parse_log::<BatchRegisteredFilter>(log)
+                .map_err(|e| IndexerError::Rpc(format!("Malformed BatchRegistered log: {}", e)))?;
+            logs.push(RegisteredLog {
+                mantle_batch_id: event.batch_id.low_u64(),
+                root: event.root,
+                owner: event.owner,
+                meta_uri: event.meta_uri,
+                block_number: block_number.as_u64(),
+                block_hash,
+                tx_hash,
+                log_index: log_index.low_u64(),
+            });
+        }
+        Ok(logs)
+    }
+}
+
+fn rpc(e: ProviderError) -> IndexerError {
+    IndexerError::Rpc(e.to_string())
+}
@@ -0,0 +1,239 @@
+//! Hash-only batches
+//!
+//! `POST /leaves` seals a batch from leaf hashes computed by the client, so
+//! the documents themselves never reach the server. The batch is built and
+//! anchored exactly like an uploaded one, but its manifest is marked
+//! `hash_only` and there is nothing to download.
+
+use crate::anchor::AnchorQueue;
+use crate::filenames::BatchFileNames;
+use crate::publish::publish_manifest;
+use crate::quotas::Limit;
+use crate::receipts::ReceiptSigner;
+use crate::tenants::Tenant;
+use crate::upload::UploadError;
+use crate::{auto_anchor, new_batch_metadata, unix_now, FileEntry, UploadResponse};
+use actix_web::{error, post, web, HttpResponse};
+use mantle_config::MantleConfig;
+use merkle_tree::{hex_to_hash, leaf_cid};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// One pre-computed leaf
+#[derive(Debug, Deserialize)]
+pub struct LeafInput {
+    /// SHA-256 of the document, 64 hex characters with optional `0x`
+    pub leaf: String,
+    /// Opaque label, sanitized into the entry's name
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// Body of `POST /leaves`
+#[derive(Debug, Deserialize)]
+pub struct LeavesRequest {
+    /// Leaves in tree order
+    pub leaves: Vec<LeafInput>,
+}
+
+/// JSON body limits, with errors in the usual `{success, error}` shape
+pub fn json_config(max_bytes: usize) -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(max_bytes)
+        .error_handler(|e, _req| {
+            let response = UploadError::BadRequest(format!("Invalid JSON body: {}", e)).response();
+            error::InternalError::from_response(e, response).into()
+        })
+}
+
+/// POST /leaves - Seal a hash-only batch from pre-computed leaf hashes
+#[post("/leaves")]
+async fn submit_leaves(
+    body: web::Json<LeavesRequest>,
+    config: web::Data<Arc<MantleConfig>>,
+    tenant: Tenant,
+    signer: web::Data<Arc<ReceiptSigner>>,
+    anchor_queue: Option<web::Data<Arc<AnchorQueue>>>,
+) -> HttpResponse {
+    let files = match leaf_entries(body.into_inner().leaves) {
+        Ok(files) => files,
+        Err(e) => return e.response(),
+    };
+
+    let quota = match tenant.reserve(Limit::BatchesPerDay, 1) {
+        Ok(quota) => quota,
+        Err(e) => return e.response(),
+    };
+    let store = &tenant.store;
+    let batch_id = match store.create_batch().await {
+        Ok(id) => id,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "success": false,
+                "error": format!("Failed to create batch: {}", e)
+            }));
+        }
+    };
+
+    let mut batch_metadata = new_batch_metadata(batch_id, files, &config);
+    batch_metadata.hash_only = true;
+    if let Err(e) = publish_manifest(&mut batch_metadata, &tenant.config).await {
+        store.abort(batch_id).await.ok();
+        return HttpResponse::BadGateway().json(serde_json::json!({
+            "success": false,
+            "error": e.to_string()
+        }));
+    }
+    let manifest = serde_json::to_vec_pretty(&batch_metadata).unwrap();
+    if let Err(e) = store.seal(batch_id, &manifest).await {
+        store.abort(batch_id).await.ok();
+        return HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "error": format!("Failed to seal batch: {}", e)
+        }));
+    }
+
+    println!("✓ Batch {} sealed from leaf hashes (hash-only):", batch_id);
+    println!("  Leaves: {}", batch_metadata.file_count);
+    println!("  Root: {}", batch_metadata.root);
+    quota.settle(unix_now());
+
+    auto_anchor(&tenant, anchor_queue, batch_id).await;
+
+    HttpResponse::Ok().json(UploadResponse {
+        success: true,
+        receipt: signer.sealed(&tenant.tenant, &batch_metadata),
+        batch: batch_metadata,
+    })
+}
+
+/// Validate submitted leaves and turn them into manifest entries
+fn leaf_entries(leaves: Vec<LeafInput>) -> Result<Vec<FileEntry>, UploadError> {
+    if leaves.is_empty() {
+        return Err(UploadError::BadRequest("No leaves submitted".to_string()));
+    }
+
+    let mut names = BatchFileNames::new();
+    leaves
+        .into_iter()
+        .enumerate()
+        .map(|(index, input)| {
+            let hex = input.leaf.trim();
+            let hex = hex.strip_prefix("0x").unwrap_or(hex);
+            let leaf = hex_to_hash(hex)
+                .map_err(|e| UploadError::BadRequest(format!("Leaf {} is not a 32-byte hex hash: {}", index, e)))?;
+            let name = names
+                .assign(input.label.as_deref(), index)
+                .map_err(|e| UploadError::BadRequest(format!("Leaf {}: {}", index, e)))?;
+            Ok(FileEntry {
+                original_name: input.label.unwrap_or_else(|| name.clone()),
+                name,
+                size: 0,
+                leaf: hex::encode(leaf),
+                cid: leaf_cid(&leaf),
+                chunks: None,
+                sha256: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::tests::server_config;
+    use crate::batches;
+    use crate::chunk_store::ChunkStore;
+    use crate::listen::DEFAULT_MAX_PAYLOAD_BYTES;
+    use crate::lookup::LookupIndex;
+    use crate::sessions::SessionRegistry;
+    use crate::store::{BatchStore, FsStore, StorageConfig};
+    use crate::tenants::Tenants;
+    use actix_web::{test as actix_test, App};
+    use merkle_tree::{hash_data, MerkleTree};
+
+    #[actix_web::test]
+    async fn test_hash_only_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("batches");
+        let store: Arc<dyn BatchStore> = Arc::new(FsStore::open(&root).unwrap());
+        let server_config = Arc::new(server_config(
+            dir.path(),
+            StorageConfig::Filesystem {
+                root: root.display().to_string(),
+            },
+        ));
+        let lookup = Arc::new(LookupIndex::open(&server_config.lookup_db).unwrap());
+        let sessions = Arc::new(SessionRegistry::open(&server_config.session_file, 3600).unwrap());
+        let mantle = Arc::new(MantleConfig::new("http://localhost:8545".into(), 5003, "0x0".into()));
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(json_config(DEFAULT_MAX_PAYLOAD_BYTES))
+                .app_data(web::Data::new(Arc::new(Tenants::single(server_config, store, lookup, sessions))))
+                .app_data(web::Data::new(Arc::new(ReceiptSigner::random())))
+                .app_data(web::Data::new(mantle))
+                .app_data(web::Data::new(None::<ChunkStore>))
+                .service(submit_leaves)
+                .service(batches::download_file)
+                .service(batches::get_proof),
+        )
+        .await;
+
+        let (a, b) = (hash_data(b"contract"), hash_data(b"invoice"));
+        let sealed: serde_json::Value = actix_test::call_and_read_body_json(
+            &app,
+            actix_test::TestRequest::post()
+                .uri("/leaves")
+                .set_json(serde_json::json!({
+                    "leaves": [
+                        { "leaf": format!("0x{}", hex::encode(a)), "label": "contract.pdf" },
+                        { "leaf": hex::encode(b).to_uppercase() }
+                    ]
+                }))
+                .to_request(),
+        )
+        .await;
+        let batch = &sealed["batch"];
+        assert_eq!(batch["hash_only"], true);
+        assert_eq!(batch["root"], MerkleTree::new(vec![a, b]).root_hex());
+        assert_eq!(batch["files"][1]["name"], "file_1");
+        assert_eq!(batch["files"][1]["leaf"], hex::encode(b));
+        assert_eq!(sealed["receipt"]["root"], batch["root"]);
+        assert_eq!(sealed["receipt"]["leaves"][1], hex::encode(b));
+        let id = batch["local_batch_id"].as_u64().unwrap();
+
+        let proof: serde_json::Value = actix_test::call_and_read_body_json(
+            &app,
+            actix_test::TestRequest::get()
+                .uri(&format!("/batches/{}/proof/contract.pdf", id))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(proof["leaf"], hex::encode(a));
+
+        let resp = actix_test::call_service(
+            &app,
+            actix_test::TestRequest::get()
+                .uri(&format!("/batches/{}/files/contract.pdf", id))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), 404);
+
+        for body in [
+            serde_json::json!({ "leaves": [] }),
+            serde_json::json!({ "leaves": [{ "leaf": "abcd" }] }),
+            serde_json::json!({ "leaves": [{ "leaf": "zz".repeat(32) }] }),
+            serde_json::json!({ "hashes": [] }),
+        ] {
+            let resp = actix_test::call_service(
+                &app,
+                actix_test::TestRequest::post().uri("/leaves").set_json(body).to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), 400);
+            let error: serde_json::Value = actix_test::read_body_json(resp).await;
+            assert_eq!(error["success"], false);
+        }
+    }
+}
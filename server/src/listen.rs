@@ -0,0 +1,319 @@
+//! Listener settings: bind address, workers, storage root, body limits, TLS and CORS
+//!
+//! Each setting comes from its command-line flag, then its environment
+//! variable, then the `[server]` section of the TOML file named by `--config`
+//! (`MERKSEAL_CONFIG`), then the default. Everything else is configured through
+//! the environment (see `ServerConfig::from_env`).
+
+use crate::config::ServerConfigError;
+use actix_cors::Cors;
+use actix_web::http::header;
+use clap::Parser;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Default largest JSON request body (about 100k leaves with short labels)
+pub const DEFAULT_MAX_PAYLOAD_BYTES: usize = 16 << 20;
+
+/// Command-line flags, each falling back to its environment variable
+#[derive(Debug, Default, Parser)]
+#[command(name = "server", about = "MerkSeal batch server")]
+pub struct Args {
+    /// TOML file with a [server] section
+    #[arg(long, env = "MERKSEAL_CONFIG")]
+    pub config: Option<String>,
+    /// Address to listen on [default: 127.0.0.1]
+    #[arg(long, env = "MERKSEAL_BIND")]
+    pub bind: Option<String>,
+    /// Port to listen on [default: 8080]
+    #[arg(long, env = "MERKSEAL_PORT")]
+    pub port: Option<u16>,
+    /// Worker threads [default: one per CPU core]
+    #[arg(long, env = "MERKSEAL_WORKERS")]
+    pub workers: Option<usize>,
+    /// Storage root for batches and server state [default: batches]
+    #[arg(long, env = "MERKSEAL_STORAGE_DIR")]
+    pub storage_dir: Option<String>,
+    /// Largest JSON request body in bytes [default: 16 MiB]
+    #[arg(long, env = "MERKSEAL_MAX_PAYLOAD_BYTES")]
+    pub max_payload_bytes: Option<usize>,
+    /// PEM certificate chain; serves HTTPS together with --tls-key
+    #[arg(long, env = "MERKSEAL_TLS_CERT")]
+    pub tls_cert: Option<String>,
+    /// PEM private key for --tls-cert
+    #[arg(long, env = "MERKSEAL_TLS_KEY")]
+    pub tls_key: Option<String>,
+    /// Comma-separated origins allowed to call the API from a browser, or `*`
+    #[arg(long, env = "MERKSEAL_CORS_ORIGINS", value_delimiter = ',')]
+    pub cors_origins: Vec<String>,
+}
+
+/// The `[server]` section of the config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileSection {
+    bind: Option<String>,
+    port: Option<u16>,
+    workers: Option<usize>,
+    storage_dir: Option<String>,
+    max_payload_bytes: Option<usize>,
+    tls_cert: Option<String>,
+    tls_key: Option<String>,
+    cors_origins: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    server: FileSection,
+}
+
+/// Native TLS certificate and key
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsConfig {
+    /// PEM certificate chain, leaf first
+    pub cert_file: String,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1)
+    pub key_file: String,
+}
+
+/// Where and how the server listens
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListenConfig {
+    /// Address to bind
+    pub bind: String,
+    /// Port to bind
+    pub port: u16,
+    /// Worker threads (`None` uses one per CPU core)
+    pub workers: Option<usize>,
+    /// Root directory for batches and the server's state files
+    pub storage_dir: String,
+    /// Largest JSON request body; file uploads are streamed and limited separately
+    pub max_payload_bytes: usize,
+    /// Serve HTTPS with this certificate (`None` serves plain HTTP)
+    pub tls: Option<TlsConfig>,
+    /// Origins allowed by CORS (empty disables CORS, `*` allows any origin)
+    pub cors_origins: Vec<String>,
+}
+
+impl ListenConfig {
+    /// Resolve flags and environment variables over the config file
+    pub fn load(args: Args) -> Result<Self, ServerConfigError> {
+        let file = match &args.config {
+            Some(path) => {
+                let text = fs::read_to_string(path)
+                    .map_err(|e| ServerConfigError::File(path.clone(), e.to_string()))?;
+                toml::from_str::<ConfigFile>(&text)
+                    .map_err(|e| ServerConfigError::File(path.clone(), e.to_string()))?
+                    .server
+            }
+            None => FileSection::default(),
+        };
+
+        let tls = match (args.tls_cert.or(file.tls_cert), args.tls_key.or(file.tls_key)) {
+            (Some(cert_file), Some(key_file)) => Some(TlsConfig { cert_file, key_file }),
+            (Some(_), None) => return Err(ServerConfigError::Missing("MERKSEAL_TLS_KEY")),
+            (None, Some(_)) => return Err(ServerConfigError::Missing("MERKSEAL_TLS_CERT")),
+            (None, None) => None,
+        };
+
+        let cors_origins = if args.cors_origins.is_empty() {
+            file.cors_origins.unwrap_or_default()
+        } else {
+            args.cors_origins
+        };
+        let cors_origins = cors_origins
+            .iter()
+            .map(|origin| origin.trim().trim_end_matches('/'))
+            .filter(|origin| !origin.is_empty())
+            .map(|origin| match origin {
+                "*" => Ok(origin.to_string()),
+                _ => match url::Url::parse(origin) {
+                    // An origin is scheme, host and port only
+                    Ok(url) if matches!(url.scheme(), "http" | "https") && url.path() == "/" => {
+                        Ok(url.origin().ascii_serialization())
+                    }
+                    _ => Err(ServerConfigError::Invalid("MERKSEAL_CORS_ORIGINS", origin.to_string())),
+                },
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            bind: args.bind.or(file.bind).unwrap_or_else(|| "127.0.0.1".to_string()),
+            port: args.port.or(file.port).unwrap_or(8080),
+            workers: args.workers.or(file.workers).filter(|n| *n > 0),
+            storage_dir: args.storage_dir.or(file.storage_dir).unwrap_or_else(|| "batches".to_string()),
+            max_payload_bytes: args
+                .max_payload_bytes
+                .or(file.max_payload_bytes)
+                .unwrap_or(DEFAULT_MAX_PAYLOAD_BYTES)
+                .max(1),
+            tls,
+            cors_origins,
+        })
+    }
+
+    /// `https` when TLS is configured, otherwise `http`
+    pub fn scheme(&self) -> &'static str {
+        if self.tls.is_some() {
+            "https"
+        } else {
+            "http"
+        }
+    }
+
+    /// Address of this server as seen from the same host
+    ///
+    /// A wildcard bind address is reached through loopback.
+    pub fn local_url(&self) -> String {
+        let host = match self.bind.parse::<IpAddr>() {
+            Ok(ip) if ip.is_unspecified() => "127.0.0.1".to_string(),
+            Ok(IpAddr::V6(ip)) => format!("[{}]", ip),
+            _ => self.bind.clone(),
+        };
+        format!("{}://{}:{}", self.scheme(), host, self.port)
+    }
+
+    /// CORS middleware for the browser SDK (only wrapped when origins are configured)
+    pub fn cors(&self) -> Cors {
+        let cors = Cors::default()
+            .allowed_methods(["GET", "POST", "PUT"])
+            .allow_any_header()
+            .expose_headers([header::RETRY_AFTER, header::CONTENT_RANGE, header::ACCEPT_RANGES])
+            .max_age(3600);
+        if self.cors_origins.iter().any(|origin| origin == "*") {
+            return cors.allow_any_origin();
+        }
+        self.cors_origins
+            .iter()
+            .fold(cors, |cors, origin| cors.allowed_origin(origin))
+    }
+}
+
+impl TlsConfig {
+    /// Read the certificate chain and key into a rustls server config
+    pub fn load(&self) -> io::Result<rustls::ServerConfig> {
+        let certs = CertificateDer::pem_file_iter(&self.cert_file)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| io::Error::other(format!("{}: {}", self.cert_file, e)))?;
+        if certs.is_empty() {
+            return Err(io::Error::other(format!("{}: no certificates found", self.cert_file)));
+        }
+        let key = PrivateKeyDer::from_pem_file(&self.key_file)
+            .map_err(|e| io::Error::other(format!("{}: {}", self.key_file, e)))?;
+
+        rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+            .map_err(io::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test as actix_test, web, App, HttpResponse};
+
+    fn args(flags: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("server").chain(flags.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn test_flags_override_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("merkseal.toml");
+        std::fs::write(
+            &file,
+            r#"
+[server]
+bind = "0.0.0.0"
+port = 9000
+workers = 4
+storage_dir = "/var/lib/merkseal"
+cors_origins = ["https://app.example.com/"]
+"#,
+        )
+        .unwrap();
+        let path = file.display().to_string();
+
+        let config = ListenConfig::load(args(&["--config", &path, "--port", "9443"])).unwrap();
+        assert_eq!(config.bind, "0.0.0.0");
+        assert_eq!(config.port, 9443);
+        assert_eq!(config.workers, Some(4));
+        assert_eq!(config.storage_dir, "/var/lib/merkseal");
+        assert_eq!(config.max_payload_bytes, DEFAULT_MAX_PAYLOAD_BYTES);
+        assert_eq!(config.cors_origins, vec!["https://app.example.com".to_string()]);
+        assert_eq!(config.scheme(), "http");
+        assert_eq!(config.local_url(), "http://127.0.0.1:9443");
+
+        let config = ListenConfig::load(args(&[
+            "--config",
+            &path,
+            "--cors-origins",
+            "http://localhost:3000,https://sdk.example.com:8443",
+            "--tls-cert",
+            "cert.pem",
+            "--tls-key",
+            "key.pem",
+        ]))
+        .unwrap();
+        assert_eq!(config.cors_origins.len(), 2);
+        assert_eq!(config.scheme(), "https");
+        assert_eq!(config.local_url(), "https://127.0.0.1:9000");
+
+        let defaults = ListenConfig::load(Args::default()).unwrap();
+        assert_eq!((defaults.bind.as_str(), defaults.port), ("127.0.0.1", 8080));
+        assert_eq!(defaults.storage_dir, "batches");
+        assert!(defaults.tls.is_none() && defaults.cors_origins.is_empty());
+
+        assert!(matches!(
+            ListenConfig::load(args(&["--tls-cert", "cert.pem"])),
+            Err(ServerConfigError::Missing("MERKSEAL_TLS_KEY"))
+        ));
+        assert!(matches!(
+            ListenConfig::load(args(&["--cors-origins", "app.example.com"])),
+            Err(ServerConfigError::Invalid("MERKSEAL_CORS_ORIGINS", _))
+        ));
+        std::fs::write(&file, "[server]\nprot = 1\n").unwrap();
+        assert!(matches!(
+            ListenConfig::load(args(&["--config", &path])),
+            Err(ServerConfigError::File(..))
+        ));
+    }
+
+    #[actix_web::test]
+    async fn test_cors_preflight() {
+        let config = ListenConfig::load(args(&["--cors-origins", "https://app.example.com"])).unwrap();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(config.cors())
+                .route("/batches", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let request = actix_test::TestRequest::default()
+            .method(actix_web::http::Method::OPTIONS)
+            .uri("/batches")
+            .insert_header((header::ORIGIN, "https://app.example.com"))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+            .to_request();
+        let response = actix_test::call_service(&app, request).await;
+        assert!(response.status().is_success());
+        assert_eq!(
+            response.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "https://app.example.com"
+        );
+
+        let request = actix_test::TestRequest::get()
+            .uri("/batches")
+            .insert_header((header::ORIGIN, "https://evil.example.com"))
+            .to_request();
+        let response = actix_test::try_call_service(&app, request).await;
+        assert!(response.is_err() || response.unwrap().headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+    }
+}
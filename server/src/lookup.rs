@@ -0,0 +1,524 @@
+//! Finding batches by content hash
+//!
+//! A SQLite index maps batch roots, leaf hashes and whole-file SHA-256s to
+//! the batches (and leaf positions) that contain them. `IndexedStore` keeps it
+//! current by indexing every manifest as its batch is sealed, and `backfill`
+//! catches up on batches sealed before the index existed.
+//! `GET /lookup/{hash}` turns a document hash into inclusion proof URLs.
+
+use crate::anchor::AnchorQueue;
+use crate::batches::{load_metadata, store_error};
+use crate::indexer::ChainIndex;
+use crate::store::{BatchStore, FileStream, StoreError, StoredFile};
+use crate::tenants::Tenant;
+use crate::BatchMetadata;
+use actix_web::{get, web, HttpResponse};
+use async_trait::async_trait;
+use merkle_tree::{hex_to_hash, Cid, Hash};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+    CREATE TABLE IF NOT EXISTS hashes (
+        hash BLOB NOT NULL,
+        batch_id INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        leaf_index INTEGER,
+        name TEXT
+    );
+    CREATE INDEX IF NOT EXISTS hashes_by_hash ON hashes (hash);
+    CREATE INDEX IF NOT EXISTS hashes_by_batch ON hashes (batch_id);
+    CREATE TABLE IF NOT EXISTS indexed_batches (
+        batch_id INTEGER PRIMARY KEY
+    );
+";
+
+/// How a hash matched a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    /// The batch's Merkle root
+    Root,
+    /// A leaf of the batch
+    Leaf,
+    /// The whole-file SHA-256 of a chunked file, whose leaf is a chunk-tree root
+    File,
+}
+
+impl MatchKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            MatchKind::Root => "root",
+            MatchKind::Leaf => "leaf",
+            MatchKind::File => "file",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "root" => Some(MatchKind::Root),
+            "leaf" => Some(MatchKind::Leaf),
+            "file" => Some(MatchKind::File),
+            _ => None,
+        }
+    }
+}
+
+/// One place a hash occurs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    pub batch_id: u64,
+    pub kind: MatchKind,
+    /// Leaf position and file name (`None` for root matches)
+    pub leaf: Option<(usize, String)>,
+}
+
+/// Hash-to-batch index
+pub struct LookupIndex {
+    conn: Mutex<Connection>,
+}
+
+impl LookupIndex {
+    /// Open (and create if needed) the index database
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path).map_err(backend)?;
+        conn.execute_batch(SCHEMA).map_err(backend)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Index a sealed batch, replacing whatever was indexed for it before
+    pub fn index_batch(&self, batch: &BatchMetadata) -> Result<(), StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(backend)?;
+        let id = batch.local_batch_id as i64;
+        tx.execute("DELETE FROM hashes WHERE batch_id = ?1", params![id])
+            .map_err(backend)?;
+        {
+            let mut insert = tx
+                .prepare("INSERT INTO hashes (hash, batch_id, kind, leaf_index, name) VALUES (?1, ?2, ?3, ?4, ?5)")
+                .map_err(backend)?;
+            let root = parse_hex(&batch.root)?;
+            insert
+                .execute(params![&root[..], id, MatchKind::Root.as_str(), None::<i64>, None::<String>])
+                .map_err(backend)?;
+            for (index, file) in batch.files.iter().enumerate() {
+                let leaf = parse_hex(&file.leaf)?;
+                insert
+                    .execute(params![&leaf[..], id, MatchKind::Leaf.as_str(), index as i64, file.name])
+                    .map_err(backend)?;
+                if let Some(sha256) = &file.sha256 {
+                    let sha256 = parse_hex(sha256)?;
+                    insert
+                        .execute(params![&sha256[..], id, MatchKind::File.as_str(), index as i64, file.name])
+                        .map_err(backend)?;
+                }
+            }
+        }
+        tx.execute("INSERT OR IGNORE INTO indexed_batches (batch_id) VALUES (?1)", params![id])
+            .map_err(backend)?;
+        tx.commit().map_err(backend)
+    }
+
+    /// Every occurrence of `hash`, by batch ID, roots before leaves
+    pub fn lookup(&self, hash: &Hash) -> Result<Vec<Hit>, StoreError> {
+        let conn = self.conn();
+        let mut query = conn
+            .prepare(
+                "SELECT batch_id, kind, leaf_index, name FROM hashes WHERE hash = ?1
+                 ORDER BY batch_id, leaf_index IS NOT NULL, leaf_index, kind",
+            )
+            .map_err(backend)?;
+        let rows = query
+            .query_map(params![&hash[..]], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<i64>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                ))
+            })
+            .map_err(backend)?;
+
+        let mut hits = Vec::new();
+        for row in rows {
+            let (batch_id, kind, leaf_index, name) = row.map_err(backend)?;
+            let kind = MatchKind::parse(&kind)
+                .ok_or_else(|| StoreError::Backend(format!("Unknown match kind {:?} in lookup index", kind)))?;
+            hits.push(Hit {
+                batch_id: batch_id as u64,
+                kind,
+                leaf: leaf_index.zip(name).map(|(index, name)| (index as usize, name)),
+            });
+        }
+        Ok(hits)
+    }
+
+    /// IDs of all batches that have been indexed
+    fn indexed(&self) -> Result<BTreeSet<u64>, StoreError> {
+        let conn = self.conn();
+        let mut query = conn.prepare("SELECT batch_id FROM indexed_batches").map_err(backend)?;
+        let ids = query
+            .query_map([], |row| row.get::<_, i64>(0))
+            .map_err(backend)?
+            .map(|id| id.map(|id| id as u64))
+            .collect::<Result<_, _>>()
+            .map_err(backend)?;
+        Ok(ids)
+    }
+}
+
+fn backend(e: rusqlite::Error) -> StoreError {
+    StoreError::Backend(e.to_string())
+}
+
+fn parse_hex(hex: &str) -> Result<Hash, StoreError> {
+    hex_to_hash(hex).map_err(|e| StoreError::Backend(format!("Invalid hash {:?} in manifest: {}", hex, e)))
+}
+
+/// Index every sealed batch the index has not seen yet, returning how many
+pub async fn backfill(index: &LookupIndex, store: &dyn BatchStore) -> Result<usize, StoreError> {
+    let indexed = index.indexed()?;
+    let mut added = 0;
+    for batch_id in store.list_batches().await? {
+        if indexed.contains(&batch_id) {
+            continue;
+        }
+        match load_metadata(store, batch_id).await {
+            Ok(batch) => {
+                index.index_batch(&batch)?;
+                added += 1;
+            }
+            Err(e) => eprintln!("✗ Skipping batch {} in lookup index: {}", batch_id, e),
+        }
+    }
+    Ok(added)
+}
+
+/// Wrapper that adds every batch to the lookup index as it is sealed
+pub struct IndexedStore {
+    inner: Arc<dyn BatchStore>,
+    index: Arc<LookupIndex>,
+}
+
+impl IndexedStore {
+    pub fn new(inner: Arc<dyn BatchStore>, index: Arc<LookupIndex>) -> Self {
+        Self { inner, index }
+    }
+}
+
+#[async_trait]
+impl BatchStore for IndexedStore {
+    async fn create_batch(&self) -> Result<u64, StoreError> {
+        self.inner.create_batch().await
+    }
+
+    async fn put_file(&self, batch_id: u64, name: &str, data: &[u8]) -> Result<(), StoreError> {
+        self.inner.put_file(batch_id, name, data).await
+    }
+
+    async fn put_file_from_path(&self, batch_id: u64, name: &str, path: &Path) -> Result<(), StoreError> {
+        self.inner.put_file_from_path(batch_id, name, path).await
+    }
+
+    async fn get_file(&self, batch_id: u64, name: &str) -> Result<Vec<u8>, StoreError> {
+        self.inner.get_file(batch_id, name).await
+    }
+
+    async fn read_file_range(&self, batch_id: u64, name: &str, range: Range<u64>) -> Result<FileStream, StoreError> {
+        self.inner.read_file_range(batch_id, name, range).await
+    }
+
+    async fn list_batch(&self, batch_id: u64) -> Result<Vec<StoredFile>, StoreError> {
+        self.inner.list_batch(batch_id).await
+    }
+
+    async fn seal(&self, batch_id: u64, manifest: &[u8]) -> Result<(), StoreError> {
+        self.inner.seal(batch_id, manifest).await?;
+        // The batch is sealed either way; `backfill` retries on the next start
+        let indexed = serde_json::from_slice::<BatchMetadata>(manifest)
+            .map_err(|e| StoreError::Backend(format!("Invalid manifest: {}", e)))
+            .and_then(|batch| self.index.index_batch(&batch));
+        if let Err(e) = indexed {
+            eprintln!("✗ Failed to index batch {} for lookup: {}", batch_id, e);
+        }
+        Ok(())
+    }
+
+    async fn write_manifest(&self, batch_id: u64, manifest: &[u8]) -> Result<(), StoreError> {
+        self.inner.write_manifest(batch_id, manifest).await
+    }
+
+    async fn read_manifest(&self, batch_id: u64) -> Result<Vec<u8>, StoreError> {
+        self.inner.read_manifest(batch_id).await
+    }
+
+    async fn list_batches(&self) -> Result<Vec<u64>, StoreError> {
+        self.inner.list_batches().await
+    }
+
+    async fn abort(&self, batch_id: u64) -> Result<(), StoreError> {
+        self.inner.abort(batch_id).await
+    }
+
+    fn location(&self, batch_id: u64) -> String {
+        self.inner.location(batch_id)
+    }
+}
+
+/// One batch in a lookup result
+#[derive(Debug, Serialize)]
+pub struct LookupMatch {
+    pub batch_id: u64,
+    #[serde(rename = "match")]
+    pub kind: MatchKind,
+    pub root: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leaf_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Inclusion proof URL path for leaf and file matches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proof: Option<String>,
+    pub anchored: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mantle_batch_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<String>,
+    /// Anchoring job state when the batch is queued
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<serde_json::Value>,
+}
+
+/// Parse a lookup key: 64 hex characters (optional `0x`) or a leaf or root CID
+fn parse_lookup_hash(input: &str) -> Result<Hash, String> {
+    let input = input.trim();
+    if let Ok(cid) = input.parse::<Cid>() {
+        return Ok(cid.digest);
+    }
+    hex_to_hash(input.strip_prefix("0x").unwrap_or(input))
+        .map_err(|e| format!("Expected a 32-byte hex hash or a CID: {}", e))
+}
+
+/// GET /lookup/{hash} - Every sealed batch containing a root, leaf or file hash
+#[get("/lookup/{hash}")]
+async fn lookup(
+    path: web::Path<String>,
+    tenant: Tenant,
+    anchor_queue: Option<web::Data<Arc<AnchorQueue>>>,
+    chain_index: Option<web::Data<Arc<ChainIndex>>>,
+) -> HttpResponse {
+    let hash = match parse_lookup_hash(&path) {
+        Ok(hash) => hash,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "success": false,
+                "error": e
+            }));
+        }
+    };
+    let hits = match tenant.lookup.lookup(&hash) {
+        Ok(hits) => hits,
+        Err(e) => return store_error(e),
+    };
+    if hits.is_empty() {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "success": false,
+            "error": format!("No sealed batch contains {}", hex::encode(hash))
+        }));
+    }
+
+    let mut batches = BTreeMap::new();
+    for batch_id in hits.iter().map(|hit| hit.batch_id).collect::<BTreeSet<_>>() {
+        match load_metadata(tenant.store.as_ref(), batch_id).await {
+            Ok(mut batch) => {
+                if let Some(index) = chain_index.as_ref().filter(|_| batch.mantle_batch_id.is_none()) {
+                    registered_elsewhere(&mut batch, index);
+                }
+                batches.insert(batch_id, batch);
+            }
+            Err(e) => eprintln!("✗ Lookup skipped batch {}: {}", batch_id, e),
+        }
+    }
+
+    let mut matches = Vec::with_capacity(hits.len());
+    for hit in hits {
+        let Some(batch) = batches.get(&hit.batch_id) else {
+            continue;
+        };
+        let (leaf_index, file) = hit.leaf.unzip();
+        matches.push(LookupMatch {
+            batch_id: hit.batch_id,
+            kind: hit.kind,
+            root: batch.root.clone(),
+            proof: file.as_ref().map(|name| format!("/batches/{}/proof/{}", hit.batch_id, name)),
+            leaf_index,
+            file,
+            anchored: batch.mantle_batch_id.is_some(),
+            mantle_batch_id: batch.mantle_batch_id,
+            tx_hash: batch.tx_hash.clone(),
+            anchor: anchor_queue
+                .as_ref()
+                .and_then(|queue| queue.status(&tenant.tenant, hit.batch_id))
+                .map(|job| serde_json::json!(job)),
+        });
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "hash": hex::encode(hash),
+        "matches": matches
+    }))
+}
+
+/// Fill in the first on-chain registration of the batch's root, if the
+/// chain indexer saw one the manifest does not record (e.g. from `scripts/anchor.js`)
+fn registered_elsewhere(batch: &mut BatchMetadata, index: &ChainIndex) {
+    let registrations = hex_to_hash(&batch.root)
+        .map_err(|e| e.to_string())
+        .and_then(|root| index.by_root(&root).map_err(|e| e.to_string()));
+    match registrations {
+        Ok(registrations) => {
+            if let Some(first) = registrations.into_iter().next() {
+                batch.mantle_batch_id = Some(first.mantle_batch_id);
+                batch.tx_hash = Some(first.tx_hash);
+                batch.block_number = Some(first.block_number);
+            }
+        }
+        Err(e) => eprintln!("✗ Chain index lookup for batch {}: {}", batch.local_batch_id, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::tests::server_config;
+    use crate::sessions::SessionRegistry;
+    use crate::store::{FsStore, StorageConfig};
+    use crate::tenants::Tenants;
+    use crate::FileEntry;
+    use actix_web::{test as actix_test, App};
+    use merkle_tree::{hash_data, leaf_cid, MerkleTree};
+
+    fn entry(name: &str, leaf: Hash, sha256: Option<Hash>) -> FileEntry {
+        FileEntry {
+            name: name.to_string(),
+            original_name: name.to_string(),
+            size: 1,
+            leaf: hex::encode(leaf),
+            cid: leaf_cid(&leaf),
+            chunks: None,
+            sha256: sha256.map(hex::encode),
+        }
+    }
+
+    async fn seal(store: &dyn BatchStore, files: Vec<FileEntry>) -> BatchMetadata {
+        let id = store.create_batch().await.unwrap();
+        let tree = MerkleTree::new(files.iter().map(|f| hex_to_hash(&f.leaf).unwrap()).collect());
+        let batch = BatchMetadata {
+            local_batch_id: id,
+            root: tree.root_hex(),
+            root_cid: tree.root_cid(),
+            file_count: files.len(),
+            suggested_meta_uri: String::new(),
+            registry_address: "0x0".to_string(),
+            hash_only: false,
+            manifest_cid: None,
+            created_at: None,
+            mantle_batch_id: None,
+            tx_hash: None,
+            block_number: None,
+            aggregate: None,
+            files,
+        };
+        store.seal(id, &serde_json::to_vec(&batch).unwrap()).await.unwrap();
+        batch
+    }
+
+    #[actix_web::test]
+    async fn test_lookup_by_leaf_file_and_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner: Arc<dyn BatchStore> = Arc::new(FsStore::open(dir.path().join("batches")).unwrap());
+        let index = Arc::new(LookupIndex::open(dir.path().join("lookup.db")).unwrap());
+        let store: Arc<dyn BatchStore> = Arc::new(IndexedStore::new(inner.clone(), index.clone()));
+
+        let (shared, other) = (hash_data(b"contract"), hash_data(b"invoice"));
+        let (chunk_root, whole_file) = (hash_data(b"chunk tree root"), hash_data(b"large file"));
+        // Sealed before the index existed, picked up by backfill
+        let old = seal(
+            inner.as_ref(),
+            vec![entry("contract.pdf", shared, None), entry("memo.txt", hash_data(b"memo"), None)],
+        )
+        .await;
+        let new = seal(
+            store.as_ref(),
+            vec![
+                entry("invoice.pdf", other, None),
+                entry("copy.pdf", shared, None),
+                entry("video.mp4", chunk_root, Some(whole_file)),
+            ],
+        )
+        .await;
+        assert_eq!(index.lookup(&shared).unwrap().len(), 1);
+        assert_eq!(backfill(&index, store.as_ref()).await.unwrap(), 1);
+        assert_eq!(backfill(&index, store.as_ref()).await.unwrap(), 0);
+
+        let config = server_config(
+            dir.path(),
+            StorageConfig::Filesystem {
+                root: dir.path().join("batches").display().to_string(),
+            },
+        );
+        let sessions = Arc::new(SessionRegistry::open(&config.session_file, 3600).unwrap());
+        let tenants = Tenants::single(Arc::new(config), store, index, sessions);
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(Arc::new(tenants)))
+                .service(lookup),
+        )
+        .await;
+        let get = |uri: String| actix_test::TestRequest::get().uri(&uri).to_request();
+
+        let found: serde_json::Value =
+            actix_test::call_and_read_body_json(&app, get(format!("/lookup/0x{}", hex::encode(shared)))).await;
+        let matches = found["matches"].as_array().unwrap();
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0]["batch_id"], old.local_batch_id);
+        assert_eq!(matches[1]["batch_id"], new.local_batch_id);
+        assert_eq!(matches[1]["match"], "leaf");
+        assert_eq!(matches[1]["leaf_index"], 1);
+        assert_eq!(matches[1]["proof"], format!("/batches/{}/proof/copy.pdf", new.local_batch_id));
+        assert_eq!(matches[1]["anchored"], false);
+
+        let found: serde_json::Value =
+            actix_test::call_and_read_body_json(&app, get(format!("/lookup/{}", hex::encode(whole_file)))).await;
+        assert_eq!(found["matches"][0]["match"], "file");
+        assert_eq!(found["matches"][0]["file"], "video.mp4");
+        assert_eq!(found["matches"][0]["leaf_index"], 2);
+
+        let found: serde_json::Value =
+            actix_test::call_and_read_body_json(&app, get(format!("/lookup/{}", new.root_cid))).await;
+        assert_eq!(found["matches"][0]["match"], "root");
+        assert_eq!(found["matches"][0]["root"], new.root);
+        assert!(found["matches"][0].get("proof").is_none());
+
+        let resp = actix_test::call_service(&app, get(format!("/lookup/{}", hex::encode(hash_data(b"absent"))))).await;
+        assert_eq!(resp.status(), 404);
+        let resp = actix_test::call_service(&app, get("/lookup/not-a-hash".to_string())).await;
+        assert_eq!(resp.status(), 400);
+    }
+}
@@ -1,17 +1,67 @@
+mod anchor;
+mod auth;
+mod batch_id;
+mod batches;
+mod chunk_store;
+mod config;
+mod filenames;
+mod graphql;
+mod indexer;
+mod leaves;
+mod listen;
+mod lookup;
+mod publish;
+mod quotas;
+mod receipts;
+mod rolling;
+mod sessions;
+mod store;
+mod tenants;
+mod upload;
+
 use actix_multipart::Multipart;
+use actix_web::middleware::{from_fn, Condition};
 use actix_web::{post, web, App, HttpResponse, HttpServer, Responder};
-use futures_util::TryStreamExt;
+use anchor::{AnchorMode, AnchorQueue, EthersChain};
+use auth::Authenticator;
+use chunk_store::ChunkStore;
+use clap::Parser;
+use config::ServerConfig;
+use indexer::{ChainIndex, EthersSource};
+use listen::{Args, ListenConfig};
 use mantle_config::MantleConfig;
-use merkle_tree::{hash_data, MerkleTree};
+use merkle_tree::{hex_to_hash, MerkleProof, MerkleTree};
+use quotas::{Limit, Quotas};
+use receipts::{Receipt, ReceiptSigner};
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::io::Write;
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::path::Path;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tenants::{Tenant, Tenants, Workspace, DEFAULT_TENANT};
+use upload::{StagedFile, Stager};
 
-/// Global batch ID counter
-static BATCH_ID_COUNTER: AtomicU64 = AtomicU64::new(1);
+/// Per-file entry in the batch manifest, in leaf order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileEntry {
+    /// Sanitized file name within the batch (as stored)
+    pub name: String,
+    /// File name as supplied by the client
+    #[serde(default)]
+    pub original_name: String,
+    /// File size in bytes
+    pub size: u64,
+    /// Leaf hash of the file (hex string)
+    pub leaf: String,
+    /// Leaf as a CIDv1 string (raw codec, or MerkSeal root codec for chunked files)
+    #[serde(default)]
+    pub cid: String,
+    /// Chunk hashes (hex) when the file is stored as content-defined chunks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<Vec<String>>,
+    /// SHA-256 of the whole file (hex) when it differs from `leaf` (multi-chunk files)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
 
 /// Batch metadata returned after upload
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,12 +70,51 @@ pub struct BatchMetadata {
     pub local_batch_id: u64,
     /// Merkle root hash (hex string)
     pub root: String,
+    /// Merkle root as a self-describing CIDv1 string
+    #[serde(default)]
+    pub root_cid: String,
     /// Number of files in this batch
     pub file_count: usize,
-    /// Suggested metadata URI (placeholder for now)
+    /// URI of the published canonical manifest, registered on-chain as `metaURI`
     pub suggested_meta_uri: String,
     /// Registry contract address (for anchoring)
     pub registry_address: String,
+    /// Built from client-supplied leaf hashes; the server holds no file contents
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hash_only: bool,
+    /// CIDv1 of the canonical manifest published at `suggested_meta_uri`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest_cid: Option<String>,
+    /// Unix timestamp (seconds) when the batch was sealed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
+    /// On-chain batch ID once the root has been anchored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mantle_batch_id: Option<u64>,
+    /// Hash of the anchoring transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<String>,
+    /// Block that included the anchoring transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_number: Option<u64>,
+    /// Proof from this batch's root to the anchored root when it was anchored as
+    /// part of an aggregate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aggregate: Option<AggregateInclusion>,
+    /// Files in this batch, in the order they were hashed into the tree
+    #[serde(default)]
+    pub files: Vec<FileEntry>,
+}
+
+/// Position of a batch root in an anchored root-of-roots tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggregateInclusion {
+    pub aggregate_id: u64,
+    /// Root of the aggregate tree, which is what was registered on-chain
+    pub root: String,
+    /// Proof with the batch root as its leaf
+    #[serde(flatten)]
+    pub proof: MerkleProof,
 }
 
 /// Upload response
@@ -33,100 +122,197 @@ pub struct BatchMetadata {
 pub struct UploadResponse {
     pub success: bool,
     pub batch: BatchMetadata,
+    /// Server-signed receipt for the sealed batch
+    pub receipt: Receipt,
 }
 
 /// POST /upload - Accept files, compute Merkle root, return batch metadata
+///
+/// Files are streamed to a staging directory while being hashed and only
+/// moved into the batch once every field has been received intact.
 #[post("/upload")]
+#[allow(clippy::too_many_arguments)]
 async fn upload_files(
-    mut payload: Multipart,
+    payload: Multipart,
     config: web::Data<Arc<MantleConfig>>,
+    tenant: Tenant,
+    chunk_store: web::Data<Option<ChunkStore>>,
+    signer: web::Data<Arc<ReceiptSigner>>,
+    anchor_queue: Option<web::Data<Arc<AnchorQueue>>>,
 ) -> impl Responder {
-    let batch_id = BATCH_ID_COUNTER.fetch_add(1, Ordering::SeqCst);
-    
-    // Create batch directory
-    let batch_dir = PathBuf::from(format!("batches/{}", batch_id));
-    if let Err(e) = fs::create_dir_all(&batch_dir) {
-        return HttpResponse::InternalServerError().json(serde_json::json!({
-            "success": false,
-            "error": format!("Failed to create batch directory: {}", e)
-        }));
+    let (server_config, store) = (&tenant.config, &tenant.store);
+    // Refuse tenants over their daily quotas before receiving anything
+    let byte_quota = match tenant.reserve(Limit::BytesPerDay, 0) {
+        Ok(quota) => quota,
+        Err(e) => return e.response(),
+    };
+    let chunks = chunk_store.get_ref().clone().filter(|_| server_config.chunking);
+    if server_config.rolling.is_some() {
+        return rolling::append(payload, &tenant, chunks, &signer, byte_quota).await;
     }
+    let batch_quota = match tenant.reserve(Limit::BatchesPerDay, 1) {
+        Ok(quota) => quota,
+        Err(e) => return e.response(),
+    };
     
-    let mut file_hashes = Vec::new();
-    let mut file_count = 0;
-    
-    // Process each uploaded file
-    while let Ok(Some(mut field)) = payload.try_next().await {
-        let filename = field
-            .content_disposition()
-            .and_then(|cd| cd.get_filename().map(|s| s.to_string()))
-            .unwrap_or_else(|| format!("file_{}", file_count));
-        
-        let filepath = batch_dir.join(&filename);
-        
-        // Read file data
-        let mut file_data = Vec::new();
-        while let Ok(Some(chunk)) = field.try_next().await {
-            file_data.extend_from_slice(&chunk);
-        }
-        
-        // Hash the file
-        let file_hash = hash_data(&file_data);
-        file_hashes.push(file_hash);
-        
-        // Save file to disk
-        if let Err(e) = fs::File::create(&filepath)
-            .and_then(|mut f| f.write_all(&file_data))
-        {
+    // Reserve a batch ID
+    let batch_id = match store.create_batch().await {
+        Ok(id) => id,
+        Err(e) => {
             return HttpResponse::InternalServerError().json(serde_json::json!({
                 "success": false,
-                "error": format!("Failed to save file {}: {}", filename, e)
+                "error": format!("Failed to create batch: {}", e)
             }));
         }
-        
-        file_count += 1;
+    };
+    
+    let mut stager = match Stager::create(
+        Path::new(&server_config.staging_dir),
+        batch_id,
+        server_config.limits,
+        chunks,
+    )
+    .await
+    {
+        Ok(stager) => stager.with_quota(byte_quota),
+        Err(e) => {
+            store.abort(batch_id).await.ok();
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "success": false,
+                "error": format!("Failed to create staging directory: {}", e)
+            }));
+        }
+    };
+    
+    // Receive each uploaded file; any stream error aborts the whole batch
+    let staged = match stager.stage_multipart(payload).await {
+        Ok(staged) => staged,
+        Err(e) => {
+            store.abort(batch_id).await.ok();
+            return e.response();
+        }
+    };
+    
+    let mut batch_metadata = new_batch_metadata(
+        batch_id,
+        staged.iter().map(StagedFile::entry).collect(),
+        &config,
+    );
+    
+    // Move staged files into the batch, then seal it by publishing its metadata
+    for file in &staged {
+        if let Some(path) = &file.path {
+            if let Err(e) = store.put_file_from_path(batch_id, &file.name, path).await {
+                store.abort(batch_id).await.ok();
+                return HttpResponse::InternalServerError().json(serde_json::json!({
+                    "success": false,
+                    "error": format!("Failed to save file {}: {}", file.name, e)
+                }));
+            }
+        }
     }
     
-    if file_hashes.is_empty() {
-        return HttpResponse::BadRequest().json(serde_json::json!({
+    if let Err(e) = publish::publish_manifest(&mut batch_metadata, server_config).await {
+        store.abort(batch_id).await.ok();
+        return HttpResponse::BadGateway().json(serde_json::json!({
             "success": false,
-            "error": "No files uploaded"
+            "error": e.to_string()
         }));
     }
     
-    // Build Merkle tree and compute root
-    let tree = MerkleTree::new(file_hashes);
-    let root_hex = tree.root_hex();
-    
-    // Create batch metadata
-    let batch_metadata = BatchMetadata {
-        local_batch_id: batch_id,
-        root: root_hex.clone(),
-        file_count,
-        suggested_meta_uri: format!("ipfs://placeholder-{}", batch_id),
-        registry_address: config.registry_address.clone(),
-    };
-    
-    // Save batch metadata to disk
-    let metadata_path = batch_dir.join("metadata.json");
-    if let Err(e) = fs::write(
-        &metadata_path,
-        serde_json::to_string_pretty(&batch_metadata).unwrap(),
-    ) {
-        eprintln!("Warning: Failed to save metadata: {}", e);
+    let manifest = serde_json::to_vec_pretty(&batch_metadata).unwrap();
+    if let Err(e) = store.seal(batch_id, &manifest).await {
+        store.abort(batch_id).await.ok();
+        return HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "error": format!("Failed to seal batch: {}", e)
+        }));
     }
+    stager.commit();
+    batch_quota.settle(unix_now());
     
     println!("✓ Batch {} uploaded:", batch_id);
-    println!("  Files: {}", file_count);
-    println!("  Root: {}", root_hex);
-    println!("  Saved to: {}", batch_dir.display());
+    println!("  Files: {}", batch_metadata.file_count);
+    println!("  Root: {}", batch_metadata.root);
+    println!("  Root CID: {}", batch_metadata.root_cid);
+    println!("  Manifest: {}", batch_metadata.suggested_meta_uri);
+    if server_config.chunking {
+        println!("  Chunks: {} new, {} deduplicated", stager.new_chunks, stager.reused_chunks);
+    }
+    println!("  Saved to: {}", store.location(batch_id));
+    
+    auto_anchor(&tenant, anchor_queue, batch_id).await;
     
     HttpResponse::Ok().json(UploadResponse {
         success: true,
+        receipt: signer.sealed(&tenant.tenant, &batch_metadata),
         batch: batch_metadata,
     })
 }
 
+/// Build the manifest of a batch about to be sealed, with files in leaf order
+fn new_batch_metadata(
+    batch_id: u64,
+    files: Vec<FileEntry>,
+    config: &MantleConfig,
+) -> BatchMetadata {
+    let leaves = files
+        .iter()
+        .map(|f| hex_to_hash(&f.leaf).expect("leaf hashes are computed by the server"))
+        .collect();
+    let tree = MerkleTree::new(leaves);
+    
+    BatchMetadata {
+        local_batch_id: batch_id,
+        root: tree.root_hex(),
+        root_cid: tree.root_cid(),
+        file_count: files.len(),
+        // Set once the manifest is published
+        suggested_meta_uri: String::new(),
+        registry_address: config.registry_address.clone(),
+        hash_only: false,
+        manifest_cid: None,
+        created_at: Some(unix_now()),
+        mantle_batch_id: None,
+        tx_hash: None,
+        block_number: None,
+        aggregate: None,
+        files,
+    }
+}
+
+/// Queue a freshly sealed batch for anchoring in `auto` mode
+///
+/// The worker records the result in the manifest; a failure to queue, or a
+/// used-up `anchors_per_day` quota, leaves the batch sealed and unanchored.
+async fn auto_anchor(workspace: &Workspace, anchor_queue: Option<web::Data<Arc<AnchorQueue>>>, batch_id: u64) {
+    let auto = workspace.config.anchor.as_ref().is_some_and(|a| a.mode == AnchorMode::Auto);
+    if let Some(queue) = anchor_queue.filter(|_| auto) {
+        let quota = match workspace.reserve(Limit::AnchorsPerDay, 1) {
+            Ok(quota) => quota,
+            Err(e) => {
+                println!("  ⚠️  Not queued for anchoring: {}", e);
+                return;
+            }
+        };
+        match queue.enqueue(workspace.store.as_ref(), &workspace.tenant, batch_id, unix_now()).await {
+            Ok(_) => {
+                quota.settle(unix_now());
+                println!("  ⚓ Queued for anchoring");
+            }
+            Err(e) => eprintln!("  ✗ Failed to queue for anchoring: {}", e),
+        }
+    }
+}
+
+/// Current time as Unix seconds
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
 /// Health check endpoint
 async fn health() -> impl Responder {
     HttpResponse::Ok().json(serde_json::json!({
@@ -137,6 +323,7 @@ async fn health() -> impl Responder {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    let args = Args::parse();
     println!("MerkSeal Server");
     
     // Load Mantle configuration
@@ -156,24 +343,283 @@ async fn main() -> std::io::Result<()> {
         }
     };
     
-    // Create batches directory
-    fs::create_dir_all("batches").ok();
+    let listen = match ListenConfig::load(args) {
+        Ok(listen) => listen,
+        Err(e) => {
+            eprintln!("✗ Failed to load listener config: {}", e);
+            std::process::exit(1);
+        }
+    };
     
-    let host = "127.0.0.1";
-    let port = 8080;
+    let server_config = match ServerConfig::from_env(&listen) {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("✗ Failed to load server config: {}", e);
+            std::process::exit(1);
+        }
+    };
+    
+    // Requests carry an API key or JWT naming their tenant when auth is configured
+    let authenticator = match &server_config.auth {
+        Some(auth_config) => match Authenticator::load(auth_config) {
+            Ok(authenticator) => {
+                println!("✓ Authentication required (API keys for {} tenant(s))", authenticator.tenants().len());
+                Some(web::Data::new(Arc::new(authenticator)))
+            }
+            Err(e) => {
+                eprintln!("✗ Failed to set up authentication: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => {
+            println!("⚠️  Authentication disabled: all batches belong to the {} tenant", DEFAULT_TENANT);
+            None
+        }
+    };
+    
+    println!("✓ Storage backend: {} (root {})", server_config.storage.kind(), listen.storage_dir);
+    if let Some(blob_config) = &server_config.blobs {
+        println!("✓ Deduplicated blob storage in {}", blob_config.dir);
+    }
+    
+    let anchoring = match &server_config.anchor {
+        Some(anchor_config) => {
+            let chain = match EthersChain::new(&config, &anchor_config.private_key) {
+                Ok(chain) => chain,
+                Err(e) => {
+                    eprintln!("✗ Failed to set up anchoring: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let queue = match AnchorQueue::open(&anchor_config.queue_path, anchor_config.queue) {
+                Ok(queue) => Arc::new(queue.with_publisher(server_config.publish.clone())),
+                Err(e) => {
+                    eprintln!("✗ Failed to open anchoring queue: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            println!(
+                "✓ Anchoring enabled ({:?}) from {:?}, queue in {}",
+                anchor_config.mode,
+                chain.signer(),
+                anchor_config.queue_path
+            );
+            Some((queue, chain, anchor_config.poll_interval))
+        }
+        None => None,
+    };
+    let anchor_queue = anchoring.as_ref().map(|(queue, _, _)| web::Data::new(queue.clone()));
+    
+    if let Some(rolling) = &server_config.rolling {
+        println!(
+            "✓ Rolling batches: sealed every {}s or at {} files",
+            rolling.interval.as_secs(),
+            rolling.max_files
+        );
+    }
+    
+    // Per-tenant limits, with daily usage kept across restarts
+    let quotas = match Quotas::open(&server_config.quotas) {
+        Ok(quotas) => {
+            if quotas.is_limited() {
+                let defaults: Vec<String> = server_config
+                    .quotas
+                    .defaults
+                    .iter()
+                    .map(|(limit, max)| format!("{}={}", limit.as_str(), max))
+                    .collect();
+                println!("✓ Tenant quotas enforced (defaults: {})", defaults.join(", "));
+            }
+            Arc::new(quotas)
+        }
+        Err(e) => {
+            eprintln!("✗ Failed to load quotas: {}", e);
+            std::process::exit(1);
+        }
+    };
+    tokio::spawn(quotas::run_saver(quotas.clone()));
+    
+    // Open the default tenant and every tenant with an API key now, others on first use
+    let tenants = Arc::new(Tenants::new(
+        config.clone(),
+        server_config.clone(),
+        anchor_queue.clone(),
+        quotas.clone(),
+    ));
+    let mut preload = vec![DEFAULT_TENANT.to_string()];
+    if let Some(authenticator) = &authenticator {
+        preload.extend(authenticator.tenants().into_iter().filter(|tenant| tenant != DEFAULT_TENANT));
+    }
+    for tenant in &preload {
+        if let Err(e) = tenants.get(tenant).await {
+            eprintln!("✗ Failed to open tenant {}: {}", tenant, e);
+            std::process::exit(1);
+        }
+    }
+    
+    // The worker writes anchors into whichever tenant queued the batch
+    if let Some((queue, chain, poll_interval)) = anchoring {
+        tokio::spawn(anchor::run_worker(queue, Arc::new(chain), tenants.clone(), poll_interval));
+    }
+    
+    // Mirror on-chain registrations, including ones made outside this server
+    let chain_index = match &server_config.indexer {
+        Some(indexer_config) => {
+            let opened = indexer::parse_registry(&config.registry_address).and_then(|registry| {
+                Ok((
+                    ChainIndex::open(&indexer_config.db_path, registry, indexer_config.settings)?,
+                    EthersSource::new(&config)?,
+                ))
+            });
+            let (index, source) = match opened {
+                Ok(opened) => opened,
+                Err(e) => {
+                    eprintln!("✗ Failed to set up chain indexer: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let index = Arc::new(index);
+            println!(
+                "✓ Chain indexer following {} from block {}, index in {}",
+                config.registry_address, indexer_config.settings.start_block, indexer_config.db_path
+            );
+            tokio::spawn(indexer::run_indexer(
+                index.clone(),
+                Arc::new(source),
+                indexer_config.poll_interval,
+            ));
+            Some(web::Data::new(index))
+        }
+        None => None,
+    };
+    
+    if server_config.chunking {
+        println!("✓ Content-defined chunking enabled (chunks in {})", server_config.chunk_dir);
+    }
+    
+    // Without chunking, an existing chunk directory is still opened to serve earlier chunked files
+    let chunk_store = if server_config.chunking || Path::new(&server_config.chunk_dir).is_dir() {
+        match ChunkStore::open(&server_config.chunk_dir) {
+            Ok(store) => Some(store),
+            Err(e) => {
+                eprintln!("✗ Failed to open chunk store: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    
+    println!("✓ Manifest publishing: {}", server_config.publish.kind());
+    
+    let signer = match ReceiptSigner::load_or_create(&server_config.receipts) {
+        Ok(signer) => {
+            println!("✓ Receipts signed by {}", signer.key_id());
+            Arc::new(signer)
+        }
+        Err(e) => {
+            eprintln!("✗ Failed to load receipt key: {}", e);
+            std::process::exit(1);
+        }
+    };
+    
+    let schema = web::Data::new(graphql::schema());
+    
+    let tls = match listen.tls.as_ref().map(|tls| tls.load()).transpose() {
+        Ok(tls) => tls,
+        Err(e) => {
+            eprintln!("✗ Failed to load TLS certificate: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(tls) = &listen.tls {
+        println!("✓ TLS enabled (certificate {})", tls.cert_file);
+    }
+    if !listen.cors_origins.is_empty() {
+        println!("✓ CORS allowed origins: {}", listen.cors_origins.join(", "));
+    }
     
-    println!("\n🚀 Server starting on http://{}:{}", host, port);
+    println!("\n🚀 Server starting on {}://{}:{}", listen.scheme(), listen.bind, listen.port);
     println!("   POST /upload - Upload files and get batch metadata");
+    println!("   GET  /batches - List sealed batches");
+    println!("   POST /leaves - Seal a hash-only batch from leaf hashes");
+    println!("   POST /batches - Open a batch session");
+    println!("   PUT  /batches/{{id}}/files/{{name}} - Add a file to an open batch");
+    println!("   POST /batches/{{id}}/seal - Seal an open batch");
+    println!("   GET  /batches/{{id}} - Batch manifest");
+    println!("   GET  /batches/{{id}}/files/{{name}} - Download a stored file");
+    println!("   GET  /batches/{{id}}/proof/{{name}} - Inclusion proof for a file");
+    println!("   POST /batches/{{id}}/anchor - Queue a batch for anchoring on Mantle");
+    println!("   GET  /aggregates/{{id}} - Aggregate root over several batches");
+    println!("   GET  /lookup/{{hash}} - Batches containing a root, leaf or file hash");
+    println!("   GET  /chain/batches - Batches registered on-chain (indexer)");
+    println!("   GET  /chain/batches/{{id}} - One on-chain batch and its local batches");
+    println!("   POST /graphql - GraphQL over on-chain and local batches (GET for GraphiQL)");
+    println!("   GET  /manifests/{{cid}} - Published canonical batch manifest");
+    println!("   GET  /.well-known/merkseal-keys - Receipt signing keys");
+    println!("   GET  /admin/usage - Per-tenant quotas and usage (admin scope)");
     println!("   GET  /health - Health check");
     println!("\nReady to accept uploads!");
     
-    HttpServer::new(move || {
-        App::new()
+    let listen_config = listen.clone();
+    let mut server = HttpServer::new(move || {
+        let mut app = App::new();
+        if let Some(authenticator) = &authenticator {
+            app = app.app_data(authenticator.clone());
+        }
+        if let Some(queue) = &anchor_queue {
+            app = app.app_data(queue.clone());
+        }
+        if let Some(index) = &chain_index {
+            app = app.app_data(index.clone());
+        }
+        // Registered last so CORS preflights skip authentication, and authentication
+        // runs before request limits so they see the tenant
+        app
+            .wrap(from_fn(quotas::limit_requests))
+            .wrap(from_fn(auth::authenticate))
+            .wrap(Condition::new(!listen_config.cors_origins.is_empty(), listen_config.cors()))
             .app_data(web::Data::new(config.clone()))
+            .app_data(web::Data::new(server_config.clone()))
+            .app_data(web::Data::new(chunk_store.clone()))
+            .app_data(web::Data::new(tenants.clone()))
+            .app_data(web::Data::new(signer.clone()))
+            .app_data(schema.clone())
+            .app_data(leaves::json_config(listen_config.max_payload_bytes))
             .service(upload_files)
+            .service(leaves::submit_leaves)
+            .service(sessions::create_session)
+            .service(sessions::put_file)
+            .service(sessions::seal_session)
+            .service(batches::list_batches)
+            .service(batches::get_batch)
+            .service(batches::download_file)
+            .service(batches::get_proof)
+            .service(anchor::anchor_batch)
+            .service(anchor::get_aggregate)
+            .service(receipts::get_keys)
+            .service(publish::get_manifest)
+            .service(lookup::lookup)
+            .service(indexer::list_chain_batches)
+            .service(indexer::get_chain_batch)
+            .service(graphql::graphql)
+            .service(graphql::graphiql)
+            .service(quotas::list_usage)
+            .service(quotas::get_usage)
             .route("/health", web::get().to(health))
-    })
-    .bind((host, port))?
-    .run()
-    .await
+    });
+    if let Some(workers) = listen.workers {
+        server = server.workers(workers);
+    }
+    let server = match tls {
+        Some(tls) => server.bind_rustls_0_23((listen.bind.as_str(), listen.port), tls)?,
+        None => server.bind((listen.bind.as_str(), listen.port))?,
+    };
+    server.run().await?;
+
+    // Keep the usage counted since the last background save
+    if let Err(e) = quotas.save().await {
+        eprintln!("✗ Failed to save usage counters: {}", e);
+    }
+    Ok(())
 }
@@ -0,0 +1,484 @@
+//! Publishing canonical batch manifests
+//!
+//! Before a batch is sealed, a canonical JSON manifest (file names, sizes,
+//! leaf hashes in tree order and the tree scheme) is built, its CIDv1 is
+//! computed and the bytes are published to the configured target. The
+//! resulting URI replaces `suggested_meta_uri`, so the `metaURI` registered
+//! on-chain points at a document that commits to the whole batch.
+
+use crate::config::ServerConfig;
+use crate::BatchMetadata;
+use actix_web::{get, web, HttpResponse};
+use merkle_tree::{hash_data, leaf_cid, Cid, Hash, MerkleTree};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Canonical manifest format version
+pub const MANIFEST_VERSION: u32 = 1;
+/// Tree scheme named in every manifest: SHA-256 leaves, `sha256(left || right)`
+/// parents, zero-padded to a power of two (the root CID codec `0x300001`)
+pub const TREE_SCHEME: &str = "merkseal-sha256-v1";
+
+/// Where canonical manifests are published
+#[derive(Clone)]
+pub enum PublishConfig {
+    /// Write `<dir>/<cid>.json`, served at `<base_url>/manifests/{cid}`
+    Local { dir: String, base_url: String },
+    /// Add to an IPFS node through its HTTP API (`/api/v0/add`)
+    Ipfs { api_url: String },
+    /// `PUT <base_url>/<cid>.json`, e.g. to a bucket with public reads
+    Http { base_url: String, token: Option<String> },
+}
+
+impl PublishConfig {
+    /// Short name for logs
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PublishConfig::Local { .. } => "local",
+            PublishConfig::Ipfs { .. } => "ipfs",
+            PublishConfig::Http { .. } => "http",
+        }
+    }
+}
+
+impl fmt::Debug for PublishConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublishConfig::Local { dir, base_url } => f
+                .debug_struct("Local")
+                .field("dir", dir)
+                .field("base_url", base_url)
+                .finish(),
+            PublishConfig::Ipfs { api_url } => f.debug_struct("Ipfs").field("api_url", api_url).finish(),
+            PublishConfig::Http { base_url, token } => f
+                .debug_struct("Http")
+                .field("base_url", base_url)
+                .field("token", &token.as_ref().map(|_| "<redacted>"))
+                .finish(),
+        }
+    }
+}
+
+/// Canonical manifest: compact JSON with fields in this order
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CanonicalManifest {
+    pub version: u32,
+    pub tree: String,
+    pub batch_id: u64,
+    pub root: String,
+    pub root_cid: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hash_only: bool,
+    /// Files in leaf order
+    pub files: Vec<CanonicalFile>,
+}
+
+/// One leaf of a canonical manifest
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CanonicalFile {
+    pub name: String,
+    pub size: u64,
+    pub leaf: String,
+    /// Chunk hashes when the leaf is a chunk-tree root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<Vec<String>>,
+}
+
+impl CanonicalManifest {
+    pub fn new(batch: &BatchMetadata) -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            tree: TREE_SCHEME.to_string(),
+            batch_id: batch.local_batch_id,
+            root: batch.root.clone(),
+            root_cid: batch.root_cid.clone(),
+            hash_only: batch.hash_only,
+            files: batch
+                .files
+                .iter()
+                .map(|f| CanonicalFile {
+                    name: f.name.clone(),
+                    size: f.size,
+                    leaf: f.leaf.clone(),
+                    chunks: f.chunks.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The exact bytes that are published and addressed by `cid`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("manifest serializes to JSON")
+    }
+}
+
+/// CIDv1 of manifest bytes (raw codec, sha2-256)
+///
+/// For manifests up to 256 KiB this is the CID IPFS assigns with
+/// `--cid-version=1 --raw-leaves`.
+pub fn manifest_cid(bytes: &[u8]) -> String {
+    leaf_cid(&hash_data(bytes))
+}
+
+/// Canonical manifest of an aggregate root over several batch roots
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AggregateManifest {
+    pub version: u32,
+    pub tree: String,
+    pub aggregate_id: u64,
+    pub root: String,
+    pub root_cid: String,
+    /// Member batch roots in leaf order
+    pub leaves: Vec<String>,
+}
+
+impl AggregateManifest {
+    pub fn new(aggregate_id: u64, leaves: &[Hash]) -> Self {
+        let tree = MerkleTree::new(leaves.to_vec());
+        Self {
+            version: MANIFEST_VERSION,
+            tree: TREE_SCHEME.to_string(),
+            aggregate_id,
+            root: tree.root_hex(),
+            root_cid: tree.root_cid(),
+            leaves: leaves.iter().map(hex::encode).collect(),
+        }
+    }
+
+    /// The exact bytes that are published and addressed by their CID
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("manifest serializes to JSON")
+    }
+}
+
+/// Publish the canonical manifest of a batch about to be sealed
+///
+/// Sets `manifest_cid` and points `suggested_meta_uri` at the published copy.
+pub async fn publish_manifest(batch: &mut BatchMetadata, server_config: &ServerConfig) -> Result<(), PublishError> {
+    let bytes = CanonicalManifest::new(batch).to_bytes();
+    let (cid, uri) = publish(bytes, &server_config.publish).await?;
+    batch.manifest_cid = Some(cid);
+    batch.suggested_meta_uri = uri;
+    Ok(())
+}
+
+/// Publish manifest bytes to the configured target, returning their CID and URI
+pub async fn publish(bytes: Vec<u8>, config: &PublishConfig) -> Result<(String, String), PublishError> {
+    let cid = manifest_cid(&bytes);
+    let uri = match config {
+        PublishConfig::Local { dir, base_url } => {
+            write_manifest(Path::new(dir), &cid, &bytes).map_err(PublishError::Io)?;
+            format!("{}/manifests/{}", base_url, cid)
+        }
+        PublishConfig::Ipfs { api_url } => format!("ipfs://{}", ipfs_add(api_url, &cid, bytes).await?),
+        PublishConfig::Http { base_url, token } => {
+            let url = format!("{}/{}.json", base_url, cid);
+            let mut request = reqwest::Client::new()
+                .put(&url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(bytes);
+            if let Some(token) = token {
+                request = request.bearer_auth(token);
+            }
+            request.send().await?.error_for_status()?;
+            url
+        }
+    };
+    Ok((cid, uri))
+}
+
+/// Response of the IPFS `add` call
+#[derive(Deserialize)]
+struct IpfsAdded {
+    #[serde(rename = "Hash")]
+    hash: String,
+}
+
+/// Add and pin manifest bytes on an IPFS node, returning the CID it assigned
+async fn ipfs_add(api_url: &str, name: &str, bytes: Vec<u8>) -> Result<String, PublishError> {
+    let part = reqwest::multipart::Part::bytes(bytes).file_name(format!("{}.json", name));
+    let added: IpfsAdded = reqwest::Client::new()
+        .post(format!("{}/api/v0/add", api_url))
+        .query(&[("cid-version", "1"), ("raw-leaves", "true"), ("pin", "true")])
+        .multipart(reqwest::multipart::Form::new().part("file", part))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(added.hash)
+}
+
+/// Write `<dir>/<cid>.json` atomically (write temp file, fsync, rename)
+fn write_manifest(dir: &Path, cid: &str, bytes: &[u8]) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = manifest_path(dir, cid);
+    let tmp = path.with_extension("json.tmp");
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp, &path)
+}
+
+fn manifest_path(dir: &Path, cid: &str) -> PathBuf {
+    dir.join(format!("{}.json", cid))
+}
+
+/// GET /manifests/{cid} - A canonical manifest published to the local directory
+#[get("/manifests/{cid}")]
+async fn get_manifest(path: web::Path<String>, server_config: web::Data<Arc<ServerConfig>>) -> HttpResponse {
+    let cid = path.into_inner();
+    let not_found = || {
+        HttpResponse::NotFound().json(serde_json::json!({
+            "success": false,
+            "error": format!("Manifest {} not found", cid)
+        }))
+    };
+    // Only well-formed CIDs reach the filesystem
+    if cid.parse::<Cid>().is_err() {
+        return not_found();
+    }
+    let PublishConfig::Local { dir, .. } = &server_config.publish else {
+        return not_found();
+    };
+    match fs::read(manifest_path(Path::new(dir), &cid)) {
+        Ok(bytes) => HttpResponse::Ok()
+            .content_type("application/json")
+            .insert_header(("Cache-Control", "public, max-age=31536000, immutable"))
+            .body(bytes),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => not_found(),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "error": format!("Failed to read manifest {}: {}", cid, e)
+        })),
+    }
+}
+
+/// Manifest publishing failures
+#[derive(Debug)]
+pub enum PublishError {
+    /// Writing to the local manifest directory failed
+    Io(io::Error),
+    /// The IPFS node or HTTP target refused or could not be reached
+    Http(reqwest::Error),
+}
+
+impl fmt::Display for PublishError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublishError::Io(e) => write!(f, "Failed to write manifest: {}", e),
+            PublishError::Http(e) => write!(f, "Failed to publish manifest: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PublishError {}
+
+impl From<reqwest::Error> for PublishError {
+    fn from(e: reqwest::Error) -> Self {
+        PublishError::Http(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth;
+    use crate::store::StorageConfig;
+    use crate::FileEntry;
+    use actix_web::{put, test as actix_test, App, HttpServer};
+    use std::sync::Mutex;
+
+    fn server_config(dir: &Path, publish: PublishConfig) -> ServerConfig {
+        let storage = StorageConfig::Filesystem {
+            root: dir.join("batches").display().to_string(),
+        };
+        ServerConfig {
+            publish,
+            ..auth::tests::server_config(dir, storage)
+        }
+    }
+
+    fn batch() -> BatchMetadata {
+        let leaves = [hash_data(b"one"), hash_data(b"two")];
+        let tree = MerkleTree::new(leaves.to_vec());
+        BatchMetadata {
+            local_batch_id: 4,
+            root: tree.root_hex(),
+            root_cid: tree.root_cid(),
+            file_count: 2,
+            suggested_meta_uri: String::new(),
+            registry_address: "0x0".to_string(),
+            hash_only: false,
+            manifest_cid: None,
+            created_at: Some(1),
+            mantle_batch_id: None,
+            tx_hash: None,
+            block_number: None,
+            aggregate: None,
+            files: ["a.txt", "b.txt"]
+                .iter()
+                .zip(leaves)
+                .map(|(name, leaf)| FileEntry {
+                    name: name.to_string(),
+                    original_name: name.to_string(),
+                    size: 3,
+                    leaf: hex::encode(leaf),
+                    cid: leaf_cid(&leaf),
+                    chunks: None,
+                    sha256: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_local_manifest_is_served_by_cid() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = PublishConfig::Local {
+            dir: dir.path().join("manifests").display().to_string(),
+            base_url: "https://seal.example".to_string(),
+        };
+        let config = Arc::new(server_config(dir.path(), local));
+
+        let mut batch = batch();
+        publish_manifest(&mut batch, &config).await.unwrap();
+        let cid = batch.manifest_cid.clone().unwrap();
+        assert_eq!(batch.suggested_meta_uri, format!("https://seal.example/manifests/{}", cid));
+
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(config.clone()))
+                .service(get_manifest),
+        )
+        .await;
+        let body = actix_test::call_and_read_body(
+            &app,
+            actix_test::TestRequest::get().uri(&format!("/manifests/{}", cid)).to_request(),
+        )
+        .await;
+        assert_eq!(manifest_cid(&body), cid);
+        let manifest: CanonicalManifest = serde_json::from_slice(&body).unwrap();
+        assert_eq!(manifest.tree, TREE_SCHEME);
+        assert_eq!(manifest.root, batch.root);
+        assert_eq!(manifest.files[1].leaf, batch.files[1].leaf);
+        // Same batch, same bytes
+        assert_eq!(CanonicalManifest::new(&batch).to_bytes(), body.to_vec());
+
+        // Aggregate manifests are published and served the same way
+        let leaves = [hash_data(b"batch one"), hash_data(b"batch two")];
+        let (aggregate_cid, uri) = publish(AggregateManifest::new(3, &leaves).to_bytes(), &config.publish)
+            .await
+            .unwrap();
+        assert_eq!(uri, format!("https://seal.example/manifests/{}", aggregate_cid));
+        let body = actix_test::call_and_read_body(
+            &app,
+            actix_test::TestRequest::get().uri(&format!("/manifests/{}", aggregate_cid)).to_request(),
+        )
+        .await;
+        let aggregate: AggregateManifest = serde_json::from_slice(&body).unwrap();
+        assert_eq!(aggregate.root, MerkleTree::new(leaves.to_vec()).root_hex());
+        assert_eq!(aggregate.leaves[1], hex::encode(leaves[1]));
+
+        for uri in ["/manifests/not-a-cid", &format!("/manifests/{}", batch.root_cid)] {
+            let resp = actix_test::call_service(&app, actix_test::TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(resp.status(), 404);
+        }
+    }
+
+    /// Stand-in for an IPFS node's `add` and an HTTP bucket's `PUT`
+    #[derive(Default)]
+    struct Target {
+        received: Mutex<Vec<Received>>,
+    }
+
+    struct Received {
+        name: String,
+        auth: Option<String>,
+        body: Vec<u8>,
+    }
+
+    #[actix_web::post("/api/v0/add")]
+    async fn mock_ipfs_add(target: web::Data<Target>, body: web::Bytes) -> HttpResponse {
+        target.received.lock().unwrap().push(Received {
+            name: "add".to_string(),
+            auth: None,
+            body: body.to_vec(),
+        });
+        HttpResponse::Ok().json(serde_json::json!({ "Name": "manifest.json", "Hash": "bafkmock", "Size": "1" }))
+    }
+
+    #[put("/bucket/{name}")]
+    async fn mock_put(
+        target: web::Data<Target>,
+        name: web::Path<String>,
+        req: actix_web::HttpRequest,
+        body: web::Bytes,
+    ) -> HttpResponse {
+        let auth = req
+            .headers()
+            .get("authorization")
+            .map(|v| v.to_str().unwrap().to_string());
+        target.received.lock().unwrap().push(Received {
+            name: name.into_inner(),
+            auth,
+            body: body.to_vec(),
+        });
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn test_ipfs_and_http_publishers() {
+        let target = web::Data::new(Target::default());
+        let data = target.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(data.clone())
+                .service(mock_ipfs_add)
+                .service(mock_put)
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let base = format!("http://{}", server.addrs()[0]);
+        let handle = server.run();
+        let server_handle = handle.handle();
+        tokio::spawn(handle);
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut batch = batch();
+        let ipfs = server_config(dir.path(), PublishConfig::Ipfs { api_url: base.clone() });
+        publish_manifest(&mut batch, &ipfs).await.unwrap();
+        assert_eq!(batch.suggested_meta_uri, "ipfs://bafkmock");
+        let cid = batch.manifest_cid.clone().unwrap();
+
+        let http = server_config(
+            dir.path(),
+            PublishConfig::Http {
+                base_url: format!("{}/bucket", base),
+                token: Some("secret".to_string()),
+            },
+        );
+        publish_manifest(&mut batch, &http).await.unwrap();
+        assert_eq!(batch.suggested_meta_uri, format!("{}/bucket/{}.json", base, cid));
+
+        {
+            let received = target.received.lock().unwrap();
+            let manifest = CanonicalManifest::new(&batch).to_bytes();
+            // The multipart body carries the manifest bytes unchanged
+            assert!(received[0].body.windows(manifest.len()).any(|w| w == manifest.as_slice()));
+            assert_eq!(received[1].name, format!("{}.json", cid));
+            assert_eq!(received[1].auth.as_deref(), Some("Bearer secret"));
+            assert_eq!(received[1].body, manifest);
+        }
+
+        // An unreachable target fails the publish instead of falling back
+        let down = server_config(dir.path(), PublishConfig::Ipfs { api_url: "http://127.0.0.1:1".into() });
+        assert!(matches!(publish_manifest(&mut batch, &down).await, Err(PublishError::Http(_))));
+        server_handle.stop(false).await;
+    }
+}
@@ -0,0 +1,674 @@
+//! Per-tenant quotas and usage counters
+//!
+//! Every tenant may be limited in requests per minute and in uploaded bytes,
+//! new batches and anchoring requests per UTC day. Limits come from server-wide
+//! defaults with per-tenant overrides; a request over a limit gets
+//! `429 Too Many Requests` with `Retry-After` set to the end of the window.
+//!
+//! Work limited by a daily quota reserves its amount when it is admitted and
+//! counts it once it is done, so concurrent requests cannot overshoot a limit
+//! and failed ones use nothing. Daily counters are saved in the background,
+//! so a restart does not reset them, and are reported by `GET /admin/usage`.
+
+use crate::auth::{self, Principal};
+use crate::tenants::{is_valid_tenant, Tenants, DEFAULT_TENANT};
+use crate::unix_now;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use actix_web::{get, web, HttpMessage, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// How long changed counters wait before they are saved, so bursts share a write
+const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// A limited quantity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Limit {
+    /// Authenticated requests in the current minute
+    RequestsPerMinute,
+    /// File bytes uploaded today
+    BytesPerDay,
+    /// Batches created today (uploads, hash-only batches, sessions, rolling batches)
+    BatchesPerDay,
+    /// Batches queued for anchoring today
+    AnchorsPerDay,
+}
+
+impl Limit {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Limit::RequestsPerMinute => "requests_per_minute",
+            Limit::BytesPerDay => "bytes_per_day",
+            Limit::BatchesPerDay => "batches_per_day",
+            Limit::AnchorsPerDay => "anchors_per_day",
+        }
+    }
+
+    /// Length of the window the limit applies to
+    fn window(&self) -> u64 {
+        match self {
+            Limit::RequestsPerMinute => 60,
+            _ => SECS_PER_DAY,
+        }
+    }
+}
+
+/// Maximum per limit; absent limits are unlimited
+pub type Limits = BTreeMap<Limit, u64>;
+
+/// Quota settings
+#[derive(Debug, Clone, Default)]
+pub struct QuotaConfig {
+    /// Limits of tenants without an override
+    pub defaults: Limits,
+    /// JSON file of per-tenant overrides (`{"acme": {"bytes_per_day": 1000}}`, 0 lifts a limit)
+    pub overrides_file: Option<String>,
+    /// File holding the usage counters (`None` keeps them in memory)
+    pub usage_file: Option<String>,
+}
+
+/// Counted usage of one tenant
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Counters {
+    pub requests: u64,
+    pub bytes: u64,
+    pub batches: u64,
+    pub anchors: u64,
+}
+
+impl Counters {
+    fn get(&self, limit: Limit) -> u64 {
+        match limit {
+            Limit::RequestsPerMinute => self.requests,
+            Limit::BytesPerDay => self.bytes,
+            Limit::BatchesPerDay => self.batches,
+            Limit::AnchorsPerDay => self.anchors,
+        }
+    }
+
+    fn counter(&mut self, limit: Limit) -> &mut u64 {
+        match limit {
+            Limit::RequestsPerMinute => &mut self.requests,
+            Limit::BytesPerDay => &mut self.bytes,
+            Limit::BatchesPerDay => &mut self.batches,
+            Limit::AnchorsPerDay => &mut self.anchors,
+        }
+    }
+
+    fn add(&mut self, limit: Limit, amount: u64) {
+        let counter = self.counter(limit);
+        *counter = counter.saturating_add(amount);
+    }
+
+    fn sub(&mut self, limit: Limit, amount: u64) {
+        let counter = self.counter(limit);
+        *counter = counter.saturating_sub(amount);
+    }
+}
+
+/// Stored usage of one tenant
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Usage {
+    /// UTC day (days since the epoch) that `today` counts
+    day: u64,
+    today: Counters,
+    total: Counters,
+    /// Minute (since the epoch) that `minute_requests` counts
+    #[serde(skip)]
+    minute: u64,
+    #[serde(skip)]
+    minute_requests: u64,
+    /// Held today by requests still in progress
+    #[serde(skip)]
+    reserved: Counters,
+}
+
+impl Usage {
+    /// Start new windows once the current ones are over
+    fn roll(&mut self, now: u64) {
+        if self.day != now / SECS_PER_DAY {
+            self.day = now / SECS_PER_DAY;
+            self.today = Counters::default();
+            self.reserved = Counters::default();
+        }
+        if self.minute != now / 60 {
+            self.minute = now / 60;
+            self.minute_requests = 0;
+        }
+    }
+
+    fn used(&self, limit: Limit) -> u64 {
+        match limit {
+            Limit::RequestsPerMinute => self.minute_requests,
+            other => self.today.get(other).saturating_add(self.reserved.get(other)),
+        }
+    }
+}
+
+/// A tenant's limits and usage, as reported by `GET /admin/usage`
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+    pub tenant: String,
+    pub limits: Limits,
+    pub requests_this_minute: u64,
+    pub today: Counters,
+    /// When the daily counters reset
+    pub resets_at: u64,
+    pub total: Counters,
+}
+
+/// Usage held against a daily limit by a request in progress
+///
+/// [`Reservation::settle`] counts it once the work is done; dropping an
+/// unsettled reservation gives it back.
+pub struct Reservation {
+    quotas: Arc<Quotas>,
+    tenant: String,
+    limit: Limit,
+    /// Amount to count when settled
+    amount: u64,
+    /// UTC day the held part of `amount` is reserved in
+    day: u64,
+    /// Part of `amount` still reserved in `day`
+    held: u64,
+}
+
+impl Reservation {
+    /// Reserve `more`, refusing if the tenant would pass its limit
+    pub fn grow(&mut self, more: u64, now: u64) -> Result<(), QuotaExceeded> {
+        let quotas = self.quotas.clone();
+        let mut usage = quotas.usage.lock().unwrap();
+        let entry = usage.entry(self.tenant.clone()).or_default();
+        entry.roll(now);
+        if entry.day != self.day {
+            // Reservations end with their day
+            (self.day, self.held) = (entry.day, 0);
+        }
+        quotas.allows(&self.tenant, entry, self.limit, more, now)?;
+        entry.reserved.add(self.limit, more);
+        self.held += more;
+        self.amount += more;
+        Ok(())
+    }
+
+    /// Count the reserved amount as used
+    pub fn settle(mut self, now: u64) {
+        let amount = std::mem::take(&mut self.amount);
+        let mut usage = self.quotas.usage.lock().unwrap();
+        let entry = usage.entry(self.tenant.clone()).or_default();
+        entry.roll(now);
+        if entry.day == self.day {
+            entry.reserved.sub(self.limit, self.held);
+        }
+        self.held = 0;
+        entry.today.add(self.limit, amount);
+        entry.total.add(self.limit, amount);
+        self.quotas.changed.notify_one();
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if self.held == 0 {
+            return;
+        }
+        let mut usage = self.quotas.usage.lock().unwrap();
+        if let Some(entry) = usage.get_mut(&self.tenant).filter(|entry| entry.day == self.day) {
+            entry.reserved.sub(self.limit, self.held);
+        }
+    }
+}
+
+/// Limits and usage counters of every tenant
+pub struct Quotas {
+    path: Option<PathBuf>,
+    defaults: Limits,
+    overrides: HashMap<String, Limits>,
+    usage: Mutex<BTreeMap<String, Usage>>,
+    /// Counters changed since the last save
+    changed: Notify,
+    /// Serializes saves, so a later snapshot is never overwritten by an earlier one
+    saving: tokio::sync::Mutex<()>,
+}
+
+impl Quotas {
+    /// Load the override and usage files (missing usage files start empty)
+    pub fn open(config: &QuotaConfig) -> io::Result<Self> {
+        let overrides = match &config.overrides_file {
+            Some(path) => {
+                let overrides: HashMap<String, Limits> = serde_json::from_slice(&fs::read(path)?)
+                    .map_err(|e| invalid_data(format!("Invalid quota file {}: {}", path, e)))?;
+                if let Some(tenant) = overrides.keys().find(|tenant| !is_valid_tenant(tenant)) {
+                    return Err(invalid_data(format!("Invalid tenant {:?} in quota file {}", tenant, path)));
+                }
+                overrides
+            }
+            None => HashMap::new(),
+        };
+
+        let path = config.usage_file.as_ref().map(PathBuf::from);
+        let usage = match &path {
+            Some(path) => match fs::read(path) {
+                Ok(data) => serde_json::from_slice(&data)
+                    .map_err(|e| invalid_data(format!("Corrupt usage file {}: {}", path.display(), e)))?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+                Err(e) => return Err(e),
+            },
+            None => BTreeMap::new(),
+        };
+
+        Ok(Self {
+            path,
+            defaults: config.defaults.clone(),
+            overrides,
+            usage: Mutex::new(usage),
+            changed: Notify::new(),
+            saving: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// Effective limits of a tenant
+    pub fn limits(&self, tenant: &str) -> Limits {
+        let mut limits = self.defaults.clone();
+        for (limit, max) in self.overrides.get(tenant).into_iter().flatten() {
+            match max {
+                0 => limits.remove(limit),
+                max => limits.insert(*limit, *max),
+            };
+        }
+        limits
+    }
+
+    /// Whether any tenant is limited
+    pub fn is_limited(&self) -> bool {
+        !self.defaults.is_empty() || self.overrides.values().any(|limits| limits.values().any(|max| *max > 0))
+    }
+
+    /// Count a request, refusing it if the tenant is over `requests_per_minute`
+    pub fn request(&self, tenant: &str, now: u64) -> Result<(), QuotaExceeded> {
+        let mut usage = self.usage.lock().unwrap();
+        let entry = usage.entry(tenant.to_string()).or_default();
+        entry.roll(now);
+        self.allows(tenant, entry, Limit::RequestsPerMinute, 1, now)?;
+        entry.minute_requests += 1;
+        entry.today.requests += 1;
+        entry.total.requests += 1;
+        self.changed.notify_one();
+        Ok(())
+    }
+
+    /// Reserve `amount` of a daily limit, refusing if the tenant has used it up
+    ///
+    /// Reserved amounts count toward the limit until they are settled or dropped.
+    pub fn reserve(self: &Arc<Self>, tenant: &str, limit: Limit, amount: u64, now: u64) -> Result<Reservation, QuotaExceeded> {
+        let mut usage = self.usage.lock().unwrap();
+        let entry = usage.entry(tenant.to_string()).or_default();
+        entry.roll(now);
+        self.allows(tenant, entry, limit, amount, now)?;
+        entry.reserved.add(limit, amount);
+        Ok(Reservation {
+            quotas: self.clone(),
+            tenant: tenant.to_string(),
+            limit,
+            amount,
+            day: entry.day,
+            held: amount,
+        })
+    }
+
+    /// Usage of one tenant
+    pub fn report(&self, tenant: &str, now: u64) -> UsageReport {
+        let mut usage = self.usage.lock().unwrap();
+        let entry = usage.entry(tenant.to_string()).or_default();
+        entry.roll(now);
+        self.report_of(tenant, entry, now)
+    }
+
+    /// Usage of every tenant that has used the server or has an override
+    pub fn reports(&self, now: u64) -> Vec<UsageReport> {
+        let mut usage = self.usage.lock().unwrap();
+        for tenant in self.overrides.keys() {
+            usage.entry(tenant.clone()).or_default();
+        }
+        usage
+            .iter_mut()
+            .map(|(tenant, entry)| {
+                entry.roll(now);
+                self.report_of(tenant, entry, now)
+            })
+            .collect()
+    }
+
+    fn report_of(&self, tenant: &str, usage: &Usage, now: u64) -> UsageReport {
+        UsageReport {
+            tenant: tenant.to_string(),
+            limits: self.limits(tenant),
+            requests_this_minute: usage.minute_requests,
+            today: usage.today,
+            resets_at: window_end(now, SECS_PER_DAY),
+            total: usage.total,
+        }
+    }
+
+    /// Whether `amount` more fits; a limit that is already reached admits nothing
+    fn allows(&self, tenant: &str, usage: &Usage, limit: Limit, amount: u64, now: u64) -> Result<(), QuotaExceeded> {
+        match self.limits(tenant).get(&limit) {
+            Some(&max) if usage.used(limit) >= max || usage.used(limit).saturating_add(amount) > max => {
+                Err(QuotaExceeded::new(tenant, limit, max, now))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Persist the counters atomically (write temp file, fsync, rename)
+    ///
+    /// The snapshot is taken under the lock; the file is written off the
+    /// async executor.
+    pub async fn save(&self) -> io::Result<()> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+        let _saving = self.saving.lock().await;
+        let data = serde_json::to_vec_pretty(&*self.usage.lock().unwrap()).map_err(io::Error::other)?;
+        tokio::task::spawn_blocking(move || {
+            let mut name = path.file_name().unwrap_or_default().to_os_string();
+            name.push(".tmp");
+            let tmp = path.with_file_name(name);
+
+            let mut file = fs::File::create(&tmp)?;
+            file.write_all(&data)?;
+            file.sync_all()?;
+            fs::rename(&tmp, &path)
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+}
+
+/// Save the usage counters whenever they change
+///
+/// Changes within `SAVE_DELAY` of each other share one write. A failed save
+/// is logged and retried with the next change.
+pub async fn run_saver(quotas: Arc<Quotas>) {
+    loop {
+        quotas.changed.notified().await;
+        tokio::time::sleep(SAVE_DELAY).await;
+        if let Err(e) = quotas.save().await {
+            eprintln!("✗ Failed to save usage counters: {}", e);
+        }
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Start of the next window of `window` seconds
+fn window_end(now: u64, window: u64) -> u64 {
+    (now / window + 1) * window
+}
+
+/// A request refused because its tenant reached a limit (429)
+#[derive(Debug, Clone)]
+pub struct QuotaExceeded {
+    pub tenant: String,
+    pub limit: Limit,
+    pub max: u64,
+    /// Seconds until the window resets
+    pub retry_after: u64,
+}
+
+impl QuotaExceeded {
+    fn new(tenant: &str, limit: Limit, max: u64, now: u64) -> Self {
+        Self {
+            tenant: tenant.to_string(),
+            limit,
+            max,
+            retry_after: window_end(now, limit.window()) - now,
+        }
+    }
+
+    /// `429 Too Many Requests` with `Retry-After`
+    pub fn response(&self) -> HttpResponse {
+        HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, self.retry_after.to_string()))
+            .json(serde_json::json!({
+                "success": false,
+                "error": self.to_string(),
+                "quota": self.limit,
+                "limit": self.max,
+                "retry_after": self.retry_after
+            }))
+    }
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Tenant {} reached its {} quota of {}; retry in {}s",
+            self.tenant,
+            self.limit.as_str(),
+            self.max,
+            self.retry_after
+        )
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+/// Middleware that counts requests and enforces `requests_per_minute`
+///
+/// Runs after authentication; public endpoints are not counted. Does nothing
+/// when no tenant registry is registered.
+pub async fn limit_requests<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    let tenants = req.app_data::<web::Data<Arc<Tenants>>>().cloned();
+    let (Some(tenants), Some(_)) = (tenants, auth::required_scope(req.method(), req.path())) else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+
+    let tenant = req
+        .extensions()
+        .get::<Principal>()
+        .map_or_else(|| DEFAULT_TENANT.to_string(), |principal| principal.tenant.clone());
+    match tenants.quotas().request(&tenant, unix_now()) {
+        Ok(()) => next.call(req).await.map(ServiceResponse::map_into_left_body),
+        Err(e) => Ok(req.into_response(e.response()).map_into_right_body()),
+    }
+}
+
+/// GET /admin/usage - Limits and usage of every tenant
+#[get("/admin/usage")]
+async fn list_usage(tenants: web::Data<Arc<Tenants>>) -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "tenants": tenants.quotas().reports(unix_now())
+    }))
+}
+
+/// GET /admin/usage/{tenant} - Limits and usage of one tenant
+#[get("/admin/usage/{tenant}")]
+async fn get_usage(path: web::Path<String>, tenants: web::Data<Arc<Tenants>>) -> HttpResponse {
+    let tenant = path.into_inner();
+    if !is_valid_tenant(&tenant) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "error": format!("Invalid tenant ID {:?}", tenant)
+        }));
+    }
+    HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "usage": tenants.quotas().report(&tenant, unix_now())
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::tests::server_config;
+    use crate::chunk_store::ChunkStore;
+    use crate::lookup::LookupIndex;
+    use crate::sessions::{self, SessionRegistry};
+    use crate::store::{BatchStore, FsStore, StorageConfig};
+    use actix_web::middleware::from_fn;
+    use actix_web::{test as actix_test, App};
+
+    #[tokio::test]
+    async fn test_windows_overrides_and_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let overrides = dir.path().join("quotas.json");
+        std::fs::write(
+            &overrides,
+            r#"{ "acme": { "bytes_per_day": 100, "requests_per_minute": 0 } }"#,
+        )
+        .unwrap();
+        let config = QuotaConfig {
+            defaults: Limits::from([(Limit::RequestsPerMinute, 2), (Limit::AnchorsPerDay, 1)]),
+            overrides_file: Some(overrides.display().to_string()),
+            usage_file: Some(dir.path().join("usage.json").display().to_string()),
+        };
+        let quotas = Arc::new(Quotas::open(&config).unwrap());
+        let day = 20_000 * SECS_PER_DAY;
+
+        // Requests reset with the minute; acme's override lifts the limit
+        quotas.request("globex", day + 10).unwrap();
+        quotas.request("globex", day + 20).unwrap();
+        let refused = quotas.request("globex", day + 30).unwrap_err();
+        assert_eq!((refused.limit, refused.max, refused.retry_after), (Limit::RequestsPerMinute, 2, 30));
+        quotas.request("globex", day + 60).unwrap();
+        for _ in 0..5 {
+            quotas.request("acme", day + 10).unwrap();
+        }
+
+        // Reserved bytes count toward the limit while an upload is in progress
+        let mut upload = quotas.reserve("acme", Limit::BytesPerDay, 0, day).unwrap();
+        upload.grow(60, day + 100).unwrap();
+        let mut concurrent = quotas.reserve("acme", Limit::BytesPerDay, 0, day + 100).unwrap();
+        let refused = concurrent.grow(41, day + 100).unwrap_err();
+        assert_eq!((refused.limit, refused.max), (Limit::BytesPerDay, 100));
+        concurrent.grow(40, day + 100).unwrap();
+        // A dropped reservation gives its bytes back; a settled one counts them
+        drop(concurrent);
+        upload.settle(day + 100);
+        assert_eq!(quotas.report("acme", day + 100).today.bytes, 60);
+        quotas.reserve("acme", Limit::BytesPerDay, 40, day + 200).unwrap().settle(day + 200);
+        let refused = quotas.reserve("acme", Limit::BytesPerDay, 0, day + 300).err().unwrap();
+        assert_eq!(refused.retry_after, SECS_PER_DAY - 300);
+        // Unlimited tenants are still counted
+        quotas.reserve("globex", Limit::BytesPerDay, 1 << 40, day).unwrap().settle(day);
+
+        let anchor = quotas.reserve("acme", Limit::AnchorsPerDay, 1, day).unwrap();
+        assert!(quotas.reserve("acme", Limit::AnchorsPerDay, 1, day).is_err());
+        anchor.settle(day);
+        assert!(quotas.reserve("acme", Limit::AnchorsPerDay, 1, day).is_err());
+        quotas.save().await.unwrap();
+        // Daily limits reset at UTC midnight
+        assert!(quotas.reserve("acme", Limit::AnchorsPerDay, 1, day + SECS_PER_DAY).is_ok());
+
+        // Daily counters, requests included, survive a restart; the per-minute window does not
+        let quotas = Quotas::open(&config).unwrap();
+        let report = quotas.report("acme", day + 400);
+        assert_eq!(report.limits, Limits::from([(Limit::AnchorsPerDay, 1), (Limit::BytesPerDay, 100)]));
+        assert_eq!(report.today.bytes, 100);
+        assert_eq!(report.today.requests, 5);
+        assert_eq!(report.requests_this_minute, 0);
+        assert_eq!(report.resets_at, day + SECS_PER_DAY);
+        let report = quotas.report("acme", day + SECS_PER_DAY);
+        assert_eq!(report.today, Counters::default());
+        assert_eq!(report.total.bytes, 100);
+
+        let tenants: Vec<String> = quotas.reports(day).into_iter().map(|report| report.tenant).collect();
+        assert_eq!(tenants, ["acme", "globex"]);
+    }
+
+    #[actix_web::test]
+    async fn test_quota_responses_and_usage_endpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("batches");
+        let mut config = server_config(
+            dir.path(),
+            StorageConfig::Filesystem {
+                root: root.display().to_string(),
+            },
+        );
+        config.quotas.defaults = Limits::from([(Limit::BatchesPerDay, 1), (Limit::BytesPerDay, 10)]);
+        let store: Arc<dyn BatchStore> = Arc::new(FsStore::open(&root).unwrap());
+        let lookup = Arc::new(LookupIndex::open(&config.lookup_db).unwrap());
+        let sessions = Arc::new(SessionRegistry::open(&config.session_file, 3600).unwrap());
+        let tenants = Tenants::single(Arc::new(config), store, lookup, sessions);
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(from_fn(limit_requests))
+                .app_data(web::Data::new(Arc::new(tenants)))
+                .app_data(web::Data::new(None::<ChunkStore>))
+                .service(sessions::create_session)
+                .service(sessions::put_file)
+                .service(list_usage)
+                .service(get_usage)
+                .route("/health", web::get().to(crate::health)),
+        )
+        .await;
+        let put = |name: &str, data: &'static [u8]| {
+            actix_test::TestRequest::put()
+                .uri(&format!("/batches/1/files/{}", name))
+                .set_payload(data)
+                .to_request()
+        };
+
+        let resp = actix_test::call_service(&app, actix_test::TestRequest::post().uri("/batches").to_request()).await;
+        assert_eq!(resp.status(), 201);
+        let resp = actix_test::call_service(&app, actix_test::TestRequest::post().uri("/batches").to_request()).await;
+        assert_eq!(resp.status(), 429);
+        let retry_after: u64 = resp.headers().get(header::RETRY_AFTER).unwrap().to_str().unwrap().parse().unwrap();
+        assert!(retry_after > 0 && retry_after <= SECS_PER_DAY);
+        let error: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(error["success"], false);
+        assert_eq!(error["quota"], "batches_per_day");
+        assert_eq!(error["retry_after"], retry_after);
+
+        // An upload is cut off once it passes the bytes left for the day
+        let resp = actix_test::call_service(&app, put("a.txt", b"12345678")).await;
+        assert_eq!(resp.status(), 200);
+        let resp = actix_test::call_service(&app, put("b.txt", b"12345678")).await;
+        assert_eq!(resp.status(), 429);
+        let error: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(error["quota"], "bytes_per_day");
+
+        // Public endpoints are not counted
+        actix_test::call_service(&app, actix_test::TestRequest::get().uri("/health").to_request()).await;
+        let usage: serde_json::Value = actix_test::call_and_read_body_json(
+            &app,
+            actix_test::TestRequest::get().uri("/admin/usage").to_request(),
+        )
+        .await;
+        let tenant = &usage["tenants"][0];
+        assert_eq!(tenant["tenant"], DEFAULT_TENANT);
+        assert_eq!(tenant["limits"], serde_json::json!({ "bytes_per_day": 10, "batches_per_day": 1 }));
+        assert_eq!(
+            tenant["today"],
+            serde_json::json!({ "requests": 5, "bytes": 8, "batches": 1, "anchors": 0 })
+        );
+        let resp = actix_test::call_service(
+            &app,
+            actix_test::TestRequest::get().uri("/admin/usage/Not-A-Tenant").to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), 400);
+    }
+}
@@ -0,0 +1,253 @@
+//! Signed upload receipts
+//!
+//! Every accepted upload is answered with a receipt signed by the server's
+//! receipt key, covering the tenant, batch ID, root, leaf hashes and the time
+//! it was issued. Receipts are EIP-191 `personal_sign` signatures over a short text
+//! message, so anyone holding the key's address (published at
+//! `GET /.well-known/merkseal-keys`) can check them offline, well before the
+//! root is anchored on-chain.
+
+use crate::{unix_now, BatchMetadata};
+use actix_web::{get, web, HttpResponse};
+use ethers::core::rand::thread_rng;
+use ethers::signers::{LocalWallet, Signer};
+use ethers::utils::hash_message;
+use merkle_tree::{hex_to_hash, DataHasher};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Receipt format version, part of the signed message (v2 adds the tenant)
+pub const RECEIPT_VERSION: u32 = 2;
+/// Signature scheme advertised for receipt keys
+pub const RECEIPT_ALGORITHM: &str = "secp256k1-eip191";
+
+/// Where the receipt signing key comes from
+#[derive(Clone)]
+pub struct ReceiptConfig {
+    /// Hex secp256k1 private key; `None` uses (or creates) `key_file`
+    pub key: Option<String>,
+    /// File holding the hex private key, created on first start
+    pub key_file: String,
+}
+
+impl fmt::Debug for ReceiptConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReceiptConfig")
+            .field("key", &self.key.as_ref().map(|_| "<redacted>"))
+            .field("key_file", &self.key_file)
+            .finish()
+    }
+}
+
+/// A server-signed statement of what was accepted into a batch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub version: u32,
+    /// Address of the signing key (`0x`-prefixed)
+    pub key_id: String,
+    /// Tenant whose batch this is; batch IDs are only unique per tenant
+    #[serde(default)]
+    pub tenant: String,
+    pub batch_id: u64,
+    /// Merkle root (hex), or `None` while a rolling batch is still open
+    pub root: Option<String>,
+    /// Leaf hashes (hex) of the receipted files, in leaf order
+    pub leaves: Vec<String>,
+    /// Unix timestamp (seconds) when the receipt was issued
+    pub issued_at: u64,
+    /// 65-byte EIP-191 signature over `message()` (`0x`-prefixed hex)
+    pub signature: String,
+}
+
+impl Receipt {
+    /// Text that is signed; must stay byte-for-byte identical to the client's
+    pub fn message(&self) -> Result<String, String> {
+        // v1 receipts predate tenants and have no tenant line
+        let tenant = match self.version {
+            1 => String::new(),
+            _ => format!("tenant: {}\n", self.tenant),
+        };
+        Ok(format!(
+            "MerkSeal receipt v{}\nkey: {}\n{}batch: {}\nroot: {}\nleaves: {} sha256:{}\nissued_at: {}",
+            self.version,
+            self.key_id.to_ascii_lowercase(),
+            tenant,
+            self.batch_id,
+            self.root.as_deref().unwrap_or("pending"),
+            self.leaves.len(),
+            leaves_digest(&self.leaves)?,
+            self.issued_at
+        ))
+    }
+}
+
+/// SHA-256 over the concatenated raw leaf hashes
+fn leaves_digest(leaves: &[String]) -> Result<String, String> {
+    let mut hasher = DataHasher::new();
+    for (index, leaf) in leaves.iter().enumerate() {
+        let leaf = hex_to_hash(leaf).map_err(|e| format!("Leaf {}: {}", index, e))?;
+        hasher.update(&leaf);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Holds the receipt key and signs receipts
+pub struct ReceiptSigner {
+    wallet: LocalWallet,
+}
+
+impl ReceiptSigner {
+    /// Use the configured key, or load the key file, creating it if missing
+    pub fn load_or_create(config: &ReceiptConfig) -> io::Result<Self> {
+        if let Some(key) = &config.key {
+            return Self::from_hex(key);
+        }
+        match fs::read_to_string(&config.key_file) {
+            Ok(key) => Self::from_hex(&key),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let signer = Self::random();
+                write_key_file(Path::new(&config.key_file), &hex::encode(signer.wallet.signer().to_bytes()))?;
+                Ok(signer)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// A fresh random key
+    pub fn random() -> Self {
+        Self {
+            wallet: LocalWallet::new(&mut thread_rng()),
+        }
+    }
+
+    fn from_hex(key: &str) -> io::Result<Self> {
+        let wallet = key
+            .trim()
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid receipt private key"))?;
+        Ok(Self { wallet })
+    }
+
+    /// Identifier of the signing key: its Ethereum address
+    pub fn key_id(&self) -> String {
+        format!("{:?}", self.wallet.address())
+    }
+
+    /// Receipt for a tenant's sealed batch, covering every file in it
+    pub fn sealed(&self, tenant: &str, batch: &BatchMetadata) -> Receipt {
+        let leaves = batch.files.iter().map(|f| f.leaf.clone()).collect();
+        self.sign(tenant, batch.local_batch_id, Some(batch.root.clone()), leaves)
+    }
+
+    /// Receipt for leaves accepted into a tenant's batch that has no root yet
+    pub fn pending(&self, tenant: &str, batch_id: u64, leaves: Vec<String>) -> Receipt {
+        self.sign(tenant, batch_id, None, leaves)
+    }
+
+    fn sign(&self, tenant: &str, batch_id: u64, root: Option<String>, leaves: Vec<String>) -> Receipt {
+        let mut receipt = Receipt {
+            version: RECEIPT_VERSION,
+            key_id: self.key_id(),
+            tenant: tenant.to_string(),
+            batch_id,
+            root,
+            leaves,
+            issued_at: unix_now(),
+            signature: String::new(),
+        };
+        // Leaves always come from manifest entries, which hold valid hex hashes
+        let message = receipt.message().expect("receipt leaves are hex hashes");
+        let signature = self
+            .wallet
+            .sign_hash(hash_message(message))
+            .expect("signing a 32-byte hash cannot fail");
+        receipt.signature = format!("0x{}", signature);
+        receipt
+    }
+}
+
+/// Write a new private key file readable only by the server's user
+fn write_key_file(path: &Path, key: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(key.as_bytes())?;
+    file.sync_all()
+}
+
+/// GET /.well-known/merkseal-keys - Keys that sign this server's receipts
+#[get("/.well-known/merkseal-keys")]
+async fn get_keys(signer: web::Data<Arc<ReceiptSigner>>) -> HttpResponse {
+    let key_id = signer.key_id();
+    HttpResponse::Ok().json(serde_json::json!({
+        "keys": [{
+            "key_id": key_id,
+            "algorithm": RECEIPT_ALGORITHM,
+            "address": key_id
+        }]
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test as actix_test, App};
+    use ethers::types::Signature;
+    use merkle_tree::hash_data;
+    use std::str::FromStr;
+
+    #[actix_web::test]
+    async fn test_receipts_verify_against_published_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ReceiptConfig {
+            key: None,
+            key_file: dir.path().join("keys/receipt_key").display().to_string(),
+        };
+        let signer = ReceiptSigner::load_or_create(&config).unwrap();
+        // The key survives a restart
+        assert_eq!(ReceiptSigner::load_or_create(&config).unwrap().key_id(), signer.key_id());
+
+        let leaves = vec![hex::encode(hash_data(b"a")), hex::encode(hash_data(b"b"))];
+        let receipt = signer.pending("acme", 7, leaves.clone());
+        let signature = Signature::from_str(&receipt.signature).unwrap();
+        let recovered = signature.recover(receipt.message().unwrap()).unwrap();
+        assert_eq!(format!("{:?}", recovered), receipt.key_id);
+        assert!(receipt.message().unwrap().contains("tenant: acme\nbatch: 7\nroot: pending"));
+
+        // Any change to the receipted leaves or tenant breaks the signature
+        let mut forged = receipt.clone();
+        forged.leaves.reverse();
+        let recovered = signature.recover(forged.message().unwrap()).unwrap();
+        assert_ne!(format!("{:?}", recovered), receipt.key_id);
+        let mut forged = receipt.clone();
+        forged.tenant = "globex".to_string();
+        let recovered = signature.recover(forged.message().unwrap()).unwrap();
+        assert_ne!(format!("{:?}", recovered), receipt.key_id);
+
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(Arc::new(signer)))
+                .service(get_keys),
+        )
+        .await;
+        let keys: serde_json::Value = actix_test::call_and_read_body_json(
+            &app,
+            actix_test::TestRequest::get().uri("/.well-known/merkseal-keys").to_request(),
+        )
+        .await;
+        assert_eq!(keys["keys"][0]["key_id"], receipt.key_id);
+        assert_eq!(keys["keys"][0]["algorithm"], RECEIPT_ALGORITHM);
+    }
+}
@@ -0,0 +1,298 @@
+//! Rolling batches
+//!
+//! With rolling batches enabled, `POST /upload` does not seal a batch per
+//! request. Files from every client are appended to one shared open batch,
+//! which the roller seals (and, in `auto` anchoring mode, queues for
+//! anchoring) after a fixed interval or once it holds enough files, whichever
+//! comes first. Uploads get a pending receipt and poll the proof endpoint,
+//! optionally waiting for the seal with `?wait=`.
+
+use crate::anchor::AnchorQueue;
+use crate::chunk_store::ChunkStore;
+use crate::quotas::{Limit, Reservation};
+use crate::receipts::ReceiptSigner;
+use crate::sessions::{seal_batch, Session, SessionError};
+use crate::tenants::Workspace;
+use crate::upload::{StagedFile, Stager, UploadError};
+use crate::{unix_now, FileEntry};
+use actix_multipart::Multipart;
+use actix_web::{web, HttpResponse};
+use mantle_config::MantleConfig;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the roller looks for batches that are due
+const ROLLER_TICK: Duration = Duration::from_secs(1);
+
+/// When rolling batches are sealed
+#[derive(Debug, Clone, Copy)]
+pub struct RollingConfig {
+    /// Seal a batch this long after it was opened
+    pub interval: Duration,
+    /// Seal a batch as soon as it holds this many files
+    pub max_files: usize,
+}
+
+impl RollingConfig {
+    /// Whether a rolling session should be sealed instead of taking more files
+    pub fn is_due(&self, session: &Session, now: u64) -> bool {
+        session.files.len() >= self.max_files || session.created_at.saturating_add(self.interval.as_secs()) <= now
+    }
+}
+
+/// Add the files of one `POST /upload` to the current rolling batch
+///
+/// Responds `202 Accepted` with a pending receipt, signed over the accepted
+/// leaves without a root; proofs are available once the batch is sealed.
+pub async fn append(
+    payload: Multipart,
+    workspace: &Workspace,
+    chunks: Option<ChunkStore>,
+    signer: &ReceiptSigner,
+    byte_quota: Reservation,
+) -> HttpResponse {
+    let (server_config, store, sessions) = (&workspace.config, &workspace.store, &workspace.sessions);
+    let staging_root = Path::new(&server_config.staging_dir).join("sessions");
+    let received = sessions.rolling_bytes(unix_now());
+    let mut stager = match Stager::create(&staging_root, sessions.next_upload(), server_config.limits, chunks).await {
+        Ok(stager) => stager.with_received(received).with_quota(byte_quota),
+        Err(e) => {
+            return UploadError::Internal(format!("Failed to create staging directory: {}", e)).response();
+        }
+    };
+    let staged = match stager.stage_multipart(payload).await {
+        Ok(staged) => staged,
+        Err(e) => return e.response(),
+    };
+    let entries: Vec<FileEntry> = staged.iter().map(StagedFile::entry).collect();
+
+    // Join the open rolling batch, opening a new one if it is due or missing
+    let upload = loop {
+        match sessions.append_rolling(entries.clone(), unix_now()) {
+            Ok(Some(upload)) => break upload,
+            Ok(None) => {}
+            Err(e) => return e.response(),
+        }
+        let quota = match workspace.reserve(Limit::BatchesPerDay, 1) {
+            Ok(quota) => quota,
+            Err(e) => return e.response(),
+        };
+        let batch_id = match store.create_batch().await {
+            Ok(id) => id,
+            Err(e) => {
+                return HttpResponse::InternalServerError().json(serde_json::json!({
+                    "success": false,
+                    "error": format!("Failed to create batch: {}", e)
+                }));
+            }
+        };
+        if let Err(e) = sessions.create(batch_id, unix_now(), true) {
+            store.abort(batch_id).await.ok();
+            return SessionError::Io(e).response();
+        }
+        quota.settle(unix_now());
+        println!("✓ Rolling batch {} opened", batch_id);
+    };
+
+    // Dropping `upload` on failure releases the reserved names
+    for (file, entry) in staged.iter().zip(&upload.files) {
+        if let Some(path) = &file.path {
+            if let Err(e) = store.put_file_from_path(upload.batch_id, &entry.name, path).await {
+                return HttpResponse::InternalServerError().json(serde_json::json!({
+                    "success": false,
+                    "error": format!("Failed to save file {}: {}", entry.name, e)
+                }));
+            }
+        }
+    }
+
+    let batch_id = upload.batch_id;
+    let seals_by = upload.seals_by;
+    let files = upload.files.clone();
+    if let Err(e) = sessions.commit(upload, unix_now()) {
+        return SessionError::Io(e).response();
+    }
+    stager.commit();
+    println!("✓ Rolling batch {}: received {} file(s)", batch_id, files.len());
+
+    let proofs: Vec<String> = files
+        .iter()
+        .map(|f| format!("/batches/{}/proof/{}", batch_id, f.name))
+        .collect();
+    let leaves = files.iter().map(|f| f.leaf.clone()).collect();
+    let receipt = signer.pending(&workspace.tenant, batch_id, leaves);
+    HttpResponse::Accepted().json(serde_json::json!({
+        "success": true,
+        "pending": true,
+        "batch_id": batch_id,
+        "seals_by": seals_by,
+        "files": files,
+        "proofs": proofs,
+        "receipt": receipt
+    }))
+}
+
+/// Seal a tenant's rolling batches once they are due
+pub async fn run_roller(
+    workspace: Arc<Workspace>,
+    config: Arc<MantleConfig>,
+    anchor_queue: Option<web::Data<Arc<AnchorQueue>>>,
+) {
+    loop {
+        tokio::time::sleep(ROLLER_TICK).await;
+        for batch_id in workspace.sessions.due_rolling(unix_now()) {
+            let sealed = seal_batch(batch_id, &config, &workspace, anchor_queue.clone()).await;
+            if let Err(e) = sealed {
+                eprintln!("✗ Failed to seal rolling batch {}: {}", batch_id, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batches;
+    use crate::config::ServerConfig;
+    use crate::lookup::LookupIndex;
+    use crate::publish::PublishConfig;
+    use crate::sessions::SessionRegistry;
+    use crate::store::BatchStore;
+    use crate::tenants::{Tenants, DEFAULT_TENANT};
+    use crate::quotas::QuotaConfig;
+    use crate::receipts::ReceiptConfig;
+    use crate::store::{FsStore, StorageConfig};
+    use crate::upload::UploadLimits;
+    use actix_web::{test as actix_test, App};
+    use merkle_tree::{hash_data, MerkleTree};
+
+    const BOUNDARY: &str = "rolling-test-boundary";
+
+    fn multipart(files: &[(&str, &[u8])]) -> actix_test::TestRequest {
+        let mut body = Vec::new();
+        for (name, data) in files {
+            body.extend_from_slice(
+                format!(
+                    "--{}\r\nContent-Disposition: form-data; name=\"files\"; filename=\"{}\"\r\n\r\n",
+                    BOUNDARY, name
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(data);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+        actix_test::TestRequest::post()
+            .uri("/upload")
+            .insert_header(("content-type", format!("multipart/form-data; boundary={}", BOUNDARY)))
+            .set_payload(body)
+    }
+
+    #[actix_web::test]
+    async fn test_uploads_share_a_rolling_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("batches");
+        let store: Arc<dyn BatchStore> = Arc::new(FsStore::open(&root).unwrap());
+        let rolling = RollingConfig {
+            interval: Duration::from_secs(3600),
+            max_files: 3,
+        };
+        let sessions = Arc::new(
+            SessionRegistry::open(dir.path().join("sessions.json"), 7200)
+                .unwrap()
+                .with_rolling(Some(rolling)),
+        );
+        let server_config = Arc::new(ServerConfig {
+            chunking: false,
+            storage: StorageConfig::Filesystem {
+                root: root.display().to_string(),
+            },
+            chunk_dir: dir.path().join("chunks").display().to_string(),
+            blobs: None,
+            lookup_db: dir.path().join("lookup.db").display().to_string(),
+            staging_dir: dir.path().join("staging").display().to_string(),
+            limits: UploadLimits {
+                max_file_bytes: 1024,
+                max_batch_bytes: 16,
+            },
+            anchor: None,
+            indexer: None,
+            publish: PublishConfig::Local {
+                dir: dir.path().join("manifests").display().to_string(),
+                base_url: "http://127.0.0.1:8080".to_string(),
+            },
+            session_file: String::new(),
+            session_ttl: 7200,
+            rolling: Some(rolling),
+            receipts: ReceiptConfig {
+                key: None,
+                key_file: String::new(),
+            },
+            auth: None,
+            quotas: QuotaConfig::default(),
+        });
+        let lookup = Arc::new(LookupIndex::open(dir.path().join("lookup.db")).unwrap());
+        let tenants = Arc::new(Tenants::single(server_config, store, lookup, sessions.clone()));
+        let workspace = tenants.get(DEFAULT_TENANT).await.unwrap();
+        let mantle = Arc::new(MantleConfig::new("http://localhost:8545".into(), 5003, "0x0".into()));
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(tenants))
+                .app_data(web::Data::new(Arc::new(ReceiptSigner::random())))
+                .app_data(web::Data::new(mantle.clone()))
+                .app_data(web::Data::new(None::<ChunkStore>))
+                .service(crate::upload_files)
+                .service(batches::get_proof),
+        )
+        .await;
+
+        let first: serde_json::Value =
+            actix_test::call_and_read_body_json(&app, multipart(&[("a.log", b"one"), ("b.log", b"two")]).to_request())
+                .await;
+        assert_eq!(first["pending"], true);
+        let batch_id = first["batch_id"].as_u64().unwrap();
+        assert_eq!(first["proofs"][0], format!("/batches/{}/proof/a.log", batch_id));
+        assert_eq!(first["receipt"]["root"], serde_json::Value::Null);
+        assert_eq!(first["receipt"]["leaves"][1], hex::encode(hash_data(b"two")));
+
+        // Together with the six bytes already in the batch this passes `max_batch_bytes`
+        let resp = actix_test::call_service(&app, multipart(&[("c.log", b"far too much")]).to_request()).await;
+        assert_eq!(resp.status(), 413);
+
+        // A second client lands in the same batch; its clashing name is renamed
+        let second: serde_json::Value =
+            actix_test::call_and_read_body_json(&app, multipart(&[("a.log", b"three")]).to_request()).await;
+        assert_eq!(second["batch_id"], batch_id);
+        assert_eq!(second["files"][0]["name"], "a-1.log");
+
+        let proof_uri = format!("/batches/{}/proof/a-1.log", batch_id);
+        let resp = actix_test::call_service(&app, actix_test::TestRequest::get().uri(&proof_uri).to_request()).await;
+        assert_eq!(resp.status(), 202);
+
+        // Three files reach `max_files`; a long-polling client is woken by the seal
+        assert_eq!(sessions.due_rolling(unix_now()), vec![batch_id]);
+        let wait_uri = format!("{}?wait=30", proof_uri);
+        let (proof, sealed) = futures_util::join!(
+            actix_test::call_and_read_body_json::<_, _, serde_json::Value>(
+                &app,
+                actix_test::TestRequest::get().uri(&wait_uri).to_request(),
+            ),
+            async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                seal_batch(batch_id, &mantle, &workspace, None).await
+            }
+        );
+        let sealed = sealed.unwrap();
+        let expected = MerkleTree::new(vec![hash_data(b"one"), hash_data(b"two"), hash_data(b"three")]);
+        assert_eq!(sealed.root, expected.root_hex());
+        assert_eq!(proof["root"], expected.root_hex());
+        assert_eq!(proof["leaf_index"], 2);
+
+        // The next upload opens a new rolling batch
+        let third: serde_json::Value =
+            actix_test::call_and_read_body_json(&app, multipart(&[("a.log", b"four")]).to_request()).await;
+        assert_ne!(third["batch_id"], batch_id);
+        assert_eq!(third["files"][0]["name"], "a.log");
+    }
+}
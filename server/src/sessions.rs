@@ -0,0 +1,866 @@
+//! Open batch sessions
+//!
+//! `POST /batches` reserves a batch that then receives files one request at a
+//! time with `PUT /batches/{id}/files/{name}`. `POST /batches/{id}/seal`
+//! freezes the file list, computes the root and publishes the manifest, just
+//! like a single-request `POST /upload`. Sessions that see no activity for the
+//! configured TTL are discarded together with their files.
+
+use crate::anchor::AnchorQueue;
+use crate::batches::store_error;
+use crate::chunk_store::ChunkStore;
+use crate::filenames::{sanitize, BatchFileNames};
+use crate::publish::{publish_manifest, PublishError};
+use crate::quotas::Limit;
+use crate::receipts::ReceiptSigner;
+use crate::store::{BatchStore, StoreError};
+use crate::tenants::{Tenant, Workspace};
+use crate::upload::{Stager, UploadError};
+use crate::rolling::RollingConfig;
+use crate::{auto_anchor, new_batch_metadata, unix_now, BatchMetadata, FileEntry, UploadResponse};
+use actix_web::{post, put, web, HttpResponse};
+use mantle_config::MantleConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::futures::Notified;
+use tokio::sync::Notify;
+
+/// An open batch and the files it has received so far
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub batch_id: u64,
+    pub created_at: u64,
+    /// Last activity; the session expires `ttl` seconds later
+    pub updated_at: u64,
+    /// Files in the order they were first added (leaf order once sealed)
+    pub files: Vec<FileEntry>,
+    /// Filled by `POST /upload` in rolling mode and sealed automatically
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub rolling: bool,
+    /// A seal is in progress, so no more files are accepted
+    #[serde(skip)]
+    sealing: bool,
+    /// Uploads still moving files into the batch; sealing waits for them
+    #[serde(skip)]
+    active: usize,
+    /// Rolling files whose upload has not finished yet, in arrival order
+    #[serde(skip)]
+    pending: Vec<FileEntry>,
+}
+
+impl Session {
+    /// Bytes received so far, optionally not counting a file about to be replaced
+    fn bytes_except(&self, name: &str) -> u64 {
+        self.files.iter().filter(|f| f.name != name).map(|f| f.size).sum()
+    }
+}
+
+/// Open sessions, persisted so they survive a restart
+pub struct SessionRegistry {
+    path: PathBuf,
+    /// Idle seconds before a session expires
+    ttl: u64,
+    sessions: Mutex<BTreeMap<u64, Session>>,
+    /// Distinguishes the staging directories of concurrent uploads
+    uploads: AtomicU64,
+    /// Rolling mode settings (`None` when `POST /upload` seals immediately)
+    rolling: Option<RollingConfig>,
+    /// Woken whenever a batch is sealed
+    sealed: Notify,
+}
+
+impl SessionRegistry {
+    /// Load the session file, starting empty if it does not exist
+    pub fn open(path: impl Into<PathBuf>, ttl: u64) -> io::Result<Self> {
+        let path = path.into();
+        let sessions = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Corrupt session file {}: {}", path.display(), e),
+                )
+            })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            path,
+            ttl,
+            sessions: Mutex::new(sessions),
+            uploads: AtomicU64::new(0),
+            rolling: None,
+            sealed: Notify::new(),
+        })
+    }
+
+    /// Enable rolling batches for `POST /upload`
+    pub fn with_rolling(mut self, rolling: Option<RollingConfig>) -> Self {
+        self.rolling = rolling;
+        self
+    }
+
+    /// When a session expires unless more files arrive
+    pub fn expires_at(&self, session: &Session) -> u64 {
+        session.updated_at.saturating_add(self.ttl)
+    }
+
+    /// When a rolling session is sealed at the latest (`None` for other sessions)
+    pub fn seals_by(&self, session: &Session) -> Option<u64> {
+        match &self.rolling {
+            Some(rolling) if session.rolling => Some(session.created_at.saturating_add(rolling.interval.as_secs())),
+            _ => None,
+        }
+    }
+
+    /// Start a session for a freshly created batch
+    pub fn create(&self, batch_id: u64, now: u64, rolling: bool) -> io::Result<Session> {
+        let session = Session {
+            batch_id,
+            created_at: now,
+            updated_at: now,
+            files: Vec::new(),
+            rolling,
+            sealing: false,
+            active: 0,
+            pending: Vec::new(),
+        };
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.insert(batch_id, session.clone());
+        self.save(&sessions)?;
+        Ok(session)
+    }
+
+    /// Check that `name` can be added and return the bytes already in the batch
+    ///
+    /// A file with the same name is replaced, so its size is not counted. The
+    /// batch cannot be sealed until the returned guard is dropped.
+    pub fn begin_upload(&self, batch_id: u64, name: &str, now: u64) -> Result<(u64, ActiveUpload<'_>), SessionError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = self.open_session(&mut sessions, batch_id, now)?;
+        check_name(session, name)?;
+        session.active += 1;
+        Ok((session.bytes_except(name), ActiveUpload::new(self, batch_id)))
+    }
+
+    /// Reserve names for `files` in the current rolling batch
+    ///
+    /// Names that clash with files already in the batch get a `-N` suffix.
+    /// Returns `None` if no rolling batch is accepting files; the caller then
+    /// creates one with `create` and tries again. The files become part of
+    /// the batch with `commit`, or are dropped with the returned upload.
+    pub fn append_rolling(&self, files: Vec<FileEntry>, now: u64) -> Result<Option<RollingUpload<'_>>, SessionError> {
+        let Some(rolling) = &self.rolling else {
+            return Ok(None);
+        };
+        let mut sessions = self.sessions.lock().unwrap();
+        let Some(session) = sessions.values_mut().find(|s| accepts_rolling(rolling, s, now)) else {
+            return Ok(None);
+        };
+
+        let mut names = BatchFileNames::with_names(session.files.iter().chain(&session.pending).map(|f| f.name.as_str()));
+        let mut assigned = Vec::with_capacity(files.len());
+        for (index, mut file) in files.into_iter().enumerate() {
+            file.name = names
+                .assign(Some(&file.name), session.files.len() + index)
+                .map_err(|e| SessionError::NameTaken(e.to_string()))?;
+            assigned.push(file);
+        }
+        session.pending.extend(assigned.iter().cloned());
+        session.active += 1;
+
+        let mut guard = ActiveUpload::new(self, session.batch_id);
+        guard.pending = assigned.iter().map(|f| f.name.clone()).collect();
+        Ok(Some(RollingUpload {
+            batch_id: session.batch_id,
+            seals_by: self.seals_by(session),
+            files: assigned,
+            guard,
+        }))
+    }
+
+    /// Bytes received or reserved so far by the rolling batch taking files
+    pub fn rolling_bytes(&self, now: u64) -> u64 {
+        let Some(rolling) = &self.rolling else {
+            return 0;
+        };
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .values()
+            .find(|s| accepts_rolling(rolling, s, now))
+            .map_or(0, |s| s.files.iter().chain(&s.pending).map(|f| f.size).sum())
+    }
+
+    /// Make the files of a finished rolling upload part of its batch
+    pub fn commit(&self, mut upload: RollingUpload<'_>, now: u64) -> io::Result<()> {
+        let names = std::mem::take(&mut upload.guard.pending);
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(session) = sessions.get_mut(&upload.batch_id) {
+            let (done, still_pending) = std::mem::take(&mut session.pending)
+                .into_iter()
+                .partition(|f| names.contains(&f.name));
+            session.pending = still_pending;
+            session.files.extend::<Vec<FileEntry>>(done);
+            session.updated_at = now;
+        }
+        self.save(&sessions)
+    }
+
+    /// Rolling sessions that are old or full enough to be sealed now
+    pub fn due_rolling(&self, now: u64) -> Vec<u64> {
+        let Some(rolling) = &self.rolling else {
+            return Vec::new();
+        };
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .values()
+            .filter(|s| s.rolling && !s.sealing && s.active == 0 && !s.files.is_empty() && rolling.is_due(s, now))
+            .map(|s| s.batch_id)
+            .collect()
+    }
+
+    /// A file that has been received but whose batch is not sealed yet
+    pub fn pending_file(&self, batch_id: u64, name: &str) -> Option<(FileEntry, Option<u64>)> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions.get(&batch_id)?;
+        let file = session.files.iter().chain(&session.pending).find(|f| f.name == name)?;
+        Some((file.clone(), self.seals_by(session)))
+    }
+
+    /// Resolves on the next seal; enable it before checking for a manifest
+    pub fn sealed(&self) -> Notified<'_> {
+        self.sealed.notified()
+    }
+
+    /// Record a received file, replacing an earlier file with the same name
+    pub fn add_file(&self, batch_id: u64, entry: FileEntry, now: u64) -> Result<Session, SessionError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = self.open_session(&mut sessions, batch_id, now)?;
+        check_name(session, &entry.name)?;
+        match session.files.iter_mut().find(|f| f.name == entry.name) {
+            Some(existing) => *existing = entry,
+            None => session.files.push(entry),
+        }
+        session.updated_at = now;
+        let session = session.clone();
+        self.save(&sessions)?;
+        Ok(session)
+    }
+
+    /// Freeze a session's file list for sealing
+    pub fn begin_seal(&self, batch_id: u64, now: u64) -> Result<Session, SessionError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = self.open_session(&mut sessions, batch_id, now)?;
+        if session.files.is_empty() {
+            return Err(SessionError::Empty(batch_id));
+        }
+        if session.active > 0 {
+            return Err(SessionError::Busy(batch_id));
+        }
+        session.sealing = true;
+        Ok(session.clone())
+    }
+
+    /// Finish a seal: drop the session once sealed, or reopen it if sealing failed
+    pub fn end_seal(&self, batch_id: u64, sealed: bool) -> io::Result<()> {
+        let mut sessions = self.sessions.lock().unwrap();
+        if sealed {
+            sessions.remove(&batch_id);
+            self.sealed.notify_waiters();
+            self.save(&sessions)
+        } else {
+            if let Some(session) = sessions.get_mut(&batch_id) {
+                session.sealing = false;
+            }
+            Ok(())
+        }
+    }
+
+    /// Remove sessions idle for longer than the TTL, returning their batch IDs
+    pub fn take_expired(&self, now: u64) -> io::Result<Vec<u64>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let expired: Vec<u64> = sessions
+            .values()
+            .filter(|s| !s.sealing && s.active == 0 && self.expires_at(s) <= now)
+            .map(|s| s.batch_id)
+            .collect();
+        if !expired.is_empty() {
+            sessions.retain(|id, _| !expired.contains(id));
+            self.save(&sessions)?;
+        }
+        Ok(expired)
+    }
+
+    /// ID for the staging directory of one upload
+    pub fn next_upload(&self) -> u64 {
+        self.uploads.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn open_session<'a>(
+        &self,
+        sessions: &'a mut BTreeMap<u64, Session>,
+        batch_id: u64,
+        now: u64,
+    ) -> Result<&'a mut Session, SessionError> {
+        let ttl = self.ttl;
+        let session = sessions.get_mut(&batch_id).ok_or(SessionError::NotFound(batch_id))?;
+        if session.updated_at.saturating_add(ttl) <= now {
+            return Err(SessionError::Expired(batch_id));
+        }
+        if session.sealing {
+            return Err(SessionError::Sealing(batch_id));
+        }
+        Ok(session)
+    }
+
+    /// Persist the sessions atomically (write temp file, fsync, rename)
+    fn save(&self, sessions: &BTreeMap<u64, Session>) -> io::Result<()> {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".tmp");
+        let tmp = self.path.with_file_name(name);
+
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec_pretty(sessions).map_err(io::Error::other)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+/// Marks an upload in progress; the batch cannot be sealed until it is dropped
+pub struct ActiveUpload<'a> {
+    registry: &'a SessionRegistry,
+    batch_id: u64,
+    /// Reserved rolling file names to release if the upload does not finish
+    pending: Vec<String>,
+}
+
+impl<'a> ActiveUpload<'a> {
+    fn new(registry: &'a SessionRegistry, batch_id: u64) -> Self {
+        Self {
+            registry,
+            batch_id,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Drop for ActiveUpload<'_> {
+    fn drop(&mut self) {
+        let mut sessions = self.registry.sessions.lock().unwrap();
+        if let Some(session) = sessions.get_mut(&self.batch_id) {
+            session.active = session.active.saturating_sub(1);
+            session.pending.retain(|f| !self.pending.contains(&f.name));
+        }
+    }
+}
+
+/// Files of one `POST /upload` on their way into a rolling batch
+pub struct RollingUpload<'a> {
+    pub batch_id: u64,
+    /// The uploaded files under their names in the batch
+    pub files: Vec<FileEntry>,
+    /// When the batch is sealed at the latest
+    pub seals_by: Option<u64>,
+    guard: ActiveUpload<'a>,
+}
+
+/// Whether a session is the open rolling batch that new files join
+fn accepts_rolling(rolling: &RollingConfig, session: &Session, now: u64) -> bool {
+    session.rolling && !session.sealing && !rolling.is_due(session, now)
+}
+
+/// Reject names that differ from an existing file only by case
+fn check_name(session: &Session, name: &str) -> Result<(), SessionError> {
+    let lower = name.to_lowercase();
+    match session.files.iter().find(|f| f.name != name && f.name.to_lowercase() == lower) {
+        Some(existing) => Err(SessionError::NameTaken(existing.name.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Discard expired sessions and their files every `interval`
+pub async fn run_sweeper(sessions: Arc<SessionRegistry>, store: Arc<dyn BatchStore>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let expired = match sessions.take_expired(unix_now()) {
+            Ok(expired) => expired,
+            Err(e) => {
+                eprintln!("✗ Failed to expire batch sessions: {}", e);
+                continue;
+            }
+        };
+        for batch_id in expired {
+            match store.abort(batch_id).await {
+                Ok(()) => println!("🗑️  Discarded expired batch session {}", batch_id),
+                Err(e) => eprintln!("✗ Failed to discard expired batch {}: {}", batch_id, e),
+            }
+        }
+    }
+}
+
+/// POST /batches - Open a batch that receives files over several requests
+#[post("/batches")]
+async fn create_session(tenant: Tenant) -> HttpResponse {
+    let (store, sessions) = (&tenant.store, &tenant.sessions);
+    let quota = match tenant.reserve(Limit::BatchesPerDay, 1) {
+        Ok(quota) => quota,
+        Err(e) => return e.response(),
+    };
+    let batch_id = match store.create_batch().await {
+        Ok(id) => id,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "success": false,
+                "error": format!("Failed to create batch: {}", e)
+            }));
+        }
+    };
+
+    match sessions.create(batch_id, unix_now(), false) {
+        Ok(session) => {
+            quota.settle(unix_now());
+            println!("✓ Batch {} opened", batch_id);
+            HttpResponse::Created().json(serde_json::json!({
+                "success": true,
+                "batch_id": batch_id,
+                "expires_at": sessions.expires_at(&session)
+            }))
+        }
+        Err(e) => {
+            store.abort(batch_id).await.ok();
+            SessionError::Io(e).response()
+        }
+    }
+}
+
+/// PUT /batches/{id}/files/{name} - Add (or replace) a file in an open batch
+///
+/// The request body is the raw file contents.
+#[put("/batches/{id}/files/{name}")]
+async fn put_file(
+    path: web::Path<(u64, String)>,
+    payload: web::Payload,
+    tenant: Tenant,
+    chunk_store: web::Data<Option<ChunkStore>>,
+) -> HttpResponse {
+    let (server_config, store, sessions) = (&tenant.config, tenant.store.as_ref(), &tenant.sessions);
+    let (batch_id, original_name) = path.into_inner();
+    let name = match sanitize(&original_name) {
+        Ok(name) if !name.is_empty() => name,
+        Ok(_) => return UploadError::BadRequest(format!("Invalid file name {:?}", original_name)).response(),
+        Err(e) => return UploadError::BadRequest(e.to_string()).response(),
+    };
+    let byte_quota = match tenant.reserve(Limit::BytesPerDay, 0) {
+        Ok(quota) => quota,
+        Err(e) => return e.response(),
+    };
+    let (received, _upload) = match sessions.begin_upload(batch_id, &name, unix_now()) {
+        Ok(started) => started,
+        Err(e) => return session_response(store, e).await,
+    };
+
+    let chunks = chunk_store.get_ref().clone().filter(|_| server_config.chunking);
+    let staging_root = Path::new(&server_config.staging_dir).join("sessions");
+    let mut stager = match Stager::create(&staging_root, sessions.next_upload(), server_config.limits, chunks).await {
+        Ok(stager) => stager.with_received(received).with_quota(byte_quota),
+        Err(e) => {
+            return UploadError::Internal(format!("Failed to create staging directory: {}", e)).response();
+        }
+    };
+    let staged = match stager.stage(name, original_name, payload).await {
+        Ok(file) => file,
+        Err(e) => return e.response(),
+    };
+
+    if let Some(path) = &staged.path {
+        if let Err(e) = store.put_file_from_path(batch_id, &staged.name, path).await {
+            return match e {
+                StoreError::Sealed(_) => session_response(store, SessionError::NotFound(batch_id)).await,
+                e => store_error(e),
+            };
+        }
+    }
+
+    let entry = staged.entry();
+    match sessions.add_file(batch_id, entry.clone(), unix_now()) {
+        Ok(session) => {
+            stager.commit();
+            println!(
+                "✓ Batch {}: received {} ({} bytes, {} files so far)",
+                batch_id,
+                entry.name,
+                entry.size,
+                session.files.len()
+            );
+            HttpResponse::Ok().json(serde_json::json!({
+                "success": true,
+                "batch_id": batch_id,
+                "file": entry,
+                "file_count": session.files.len(),
+                "expires_at": sessions.expires_at(&session)
+            }))
+        }
+        Err(e) => session_response(store, e).await,
+    }
+}
+
+/// POST /batches/{id}/seal - Freeze an open batch, compute its root and publish the manifest
+#[post("/batches/{id}/seal")]
+async fn seal_session(
+    path: web::Path<u64>,
+    config: web::Data<Arc<MantleConfig>>,
+    tenant: Tenant,
+    signer: web::Data<Arc<ReceiptSigner>>,
+    anchor_queue: Option<web::Data<Arc<AnchorQueue>>>,
+) -> HttpResponse {
+    let batch_id = path.into_inner();
+    match seal_batch(batch_id, &config, &tenant, anchor_queue).await {
+        Ok(batch) => HttpResponse::Ok().json(UploadResponse {
+            success: true,
+            receipt: signer.sealed(&tenant.tenant, &batch),
+            batch,
+        }),
+        Err(e) => session_response(tenant.store.as_ref(), e).await,
+    }
+}
+
+/// Seal an open batch and queue it for anchoring in `auto` mode
+pub async fn seal_batch(
+    batch_id: u64,
+    config: &MantleConfig,
+    workspace: &Workspace,
+    anchor_queue: Option<web::Data<Arc<AnchorQueue>>>,
+) -> Result<BatchMetadata, SessionError> {
+    let (store, sessions) = (&workspace.store, &workspace.sessions);
+    let session = sessions.begin_seal(batch_id, unix_now())?;
+    if let Err(e) = check_stored(store.as_ref(), batch_id, &session.files).await {
+        sessions.end_seal(batch_id, false).ok();
+        return Err(SessionError::Store(e));
+    }
+
+    let mut batch_metadata = new_batch_metadata(batch_id, session.files, config);
+    if let Err(e) = publish_manifest(&mut batch_metadata, &workspace.config).await {
+        sessions.end_seal(batch_id, false).ok();
+        return Err(SessionError::Publish(e));
+    }
+    let manifest = serde_json::to_vec_pretty(&batch_metadata).unwrap();
+    if let Err(e) = store.seal(batch_id, &manifest).await {
+        sessions.end_seal(batch_id, false).ok();
+        return Err(SessionError::Store(e));
+    }
+    // The batch is sealed either way; a stale session entry is rejected by the store
+    if let Err(e) = sessions.end_seal(batch_id, true) {
+        eprintln!("✗ Failed to close session {}: {}", batch_id, e);
+    }
+
+    println!("✓ Batch {} sealed:", batch_id);
+    println!("  Files: {}", batch_metadata.file_count);
+    println!("  Root: {}", batch_metadata.root);
+    println!("  Saved to: {}", store.location(batch_id));
+
+    auto_anchor(workspace, anchor_queue, batch_id).await;
+    Ok(batch_metadata)
+}
+
+/// Make sure every non-chunked file in the session is in the store at its recorded size
+async fn check_stored(store: &dyn BatchStore, batch_id: u64, files: &[FileEntry]) -> Result<(), StoreError> {
+    let stored: BTreeMap<String, u64> = store
+        .list_batch(batch_id)
+        .await?
+        .into_iter()
+        .map(|f| (f.name, f.size))
+        .collect();
+    for file in files.iter().filter(|f| f.chunks.is_none()) {
+        if stored.get(&file.name) != Some(&file.size) {
+            return Err(StoreError::Backend(format!(
+                "{} is missing from batch {} or has the wrong size",
+                file.name, batch_id
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Error response for a session lookup, telling sealed batches apart from unknown ones
+async fn session_response(store: &dyn BatchStore, e: SessionError) -> HttpResponse {
+    if let SessionError::NotFound(batch_id) = e {
+        if store.read_manifest(batch_id).await.is_ok() {
+            return SessionError::Sealed(batch_id).response();
+        }
+    }
+    e.response()
+}
+
+/// Reasons a session request is refused
+#[derive(Debug)]
+pub enum SessionError {
+    /// No open session for this batch (404)
+    NotFound(u64),
+    /// The session was idle for too long (410)
+    Expired(u64),
+    /// The batch is being sealed (409)
+    Sealing(u64),
+    /// Files are still being uploaded into the batch (409)
+    Busy(u64),
+    /// The batch is already sealed (409)
+    Sealed(u64),
+    /// Another file has the same name up to case (409)
+    NameTaken(String),
+    /// Sealing a batch without files (400)
+    Empty(u64),
+    /// Persisting the sessions failed (500)
+    Io(io::Error),
+    /// Writing the manifest failed (500)
+    Store(StoreError),
+    /// Publishing the canonical manifest failed (502)
+    Publish(PublishError),
+}
+
+impl SessionError {
+    /// JSON error response with the matching status code
+    pub fn response(&self) -> HttpResponse {
+        let mut builder = match self {
+            SessionError::NotFound(_) => HttpResponse::NotFound(),
+            SessionError::Expired(_) => HttpResponse::Gone(),
+            SessionError::Sealing(_)
+            | SessionError::Busy(_)
+            | SessionError::Sealed(_)
+            | SessionError::NameTaken(_) => HttpResponse::Conflict(),
+            SessionError::Empty(_) => HttpResponse::BadRequest(),
+            SessionError::Io(_) | SessionError::Store(_) => HttpResponse::InternalServerError(),
+            SessionError::Publish(_) => HttpResponse::BadGateway(),
+        };
+        builder.json(serde_json::json!({
+            "success": false,
+            "error": self.to_string()
+        }))
+    }
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::NotFound(id) => write!(f, "Batch {} has no open session", id),
+            SessionError::Expired(id) => write!(f, "Session for batch {} has expired", id),
+            SessionError::Sealing(id) => write!(f, "Batch {} is being sealed", id),
+            SessionError::Busy(id) => write!(f, "Batch {} is still receiving files", id),
+            SessionError::Sealed(id) => write!(f, "Batch {} is sealed", id),
+            SessionError::NameTaken(name) => write!(f, "File name conflicts with {:?}", name),
+            SessionError::Empty(id) => write!(f, "Batch {} has no files to seal", id),
+            SessionError::Io(e) => write!(f, "Failed to save session: {}", e),
+            SessionError::Store(e) => write!(f, "Failed to seal batch: {}", e),
+            SessionError::Publish(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+impl From<io::Error> for SessionError {
+    fn from(e: io::Error) -> Self {
+        SessionError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batches;
+    use crate::config::ServerConfig;
+    use crate::lookup::LookupIndex;
+    use crate::publish::PublishConfig;
+    use crate::quotas::QuotaConfig;
+    use crate::receipts::ReceiptConfig;
+    use crate::store::{FsStore, StorageConfig};
+    use crate::tenants::Tenants;
+    use crate::upload::UploadLimits;
+    use actix_web::{test as actix_test, App};
+    use merkle_tree::{hash_data, MerkleTree};
+
+    fn entry(name: &str, data: &[u8]) -> FileEntry {
+        FileEntry {
+            name: name.into(),
+            original_name: name.into(),
+            size: data.len() as u64,
+            leaf: hex::encode(hash_data(data)),
+            cid: String::new(),
+            chunks: None,
+            sha256: None,
+        }
+    }
+
+    #[test]
+    fn test_registry_lifecycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.json");
+        let registry = SessionRegistry::open(&path, 100).unwrap();
+        registry.create(1, 0, false).unwrap();
+        registry.create(2, 0, false).unwrap();
+
+        registry.add_file(1, entry("a.txt", b"one"), 10).unwrap();
+        registry.add_file(1, entry("b.txt", b"two"), 20).unwrap();
+        // Replacing keeps the position; the replaced size does not count
+        registry.add_file(1, entry("a.txt", b"uno!"), 30).unwrap();
+        let (received, upload) = registry.begin_upload(1, "a.txt", 31).unwrap();
+        assert_eq!(received, 3);
+        assert!(matches!(registry.begin_seal(1, 31), Err(SessionError::Busy(1))));
+        drop(upload);
+        assert!(matches!(registry.begin_upload(1, "A.TXT", 31), Err(SessionError::NameTaken(_))));
+
+        // Survives a restart
+        let registry = SessionRegistry::open(&path, 100).unwrap();
+        let session = registry.begin_seal(1, 40).unwrap();
+        assert_eq!(session.files.iter().map(|f| f.size).collect::<Vec<_>>(), vec![4, 3]);
+        assert!(matches!(registry.begin_upload(1, "c.txt", 41), Err(SessionError::Sealing(1))));
+        assert!(matches!(registry.begin_seal(2, 41), Err(SessionError::Empty(2))));
+
+        // Session 2 has been idle since 0; session 1 is mid-seal
+        assert!(matches!(registry.begin_upload(2, "x", 100), Err(SessionError::Expired(2))));
+        assert_eq!(registry.take_expired(200).unwrap(), vec![2]);
+        registry.end_seal(1, true).unwrap();
+        assert!(matches!(registry.begin_upload(1, "c.txt", 41), Err(SessionError::NotFound(1))));
+    }
+
+    #[actix_web::test]
+    async fn test_session_endpoints() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("batches");
+        let store: Arc<dyn BatchStore> = Arc::new(FsStore::open(&root).unwrap());
+        let sessions = Arc::new(SessionRegistry::open(dir.path().join("sessions.json"), 3600).unwrap());
+        let server_config = Arc::new(ServerConfig {
+            chunking: false,
+            storage: StorageConfig::Filesystem {
+                root: root.display().to_string(),
+            },
+            chunk_dir: dir.path().join("chunks").display().to_string(),
+            blobs: None,
+            lookup_db: dir.path().join("lookup.db").display().to_string(),
+            staging_dir: dir.path().join("staging").display().to_string(),
+            limits: UploadLimits {
+                max_file_bytes: 1024,
+                max_batch_bytes: 16,
+            },
+            anchor: None,
+            indexer: None,
+            publish: PublishConfig::Local {
+                dir: dir.path().join("manifests").display().to_string(),
+                base_url: "http://127.0.0.1:8080".to_string(),
+            },
+            session_file: String::new(),
+            session_ttl: 3600,
+            rolling: None,
+            receipts: ReceiptConfig {
+                key: None,
+                key_file: String::new(),
+            },
+            auth: None,
+            quotas: QuotaConfig::default(),
+        });
+        let lookup = Arc::new(LookupIndex::open(dir.path().join("lookup.db")).unwrap());
+        let mantle = Arc::new(MantleConfig::new("http://localhost:8545".into(), 5003, "0x0".into()));
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(Arc::new(Tenants::single(server_config, store, lookup, sessions))))
+                .app_data(web::Data::new(Arc::new(ReceiptSigner::random())))
+                .app_data(web::Data::new(mantle))
+                .app_data(web::Data::new(None::<ChunkStore>))
+                .service(create_session)
+                .service(put_file)
+                .service(seal_session)
+                .service(batches::download_file),
+        )
+        .await;
+
+        let created: serde_json::Value = actix_test::call_and_read_body_json(
+            &app,
+            actix_test::TestRequest::post().uri("/batches").to_request(),
+        )
+        .await;
+        let id = created["batch_id"].as_u64().unwrap();
+
+        for (name, data) in [("b.txt", &b"first"[..]), ("a.txt", b"second")] {
+            let resp = actix_test::call_service(
+                &app,
+                actix_test::TestRequest::put()
+                    .uri(&format!("/batches/{}/files/{}", id, name))
+                    .set_payload(data)
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), 200);
+        }
+
+        // Over the 16-byte batch limit
+        let resp = actix_test::call_service(
+            &app,
+            actix_test::TestRequest::put()
+                .uri(&format!("/batches/{}/files/c.txt", id))
+                .set_payload(&b"too much"[..])
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), 413);
+
+        let sealed: serde_json::Value = actix_test::call_and_read_body_json(
+            &app,
+            actix_test::TestRequest::post().uri(&format!("/batches/{}/seal", id)).to_request(),
+        )
+        .await;
+        let expected = MerkleTree::new(vec![hash_data(b"first"), hash_data(b"second")]);
+        assert_eq!(sealed["batch"]["root"], expected.root_hex());
+        assert_eq!(sealed["batch"]["files"][0]["name"], "b.txt");
+
+        let resp = actix_test::call_service(
+            &app,
+            actix_test::TestRequest::get().uri(&format!("/batches/{}/files/a.txt", id)).to_request(),
+        )
+        .await;
+        assert_eq!(actix_test::read_body(resp).await, &b"second"[..]);
+
+        for req in [
+            actix_test::TestRequest::put()
+                .uri(&format!("/batches/{}/files/late.txt", id))
+                .set_payload(&b"x"[..]),
+            actix_test::TestRequest::post().uri(&format!("/batches/{}/seal", id)),
+        ] {
+            assert_eq!(actix_test::call_service(&app, req.to_request()).await.status(), 409);
+        }
+        let resp = actix_test::call_service(
+            &app,
+            actix_test::TestRequest::post().uri("/batches/999/seal").to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), 404);
+
+        // A file lost from the store keeps the batch from sealing
+        let created: serde_json::Value = actix_test::call_and_read_body_json(
+            &app,
+            actix_test::TestRequest::post().uri("/batches").to_request(),
+        )
+        .await;
+        let id = created["batch_id"].as_u64().unwrap();
+        let resp = actix_test::call_service(
+            &app,
+            actix_test::TestRequest::put()
+                .uri(&format!("/batches/{}/files/lost.txt", id))
+                .set_payload(&b"lost"[..])
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), 200);
+        fs::remove_file(root.join(id.to_string()).join("lost.txt")).unwrap();
+        let resp = actix_test::call_service(
+            &app,
+            actix_test::TestRequest::post().uri(&format!("/batches/{}/seal", id)).to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), 500);
+    }
+}
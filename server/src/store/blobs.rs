@@ -0,0 +1,438 @@
+use super::fs::move_file;
+use super::sqlite::backend;
+use super::{check_name, BatchStore, FileStream, StoreError, StoredFile};
+use async_trait::async_trait;
+use merkle_tree::{hash_data, DataHasher, Hash};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::fs;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+    CREATE TABLE IF NOT EXISTS blobs (
+        hash TEXT PRIMARY KEY,
+        size INTEGER NOT NULL,
+        refs INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS batch_files (
+        batch_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        hash TEXT NOT NULL,
+        PRIMARY KEY (batch_id, name)
+    );
+";
+
+/// Shared blob store settings
+#[derive(Debug, Clone)]
+pub struct BlobConfig {
+    /// Directory holding the blobs and their index
+    pub dir: String,
+    /// How often unreferenced blobs are collected
+    pub gc_interval: Duration,
+}
+
+/// What one garbage collection pass removed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GcStats {
+    pub blobs: usize,
+    pub bytes: u64,
+}
+
+/// Deduplicating wrapper that stores file contents once per content hash
+///
+/// Files are kept as `<dir>/<hex sha256>`, the same hash as the manifest's
+/// `leaf` for unchunked files, so a document uploaded in many batches is
+/// stored once. A SQLite index maps each `(batch, name)` to its blob and
+/// counts references; aborting a batch or replacing a file drops its
+/// reference, and `collect_garbage` deletes blobs nothing refers to.
+/// Batches, manifests and sealing are delegated to the wrapped backend, and
+/// files it stored before blobs were enabled are still served from it.
+///
+/// Every change to the index and the blob files happens under one lock, so
+/// collection never races with an upload that is about to reuse a blob.
+pub struct BlobStore {
+    inner: Arc<dyn BatchStore>,
+    dir: PathBuf,
+    conn: Mutex<Connection>,
+}
+
+impl BlobStore {
+    /// Open (and create if needed) the blob directory and its index
+    pub fn open(inner: Arc<dyn BatchStore>, dir: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let conn = Connection::open(dir.join("index.db")).map_err(backend)?;
+        conn.execute_batch(SCHEMA).map_err(backend)?;
+        Ok(Self {
+            inner,
+            dir,
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(hash)
+    }
+
+    /// Blob holding a file, if the file was stored through this store
+    fn lookup(&self, batch_id: u64, name: &str) -> Result<Option<String>, StoreError> {
+        self.conn()
+            .query_row(
+                "SELECT hash FROM batch_files WHERE batch_id = ?1 AND name = ?2",
+                params![batch_id, name],
+                |r| r.get(0),
+            )
+            .optional()
+            .map_err(backend)
+    }
+
+    /// Fail unless the batch exists in the wrapped store and is still open
+    async fn require_open(&self, batch_id: u64) -> Result<(), StoreError> {
+        self.inner.list_batch(batch_id).await?;
+        if self.inner.read_manifest(batch_id).await.is_ok() {
+            return Err(StoreError::Sealed(batch_id));
+        }
+        Ok(())
+    }
+
+    /// Point `(batch, name)` at a blob, placing its contents if they are new
+    ///
+    /// `place` writes the blob file and only runs if no blob with this hash
+    /// exists yet; `discard` cleans up the source otherwise.
+    fn link(
+        &self,
+        batch_id: u64,
+        name: &str,
+        hash: &Hash,
+        size: u64,
+        place: impl FnOnce(&Path) -> io::Result<()>,
+        discard: impl FnOnce(),
+    ) -> Result<(), StoreError> {
+        let hash = hex::encode(hash);
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(backend)?;
+
+        let replaced: Option<String> = tx
+            .query_row(
+                "SELECT hash FROM batch_files WHERE batch_id = ?1 AND name = ?2",
+                params![batch_id, name],
+                |r| r.get(0),
+            )
+            .optional()
+            .map_err(backend)?;
+        if let Some(old) = replaced {
+            tx.execute("UPDATE blobs SET refs = refs - 1 WHERE hash = ?1", params![old])
+                .map_err(backend)?;
+        }
+        tx.execute(
+            "INSERT INTO blobs (hash, size, refs) VALUES (?1, ?2, 1)
+             ON CONFLICT (hash) DO UPDATE SET refs = refs + 1",
+            params![hash, size],
+        )
+        .map_err(backend)?;
+        tx.execute(
+            "INSERT OR REPLACE INTO batch_files (batch_id, name, hash) VALUES (?1, ?2, ?3)",
+            params![batch_id, name, hash],
+        )
+        .map_err(backend)?;
+
+        let path = self.blob_path(&hash);
+        if path.is_file() {
+            discard();
+        } else {
+            place(&path)?;
+        }
+        tx.commit().map_err(backend)
+    }
+
+    /// Delete every blob with no references, plus blob files missing from the
+    /// index (left by a crash mid-upload)
+    pub fn collect_garbage(&self) -> Result<GcStats, StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(backend)?;
+        let mut stats = GcStats::default();
+
+        let unreferenced: Vec<(String, u64)> = {
+            let mut stmt = tx
+                .prepare("SELECT hash, size FROM blobs WHERE refs <= 0")
+                .map_err(backend)?;
+            let rows = stmt
+                .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+                .map_err(backend)?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(backend)?;
+            rows
+        };
+        for (hash, size) in unreferenced {
+            remove_blob(&self.blob_path(&hash))?;
+            tx.execute("DELETE FROM blobs WHERE hash = ?1", params![hash])
+                .map_err(backend)?;
+            stats.blobs += 1;
+            stats.bytes += size;
+        }
+
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !is_blob_name(&name) {
+                continue;
+            }
+            let indexed = tx
+                .query_row("SELECT 1 FROM blobs WHERE hash = ?1", params![name], |_| Ok(()))
+                .optional()
+                .map_err(backend)?;
+            if indexed.is_none() {
+                stats.bytes += entry.metadata()?.len();
+                remove_blob(&entry.path())?;
+                stats.blobs += 1;
+            }
+        }
+
+        tx.commit().map_err(backend)?;
+        Ok(stats)
+    }
+
+    /// Number of files referring to a blob (0 if unknown)
+    #[cfg(test)]
+    fn refs(&self, hash: &Hash) -> i64 {
+        self.conn()
+            .query_row("SELECT refs FROM blobs WHERE hash = ?1", params![hex::encode(hash)], |r| r.get(0))
+            .optional()
+            .unwrap()
+            .unwrap_or(0)
+    }
+}
+
+#[async_trait]
+impl BatchStore for BlobStore {
+    async fn create_batch(&self) -> Result<u64, StoreError> {
+        self.inner.create_batch().await
+    }
+
+    async fn put_file(&self, batch_id: u64, name: &str, data: &[u8]) -> Result<(), StoreError> {
+        check_name(name)?;
+        self.require_open(batch_id).await?;
+        let hash = hash_data(data);
+        self.link(
+            batch_id,
+            name,
+            &hash,
+            data.len() as u64,
+            |path| {
+                // Write to a temp file first so a crash never leaves a partial blob
+                let tmp = self.dir.join(format!(".{}.tmp", hex::encode(hash)));
+                fs::write(&tmp, data)?;
+                fs::rename(&tmp, path)
+            },
+            || {},
+        )
+    }
+
+    async fn put_file_from_path(&self, batch_id: u64, name: &str, path: &Path) -> Result<(), StoreError> {
+        check_name(name)?;
+        self.require_open(batch_id).await?;
+        let (hash, size) = hash_file(path)?;
+        self.link(
+            batch_id,
+            name,
+            &hash,
+            size,
+            |blob| move_file(path, blob),
+            || {
+                fs::remove_file(path).ok();
+            },
+        )
+    }
+
+    async fn get_file(&self, batch_id: u64, name: &str) -> Result<Vec<u8>, StoreError> {
+        check_name(name)?;
+        match self.lookup(batch_id, name)? {
+            Some(hash) => Ok(fs::read(self.blob_path(&hash))?),
+            None => self.inner.get_file(batch_id, name).await,
+        }
+    }
+
+    async fn read_file_range(
+        &self,
+        batch_id: u64,
+        name: &str,
+        range: Range<u64>,
+    ) -> Result<FileStream, StoreError> {
+        check_name(name)?;
+        match self.lookup(batch_id, name)? {
+            Some(hash) => Ok(super::fs::stream_file_range(&self.blob_path(&hash), range).await?),
+            None => self.inner.read_file_range(batch_id, name, range).await,
+        }
+    }
+
+    async fn list_batch(&self, batch_id: u64) -> Result<Vec<StoredFile>, StoreError> {
+        let mut files = self.inner.list_batch(batch_id).await?;
+        {
+            let conn = self.conn();
+            let mut stmt = conn
+                .prepare(
+                    "SELECT f.name, b.size FROM batch_files f JOIN blobs b ON b.hash = f.hash
+                     WHERE f.batch_id = ?1",
+                )
+                .map_err(backend)?;
+            let blobs = stmt
+                .query_map(params![batch_id], |r| {
+                    Ok(StoredFile {
+                        name: r.get(0)?,
+                        size: r.get(1)?,
+                    })
+                })
+                .map_err(backend)?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(backend)?;
+            files.extend(blobs);
+        }
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    }
+
+    async fn seal(&self, batch_id: u64, manifest: &[u8]) -> Result<(), StoreError> {
+        self.inner.seal(batch_id, manifest).await
+    }
+
+    async fn write_manifest(&self, batch_id: u64, manifest: &[u8]) -> Result<(), StoreError> {
+        self.inner.write_manifest(batch_id, manifest).await
+    }
+
+    async fn read_manifest(&self, batch_id: u64) -> Result<Vec<u8>, StoreError> {
+        self.inner.read_manifest(batch_id).await
+    }
+
+    async fn list_batches(&self) -> Result<Vec<u64>, StoreError> {
+        self.inner.list_batches().await
+    }
+
+    async fn abort(&self, batch_id: u64) -> Result<(), StoreError> {
+        self.inner.abort(batch_id).await?;
+
+        // Drop the batch's references; the blobs go at the next collection
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(backend)?;
+        tx.execute(
+            "UPDATE blobs SET refs = refs - (
+                 SELECT COUNT(*) FROM batch_files f WHERE f.batch_id = ?1 AND f.hash = blobs.hash
+             ) WHERE hash IN (SELECT hash FROM batch_files WHERE batch_id = ?1)",
+            params![batch_id],
+        )
+        .map_err(backend)?;
+        tx.execute("DELETE FROM batch_files WHERE batch_id = ?1", params![batch_id])
+            .map_err(backend)?;
+        tx.commit().map_err(backend)
+    }
+
+    fn location(&self, batch_id: u64) -> String {
+        format!("{} (contents in {})", self.inner.location(batch_id), self.dir.display())
+    }
+}
+
+/// Collect unreferenced blobs every `interval`
+pub async fn run_gc(blobs: Arc<BlobStore>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        match blobs.collect_garbage() {
+            Ok(stats) if stats.blobs > 0 => {
+                println!("🗑️  Collected {} unreferenced blobs ({} bytes)", stats.blobs, stats.bytes)
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("✗ Blob garbage collection failed: {}", e),
+        }
+    }
+}
+
+/// SHA-256 and size of a local file, read in pieces
+fn hash_file(path: &Path) -> io::Result<(Hash, u64)> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = DataHasher::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok((hasher.finalize(), size));
+        }
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+}
+
+fn is_blob_name(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn remove_blob(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::FsStore;
+
+    fn open(dir: &Path) -> BlobStore {
+        let inner: Arc<dyn BatchStore> = Arc::new(FsStore::open(dir.join("batches")).unwrap());
+        BlobStore::open(inner, dir.join("blobs")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_blob_store_conformance() {
+        let dir = tempfile::tempdir().unwrap();
+        super::super::conformance::run(&open(dir.path())).await;
+    }
+
+    #[tokio::test]
+    async fn test_identical_files_are_stored_once_and_collected() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open(dir.path());
+        let template = hash_data(b"contract template");
+        let blob = dir.path().join("blobs").join(hex::encode(template));
+
+        let a = store.create_batch().await.unwrap();
+        let b = store.create_batch().await.unwrap();
+        store.put_file(a, "t.pdf", b"contract template").await.unwrap();
+        let staged = dir.path().join("staged");
+        fs::write(&staged, b"contract template").unwrap();
+        store.put_file_from_path(b, "copy.pdf", &staged).await.unwrap();
+        assert!(!staged.exists());
+        assert_eq!(store.refs(&template), 2);
+        store.seal(a, b"{}").await.unwrap();
+
+        // Replacing a file drops its reference
+        store.put_file(b, "copy.pdf", b"something else").await.unwrap();
+        assert_eq!(store.refs(&template), 1);
+        assert_eq!(store.refs(&hash_data(b"something else")), 1);
+
+        // Aborting drops the rest; only then is the blob collectable
+        store.abort(b).await.unwrap();
+        assert_eq!(store.collect_garbage().unwrap(), GcStats { blobs: 1, bytes: 14 });
+        assert!(blob.is_file());
+        assert_eq!(store.get_file(a, "t.pdf").await.unwrap(), b"contract template");
+
+        // Files from before blobs were enabled are still readable
+        let legacy = store.create_batch().await.unwrap();
+        store.inner.put_file(legacy, "old.txt", b"legacy").await.unwrap();
+        assert_eq!(store.get_file(legacy, "old.txt").await.unwrap(), b"legacy");
+
+        // Orphaned blob files are swept, the index and temp files are not
+        let orphan = dir.path().join("blobs").join(hex::encode(hash_data(b"orphan")));
+        fs::write(&orphan, b"orphan").unwrap();
+        assert_eq!(store.collect_garbage().unwrap(), GcStats { blobs: 1, bytes: 6 });
+        assert!(!orphan.exists());
+        assert!(dir.path().join("blobs/index.db").is_file());
+    }
+}
@@ -0,0 +1,222 @@
+use super::{check_name, BatchStore, FileStream, StoreError, StoredFile, MANIFEST_NAME};
+use crate::batch_id::BatchIdAllocator;
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use std::fs;
+use std::io::{self, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+/// Local filesystem backend (`<root>/<batch id>/<file>`)
+///
+/// A batch is sealed once its `metadata.json` exists; the manifest is written
+/// to a temp file and renamed into place, so readers never see a partial one.
+#[derive(Debug)]
+pub struct FsStore {
+    root: PathBuf,
+    ids: BatchIdAllocator,
+}
+
+impl FsStore {
+    /// Open a store rooted at `root`, creating it if needed
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let root = root.into();
+        let ids = BatchIdAllocator::open(&root)?;
+        Ok(Self { root, ids })
+    }
+
+    fn batch_dir(&self, batch_id: u64) -> PathBuf {
+        self.root.join(batch_id.to_string())
+    }
+
+    fn existing_batch_dir(&self, batch_id: u64) -> Result<PathBuf, StoreError> {
+        let dir = self.batch_dir(batch_id);
+        if !dir.is_dir() {
+            return Err(StoreError::NotFound(format!("Batch {}", batch_id)));
+        }
+        Ok(dir)
+    }
+
+    fn is_sealed(&self, batch_id: u64) -> bool {
+        self.batch_dir(batch_id).join(MANIFEST_NAME).is_file()
+    }
+}
+
+#[async_trait]
+impl BatchStore for FsStore {
+    async fn create_batch(&self) -> Result<u64, StoreError> {
+        Ok(self.ids.reserve()?.0)
+    }
+
+    async fn put_file(&self, batch_id: u64, name: &str, data: &[u8]) -> Result<(), StoreError> {
+        check_name(name)?;
+        let dir = self.existing_batch_dir(batch_id)?;
+        if self.is_sealed(batch_id) {
+            return Err(StoreError::Sealed(batch_id));
+        }
+        write_atomic(&dir, name, data)?;
+        Ok(())
+    }
+
+    async fn put_file_from_path(&self, batch_id: u64, name: &str, path: &Path) -> Result<(), StoreError> {
+        check_name(name)?;
+        let dir = self.existing_batch_dir(batch_id)?;
+        if self.is_sealed(batch_id) {
+            return Err(StoreError::Sealed(batch_id));
+        }
+        move_file(path, &dir.join(name))?;
+        Ok(())
+    }
+
+    async fn get_file(&self, batch_id: u64, name: &str) -> Result<Vec<u8>, StoreError> {
+        check_name(name)?;
+        let path = self.existing_batch_dir(batch_id)?.join(name);
+        fs::read(&path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                StoreError::NotFound(format!("File {} in batch {}", name, batch_id))
+            }
+            _ => e.into(),
+        })
+    }
+
+    async fn read_file_range(
+        &self,
+        batch_id: u64,
+        name: &str,
+        range: Range<u64>,
+    ) -> Result<FileStream, StoreError> {
+        check_name(name)?;
+        let path = self.existing_batch_dir(batch_id)?.join(name);
+        stream_file_range(&path, range).await.map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                StoreError::NotFound(format!("File {} in batch {}", name, batch_id))
+            }
+            _ => e.into(),
+        })
+    }
+
+    async fn list_batch(&self, batch_id: u64) -> Result<Vec<StoredFile>, StoreError> {
+        let dir = self.existing_batch_dir(batch_id)?;
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name == MANIFEST_NAME || name.starts_with('.') || !entry.file_type()?.is_file() {
+                continue;
+            }
+            files.push(StoredFile {
+                name,
+                size: entry.metadata()?.len(),
+            });
+        }
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    }
+
+    async fn seal(&self, batch_id: u64, manifest: &[u8]) -> Result<(), StoreError> {
+        let dir = self.existing_batch_dir(batch_id)?;
+        if self.is_sealed(batch_id) {
+            return Err(StoreError::Sealed(batch_id));
+        }
+        write_atomic(&dir, MANIFEST_NAME, manifest)?;
+        Ok(())
+    }
+
+    async fn write_manifest(&self, batch_id: u64, manifest: &[u8]) -> Result<(), StoreError> {
+        let dir = self.existing_batch_dir(batch_id)?;
+        if !self.is_sealed(batch_id) {
+            return Err(StoreError::NotFound(format!("Manifest of batch {}", batch_id)));
+        }
+        write_atomic(&dir, MANIFEST_NAME, manifest)?;
+        Ok(())
+    }
+
+    async fn read_manifest(&self, batch_id: u64) -> Result<Vec<u8>, StoreError> {
+        let path = self.batch_dir(batch_id).join(MANIFEST_NAME);
+        fs::read(&path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => StoreError::NotFound(format!("Manifest of batch {}", batch_id)),
+            _ => e.into(),
+        })
+    }
+
+    async fn list_batches(&self) -> Result<Vec<u64>, StoreError> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let entry = entry?;
+            if let Some(id) = entry.file_name().to_str().and_then(|s| s.parse::<u64>().ok()) {
+                if self.is_sealed(id) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    async fn abort(&self, batch_id: u64) -> Result<(), StoreError> {
+        if self.is_sealed(batch_id) {
+            return Err(StoreError::Sealed(batch_id));
+        }
+        match fs::remove_dir_all(self.batch_dir(batch_id)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn location(&self, batch_id: u64) -> String {
+        self.batch_dir(batch_id).display().to_string()
+    }
+}
+
+/// Write a file via a temp file and rename so it appears atomically
+pub(super) fn write_atomic(dir: &Path, name: &str, data: &[u8]) -> io::Result<()> {
+    let tmp = dir.join(format!(".{}.tmp", name));
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, dir.join(name))
+}
+
+/// Stream a byte range of a local file without reading it into memory
+pub(super) async fn stream_file_range(path: &Path, range: Range<u64>) -> io::Result<FileStream> {
+    let mut file = tokio::fs::File::open(path).await?;
+    file.seek(SeekFrom::Start(range.start)).await?;
+    let len = range.end.saturating_sub(range.start);
+    Ok(ReaderStream::new(file.take(len)).boxed())
+}
+
+/// Move a file by renaming, copying if it lives on another filesystem
+pub(super) fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            fs::copy(from, to)?;
+            fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fs_store_conformance() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FsStore::open(dir.path()).unwrap();
+        super::super::conformance::run(&store).await;
+    }
+
+    #[tokio::test]
+    async fn test_fs_layout_matches_client() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FsStore::open(dir.path()).unwrap();
+        let id = store.create_batch().await.unwrap();
+        store.put_file(id, "doc.txt", b"hello").await.unwrap();
+        store.seal(id, b"{}").await.unwrap();
+
+        let batch_dir = dir.path().join(id.to_string());
+        assert_eq!(fs::read(batch_dir.join("doc.txt")).unwrap(), b"hello");
+        assert!(batch_dir.join(MANIFEST_NAME).is_file());
+    }
+}
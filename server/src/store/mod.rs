@@ -0,0 +1,255 @@
+//! Pluggable storage for batch files and manifests
+//!
+//! A batch is created empty, receives files with `put_file`, and is frozen by
+//! `seal`, which atomically publishes its manifest. Sealed batches reject new
+//! files; their manifest can still be rewritten (e.g. to record anchoring).
+
+mod blobs;
+mod fs;
+mod s3;
+mod sqlite;
+
+pub use self::blobs::{run_gc, BlobConfig, BlobStore};
+pub use self::fs::FsStore;
+pub use self::s3::{S3Config, S3Store};
+pub use self::sqlite::SqliteStore;
+
+use actix_web::web::Bytes;
+use async_trait::async_trait;
+use futures_util::stream::{self, BoxStream};
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Name of the manifest object in every batch
+pub const MANIFEST_NAME: &str = "metadata.json";
+
+/// A stored file in a batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredFile {
+    pub name: String,
+    pub size: u64,
+}
+
+/// Byte stream of a stored file (or a range of it)
+pub type FileStream = BoxStream<'static, Result<Bytes, io::Error>>;
+
+/// Storage backend for batches
+#[async_trait]
+pub trait BatchStore: Send + Sync {
+    /// Reserve a fresh batch ID that has never been handed out
+    async fn create_batch(&self) -> Result<u64, StoreError>;
+
+    /// Store a file in an unsealed batch
+    async fn put_file(&self, batch_id: u64, name: &str, data: &[u8]) -> Result<(), StoreError>;
+
+    /// Move a staged file on local disk into an unsealed batch
+    ///
+    /// The default reads it into memory; local backends rename it instead.
+    async fn put_file_from_path(&self, batch_id: u64, name: &str, path: &Path) -> Result<(), StoreError> {
+        let data = tokio::fs::read(path).await?;
+        self.put_file(batch_id, name, &data).await?;
+        tokio::fs::remove_file(path).await.ok();
+        Ok(())
+    }
+
+    /// Read a file from a batch
+    async fn get_file(&self, batch_id: u64, name: &str) -> Result<Vec<u8>, StoreError>;
+
+    /// Stream the bytes `range` of a file from a batch
+    ///
+    /// The default reads the whole file; backends override it to stream.
+    async fn read_file_range(
+        &self,
+        batch_id: u64,
+        name: &str,
+        range: Range<u64>,
+    ) -> Result<FileStream, StoreError> {
+        let data = self.get_file(batch_id, name).await?;
+        let end = (range.end as usize).min(data.len());
+        let start = (range.start as usize).min(end);
+        let bytes = Bytes::from(data).slice(start..end);
+        Ok(Box::pin(stream::once(async move { Ok(bytes) })))
+    }
+
+    /// List the files in a batch (excluding the manifest)
+    async fn list_batch(&self, batch_id: u64) -> Result<Vec<StoredFile>, StoreError>;
+
+    /// Atomically publish the manifest and freeze the batch
+    async fn seal(&self, batch_id: u64, manifest: &[u8]) -> Result<(), StoreError>;
+
+    /// Rewrite the manifest of a sealed batch
+    async fn write_manifest(&self, batch_id: u64, manifest: &[u8]) -> Result<(), StoreError>;
+
+    /// Read the manifest of a sealed batch
+    async fn read_manifest(&self, batch_id: u64) -> Result<Vec<u8>, StoreError>;
+
+    /// List the IDs of all sealed batches in ascending order
+    async fn list_batches(&self) -> Result<Vec<u64>, StoreError>;
+
+    /// Discard an unsealed batch and everything written to it
+    async fn abort(&self, batch_id: u64) -> Result<(), StoreError>;
+
+    /// Human-readable location of a batch, for logs
+    fn location(&self, batch_id: u64) -> String;
+}
+
+/// Storage errors
+#[derive(Debug)]
+pub enum StoreError {
+    /// Batch or file does not exist
+    NotFound(String),
+    /// Batch is sealed and can no longer be modified
+    Sealed(u64),
+    /// Filesystem error
+    Io(io::Error),
+    /// Backend-specific failure (SQLite, S3)
+    Backend(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::NotFound(what) => write!(f, "{} not found", what),
+            StoreError::Sealed(id) => write!(f, "Batch {} is sealed", id),
+            StoreError::Io(e) => write!(f, "I/O error: {}", e),
+            StoreError::Backend(e) => write!(f, "Storage backend error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+/// Storage backend selection
+#[derive(Debug, Clone)]
+pub enum StorageConfig {
+    /// Plain directories under a root (`<root>/<batch id>/<file>`)
+    Filesystem { root: String },
+    /// Files on disk, batches and file lists indexed in SQLite
+    Sqlite { root: String, database: String },
+    /// S3-compatible object store (AWS, MinIO, ...)
+    S3(S3Config),
+}
+
+impl StorageConfig {
+    /// Short backend name, for logs
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StorageConfig::Filesystem { .. } => "filesystem",
+            StorageConfig::Sqlite { .. } => "sqlite",
+            StorageConfig::S3(_) => "s3",
+        }
+    }
+}
+
+/// Open the configured storage backend
+pub async fn open(config: &StorageConfig) -> Result<Arc<dyn BatchStore>, StoreError> {
+    Ok(match config {
+        StorageConfig::Filesystem { root } => Arc::new(FsStore::open(root)?),
+        StorageConfig::Sqlite { root, database } => Arc::new(SqliteStore::open(root, database)?),
+        StorageConfig::S3(s3) => Arc::new(S3Store::open(s3.clone()).await?),
+    })
+}
+
+/// Validate a file name before it is used as a storage key
+fn check_name(name: &str) -> Result<(), StoreError> {
+    if name.is_empty()
+        || name == MANIFEST_NAME
+        || name.starts_with('.')
+        || name.contains(['/', '\\', '\0'])
+    {
+        return Err(StoreError::Backend(format!("Invalid file name {:?}", name)));
+    }
+    Ok(())
+}
+
+/// Backend-agnostic behaviour checks shared by every backend's tests
+#[cfg(test)]
+pub(crate) mod conformance {
+    use super::*;
+
+    pub async fn run(store: &dyn BatchStore) {
+        let a = store.create_batch().await.unwrap();
+        let b = store.create_batch().await.unwrap();
+        assert!(b > a);
+
+        store.put_file(a, "one.txt", b"one").await.unwrap();
+        let staged = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(staged.path(), b"staged!").unwrap();
+        store.put_file_from_path(a, "staged.txt", staged.path()).await.unwrap();
+        store.put_file(a, "two.txt", b"second").await.unwrap();
+        assert!(store.put_file(a, "../escape", b"x").await.is_err());
+        assert!(store.put_file(a, MANIFEST_NAME, b"x").await.is_err());
+
+        // Unsealed batches are not listed and have no manifest
+        assert!(!store.list_batches().await.unwrap().contains(&a));
+        assert!(store.read_manifest(a).await.is_err());
+
+        store.seal(a, b"{\"v\":1}").await.unwrap();
+        assert!(matches!(
+            store.put_file(a, "three.txt", b"late").await,
+            Err(StoreError::Sealed(_))
+        ));
+        assert!(matches!(store.seal(a, b"{}").await, Err(StoreError::Sealed(_))));
+
+        assert_eq!(store.get_file(a, "two.txt").await.unwrap(), b"second");
+        assert_eq!(store.get_file(a, "staged.txt").await.unwrap(), b"staged!");
+        assert!(matches!(
+            store.get_file(a, "missing.txt").await,
+            Err(StoreError::NotFound(_))
+        ));
+        assert_eq!(read_range(store, a, "two.txt", 1..4).await, b"eco");
+        assert_eq!(read_range(store, a, "staged.txt", 0..7).await, b"staged!");
+        assert!(matches!(
+            store.read_file_range(a, "missing.txt", 0..1).await,
+            Err(StoreError::NotFound(_))
+        ));
+
+        let mut files = store.list_batch(a).await.unwrap();
+        files.sort_by(|x, y| x.name.cmp(&y.name));
+        assert_eq!(
+            files,
+            vec![
+                StoredFile { name: "one.txt".into(), size: 3 },
+                StoredFile { name: "staged.txt".into(), size: 7 },
+                StoredFile { name: "two.txt".into(), size: 6 },
+            ]
+        );
+
+        assert_eq!(store.read_manifest(a).await.unwrap(), b"{\"v\":1}");
+        store.write_manifest(a, b"{\"v\":2}").await.unwrap();
+        assert_eq!(store.read_manifest(a).await.unwrap(), b"{\"v\":2}");
+        assert!(store.write_manifest(b, b"{}").await.is_err());
+
+        store.put_file(b, "gone.txt", b"bye").await.unwrap();
+        store.abort(b).await.unwrap();
+        assert!(store.get_file(b, "gone.txt").await.is_err());
+
+        assert!(store.list_batches().await.unwrap().contains(&a));
+        assert!(!store.list_batches().await.unwrap().contains(&b));
+
+        // IDs keep increasing after an abort
+        let c = store.create_batch().await.unwrap();
+        assert!(c > b);
+    }
+
+    async fn read_range(store: &dyn BatchStore, batch_id: u64, name: &str, range: Range<u64>) -> Vec<u8> {
+        use futures_util::TryStreamExt;
+        let parts: Vec<Bytes> = store
+            .read_file_range(batch_id, name, range)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        parts.concat()
+    }
+}
@@ -0,0 +1,370 @@
+use super::{check_name, BatchStore, FileStream, StoreError, StoredFile, MANIFEST_NAME};
+use async_trait::async_trait;
+use futures_util::{StreamExt, TryStreamExt};
+use reqwest::{Client, StatusCode};
+use rusty_s3::actions::{ListObjectsV2, S3Action};
+use rusty_s3::{Bucket, Credentials, UrlStyle};
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio_util::io::ReaderStream;
+use url::Url;
+
+/// Lifetime of presigned request URLs
+const SIGN_DURATION: Duration = Duration::from_secs(3600);
+
+/// Marker object that reserves a batch ID
+const RESERVATION_NAME: &str = ".batch";
+
+/// Connection settings for an S3-compatible object store
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    /// Endpoint URL, e.g. `http://127.0.0.1:9000` for a local MinIO
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// Key prefix for all batches (e.g. `merkseal/`)
+    pub prefix: String,
+    /// Use path-style URLs (`endpoint/bucket/key`), required by MinIO
+    pub path_style: bool,
+}
+
+/// S3-compatible object store backend (`<prefix><batch id>/<file>`)
+///
+/// Batch IDs are reserved with a conditional `PUT` (`If-None-Match: *`) of a
+/// marker object, and a batch is sealed by a conditional `PUT` of its
+/// manifest, so both are atomic even with several servers sharing a bucket.
+pub struct S3Store {
+    bucket: Bucket,
+    credentials: Credentials,
+    prefix: String,
+    client: Client,
+    next_id: AtomicU64,
+}
+
+impl S3Store {
+    /// Connect to the bucket and resume after the highest existing batch
+    pub async fn open(config: S3Config) -> Result<Self, StoreError> {
+        let endpoint: Url = config
+            .endpoint
+            .parse()
+            .map_err(|e| StoreError::Backend(format!("Invalid S3 endpoint: {}", e)))?;
+        let style = if config.path_style {
+            UrlStyle::Path
+        } else {
+            UrlStyle::VirtualHost
+        };
+        let bucket = Bucket::new(endpoint, style, config.bucket, config.region)
+            .map_err(|e| StoreError::Backend(format!("Invalid S3 bucket: {}", e)))?;
+
+        let store = Self {
+            bucket,
+            credentials: Credentials::new(config.access_key, config.secret_key),
+            prefix: config.prefix,
+            client: Client::new(),
+            next_id: AtomicU64::new(1),
+        };
+
+        let highest = store
+            .list_keys(&store.prefix, Some("/"))
+            .await?
+            .1
+            .iter()
+            .filter_map(|p| p.strip_prefix(&store.prefix))
+            .filter_map(|p| p.trim_end_matches('/').parse::<u64>().ok())
+            .max()
+            .unwrap_or(0);
+        store.next_id.store(highest + 1, Ordering::SeqCst);
+
+        Ok(store)
+    }
+
+    fn key(&self, batch_id: u64, name: &str) -> String {
+        format!("{}{}/{}", self.prefix, batch_id, name)
+    }
+
+    /// PUT an object, optionally only if it does not exist yet
+    ///
+    /// Returns `false` if the conditional put lost to an existing object.
+    async fn put(&self, key: &str, data: &[u8], if_absent: bool) -> Result<bool, StoreError> {
+        let url = self.bucket.put_object(Some(&self.credentials), key).sign(SIGN_DURATION);
+        let mut request = self.client.put(url).body(data.to_vec());
+        if if_absent {
+            request = request.header("If-None-Match", "*");
+        }
+        let response = request.send().await.map_err(http)?;
+        match response.status() {
+            s if s.is_success() => Ok(true),
+            StatusCode::PRECONDITION_FAILED | StatusCode::CONFLICT if if_absent => Ok(false),
+            s => Err(StoreError::Backend(format!("PUT {} failed: {}", key, s))),
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StoreError> {
+        let url = self.bucket.get_object(Some(&self.credentials), key).sign(SIGN_DURATION);
+        let response = self.client.get(url).send().await.map_err(http)?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            s if s.is_success() => Ok(Some(response.bytes().await.map_err(http)?.to_vec())),
+            s => Err(StoreError::Backend(format!("GET {} failed: {}", key, s))),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, StoreError> {
+        let url = self.bucket.head_object(Some(&self.credentials), key).sign(SIGN_DURATION);
+        let response = self.client.head(url).send().await.map_err(http)?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(false),
+            s if s.is_success() => Ok(true),
+            s => Err(StoreError::Backend(format!("HEAD {} failed: {}", key, s))),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StoreError> {
+        let url = self.bucket.delete_object(Some(&self.credentials), key).sign(SIGN_DURATION);
+        let response = self.client.delete(url).send().await.map_err(http)?;
+        if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+            return Err(StoreError::Backend(format!("DELETE {} failed: {}", key, response.status())));
+        }
+        Ok(())
+    }
+
+    /// List objects (key, size) and common prefixes under a prefix, following pagination
+    async fn list_keys(
+        &self,
+        prefix: &str,
+        delimiter: Option<&str>,
+    ) -> Result<(Vec<(String, u64)>, Vec<String>), StoreError> {
+        let mut objects = Vec::new();
+        let mut prefixes = Vec::new();
+        let mut token: Option<String> = None;
+
+        loop {
+            let mut action = self.bucket.list_objects_v2(Some(&self.credentials));
+            action.with_prefix(prefix);
+            if let Some(delimiter) = delimiter {
+                action.with_delimiter(delimiter);
+            }
+            if let Some(token) = &token {
+                action.with_continuation_token(token.clone());
+            }
+            let url = action.sign(SIGN_DURATION);
+
+            let response = self.client.get(url).send().await.map_err(http)?;
+            if !response.status().is_success() {
+                return Err(StoreError::Backend(format!("LIST {} failed: {}", prefix, response.status())));
+            }
+            let body = response.text().await.map_err(http)?;
+            let parsed = ListObjectsV2::parse_response(&body)
+                .map_err(|e| StoreError::Backend(format!("Invalid LIST response: {}", e)))?;
+
+            objects.extend(parsed.contents.into_iter().map(|c| (c.key, c.size)));
+            prefixes.extend(parsed.common_prefixes.into_iter().map(|p| p.prefix));
+
+            match parsed.next_continuation_token {
+                Some(next) => token = Some(next),
+                None => return Ok((objects, prefixes)),
+            }
+        }
+    }
+
+    async fn require_unsealed(&self, batch_id: u64) -> Result<(), StoreError> {
+        if !self.exists(&self.key(batch_id, RESERVATION_NAME)).await? {
+            return Err(StoreError::NotFound(format!("Batch {}", batch_id)));
+        }
+        if self.exists(&self.key(batch_id, MANIFEST_NAME)).await? {
+            return Err(StoreError::Sealed(batch_id));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BatchStore for S3Store {
+    async fn create_batch(&self) -> Result<u64, StoreError> {
+        loop {
+            let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+            if self.put(&self.key(id, RESERVATION_NAME), b"", true).await? {
+                return Ok(id);
+            }
+        }
+    }
+
+    async fn put_file(&self, batch_id: u64, name: &str, data: &[u8]) -> Result<(), StoreError> {
+        check_name(name)?;
+        self.require_unsealed(batch_id).await?;
+        self.put(&self.key(batch_id, name), data, false).await?;
+        Ok(())
+    }
+
+    async fn put_file_from_path(&self, batch_id: u64, name: &str, path: &Path) -> Result<(), StoreError> {
+        check_name(name)?;
+        self.require_unsealed(batch_id).await?;
+
+        // Stream the staged file instead of buffering it
+        let key = self.key(batch_id, name);
+        let file = tokio::fs::File::open(path).await?;
+        let size = file.metadata().await?.len();
+        let url = self.bucket.put_object(Some(&self.credentials), &key).sign(SIGN_DURATION);
+        let response = self
+            .client
+            .put(url)
+            .header(reqwest::header::CONTENT_LENGTH, size)
+            .body(reqwest::Body::wrap_stream(ReaderStream::new(file)))
+            .send()
+            .await
+            .map_err(http)?;
+        if !response.status().is_success() {
+            return Err(StoreError::Backend(format!("PUT {} failed: {}", key, response.status())));
+        }
+
+        tokio::fs::remove_file(path).await.ok();
+        Ok(())
+    }
+
+    async fn get_file(&self, batch_id: u64, name: &str) -> Result<Vec<u8>, StoreError> {
+        check_name(name)?;
+        self.get(&self.key(batch_id, name))
+            .await?
+            .ok_or_else(|| StoreError::NotFound(format!("File {} in batch {}", name, batch_id)))
+    }
+
+    async fn read_file_range(
+        &self,
+        batch_id: u64,
+        name: &str,
+        range: Range<u64>,
+    ) -> Result<FileStream, StoreError> {
+        check_name(name)?;
+        if range.is_empty() {
+            return Ok(futures_util::stream::empty().boxed());
+        }
+
+        let key = self.key(batch_id, name);
+        let url = self.bucket.get_object(Some(&self.credentials), &key).sign(SIGN_DURATION);
+        let response = self
+            .client
+            .get(url)
+            .header(reqwest::header::RANGE, format!("bytes={}-{}", range.start, range.end - 1))
+            .send()
+            .await
+            .map_err(http)?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Err(StoreError::NotFound(format!("File {} in batch {}", name, batch_id))),
+            s if s.is_success() => Ok(response.bytes_stream().map_err(io::Error::other).boxed()),
+            s => Err(StoreError::Backend(format!("GET {} failed: {}", key, s))),
+        }
+    }
+
+    async fn list_batch(&self, batch_id: u64) -> Result<Vec<StoredFile>, StoreError> {
+        let batch_prefix = self.key(batch_id, "");
+        let (objects, _) = self.list_keys(&batch_prefix, None).await?;
+        if objects.is_empty() {
+            return Err(StoreError::NotFound(format!("Batch {}", batch_id)));
+        }
+
+        let mut files: Vec<StoredFile> = objects
+            .into_iter()
+            .filter_map(|(key, size)| {
+                let name = key.strip_prefix(&batch_prefix)?.to_string();
+                (name != MANIFEST_NAME && !name.starts_with('.')).then_some(StoredFile { name, size })
+            })
+            .collect();
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    }
+
+    async fn seal(&self, batch_id: u64, manifest: &[u8]) -> Result<(), StoreError> {
+        self.require_unsealed(batch_id).await?;
+        if !self.put(&self.key(batch_id, MANIFEST_NAME), manifest, true).await? {
+            return Err(StoreError::Sealed(batch_id));
+        }
+        Ok(())
+    }
+
+    async fn write_manifest(&self, batch_id: u64, manifest: &[u8]) -> Result<(), StoreError> {
+        let key = self.key(batch_id, MANIFEST_NAME);
+        if !self.exists(&key).await? {
+            return Err(StoreError::NotFound(format!("Manifest of batch {}", batch_id)));
+        }
+        self.put(&key, manifest, false).await?;
+        Ok(())
+    }
+
+    async fn read_manifest(&self, batch_id: u64) -> Result<Vec<u8>, StoreError> {
+        self.get(&self.key(batch_id, MANIFEST_NAME))
+            .await?
+            .ok_or_else(|| StoreError::NotFound(format!("Manifest of batch {}", batch_id)))
+    }
+
+    async fn list_batches(&self) -> Result<Vec<u64>, StoreError> {
+        let (objects, _) = self.list_keys(&self.prefix, None).await?;
+        let suffix = format!("/{}", MANIFEST_NAME);
+        let mut ids: Vec<u64> = objects
+            .iter()
+            .filter_map(|(key, _)| key.strip_prefix(&self.prefix)?.strip_suffix(&suffix)?.parse().ok())
+            .collect();
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    async fn abort(&self, batch_id: u64) -> Result<(), StoreError> {
+        if self.exists(&self.key(batch_id, MANIFEST_NAME)).await? {
+            return Err(StoreError::Sealed(batch_id));
+        }
+        let (objects, _) = self.list_keys(&self.key(batch_id, ""), None).await?;
+        for (key, _) in objects {
+            self.delete(&key).await?;
+        }
+        Ok(())
+    }
+
+    fn location(&self, batch_id: u64) -> String {
+        format!("s3://{}/{}", self.bucket.name(), self.key(batch_id, ""))
+    }
+}
+
+fn http(e: reqwest::Error) -> StoreError {
+    StoreError::Backend(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs against a real S3-compatible store, e.g. a local MinIO:
+    ///
+    /// ```text
+    /// docker run -p 9000:9000 minio/minio server /data
+    /// mc mb local/merkseal-test
+    /// MERKSEAL_TEST_S3_ENDPOINT=http://127.0.0.1:9000 cargo test -p server -- --ignored
+    /// ```
+    #[tokio::test]
+    #[ignore = "requires an S3-compatible endpoint (MERKSEAL_TEST_S3_ENDPOINT)"]
+    async fn test_s3_store_conformance() {
+        let endpoint = std::env::var("MERKSEAL_TEST_S3_ENDPOINT").expect("MERKSEAL_TEST_S3_ENDPOINT");
+        let env = |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+        let nonce = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+
+        let store = S3Store::open(S3Config {
+            endpoint,
+            bucket: env("MERKSEAL_TEST_S3_BUCKET", "merkseal-test"),
+            region: env("MERKSEAL_TEST_S3_REGION", "us-east-1"),
+            access_key: env("MERKSEAL_TEST_S3_ACCESS_KEY", "minioadmin"),
+            secret_key: env("MERKSEAL_TEST_S3_SECRET_KEY", "minioadmin"),
+            prefix: format!("test-{}/", nonce),
+            path_style: true,
+        })
+        .await
+        .unwrap();
+
+        super::super::conformance::run(&store).await;
+    }
+}
@@ -0,0 +1,313 @@
+use super::fs::{move_file, stream_file_range, write_atomic};
+use super::{check_name, BatchStore, FileStream, StoreError, StoredFile, MANIFEST_NAME};
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SCHEMA: &str = "
+    PRAGMA foreign_keys = ON;
+    PRAGMA journal_mode = WAL;
+    CREATE TABLE IF NOT EXISTS batches (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        created_at INTEGER NOT NULL,
+        sealed INTEGER NOT NULL DEFAULT 0,
+        manifest BLOB
+    );
+    CREATE TABLE IF NOT EXISTS files (
+        batch_id INTEGER NOT NULL REFERENCES batches(id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        PRIMARY KEY (batch_id, name)
+    );
+";
+
+/// Filesystem backend with a SQLite index of batches and files
+///
+/// File contents live on disk in the same layout as `FsStore`; the index is
+/// the source of truth for which batches exist and are sealed. `AUTOINCREMENT`
+/// guarantees batch IDs are never reused, even after an abort.
+pub struct SqliteStore {
+    root: PathBuf,
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Open (and create if needed) the index and file root
+    pub fn open(root: impl Into<PathBuf>, database: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+
+        let conn = Connection::open(database).map_err(backend)?;
+        conn.execute_batch(SCHEMA).map_err(backend)?;
+
+        // Never hand out IDs below directories already on disk
+        let highest_dir = highest_numeric_dir(&root)?;
+        let seq: u64 = conn
+            .query_row("SELECT seq FROM sqlite_sequence WHERE name = 'batches'", [], |r| r.get(0))
+            .optional()
+            .map_err(backend)?
+            .unwrap_or(0);
+        if highest_dir > seq {
+            conn.execute("DELETE FROM sqlite_sequence WHERE name = 'batches'", [])
+                .map_err(backend)?;
+            conn.execute(
+                "INSERT INTO sqlite_sequence (name, seq) VALUES ('batches', ?1)",
+                params![highest_dir],
+            )
+            .map_err(backend)?;
+        }
+
+        Ok(Self {
+            root,
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn batch_dir(&self, batch_id: u64) -> PathBuf {
+        self.root.join(batch_id.to_string())
+    }
+
+    /// Look up whether a batch is sealed (`None` if it does not exist)
+    fn sealed(conn: &Connection, batch_id: u64) -> Result<Option<bool>, StoreError> {
+        conn.query_row("SELECT sealed FROM batches WHERE id = ?1", params![batch_id], |r| {
+            r.get::<_, bool>(0)
+        })
+        .optional()
+        .map_err(backend)
+    }
+
+    fn require_file(&self, batch_id: u64, name: &str) -> Result<(), StoreError> {
+        check_name(name)?;
+        let indexed = self
+            .conn()
+            .query_row(
+                "SELECT 1 FROM files WHERE batch_id = ?1 AND name = ?2",
+                params![batch_id, name],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(backend)?;
+        if indexed.is_none() {
+            return Err(StoreError::NotFound(format!("File {} in batch {}", name, batch_id)));
+        }
+        Ok(())
+    }
+
+    fn require_batch(conn: &Connection, batch_id: u64) -> Result<bool, StoreError> {
+        Self::sealed(conn, batch_id)?.ok_or_else(|| StoreError::NotFound(format!("Batch {}", batch_id)))
+    }
+}
+
+#[async_trait]
+impl BatchStore for SqliteStore {
+    async fn create_batch(&self) -> Result<u64, StoreError> {
+        let conn = self.conn();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        loop {
+            conn.execute("INSERT INTO batches (created_at) VALUES (?1)", params![now])
+                .map_err(backend)?;
+            let id = conn.last_insert_rowid() as u64;
+
+            match fs::create_dir(self.batch_dir(id)) {
+                Ok(()) => return Ok(id),
+                // Directory left by another backend; skip the ID
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => {
+                    conn.execute("DELETE FROM batches WHERE id = ?1", params![id]).ok();
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+
+    async fn put_file(&self, batch_id: u64, name: &str, data: &[u8]) -> Result<(), StoreError> {
+        check_name(name)?;
+        let conn = self.conn();
+        if Self::require_batch(&conn, batch_id)? {
+            return Err(StoreError::Sealed(batch_id));
+        }
+
+        write_atomic(&self.batch_dir(batch_id), name, data)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO files (batch_id, name, size) VALUES (?1, ?2, ?3)",
+            params![batch_id, name, data.len() as u64],
+        )
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn put_file_from_path(&self, batch_id: u64, name: &str, path: &Path) -> Result<(), StoreError> {
+        check_name(name)?;
+        let conn = self.conn();
+        if Self::require_batch(&conn, batch_id)? {
+            return Err(StoreError::Sealed(batch_id));
+        }
+
+        let size = fs::metadata(path)?.len();
+        move_file(path, &self.batch_dir(batch_id).join(name))?;
+        conn.execute(
+            "INSERT OR REPLACE INTO files (batch_id, name, size) VALUES (?1, ?2, ?3)",
+            params![batch_id, name, size],
+        )
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn get_file(&self, batch_id: u64, name: &str) -> Result<Vec<u8>, StoreError> {
+        self.require_file(batch_id, name)?;
+        Ok(fs::read(self.batch_dir(batch_id).join(name))?)
+    }
+
+    async fn read_file_range(
+        &self,
+        batch_id: u64,
+        name: &str,
+        range: Range<u64>,
+    ) -> Result<FileStream, StoreError> {
+        self.require_file(batch_id, name)?;
+        Ok(stream_file_range(&self.batch_dir(batch_id).join(name), range).await?)
+    }
+
+    async fn list_batch(&self, batch_id: u64) -> Result<Vec<StoredFile>, StoreError> {
+        let conn = self.conn();
+        Self::require_batch(&conn, batch_id)?;
+
+        let mut stmt = conn
+            .prepare("SELECT name, size FROM files WHERE batch_id = ?1 ORDER BY name")
+            .map_err(backend)?;
+        let files = stmt
+            .query_map(params![batch_id], |r| {
+                Ok(StoredFile {
+                    name: r.get(0)?,
+                    size: r.get(1)?,
+                })
+            })
+            .map_err(backend)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(backend)?;
+        Ok(files)
+    }
+
+    async fn seal(&self, batch_id: u64, manifest: &[u8]) -> Result<(), StoreError> {
+        let conn = self.conn();
+        if Self::require_batch(&conn, batch_id)? {
+            return Err(StoreError::Sealed(batch_id));
+        }
+
+        // Mirror the manifest on disk so the directory stays self-describing
+        write_atomic(&self.batch_dir(batch_id), MANIFEST_NAME, manifest)?;
+        conn.execute(
+            "UPDATE batches SET sealed = 1, manifest = ?2 WHERE id = ?1 AND sealed = 0",
+            params![batch_id, manifest],
+        )
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn write_manifest(&self, batch_id: u64, manifest: &[u8]) -> Result<(), StoreError> {
+        let conn = self.conn();
+        if !Self::require_batch(&conn, batch_id)? {
+            return Err(StoreError::NotFound(format!("Manifest of batch {}", batch_id)));
+        }
+
+        write_atomic(&self.batch_dir(batch_id), MANIFEST_NAME, manifest)?;
+        conn.execute(
+            "UPDATE batches SET manifest = ?2 WHERE id = ?1",
+            params![batch_id, manifest],
+        )
+        .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn read_manifest(&self, batch_id: u64) -> Result<Vec<u8>, StoreError> {
+        self.conn()
+            .query_row(
+                "SELECT manifest FROM batches WHERE id = ?1 AND sealed = 1",
+                params![batch_id],
+                |r| r.get(0),
+            )
+            .optional()
+            .map_err(backend)?
+            .ok_or_else(|| StoreError::NotFound(format!("Manifest of batch {}", batch_id)))
+    }
+
+    async fn list_batches(&self) -> Result<Vec<u64>, StoreError> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare("SELECT id FROM batches WHERE sealed = 1 ORDER BY id")
+            .map_err(backend)?;
+        let ids = stmt
+            .query_map([], |r| r.get(0))
+            .map_err(backend)?
+            .collect::<Result<Vec<u64>, _>>()
+            .map_err(backend)?;
+        Ok(ids)
+    }
+
+    async fn abort(&self, batch_id: u64) -> Result<(), StoreError> {
+        let conn = self.conn();
+        if Self::sealed(&conn, batch_id)? == Some(true) {
+            return Err(StoreError::Sealed(batch_id));
+        }
+
+        conn.execute("DELETE FROM batches WHERE id = ?1", params![batch_id])
+            .map_err(backend)?;
+        match fs::remove_dir_all(self.batch_dir(batch_id)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn location(&self, batch_id: u64) -> String {
+        self.batch_dir(batch_id).display().to_string()
+    }
+}
+
+pub(super) fn backend(e: rusqlite::Error) -> StoreError {
+    StoreError::Backend(e.to_string())
+}
+
+fn highest_numeric_dir(root: &Path) -> io::Result<u64> {
+    let mut highest = 0;
+    for entry in fs::read_dir(root)? {
+        if let Some(id) = entry?.file_name().to_str().and_then(|s| s.parse::<u64>().ok()) {
+            highest = highest.max(id);
+        }
+    }
+    Ok(highest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sqlite_store_conformance() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open(dir.path().join("files"), dir.path().join("index.db")).unwrap();
+        super::super::conformance::run(&store).await;
+    }
+
+    #[tokio::test]
+    async fn test_ids_continue_after_existing_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("files");
+        fs::create_dir_all(root.join("41")).unwrap();
+
+        let store = SqliteStore::open(&root, dir.path().join("index.db")).unwrap();
+        assert_eq!(store.create_batch().await.unwrap(), 42);
+    }
+}
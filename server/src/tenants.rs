@@ -0,0 +1,342 @@
+//! Per-tenant batch namespaces
+//!
+//! Every tenant gets its own batch ID space, storage location, lookup index
+//! and open sessions, so batch 1 of one tenant is unrelated to batch 1 of
+//! another and listings never cross tenants. The default tenant keeps the
+//! original single-tenant layout; other tenants live under a `tenants/{id}/`
+//! prefix next to each configured path. Content-addressed data (chunks,
+//! published manifests) stays shared.
+
+use crate::anchor::{AnchorQueue, TenantStores};
+use crate::auth::Principal;
+use crate::config::ServerConfig;
+use crate::lookup::{self, IndexedStore, LookupIndex};
+use crate::quotas::{Limit, QuotaExceeded, Quotas, Reservation};
+use crate::rolling;
+use crate::sessions::{self, SessionRegistry};
+use crate::store::{self, BatchStore, BlobStore, StorageConfig, StoreError};
+use crate::unix_now;
+use actix_web::dev::Payload;
+use actix_web::{error::InternalError, web, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use async_trait::async_trait;
+use futures_util::future::LocalBoxFuture;
+use mantle_config::MantleConfig;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+/// Tenant of unauthenticated servers and of batches created before tenants existed
+pub const DEFAULT_TENANT: &str = "default";
+
+/// Whether `id` can name a tenant (`[a-z0-9][a-z0-9_-]{0,62}`)
+pub fn is_valid_tenant(id: &str) -> bool {
+    let mut chars = id.chars();
+    chars.next().is_some_and(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && id.len() <= 63
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
+/// One tenant's batches and the indexes over them
+pub struct Workspace {
+    pub tenant: String,
+    /// Server configuration with this tenant's paths
+    pub config: Arc<ServerConfig>,
+    pub store: Arc<dyn BatchStore>,
+    pub lookup: Arc<LookupIndex>,
+    pub sessions: Arc<SessionRegistry>,
+    /// Limits and usage counters, shared by all tenants
+    pub quotas: Arc<Quotas>,
+}
+
+impl Workspace {
+    /// Open a tenant's storage and start its background tasks
+    async fn open(tenant: &str, opener: &Opener, quotas: Arc<Quotas>) -> Result<Arc<Self>, StoreError> {
+        let config = Arc::new(tenant_config(&opener.config, tenant));
+
+        let store = store::open(&config.storage).await?;
+        let store = match &config.blobs {
+            Some(blob_config) => {
+                let blobs = Arc::new(BlobStore::open(store, &blob_config.dir)?);
+                tokio::spawn(store::run_gc(blobs.clone(), blob_config.gc_interval));
+                blobs as Arc<dyn BatchStore>
+            }
+            None => store,
+        };
+
+        // Index roots and leaves as batches are sealed, catching up on older ones
+        let lookup = Arc::new(LookupIndex::open(&config.lookup_db)?);
+        {
+            let (index, store, tenant) = (lookup.clone(), store.clone(), tenant.to_string());
+            tokio::spawn(async move {
+                match lookup::backfill(&index, store.as_ref()).await {
+                    Ok(0) => {}
+                    Ok(added) => println!("✓ Lookup index ({}): added {} existing batch(es)", tenant, added),
+                    Err(e) => eprintln!("✗ Failed to backfill lookup index of {}: {}", tenant, e),
+                }
+            });
+        }
+        let store: Arc<dyn BatchStore> = Arc::new(IndexedStore::new(store, lookup.clone()));
+
+        if let Some(parent) = Path::new(&config.session_file).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let sessions = Arc::new(SessionRegistry::open(&config.session_file, config.session_ttl)?.with_rolling(config.rolling));
+        tokio::spawn(sessions::run_sweeper(
+            sessions.clone(),
+            store.clone(),
+            Duration::from_secs(config.session_ttl.min(60)),
+        ));
+
+        let workspace = Arc::new(Self {
+            tenant: tenant.to_string(),
+            config,
+            store,
+            lookup,
+            sessions,
+            quotas,
+        });
+        if workspace.config.rolling.is_some() {
+            tokio::spawn(rolling::run_roller(
+                workspace.clone(),
+                opener.mantle.clone(),
+                opener.anchor_queue.clone(),
+            ));
+        }
+        println!("✓ Tenant {} ready ({} storage)", tenant, workspace.config.storage.kind());
+        Ok(workspace)
+    }
+
+    /// Reserve `amount` of a daily limit, refusing if this tenant has used it up
+    ///
+    /// Settle the reservation once the work is done; dropping it gives the amount back.
+    pub fn reserve(&self, limit: Limit, amount: u64) -> Result<Reservation, QuotaExceeded> {
+        self.quotas.reserve(&self.tenant, limit, amount, unix_now())
+    }
+}
+
+/// Server configuration with a tenant's storage, index, session and staging paths
+///
+/// The default tenant keeps the configured paths unchanged.
+pub fn tenant_config(base: &ServerConfig, tenant: &str) -> ServerConfig {
+    let mut config = base.clone();
+    if tenant == DEFAULT_TENANT {
+        return config;
+    }
+
+    config.storage = match &base.storage {
+        StorageConfig::Filesystem { root } => StorageConfig::Filesystem {
+            root: tenant_dir(root, tenant),
+        },
+        StorageConfig::Sqlite { root, database } => StorageConfig::Sqlite {
+            root: tenant_dir(root, tenant),
+            database: tenant_path(database, tenant),
+        },
+        StorageConfig::S3(s3) => {
+            let mut s3 = s3.clone();
+            s3.prefix = format!("{}tenants/{}/", s3.prefix, tenant);
+            StorageConfig::S3(s3)
+        }
+    };
+    if let Some(blobs) = &mut config.blobs {
+        blobs.dir = tenant_path(&blobs.dir, tenant);
+    }
+    config.lookup_db = tenant_path(&base.lookup_db, tenant);
+    config.staging_dir = tenant_path(&base.staging_dir, tenant);
+    config.session_file = tenant_path(&base.session_file, tenant);
+    config
+}
+
+/// `{dir}/tenants/{tenant}`
+fn tenant_dir(dir: &str, tenant: &str) -> String {
+    Path::new(dir).join("tenants").join(tenant).display().to_string()
+}
+
+/// `{parent}/tenants/{tenant}/{name}` for a file or directory at `path`
+fn tenant_path(path: &str, tenant: &str) -> String {
+    let path = Path::new(path);
+    let parent = path.parent().unwrap_or(Path::new(""));
+    let name = path.file_name().unwrap_or_default();
+    parent.join("tenants").join(tenant).join(name).display().to_string()
+}
+
+/// What opening a tenant on first use needs
+struct Opener {
+    mantle: Arc<MantleConfig>,
+    config: Arc<ServerConfig>,
+    anchor_queue: Option<web::Data<Arc<AnchorQueue>>>,
+}
+
+/// Open tenants by ID
+///
+/// Tenants are opened on their first request (or at startup when listed in
+/// the API key file) and stay open, together with their sweeper and roller.
+pub struct Tenants {
+    /// `None` serves only the tenants it was created with
+    opener: Option<Opener>,
+    quotas: Arc<Quotas>,
+    /// One cell per tenant, so opening a tenant only holds up its own requests
+    open: Mutex<HashMap<String, Arc<OnceCell<Arc<Workspace>>>>>,
+}
+
+impl Tenants {
+    pub fn new(
+        mantle: Arc<MantleConfig>,
+        config: Arc<ServerConfig>,
+        anchor_queue: Option<web::Data<Arc<AnchorQueue>>>,
+        quotas: Arc<Quotas>,
+    ) -> Self {
+        Self {
+            opener: Some(Opener {
+                mantle,
+                config,
+                anchor_queue,
+            }),
+            quotas,
+            open: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Serve only the default tenant, from already opened parts
+    #[cfg(test)]
+    pub fn single(
+        config: Arc<ServerConfig>,
+        store: Arc<dyn BatchStore>,
+        lookup: Arc<LookupIndex>,
+        sessions: Arc<SessionRegistry>,
+    ) -> Self {
+        let quotas = Arc::new(Quotas::open(&config.quotas).expect("quota files"));
+        let workspace = Arc::new(Workspace {
+            tenant: DEFAULT_TENANT.to_string(),
+            config,
+            store,
+            lookup,
+            sessions,
+            quotas: quotas.clone(),
+        });
+        Self {
+            opener: None,
+            quotas,
+            open: Mutex::new(HashMap::from([(
+                DEFAULT_TENANT.to_string(),
+                Arc::new(OnceCell::new_with(Some(workspace))),
+            )])),
+        }
+    }
+
+    /// Limits and usage counters of all tenants
+    pub fn quotas(&self) -> &Arc<Quotas> {
+        &self.quotas
+    }
+
+    /// A tenant's workspace, opening it if needed
+    pub async fn get(&self, tenant: &str) -> Result<Arc<Workspace>, StoreError> {
+        if !is_valid_tenant(tenant) {
+            return Err(StoreError::Backend(format!("Invalid tenant ID {:?}", tenant)));
+        }
+        let cell = {
+            let mut open = self.open.lock().unwrap_or_else(|e| e.into_inner());
+            match &self.opener {
+                Some(_) => open.entry(tenant.to_string()).or_default().clone(),
+                None => open.get(tenant).cloned().unwrap_or_default(),
+            }
+        };
+        // A failed open leaves the cell empty, so the next request retries
+        cell.get_or_try_init(|| async {
+            match &self.opener {
+                Some(opener) => Workspace::open(tenant, opener, self.quotas.clone()).await,
+                None => Err(StoreError::NotFound(format!("Tenant {}", tenant))),
+            }
+        })
+        .await
+        .cloned()
+    }
+}
+
+#[async_trait]
+impl TenantStores for Tenants {
+    async fn store(&self, tenant: &str) -> Result<Arc<dyn BatchStore>, StoreError> {
+        Ok(self.get(tenant).await?.store.clone())
+    }
+}
+
+/// The calling tenant's workspace
+///
+/// Taken from the authenticated principal, or the default tenant when
+/// authentication is off.
+pub struct Tenant(pub Arc<Workspace>);
+
+impl Deref for Tenant {
+    type Target = Workspace;
+
+    fn deref(&self) -> &Workspace {
+        &self.0
+    }
+}
+
+impl FromRequest for Tenant {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let tenant = req
+            .extensions()
+            .get::<Principal>()
+            .map_or_else(|| DEFAULT_TENANT.to_string(), |principal| principal.tenant.clone());
+        let tenants = req.app_data::<web::Data<Arc<Tenants>>>().cloned();
+        Box::pin(async move {
+            let opened = match tenants {
+                Some(tenants) => tenants.get(&tenant).await,
+                None => Err(StoreError::Backend("No tenant registry configured".to_string())),
+            };
+            opened.map(Tenant).map_err(|e| {
+                let response = HttpResponse::InternalServerError().json(serde_json::json!({
+                    "success": false,
+                    "error": format!("Failed to open tenant {}: {}", tenant, e)
+                }));
+                InternalError::from_response(e, response).into()
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::S3Config;
+
+    #[test]
+    fn test_tenant_ids_and_paths() {
+        assert!(is_valid_tenant("acme"));
+        assert!(is_valid_tenant("team-7_b"));
+        for invalid in ["", "Acme", "-acme", "a/b", "..", &"a".repeat(64)] {
+            assert!(!is_valid_tenant(invalid), "{:?}", invalid);
+        }
+
+        assert_eq!(tenant_path("batches/.lookup.db", "acme"), "batches/tenants/acme/.lookup.db");
+        assert_eq!(tenant_dir("batches", "acme"), "batches/tenants/acme");
+
+        let s3 = StorageConfig::S3(S3Config {
+            endpoint: "http://localhost:9000".into(),
+            bucket: "merkseal".into(),
+            region: "us-east-1".into(),
+            access_key: "key".into(),
+            secret_key: "secret".into(),
+            prefix: "batches/".into(),
+            path_style: true,
+        });
+        let StorageConfig::S3(s3) = tenant_storage(s3, "acme") else {
+            unreachable!()
+        };
+        assert_eq!(s3.prefix, "batches/tenants/acme/");
+    }
+
+    fn tenant_storage(storage: StorageConfig, tenant: &str) -> StorageConfig {
+        let config = crate::auth::tests::server_config(Path::new("/srv"), storage);
+        let default = tenant_config(&config, DEFAULT_TENANT);
+        assert_eq!(default.lookup_db, config.lookup_db);
+        tenant_config(&config, tenant).storage
+    }
+}
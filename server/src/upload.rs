@@ -0,0 +1,458 @@
+use crate::chunk_store::{ChunkClaims, ChunkStore};
+use crate::filenames::BatchFileNames;
+use crate::quotas::{QuotaExceeded, Reservation};
+use crate::{unix_now, FileEntry};
+use actix_multipart::Multipart;
+use actix_web::web::Bytes;
+use actix_web::HttpResponse;
+use futures_util::{Stream, StreamExt, TryStreamExt};
+use merkle_tree::{leaf_cid, root_cid, Chunker, DataHasher, Hash, StreamingChunker};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// Size limits applied while receiving an upload
+#[derive(Debug, Clone, Copy)]
+pub struct UploadLimits {
+    /// Largest accepted single file in bytes
+    pub max_file_bytes: u64,
+    /// Largest accepted total upload per batch in bytes
+    pub max_batch_bytes: u64,
+}
+
+/// A file that has been fully received and hashed but not yet stored
+#[derive(Debug)]
+pub struct StagedFile {
+    pub name: String,
+    pub original_name: String,
+    pub size: u64,
+    pub leaf: Hash,
+    /// SHA-256 of the whole file (equal to `leaf` unless chunked)
+    pub sha256: Hash,
+    /// Chunk hashes when stored as content-defined chunks
+    pub chunks: Option<Vec<Hash>>,
+    /// Staged contents on disk (`None` for chunked files)
+    pub path: Option<PathBuf>,
+}
+
+impl StagedFile {
+    /// Manifest entry for this file
+    pub fn entry(&self) -> FileEntry {
+        // Multi-chunk leaves are tree roots, not raw blocks
+        let cid = match &self.chunks {
+            Some(chunks) if chunks.len() > 1 => root_cid(&self.leaf),
+            _ => leaf_cid(&self.leaf),
+        };
+
+        FileEntry {
+            name: self.name.clone(),
+            original_name: self.original_name.clone(),
+            size: self.size,
+            leaf: hex::encode(self.leaf),
+            cid,
+            chunks: self
+                .chunks
+                .as_ref()
+                .map(|chunks| chunks.iter().map(hex::encode).collect()),
+            sha256: (self.sha256 != self.leaf).then(|| hex::encode(self.sha256)),
+        }
+    }
+}
+
+/// Streams incoming files to a per-batch staging directory, hashing as they arrive
+///
+/// Nothing becomes part of a batch until the caller moves the staged files
+/// into the store and calls [`Stager::commit`]. The staging directory is
+/// removed when the stager is dropped, along with the chunks of an upload that
+/// was never committed, so an aborted upload leaves nothing behind.
+pub struct Stager {
+    dir: PathBuf,
+    limits: UploadLimits,
+    batch_bytes: u64,
+    /// The tenant's `bytes_per_day`, reserved as bytes arrive
+    quota: Option<Reservation>,
+    chunk_store: Option<ChunkStore>,
+    /// Chunks this upload created or reused while they were still unclaimed by a committed upload
+    claims: ChunkClaims,
+    /// The staged files are referenced by a batch, so their chunks are kept
+    committed: bool,
+    /// Chunks written to the chunk store by this upload
+    pub new_chunks: usize,
+    /// Chunks that were already present in the chunk store
+    pub reused_chunks: usize,
+}
+
+impl Stager {
+    /// Create the staging directory for one batch
+    ///
+    /// With a chunk store, files are split into content-defined chunks and
+    /// written there instead of being staged whole.
+    pub async fn create(
+        staging_root: &Path,
+        batch_id: u64,
+        limits: UploadLimits,
+        chunk_store: Option<ChunkStore>,
+    ) -> std::io::Result<Self> {
+        let dir = staging_root.join(batch_id.to_string());
+        tokio::fs::create_dir_all(&dir).await?;
+        Ok(Self {
+            dir,
+            limits,
+            batch_bytes: 0,
+            quota: None,
+            chunk_store,
+            claims: ChunkClaims::default(),
+            committed: false,
+            new_chunks: 0,
+            reused_chunks: 0,
+        })
+    }
+
+    /// Count bytes already stored in the batch toward `max_batch_bytes`
+    pub fn with_received(mut self, bytes: u64) -> Self {
+        self.batch_bytes = bytes;
+        self
+    }
+
+    /// Reserve received bytes from the tenant's daily quota, stopping the
+    /// upload once it would pass it
+    ///
+    /// The bytes are counted when the upload is committed.
+    pub fn with_quota(mut self, quota: Reservation) -> Self {
+        self.quota = Some(quota);
+        self
+    }
+
+    /// Keep the chunks written by this upload once a batch references its files,
+    /// and count its bytes against the tenant's quota
+    ///
+    /// Chunks of an upload dropped without being committed are deleted unless
+    /// a committed upload has used them too, and its reserved bytes are released.
+    pub fn commit(&mut self) {
+        self.committed = true;
+        if let Some(quota) = self.quota.take() {
+            quota.settle(unix_now());
+        }
+    }
+
+    /// Receive every file of a multipart upload under unique, sanitized names
+    ///
+    /// Any stream error aborts the upload; the caller must abort the batch.
+    pub async fn stage_multipart(&mut self, mut payload: Multipart) -> Result<Vec<StagedFile>, UploadError> {
+        let mut staged = Vec::new();
+        let mut names = BatchFileNames::new();
+
+        while let Some(field) = payload
+            .try_next()
+            .await
+            .map_err(|e| UploadError::BadRequest(format!("Upload interrupted: {}", e)))?
+        {
+            let original_name = field
+                .content_disposition()
+                .and_then(|cd| cd.get_filename().map(|s| s.to_string()));
+            let filename = names
+                .assign(original_name.as_deref(), staged.len())
+                .map_err(|e| UploadError::BadRequest(e.to_string()))?;
+            let original_name = original_name.unwrap_or_else(|| filename.clone());
+            staged.push(self.stage(filename, original_name, field).await?);
+        }
+
+        if staged.is_empty() {
+            return Err(UploadError::BadRequest("No files uploaded".to_string()));
+        }
+        Ok(staged)
+    }
+
+    /// Receive one file from a byte stream
+    ///
+    /// Any stream error aborts the file; the caller must abort the batch.
+    pub async fn stage<S, E>(
+        &mut self,
+        name: String,
+        original_name: String,
+        mut stream: S,
+    ) -> Result<StagedFile, UploadError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin,
+        E: fmt::Display,
+    {
+        let mut size = 0u64;
+        let mut hasher = DataHasher::new();
+        let mut chunker = self
+            .chunk_store
+            .as_ref()
+            .map(|_| StreamingChunker::new(Chunker::default()));
+        let mut chunk_hashes = Vec::new();
+
+        let path = self.dir.join(&name);
+        let mut file = match chunker {
+            Some(_) => None,
+            None => Some(tokio::fs::File::create(&path).await.map_err(|e| {
+                UploadError::Internal(format!("Failed to stage file {}: {}", name, e))
+            })?),
+        };
+
+        while let Some(piece) = stream.next().await {
+            let piece = piece
+                .map_err(|e| UploadError::BadRequest(format!("Upload of {} interrupted: {}", name, e)))?;
+
+            size += piece.len() as u64;
+            self.batch_bytes += piece.len() as u64;
+            if size > self.limits.max_file_bytes {
+                return Err(UploadError::TooLarge(format!(
+                    "File {} exceeds the {} byte limit",
+                    name, self.limits.max_file_bytes
+                )));
+            }
+            if self.batch_bytes > self.limits.max_batch_bytes {
+                return Err(UploadError::TooLarge(format!(
+                    "Batch exceeds the {} byte limit",
+                    self.limits.max_batch_bytes
+                )));
+            }
+            if let Some(quota) = &mut self.quota {
+                quota.grow(piece.len() as u64, unix_now()).map_err(UploadError::Quota)?;
+            }
+
+            hasher.update(&piece);
+            match (&mut chunker, &mut file) {
+                (Some(chunker), _) => {
+                    for (chunk, data) in chunker.update(&piece) {
+                        self.store_chunk(&chunk.hash, &data)?;
+                        chunk_hashes.push(chunk.hash);
+                    }
+                }
+                (None, Some(file)) => {
+                    file.write_all(&piece).await.map_err(|e| {
+                        UploadError::Internal(format!("Failed to stage file {}: {}", name, e))
+                    })?;
+                }
+                (None, None) => unreachable!("unchunked uploads always have a staging file"),
+            }
+        }
+
+        let sha256 = hasher.finalize();
+        let (leaf, chunks, path) = match (chunker, file) {
+            (Some(chunker), _) => {
+                let (rest, leaf) = chunker.finish();
+                for (chunk, data) in rest {
+                    self.store_chunk(&chunk.hash, &data)?;
+                    chunk_hashes.push(chunk.hash);
+                }
+                (leaf, Some(chunk_hashes), None)
+            }
+            (None, Some(mut file)) => {
+                file.flush().await.map_err(|e| {
+                    UploadError::Internal(format!("Failed to stage file {}: {}", name, e))
+                })?;
+                file.sync_all().await.map_err(|e| {
+                    UploadError::Internal(format!("Failed to stage file {}: {}", name, e))
+                })?;
+                (sha256, None, Some(path))
+            }
+            (None, None) => unreachable!("unchunked uploads always have a staging file"),
+        };
+
+        Ok(StagedFile {
+            name,
+            original_name,
+            size,
+            leaf,
+            sha256,
+            chunks,
+            path,
+        })
+    }
+
+    fn store_chunk(&mut self, hash: &Hash, data: &[u8]) -> Result<(), UploadError> {
+        let store = self.chunk_store.as_ref().expect("chunked upload without chunk store");
+        match store.put(hash, data, &mut self.claims) {
+            Ok(true) => self.new_chunks += 1,
+            Ok(false) => self.reused_chunks += 1,
+            Err(e) => return Err(UploadError::Internal(format!("Failed to save chunk: {}", e))),
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Stager {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.dir).ok();
+        if let Some(store) = &self.chunk_store {
+            store.release(std::mem::take(&mut self.claims), self.committed);
+        }
+    }
+}
+
+/// Reasons an upload is refused
+#[derive(Debug)]
+pub enum UploadError {
+    /// Malformed or interrupted request (400)
+    BadRequest(String),
+    /// A size limit was exceeded (413)
+    TooLarge(String),
+    /// The tenant's daily byte quota was exceeded (429)
+    Quota(QuotaExceeded),
+    /// Server-side failure (500)
+    Internal(String),
+}
+
+impl UploadError {
+    /// JSON error response with the matching status code
+    pub fn response(&self) -> HttpResponse {
+        let mut builder = match self {
+            UploadError::Quota(e) => return e.response(),
+            UploadError::BadRequest(_) => HttpResponse::BadRequest(),
+            UploadError::TooLarge(_) => HttpResponse::PayloadTooLarge(),
+            UploadError::Internal(_) => HttpResponse::InternalServerError(),
+        };
+        builder.json(serde_json::json!({
+            "success": false,
+            "error": self.to_string()
+        }))
+    }
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UploadError::BadRequest(msg) | UploadError::TooLarge(msg) | UploadError::Internal(msg) => {
+                write!(f, "{}", msg)
+            }
+            UploadError::Quota(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream;
+    use merkle_tree::hash_data;
+
+    const LIMITS: UploadLimits = UploadLimits {
+        max_file_bytes: 10,
+        max_batch_bytes: 15,
+    };
+
+    fn pieces(parts: &[&'static [u8]]) -> impl Stream<Item = Result<Bytes, String>> + Unpin {
+        stream::iter(parts.iter().map(|p| Ok(Bytes::from_static(p))).collect::<Vec<_>>())
+    }
+
+    #[tokio::test]
+    async fn test_stage_hashes_incrementally() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut stager = Stager::create(dir.path(), 1, LIMITS, None).await.unwrap();
+
+        let staged = stager
+            .stage("a.txt".into(), "a.txt".into(), pieces(&[b"hel", b"lo"]))
+            .await
+            .unwrap();
+        assert_eq!(staged.leaf, hash_data(b"hello"));
+        assert_eq!(staged.size, 5);
+        assert_eq!(std::fs::read(staged.path.unwrap()).unwrap(), b"hello");
+
+        drop(stager);
+        assert!(!dir.path().join("1").exists());
+    }
+
+    #[tokio::test]
+    async fn test_size_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut stager = Stager::create(dir.path(), 1, LIMITS, None).await.unwrap();
+
+        let err = stager
+            .stage("big".into(), "big".into(), pieces(&[b"0123456789", b"x"]))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UploadError::TooLarge(_)));
+
+        let mut stager = Stager::create(dir.path(), 2, LIMITS, None).await.unwrap();
+        stager
+            .stage("a".into(), "a".into(), pieces(&[b"0123456789"]))
+            .await
+            .unwrap();
+        let err = stager
+            .stage("b".into(), "b".into(), pieces(&[b"012345"]))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UploadError::TooLarge(_)));
+        assert_eq!(err.response().status(), 413);
+    }
+
+    #[tokio::test]
+    async fn test_stream_error_aborts_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut stager = Stager::create(dir.path(), 1, LIMITS, None).await.unwrap();
+
+        let broken = stream::iter(vec![
+            Ok(Bytes::from_static(b"partial")),
+            Err("connection reset".to_string()),
+        ]);
+        let err = stager
+            .stage("a".into(), "a".into(), broken)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UploadError::BadRequest(_)));
+        assert!(err.to_string().contains("connection reset"));
+    }
+
+    #[tokio::test]
+    async fn test_chunked_staging_writes_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let chunks = ChunkStore::open(dir.path().join("chunks")).unwrap();
+        let limits = UploadLimits {
+            max_file_bytes: 1 << 20,
+            max_batch_bytes: 1 << 20,
+        };
+        let mut stager = Stager::create(dir.path(), 1, limits, Some(chunks.clone()))
+            .await
+            .unwrap();
+
+        let staged = stager
+            .stage("a".into(), "a".into(), pieces(&[b"small ", b"file"]))
+            .await
+            .unwrap();
+        assert_eq!(staged.leaf, hash_data(b"small file"));
+        assert!(staged.path.is_none());
+        assert!(chunks.path(&hash_data(b"small file")).is_file());
+        assert_eq!(stager.new_chunks, 1);
+        assert_eq!(staged.entry().sha256, None);
+
+        // Multi-chunk leaves are chunk-tree roots; the file hash is kept alongside
+        let large: Vec<u8> = (0..200_000u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+        let body = stream::iter(vec![Ok::<_, String>(Bytes::from(large.clone()))]);
+        let staged = stager.stage("b".into(), "b".into(), body).await.unwrap();
+        assert!(staged.chunks.as_ref().unwrap().len() > 1);
+        assert_eq!(staged.entry().sha256, Some(hex::encode(hash_data(&large))));
+    }
+    #[tokio::test]
+    async fn test_aborted_upload_removes_its_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let chunk_dir = dir.path().join("chunks");
+        let chunks = ChunkStore::open(&chunk_dir).unwrap();
+        let limits = UploadLimits {
+            max_file_bytes: 150_000,
+            max_batch_bytes: 1 << 20,
+        };
+        let large: Vec<u8> = (0..200_000u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+        let pieces = || stream::iter(large.chunks(10_000).map(|p| Ok::<_, String>(Bytes::copy_from_slice(p))));
+
+        // Chunks are written as the data arrives, before the size limit is hit
+        let mut stager = Stager::create(dir.path(), 1, limits, Some(chunks.clone())).await.unwrap();
+        let err = stager.stage("big".into(), "big".into(), pieces()).await.unwrap_err();
+        assert!(matches!(err, UploadError::TooLarge(_)));
+        assert!(stager.new_chunks > 0);
+        drop(stager);
+        assert_eq!(std::fs::read_dir(&chunk_dir).unwrap().count(), 0);
+
+        let mut stager = Stager::create(dir.path(), 2, limits, Some(chunks.clone())).await.unwrap();
+        stager.stage("a".into(), "a".into(), pieces().take(10)).await.unwrap();
+        stager.commit();
+        let written = stager.new_chunks;
+        drop(stager);
+        assert_eq!(std::fs::read_dir(&chunk_dir).unwrap().count(), written);
+    }
+}